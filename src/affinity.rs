@@ -0,0 +1,91 @@
+use std::process::Child;
+
+/// Whether pinning engine processes to cores is supported on this platform.
+pub const SUPPORTED: bool = cfg!(any(target_os = "linux", windows));
+
+/// Returns the cores assigned to an engine process within a concurrency slot.
+///
+/// Every engine process in a slot gets its own contiguous range of `cores_per_engine` cores,
+/// so the two engines playing a game never share a core. Slots are laid out one after
+/// another, wrapping around if there are not enough cores available.
+pub fn cores_for(
+    slot: usize,
+    engine_index: usize,
+    engines_per_slot: usize,
+    cores_per_engine: usize,
+    total_cores: usize,
+) -> Vec<usize> {
+    let first = (slot * engines_per_slot + engine_index) * cores_per_engine;
+    (first..first + cores_per_engine)
+        .map(|core| core % total_cores)
+        .collect()
+}
+
+pub fn total_cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(target_os = "linux")]
+pub fn pin(child: &Child, cores: &[usize]) -> std::io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &core in cores {
+            libc::CPU_SET(core, &mut set);
+        }
+        let result = libc::sched_setaffinity(
+            child.id() as libc::pid_t,
+            std::mem::size_of::<libc::cpu_set_t>(),
+            &set,
+        );
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn pin(child: &Child, cores: &[usize]) -> std::io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows::{Win32::Foundation::HANDLE, Win32::System::Threading::SetProcessAffinityMask};
+
+    let mask = cores
+        .iter()
+        .filter(|&&core| core < usize::BITS as usize)
+        .fold(0usize, |mask, &core| mask | (1 << core));
+    unsafe { SetProcessAffinityMask(HANDLE(child.as_raw_handle()), mask) }
+        .map_err(|err| std::io::Error::other(format!("SetProcessAffinityMask Failed: {:?}", err)))
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn pin(_: &Child, _: &[usize]) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engines_in_slot_are_disjoint() {
+        assert_eq!(cores_for(0, 0, 2, 1, 16), vec![0]);
+        assert_eq!(cores_for(0, 1, 2, 1, 16), vec![1]);
+        assert_eq!(cores_for(1, 0, 2, 1, 16), vec![2]);
+        assert_eq!(cores_for(1, 1, 2, 1, 16), vec![3]);
+    }
+
+    #[test]
+    fn multithreaded_engines_get_contiguous_ranges() {
+        assert_eq!(cores_for(0, 0, 2, 4, 16), vec![0, 1, 2, 3]);
+        assert_eq!(cores_for(0, 1, 2, 4, 16), vec![4, 5, 6, 7]);
+        assert_eq!(cores_for(1, 0, 2, 4, 16), vec![8, 9, 10, 11]);
+        assert_eq!(cores_for(1, 1, 2, 4, 16), vec![12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn wraps_when_out_of_cores() {
+        assert_eq!(cores_for(2, 0, 2, 2, 8), vec![0, 1]);
+        assert_eq!(cores_for(1, 2, 3, 2, 8), vec![2, 3]);
+    }
+}
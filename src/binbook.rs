@@ -0,0 +1,221 @@
+// A Polyglot-style binary opening book adapted to shogi.
+//
+// The file is a sequence of 16 byte entries, each made up of big-endian fields:
+//
+//   key    u64  Zobrist hash of the position, see `shogi::Position::zobrist_hash`
+//   move   u16  bits 0-6: destination square, bits 7-13: origin square or dropped piece,
+//               bit 14: drop, bit 15: promotion
+//   weight u16  relative probability of playing the move
+//   learn  u32  unused
+//
+// Squares are indexed as rank * 9 + file, with file 0 being file 1 and rank 0 being rank a.
+// Dropped pieces use the `shogi::PieceType` codes (pawn 1, bishop 2, rook 3, lance 4,
+// knight 5, silver 6, gold 7).
+
+use crate::{
+    book::Opening,
+    shogi::{Move, PieceType, Position, Square, Variant},
+};
+use rand::{Rng, seq::IndexedRandom};
+use std::collections::HashMap;
+
+const ENTRY_SIZE: usize = 16;
+
+const DROP_FLAG: u16 = 1 << 14;
+const PROMO_FLAG: u16 = 1 << 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    pub m: Move,
+    pub weight: u16,
+}
+
+/// Parses the entries of a binary book. Returns None if the file is truncated or contains a
+/// move that cannot be decoded.
+pub fn parse(bytes: &[u8]) -> Option<Vec<Entry>> {
+    if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+        return None;
+    }
+    bytes
+        .chunks_exact(ENTRY_SIZE)
+        .map(|chunk| {
+            Some(Entry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                m: decode_move(u16::from_be_bytes(chunk[8..10].try_into().unwrap()))?,
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+            })
+        })
+        .collect()
+}
+
+fn square_from_index(index: u16) -> Option<Square> {
+    if index >= 81 {
+        return None;
+    }
+    Square::new((index % 9) as i8, (index / 9) as i8)
+}
+
+fn decode_move(bits: u16) -> Option<Move> {
+    let to = square_from_index(bits & 0x7f)?;
+    let from = (bits >> 7) & 0x7f;
+    if bits & DROP_FLAG != 0 {
+        if bits & PROMO_FLAG != 0 {
+            return None;
+        }
+        let pt = match from {
+            0o01 => PieceType::Pawn,
+            0o02 => PieceType::Bishop,
+            0o03 => PieceType::Rook,
+            0o04 => PieceType::Lance,
+            0o05 => PieceType::Knight,
+            0o06 => PieceType::Silver,
+            0o07 => PieceType::Gold,
+            _ => return None,
+        };
+        Some(Move::Drop(pt, to))
+    } else {
+        Some(Move::Normal {
+            from: square_from_index(from)?,
+            to,
+            promo: bits & PROMO_FLAG != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+fn encode_move(m: Move) -> u16 {
+    match m {
+        Move::Drop(pt, to) => DROP_FLAG | (pt as u16) << 7 | to.to_index() as u16,
+        Move::Normal { from, to, promo } => {
+            (if promo { PROMO_FLAG } else { 0 })
+                | (from.to_index() as u16) << 7
+                | to.to_index() as u16
+        }
+        _ => panic!("cannot encode {m} in a binary book"),
+    }
+}
+
+#[cfg(test)]
+fn to_bytes(entries: &[Entry]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for entry in entries {
+        bytes.extend(entry.key.to_be_bytes());
+        bytes.extend(encode_move(entry.m).to_be_bytes());
+        bytes.extend(entry.weight.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+    }
+    bytes
+}
+
+/// Generates `count` openings by walking the book from the starting position of `variant`,
+/// picking each move at random in proportion to its weight. A walk stops after `depth` plies
+/// or when the book has no legal move for the position reached. Walks that leave the starting
+/// position without a single book move are dropped.
+pub fn generate_openings<R>(
+    entries: &[Entry],
+    variant: Variant,
+    count: usize,
+    depth: usize,
+    rng: &mut R,
+) -> Vec<Opening>
+where
+    R: Rng + ?Sized,
+{
+    let mut book = HashMap::<u64, Vec<(Move, u16)>>::new();
+    for entry in entries.iter().filter(|entry| entry.weight > 0) {
+        book.entry(entry.key)
+            .or_default()
+            .push((entry.m, entry.weight));
+    }
+
+    let startpos = Position::startpos(variant);
+    let mut openings = vec![];
+    for _ in 0..count {
+        let mut position = startpos;
+        let mut moves = vec![];
+        while moves.len() < depth {
+            let Some(candidates) = book.get(&position.zobrist_hash()) else {
+                break;
+            };
+            let candidates: Vec<&(Move, u16)> = candidates
+                .iter()
+                .filter(|(m, _)| position.is_legal(*m))
+                .collect();
+            let Ok(&&(m, _)) = candidates.choose_weighted(rng, |(_, weight)| *weight as u32) else {
+                break;
+            };
+            position = position.do_move(m).unwrap();
+            moves.push(m);
+        }
+        if !moves.is_empty() {
+            openings.push(Opening {
+                position: startpos,
+                moves,
+            });
+        }
+    }
+    openings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    fn entry(position: &Position, m: &str, weight: u16) -> Entry {
+        Entry {
+            key: position.zobrist_hash(),
+            m: Move::parse(m).unwrap(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn move_encoding() {
+        for m in ["7g7f", "8h2b+", "P*5e", "G*1a", "1i1a"] {
+            let m = Move::parse(m).unwrap();
+            assert_eq!(decode_move(encode_move(m)), Some(m));
+        }
+        assert_eq!(decode_move(DROP_FLAG | 0o10 << 7), None);
+        assert_eq!(decode_move(81), None);
+    }
+
+    #[test]
+    fn generates_legal_varied_openings() {
+        let startpos = Position::default();
+        let after = |m: &str| startpos.do_move(Move::parse(m).unwrap()).unwrap();
+        let entries = vec![
+            entry(&startpos, "7g7f", 3),
+            entry(&startpos, "2g2f", 1),
+            entry(&startpos, "5i5h", 0),
+            // Illegal for the position, must never be picked.
+            entry(&startpos, "3c3d", 100),
+            entry(&after("7g7f"), "3c3d", 1),
+            entry(&after("7g7f"), "8c8d", 1),
+            entry(&after("2g2f"), "8c8d", 1),
+            entry(
+                &after("7g7f").do_move(Move::parse("3c3d").unwrap()).unwrap(),
+                "2g2f",
+                1,
+            ),
+        ];
+        let entries = parse(&to_bytes(&entries)).unwrap();
+        assert!(parse(&to_bytes(&entries)[1..]).is_none());
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let openings = generate_openings(&entries, Variant::Standard, 100, 2, &mut rng);
+        assert_eq!(openings.len(), 100);
+        for opening in &openings {
+            assert!(opening.to_game().is_some());
+            assert!((1..=2).contains(&opening.moves.len()));
+            assert_ne!(opening.moves[0], Move::parse("5i5h").unwrap());
+        }
+        let distinct: HashSet<Vec<String>> = openings
+            .iter()
+            .map(|opening| opening.moves.iter().map(|m| m.to_string()).collect())
+            .collect();
+        assert_eq!(distinct.len(), 3);
+    }
+}
@@ -1,48 +1,802 @@
-use crate::{cli, shogi, util};
+use crate::{binbook, cli, csa, shogi, util};
 use rand::{Rng, seq::SliceRandom};
+use std::{collections::HashSet, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub position: shogi::Position,
+    pub moves: Vec<shogi::Move>,
+}
+
+impl fmt::Display for Opening {
+    /// Formats the opening as a book line: its sfen, followed by `moves` and the book moves if
+    /// there are any.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.position)?;
+        if !self.moves.is_empty() {
+            write!(f, " moves")?;
+            for m in &self.moves {
+                write!(f, " {m}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Opening {
+    /// Parses a single line of an opening book. Accepts a bare sfen, `sfen <sfen>` or
+    /// `startpos`, optionally followed by `moves` and a list of USI moves. `startpos` refers to
+    /// the starting position of `variant`.
+    pub fn parse(s: &str, variant: shogi::Variant) -> Option<Opening> {
+        let mut it = s.split_whitespace();
+        let position = match it.next()? {
+            "startpos" => shogi::Position::startpos(variant),
+            "sfen" => shogi::Position::parse_parts(it.next()?, it.next()?, it.next()?, it.next()?)?,
+            board => shogi::Position::parse_parts(board, it.next()?, it.next()?, it.next()?)?,
+        };
+        let moves = match it.next() {
+            None => vec![],
+            Some("moves") => it.map(shogi::Move::parse).collect::<Option<Vec<_>>>()?,
+            Some(_) => return None,
+        };
+        Some(Opening { position, moves })
+    }
+
+    /// Plays out the book moves. Returns None if any book move is illegal or ends the game. The
+    /// positions reached during the book moves are kept in the game's history, so repetitions
+    /// that began in the book are counted once the engines take over.
+    pub fn to_game(&self) -> Option<shogi::Game> {
+        if self.position.is_in_check() {
+            return None;
+        }
+        let mut game = shogi::Game::new(self.position);
+        for &m in &self.moves {
+            if game.do_move(m).is_determined() {
+                return None;
+            }
+        }
+        Some(game)
+    }
+
+    /// The side to move once the book moves are played.
+    pub fn stm(&self) -> shogi::Color {
+        if self.moves.len().is_multiple_of(2) {
+            self.position.stm()
+        } else {
+            !self.position.stm()
+        }
+    }
+}
+
+/// Number of openings generated up front by walking a binary book.
+const BINARY_BOOK_OPENING_COUNT: usize = 4096;
 
 #[derive(Debug)]
 pub struct OpeningBook {
-    openings: Vec<shogi::Position>,
+    openings: Vec<Opening>,
+    /// The index of the file each opening came from, with `buckets`; empty otherwise.
+    buckets: Vec<usize>,
     current: usize,
+    no_repeat: bool,
+    /// With `stride`, the book is split into this many contiguous slices and consecutive
+    /// openings are taken from each slice in turn, so each concurrent game walks its own part
+    /// of the book.
+    workers: usize,
 }
 
 impl OpeningBook {
-    pub fn new<R>(options: &cli::BookOptions, rng: &mut R) -> Option<OpeningBook>
+    pub fn new<R>(
+        options: &cli::BookOptions,
+        variant: shogi::Variant,
+        rng: &mut R,
+    ) -> Option<OpeningBook>
     where
         R: Rng + ?Sized,
     {
-        let Ok(lines) = util::read_lines(&options.file) else {
-            eprintln!("Unable to read file for opening book: {}", options.file);
+        if options.start_index == 0 {
+            eprintln!("Invalid opening book start index 0 (must be bigger than zero)");
             return None;
-        };
+        }
 
-        let mut openings = Vec::<shogi::Position>::new();
-        for line in lines.map_while(Result::ok).filter(|l| !l.trim().is_empty()) {
-            let Some(position) = shogi::Position::parse(line.trim()) else {
-                eprintln!("Invalid sfen: {line}");
-                return None;
-            };
-            openings.push(position);
+        let mut openings = Vec::<Opening>::new();
+        let mut buckets = Vec::<usize>::new();
+        let mut seen = HashSet::<String>::new();
+        for (file_index, file) in options.files.iter().enumerate() {
+            let parsed = Self::read_file(file, options, variant, rng)?;
+
+            let file_start = openings.len();
+            let mut duplicate_count = 0;
+            let mut terminal_count = 0;
+            let mut unbalanced_count = 0;
+            for mut opening in parsed {
+                if let Some(plies) = options.plies {
+                    opening.moves.truncate(plies);
+                }
+                if opening.position.variant() != variant {
+                    eprintln!("Opening is not a {variant} position: {}", opening.position);
+                    return None;
+                }
+                let Some(game) = opening.to_game() else {
+                    eprintln!(
+                        "Warning; Skipping opening whose book moves are illegal or end the game: {}",
+                        opening.position
+                    );
+                    continue;
+                };
+                // Games from such openings would end before either engine moves.
+                if !game.position().has_legal_move() {
+                    terminal_count += 1;
+                    continue;
+                }
+                if !is_balanced(options, game.position()) {
+                    unbalanced_count += 1;
+                    continue;
+                }
+                // Shared between files, so an opening in two files is only kept the first time.
+                if options.dedup && !seen.insert(game.position().canonical_sfen()) {
+                    duplicate_count += 1;
+                    continue;
+                }
+                openings.push(opening);
+                buckets.push(file_index);
+            }
+
+            if terminal_count > 0 {
+                eprintln!(
+                    "Warning; Skipped {terminal_count} openings from {file} where the side to move has no legal moves"
+                );
+            }
+
+            if unbalanced_count > 0 {
+                println!(
+                    "Removed {unbalanced_count} openings outside the material and ply limits from {file}"
+                );
+            }
+
+            if duplicate_count > 0 {
+                println!("Removed {duplicate_count} duplicate openings from {file}");
+            }
+
+            if options.files.len() > 1 {
+                println!(
+                    "Loaded {} openings from {file}",
+                    openings.len() - file_start
+                );
+            }
+        }
+
+        if options.files.len() > 1 {
+            println!(
+                "Loaded {} openings in total from {} files",
+                openings.len(),
+                options.files.len()
+            );
+        }
+
+        if openings.is_empty() {
+            eprintln!(
+                "Opening book contains no openings: {}",
+                options.files.join(", ")
+            );
+            return None;
         }
 
         if options.random_order {
-            // Fisher-Yates Shuffle
-            openings.shuffle(rng);
+            // Fisher-Yates Shuffle, keeping each opening with its bucket.
+            let mut tagged: Vec<(Opening, usize)> = openings.into_iter().zip(buckets).collect();
+            tagged.shuffle(rng);
+            (openings, buckets) = tagged.into_iter().unzip();
+        }
+        if !options.buckets {
+            buckets.clear();
         }
 
         let openings_len = openings.len();
         Some(OpeningBook {
             openings,
+            buckets,
             current: (options.start_index - 1) % openings_len,
+            no_repeat: options.no_repeat,
+            workers: 1,
         })
     }
 
-    pub fn current(&self) -> shogi::Position {
-        self.openings[self.current]
+    /// Reads the openings of a single book file, in the format given by `options` or else
+    /// detected from the file itself.
+    fn read_file<R>(
+        file: &str,
+        options: &cli::BookOptions,
+        variant: shogi::Variant,
+        rng: &mut R,
+    ) -> Option<Vec<Opening>>
+    where
+        R: Rng + ?Sized,
+    {
+        let format = options.format.unwrap_or_else(|| Self::detect_format(file));
+
+        let openings = match format {
+            cli::BookFormat::Bin => {
+                let Ok(bytes) = std::fs::read(file) else {
+                    eprintln!("Unable to read file for opening book: {file}");
+                    return None;
+                };
+                let Some(entries) = binbook::parse(&bytes) else {
+                    eprintln!("Invalid binary opening book: {file}");
+                    return None;
+                };
+                binbook::generate_openings(
+                    &entries,
+                    variant,
+                    BINARY_BOOK_OPENING_COUNT,
+                    options.depth,
+                    rng,
+                )
+            }
+            cli::BookFormat::Epd | cli::BookFormat::Csa => {
+                let Ok(lines) = util::read_lines(file) else {
+                    eprintln!("Unable to read file for opening book: {file}");
+                    return None;
+                };
+                let lines: Vec<String> = lines.map_while(Result::ok).collect();
+                if format == cli::BookFormat::Csa {
+                    let Some(parsed) = csa::parse_openings(lines) else {
+                        eprintln!("Invalid CSA opening book: {file}");
+                        return None;
+                    };
+                    parsed
+                } else {
+                    let mut parsed = Vec::<Opening>::new();
+                    for (i, line) in lines.iter().enumerate() {
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        match parse_book_line(line, variant) {
+                            Ok(opening) => parsed.push(opening),
+                            Err(error) => {
+                                eprintln!("{error} on line {} of {file}: {line}", i + 1);
+                                return None;
+                            }
+                        }
+                    }
+                    parsed
+                }
+            }
+        };
+        Some(openings)
+    }
+
+    /// Guesses the format of a book from its extension, falling back to sniffing the first
+    /// non-empty line for CSA records.
+    fn detect_format(file: &str) -> cli::BookFormat {
+        let file_lower = file.to_lowercase();
+        if file_lower.ends_with(".bin") {
+            return cli::BookFormat::Bin;
+        }
+        if file_lower.ends_with(".csa") {
+            return cli::BookFormat::Csa;
+        }
+        let first_line = util::read_lines(file).ok().and_then(|lines| {
+            lines
+                .map_while(Result::ok)
+                .map(|l| String::from(l.trim()))
+                .find(|l| !l.is_empty())
+        });
+        if first_line.is_some_and(|l| csa::looks_like_csa(&l)) {
+            cli::BookFormat::Csa
+        } else {
+            cli::BookFormat::Epd
+        }
+    }
+
+    #[cfg(test)]
+    pub fn from_positions(openings: Vec<shogi::Position>, no_repeat: bool) -> OpeningBook {
+        assert!(!openings.is_empty());
+        OpeningBook {
+            openings: openings
+                .into_iter()
+                .map(|position| Opening {
+                    position,
+                    moves: vec![],
+                })
+                .collect(),
+            buckets: vec![],
+            current: 0,
+            no_repeat,
+            workers: 1,
+        }
+    }
+
+    /// Splits the book into one slice per concurrent game. Slices differ in length by at most
+    /// one opening, and together still cover every opening exactly once before repeating.
+    pub fn set_stride(&mut self, workers: usize) {
+        assert!(workers > 0);
+        self.workers = workers.min(self.openings.len());
+    }
+
+    /// Maps the `current`th opening handed out to its index in the book. Each pass over the
+    /// book takes the `j`th opening of every slice in turn, and the longer slices' last
+    /// openings at the end.
+    fn index(&self) -> usize {
+        let (len, workers) = (self.openings.len(), self.workers);
+        let k = self.current % len;
+        let short_len = len / workers;
+        let (worker, j) = if k < short_len * workers {
+            (k % workers, k / workers)
+        } else {
+            (k - short_len * workers, short_len)
+        };
+        let slice_start = worker * short_len + worker.min(len % workers);
+        slice_start + j
+    }
+
+    /// Returns None once the book is exhausted, which only happens with `norepeat`.
+    pub fn current(&self) -> Option<Opening> {
+        if self.no_repeat && self.current >= self.openings.len() {
+            return None;
+        }
+        Some(self.openings[self.index()].clone())
+    }
+
+    /// The bucket of the opening `current` returns, with `buckets`.
+    pub fn current_bucket(&self) -> Option<usize> {
+        self.buckets.get(self.index()).copied()
     }
 
     pub fn advance(&mut self) {
-        self.current = (self.current + 1) % self.openings.len();
+        self.current += 1;
+        if !self.no_repeat {
+            self.current %= self.openings.len();
+        }
+    }
+
+    /// Number of openings left to hand out, if the book is not allowed to repeat.
+    pub fn remaining(&self) -> Option<u64> {
+        self.no_repeat
+            .then(|| self.openings.len().saturating_sub(self.current) as u64)
+    }
+}
+
+/// Whether the position reached at the end of an opening is within the material and ply limits
+/// of `options`.
+fn is_balanced(options: &cli::BookOptions, position: &shogi::Position) -> bool {
+    let ply = position.ply().saturating_sub(1);
+    options
+        .max_material
+        .is_none_or(|max| position.material_balance().unsigned_abs() <= max)
+        && options.min_ply.is_none_or(|min| ply >= min)
+        && options.max_ply.is_none_or(|max| ply <= max)
+}
+
+/// Parses a line of a text opening book, checking that it is a position of `variant`.
+fn parse_book_line(line: &str, variant: shogi::Variant) -> Result<Opening, String> {
+    let Some(opening) = Opening::parse(line.trim(), variant) else {
+        return Err(String::from("Invalid opening"));
+    };
+    let found = opening.position.variant();
+    if found != variant {
+        return Err(format!("Opening is a {found} position, expected {variant}"));
+    }
+    Ok(opening)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn book_with_options(
+        name: &str,
+        contents: &str,
+        options: cli::BookOptions,
+    ) -> Option<OpeningBook> {
+        book_for_variant(name, contents, options, shogi::Variant::Standard)
+    }
+
+    fn book_for_variant(
+        name: &str,
+        contents: &str,
+        options: cli::BookOptions,
+        variant: shogi::Variant,
+    ) -> Option<OpeningBook> {
+        let path =
+            std::env::temp_dir().join(format!("shogitest-book-{}-{name}.epd", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let options = cli::BookOptions {
+            files: vec![path.to_string_lossy().to_string()],
+            ..options
+        };
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let book = OpeningBook::new(&options, variant, &mut rng);
+        std::fs::remove_file(&path).unwrap();
+        book
+    }
+
+    fn book_from_str(name: &str, contents: &str, start_index: usize) -> Option<OpeningBook> {
+        let options = cli::BookOptions {
+            start_index,
+            ..cli::BookOptions::default()
+        };
+        book_with_options(name, contents, options)
+    }
+
+    #[test]
+    fn empty_book() {
+        assert!(book_from_str("empty", "", 1).is_none());
+    }
+
+    #[test]
+    fn whitespace_only_book() {
+        assert!(book_from_str("whitespace", "  \n\t\n\n", 1).is_none());
+    }
+
+    #[test]
+    fn zero_start_index() {
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1\n";
+        assert!(book_from_str("zero-start", sfen, 0).is_none());
+    }
+
+    #[test]
+    fn single_entry_book() {
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1\n";
+        let mut book = book_from_str("single", sfen, 3).unwrap();
+        assert_eq!(book.current().unwrap().position, shogi::Position::default());
+        book.advance();
+        assert_eq!(book.current().unwrap().position, shogi::Position::default());
+    }
+
+    #[test]
+    fn no_repeat_book() {
+        let book = "startpos\nstartpos moves 7g7f\nstartpos moves 2g2f\n";
+        let options = cli::BookOptions {
+            start_index: 2,
+            no_repeat: true,
+            ..cli::BookOptions::default()
+        };
+        let mut book = book_with_options("norepeat", book, options).unwrap();
+
+        assert_eq!(book.remaining(), Some(2));
+        assert!(book.current().is_some());
+        book.advance();
+        assert_eq!(book.remaining(), Some(1));
+        assert!(book.current().is_some());
+        book.advance();
+        assert_eq!(book.remaining(), Some(0));
+        assert!(book.current().is_none());
+    }
+
+    #[test]
+    fn stride_book() {
+        let book: String = ["7g7f", "2g2f", "6g6f", "5g5f", "1g1f", "9g9f"]
+            .iter()
+            .map(|m| format!("startpos moves {m}\n"))
+            .collect();
+        let mut book = book_with_options("stride", &book, cli::BookOptions::default()).unwrap();
+        book.set_stride(2);
+
+        let mut worker_openings = [vec![], vec![]];
+        for i in 0..12 {
+            worker_openings[i % 2].push(book.current().unwrap().moves[0].to_string());
+            book.advance();
+        }
+        assert_eq!(worker_openings[0][..3], ["7g7f", "2g2f", "6g6f"]);
+        assert_eq!(worker_openings[1][..3], ["5g5f", "1g1f", "9g9f"]);
+        for openings in &worker_openings {
+            assert_eq!(openings[..3], openings[3..]);
+        }
+
+        // An uneven split still plays every opening once before repeating.
+        let options = cli::BookOptions {
+            no_repeat: true,
+            ..cli::BookOptions::default()
+        };
+        let book: String = ["7g7f", "2g2f", "6g6f", "5g5f", "1g1f"]
+            .iter()
+            .map(|m| format!("startpos moves {m}\n"))
+            .collect();
+        let mut book = book_with_options("stride-uneven", &book, options).unwrap();
+        book.set_stride(2);
+        let mut played = vec![];
+        while let Some(opening) = book.current() {
+            played.push(opening.moves[0].to_string());
+            book.advance();
+        }
+        assert_eq!(played, ["7g7f", "5g5f", "2g2f", "1g1f", "6g6f"]);
+
+        // Repeating passes stay in the same order, so no opening is played a third time
+        // before every other one has been played twice.
+        for (len, workers) in [(5, 2), (7, 3), (8, 3)] {
+            let book: String = (1..=len)
+                .map(|file| format!("startpos moves {file}g{file}f\n"))
+                .collect();
+            let mut book =
+                book_with_options("stride-passes", &book, cli::BookOptions::default()).unwrap();
+            book.set_stride(workers);
+            let mut passes = vec![];
+            for _ in 0..2 {
+                let mut pass = vec![];
+                for _ in 0..len {
+                    pass.push(book.current().unwrap().moves[0].to_string());
+                    book.advance();
+                }
+                passes.push(pass);
+            }
+            let mut sorted = passes[0].clone();
+            sorted.sort();
+            sorted.dedup();
+            assert_eq!(sorted.len(), len, "{len} openings, {workers} workers");
+            assert_eq!(passes[0], passes[1], "{len} openings, {workers} workers");
+        }
+    }
+
+    #[test]
+    fn opening_with_moves() {
+        let expected = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P4P1/PP1PPPP1P/1B5R1/LNSGKGSNL w - 4";
+        let cases = [
+            "startpos moves 7g7f 3c3d 2g2f",
+            "sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1 moves 7g7f 3c3d 2g2f",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1 moves 7g7f 3c3d 2g2f",
+        ];
+        for case in cases {
+            let opening = Opening::parse(case, shogi::Variant::Standard).unwrap();
+            assert_eq!(opening.position, shogi::Position::default());
+            assert_eq!(opening.moves.len(), 3);
+            let game = opening.to_game().unwrap();
+            assert_eq!(game.position().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn opening_with_illegal_move() {
+        let opening =
+            Opening::parse("startpos moves 7g7f 3c3d 7f7d", shogi::Variant::Standard).unwrap();
+        assert!(opening.to_game().is_none());
+
+        let book = "startpos moves 7g7f 3c3d 7f7d\nstartpos moves 2g2f\n";
+        let mut book = book_from_str("illegal", book, 1).unwrap();
+        assert_eq!(book.current().unwrap().moves.len(), 1);
+        book.advance();
+        assert_eq!(book.current().unwrap().moves.len(), 1);
+    }
+
+    #[test]
+    fn terminal_openings() {
+        let mated = "4k4/4G4/4P4/9/9/9/9/9/4K4 w - 1";
+        let mating = "4k4/9/4P4/9/9/9/9/9/4K4 b G 1 moves G*5b";
+        let stalemated = "8k/6G2/7G1/9/9/9/9/9/4K4 w - 1";
+        let has_legal_move = |line| {
+            let opening = Opening::parse(line, shogi::Variant::Standard).unwrap();
+            opening
+                .to_game()
+                .is_some_and(|game| game.position().has_legal_move())
+        };
+        for line in [mated, mating, stalemated] {
+            assert!(!has_legal_move(line), "{line}");
+        }
+        assert!(has_legal_move("4k4/9/4P4/9/9/9/9/9/4K4 b G 1"));
+
+        let contents = format!("{mated}\nstartpos moves 7g7f\n{mating}\n{stalemated}\n");
+        let options = cli::BookOptions {
+            no_repeat: true,
+            ..cli::BookOptions::default()
+        };
+        let book = book_with_options("terminal", &contents, options).unwrap();
+        assert_eq!(book.remaining(), Some(1));
+
+        assert!(book_from_str("all-terminal", mated, 1).is_none());
+    }
+
+    #[test]
+    fn dedup_book() {
+        let contents = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1\n\
+                    startpos moves 7g7f\n\
+                    lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 5\n\
+                    startpos moves 2g2f\n";
+        let options = cli::BookOptions {
+            no_repeat: true,
+            dedup: true,
+            ..cli::BookOptions::default()
+        };
+        let mut book = book_with_options("dedup", contents, options.clone()).unwrap();
+        assert_eq!(book.remaining(), Some(3));
+        assert_eq!(book.current().unwrap().position, shogi::Position::default());
+        book.advance();
+        assert_eq!(
+            book.current().unwrap().moves,
+            [shogi::Move::parse("7g7f").unwrap()]
+        );
+        book.advance();
+        assert_eq!(
+            book.current().unwrap().moves,
+            [shogi::Move::parse("2g2f").unwrap()]
+        );
+
+        let options = cli::BookOptions {
+            dedup: false,
+            ..options
+        };
+        let book = book_with_options("no-dedup", contents, options).unwrap();
+        assert_eq!(book.remaining(), Some(4));
+    }
+
+    #[test]
+    fn multiple_files() {
+        let dir = std::env::temp_dir();
+        let epd = dir.join(format!("shogitest-book-{}-multi.epd", std::process::id()));
+        let csa = dir.join(format!("shogitest-book-{}-multi.csa", std::process::id()));
+        std::fs::write(&epd, "startpos moves 7g7f\nstartpos moves 2g2f\n").unwrap();
+        // The first record repeats the last opening of the EPD file.
+        std::fs::write(&csa, "PI\n+\n+2726FU\n/\nPI\n+\n+5756FU\n").unwrap();
+        let options = cli::BookOptions {
+            files: [&epd, &csa]
+                .map(|p| p.to_string_lossy().to_string())
+                .to_vec(),
+            no_repeat: true,
+            ..cli::BookOptions::default()
+        };
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let mut book = OpeningBook::new(&options, shogi::Variant::Standard, &mut rng).unwrap();
+        let mut deduped = OpeningBook::new(
+            &cli::BookOptions {
+                dedup: true,
+                buckets: true,
+                ..options
+            },
+            shogi::Variant::Standard,
+            &mut rng,
+        )
+        .unwrap();
+        std::fs::remove_file(&epd).unwrap();
+        std::fs::remove_file(&csa).unwrap();
+
+        assert_eq!(deduped.remaining(), Some(3));
+        assert_eq!(book.remaining(), Some(4));
+        for m in ["7g7f", "2g2f", "2g2f", "5g5f"] {
+            assert_eq!(
+                book.current().unwrap().moves,
+                [shogi::Move::parse(m).unwrap()]
+            );
+            assert_eq!(book.current_bucket(), None);
+            book.advance();
+        }
+        assert!(book.current().is_none());
+
+        for bucket in [0, 0, 1] {
+            assert_eq!(deduped.current_bucket(), Some(bucket));
+            deduped.advance();
+        }
+    }
+
+    #[test]
+    fn truncate_to_plies() {
+        let line = "startpos moves 7g7f 3c3d 2g2f 8c8d 2f2e 8d8e 6i7h 4a3b 2e2d 2c2d";
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2";
+        let options = cli::BookOptions {
+            plies: Some(6),
+            ..cli::BookOptions::default()
+        };
+        let mut book = book_with_options("plies", &format!("{line}\n{sfen}\n"), options).unwrap();
+
+        let opening = book.current().unwrap();
+        assert_eq!(opening.moves.len(), 6);
+        assert_eq!(
+            opening.moves,
+            Opening::parse(line, shogi::Variant::Standard)
+                .unwrap()
+                .moves[..6]
+        );
+        let game = opening.to_game().unwrap();
+        assert_eq!(game.stm(), shogi::Color::Sente);
+        assert_eq!(
+            game.position().canonical_sfen(),
+            "lnsgkgsnl/1r5b1/p1pppp1pp/6p2/1p5P1/2P6/PP1PPPP1P/1B5R1/LNSGKGSNL b -"
+        );
+
+        book.advance();
+        let opening = book.current().unwrap();
+        assert!(opening.moves.is_empty());
+        assert_eq!(opening.position, shogi::Position::parse(sfen).unwrap());
+    }
+
+    #[test]
+    fn minishogi_book() {
+        let options = cli::BookOptions {
+            random_order: false,
+            ..cli::BookOptions::default()
+        };
+        let contents = "startpos moves 1e1d\nrbsgk/4p/5/P4/KGSBR w - 1\n";
+        let book = book_for_variant(
+            "minishogi",
+            contents,
+            options.clone(),
+            shogi::Variant::Minishogi,
+        )
+        .unwrap();
+        let opening = book.current().unwrap();
+        assert_eq!(
+            opening.position,
+            shogi::Position::startpos(shogi::Variant::Minishogi)
+        );
+        assert_eq!(opening.moves.len(), 1);
+
+        // Standard positions are rejected in a minishogi book and vice versa.
+        let standard = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1\n";
+        assert!(
+            book_for_variant(
+                "minishogi-mismatch",
+                standard,
+                options.clone(),
+                shogi::Variant::Minishogi
+            )
+            .is_none()
+        );
+        assert!(book_with_options("standard-mismatch", contents, options).is_none());
+    }
+
+    #[test]
+    fn material_and_ply_filters() {
+        let contents = "startpos\n\
+            startpos moves 7g7f 3c3d\n\
+            lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1\n";
+        let count = |options: cli::BookOptions| {
+            let options = cli::BookOptions {
+                no_repeat: true,
+                ..options
+            };
+            book_with_options("filters", contents, options).and_then(|book| book.remaining())
+        };
+
+        assert_eq!(count(cli::BookOptions::default()), Some(3));
+        let balanced = cli::BookOptions {
+            max_material: Some(5),
+            ..cli::BookOptions::default()
+        };
+        assert_eq!(count(balanced.clone()), Some(2));
+        assert_eq!(
+            count(cli::BookOptions {
+                min_ply: Some(1),
+                ..balanced
+            }),
+            Some(1)
+        );
+        assert_eq!(
+            count(cli::BookOptions {
+                max_ply: Some(1),
+                ..cli::BookOptions::default()
+            }),
+            Some(2)
+        );
+        // Nothing left is an error, as for any other empty book.
+        assert_eq!(
+            count(cli::BookOptions {
+                max_material: Some(0),
+                min_ply: Some(3),
+                ..cli::BookOptions::default()
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn book_line_variant() {
+        let standard = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        assert_eq!(
+            parse_book_line(standard, shogi::Variant::Minishogi),
+            Err(String::from(
+                "Opening is a standard position, expected minishogi"
+            ))
+        );
+        assert_eq!(
+            parse_book_line("sfen rbsgk/4p/5/P4/KGSBR b - 1", shogi::Variant::Standard),
+            Err(String::from(
+                "Opening is a minishogi position, expected standard"
+            ))
+        );
+        // Lances do not exist in minishogi.
+        assert_eq!(
+            parse_book_line("rbsgk/4p/5/P4/KGSBL b - 1", shogi::Variant::Minishogi),
+            Err(String::from("Invalid opening"))
+        );
+        assert!(parse_book_line(standard, shogi::Variant::Standard).is_ok());
+        assert!(parse_book_line("startpos moves 1e1d", shogi::Variant::Minishogi).is_ok());
     }
 }
@@ -1,6 +1,9 @@
 use std::time::Duration;
 
+use crate::config;
 use crate::engine;
+use crate::shogi;
+use crate::sprt;
 use crate::tc;
 
 #[derive(Debug, Clone)]
@@ -9,19 +12,76 @@ pub struct MetaDataOptions {
     pub site_name: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Concurrency {
+    Fixed(u64),
+    /// As many games as the machine's logical CPUs allow, see `runner::resolve_concurrency`.
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookFormat {
+    Epd,
+    Csa,
+    Bin,
+}
+
+/// How the two engines of a pairing are assigned colours on each opening.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorPolicy {
+    /// The first engine of the pairing is sente in the first game of each pair, then colours
+    /// are swapped.
+    #[default]
+    Alternate,
+    /// The first engine of the pairing plays the side to move of the opening in the first game
+    /// of each pair, then colours are swapped.
+    Book,
+    /// The first engine of the pairing is sente in every game.
+    FirstEngineSente,
+}
+
 #[derive(Debug, Clone)]
 pub struct BookOptions {
-    pub file: String,
+    /// Opening files, whose openings are joined in order into a single book.
+    pub files: Vec<String>,
+    pub format: Option<BookFormat>,
     pub random_order: bool,
     pub start_index: usize,
+    pub no_repeat: bool,
+    pub dedup: bool,
+    /// Give each concurrent game its own slice of the book, see `OpeningBook::set_stride`.
+    pub stride: bool,
+    /// Tag each game with the file its opening came from, so that results are also reported
+    /// for each file.
+    pub buckets: bool,
+    pub depth: usize,
+    pub plies: Option<usize>,
+    pub color_policy: ColorPolicy,
+    /// Skip openings whose material imbalance, in pawns, is bigger than this.
+    pub max_material: Option<u32>,
+    /// Skip openings that end before this ply.
+    pub min_ply: Option<usize>,
+    /// Skip openings that end after this ply.
+    pub max_ply: Option<usize>,
 }
 
 impl Default for BookOptions {
     fn default() -> Self {
         BookOptions {
-            file: String::from("<none>"),
+            files: vec![],
+            format: None,
             random_order: false,
             start_index: 1,
+            no_repeat: false,
+            dedup: false,
+            stride: false,
+            buckets: false,
+            depth: 16,
+            plies: None,
+            color_policy: ColorPolicy::Alternate,
+            max_material: None,
+            min_ply: None,
+            max_ply: None,
         }
     }
 }
@@ -31,6 +91,29 @@ pub struct AdjudicationOptions {
     pub max_moves: Option<u64>,
     pub draw: Option<DrawAdjudicationOptions>,
     pub resign: Option<ResignAdjudicationOptions>,
+    pub watchdog: Option<WatchdogOptions>,
+    /// What `max_moves` and the draw and resign move counts are counted in.
+    pub move_unit: MoveUnit,
+}
+
+/// How move limits for adjudication are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MoveUnit {
+    /// Single moves by either side, as is usual in shogi.
+    #[default]
+    Plies,
+    /// A move by each side, as is usual in chess.
+    FullMoves,
+}
+
+impl MoveUnit {
+    /// The number of plies in `count` of this unit.
+    pub fn plies(self, count: usize) -> usize {
+        match self {
+            MoveUnit::Plies => count,
+            MoveUnit::FullMoves => count.saturating_mul(2),
+        }
+    }
 }
 
 impl Default for AdjudicationOptions {
@@ -39,6 +122,31 @@ impl Default for AdjudicationOptions {
             max_moves: Some(512),
             draw: None,
             resign: None,
+            watchdog: None,
+            move_unit: MoveUnit::Plies,
+        }
+    }
+}
+
+/// Sanity limits on reported search speed and measured move times, to catch broken clocks.
+#[derive(Debug, Clone)]
+pub struct WatchdogOptions {
+    pub max_nps: Option<u64>,
+    pub min_nps: Option<u64>,
+    /// A move taking more than this many times the time the engine had for it, without having
+    /// lost on time, is suspect.
+    pub time_factor: f64,
+    /// Forfeit the game of an engine that trips the watchdog, rather than only logging it.
+    pub forfeit: bool,
+}
+
+impl Default for WatchdogOptions {
+    fn default() -> Self {
+        WatchdogOptions {
+            max_nps: None,
+            min_nps: None,
+            time_factor: 3.0,
+            forfeit: false,
         }
     }
 }
@@ -79,64 +187,263 @@ impl Default for ResignAdjudicationOptions {
 
 #[derive(Debug, Clone)]
 pub struct SprtOptions {
-    pub nelo0: f64,
-    pub nelo1: f64,
+    pub model: sprt::SprtModel,
+    pub elo0: f64,
+    pub elo1: f64,
     pub alpha: f64,
     pub beta: f64,
+    /// Test the results of the first engine against the second, rather than those of the
+    /// second engine of a two-engine match against the first.
+    pub pair: Option<[usize; 2]>,
+    pub drain: SprtDrain,
+}
+
+/// What happens to the games still being played once the SPRT has reached a verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SprtDrain {
+    /// Play them out and count them.
+    #[default]
+    Complete,
+    /// Abort them and leave them out of the results.
+    Cancel,
+}
+
+impl SprtOptions {
+    pub fn pair(&self) -> [usize; 2] {
+        self.pair.unwrap_or([1, 0])
+    }
 }
 
 impl Default for SprtOptions {
     fn default() -> Self {
         SprtOptions {
-            nelo0: 0.0,
-            nelo1: 0.0,
+            model: sprt::SprtModel::Normalized,
+            elo0: 0.0,
+            elo1: 0.0,
             alpha: 0.0,
             beta: 0.0,
+            pair: None,
+            drain: SprtDrain::Complete,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReportIntervalOptions {
+    pub games: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+impl Default for ReportIntervalOptions {
+    fn default() -> Self {
+        ReportIntervalOptions {
+            games: Some(10),
+            time: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AffinityOptions {
+    pub cores_per_engine: usize,
+}
+
+impl Default for AffinityOptions {
+    fn default() -> Self {
+        AffinityOptions {
+            cores_per_engine: 1,
+        }
+    }
+}
+
+/// How results are reported while the tournament runs.
+#[derive(Debug, Clone, Copy)]
+pub struct StatsOptions {
+    /// Confidence level of Elo error bars, e.g. 0.95.
+    pub confidence: f64,
+    pub elo: EloReport,
+    pub performance: bool,
+    pub progress: bool,
+    /// Also print the results of the tested pair in fishtest's layout.
+    pub fishtest: bool,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        StatsOptions {
+            confidence: 0.95,
+            elo: EloReport::Both,
+            performance: false,
+            progress: false,
+            fishtest: false,
         }
     }
 }
 
+/// Which Elo estimates the reports show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EloReport {
+    #[default]
+    Both,
+    Logistic,
+    Normalized,
+}
+
+impl EloReport {
+    pub fn logistic(self) -> bool {
+        self != EloReport::Normalized
+    }
+
+    pub fn normalized(self) -> bool {
+        self != EloReport::Logistic
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineIdentity {
+    pub name: String,
+    /// The engine's `id name`, which usually includes its version.
+    pub usi_name: Option<String>,
+    pub usi_author: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CliOptions {
     pub engines: Vec<EngineOptions>,
     pub book: Option<BookOptions>,
-    pub games: Option<u64>,
-    pub rounds: u64,
-    pub concurrency: u64,
+    /// Number of rounds, each of which plays every pairing once. Infinite if unset.
+    pub rounds: Option<u64>,
+    /// Number of games per pairing within a round, all on the same opening. Always even.
+    pub games: u64,
+    pub concurrency: Concurrency,
     pub rand_seed: Option<u64>,
+    pub random_pairings: bool,
+    /// Seed for the pairing schedule. Derived from `rand_seed` if unset.
+    pub pairing_seed: Option<u64>,
+    /// Play a gauntlet in which the first `N` engines each play every other engine, rather
+    /// than a round robin.
+    pub gauntlet_seeds: Option<usize>,
+    pub variant: shogi::Variant,
+    pub impasse_rule: shogi::ImpasseRule,
     pub meta: MetaDataOptions,
     pub pgn: Option<PgnOutOptions>,
+    pub events: Option<String>,
+    /// PGN files from earlier runs whose results are added to the stats.
+    pub import_pgn: Vec<String>,
     pub adjudication: AdjudicationOptions,
-    pub report_interval: Option<u64>,
+    pub report_interval: ReportIntervalOptions,
     pub sprt: Option<SprtOptions>,
+    pub stats: StatsOptions,
+    pub affinity: Option<AffinityOptions>,
+    /// Refuse to start when engine threads oversubscribe the CPUs, rather than warning.
+    pub strict: bool,
+    /// Exit with a code reflecting the SPRT verdict.
+    pub exit_code: bool,
+    /// Print the schedule instead of playing it.
+    pub dry_run: bool,
 }
 
 impl CliOptions {
-    pub fn engine_names(&self) -> Vec<String> {
-        self.engines
+    /// Whether the schedule plays each opening as a pair of games with colours swapped, whose
+    /// results the SPRT can then be computed from. See `tournament::RoundRobin`.
+    pub fn pairs_games(&self) -> bool {
+        self.games.is_multiple_of(2)
+            && self
+                .book
+                .as_ref()
+                .is_none_or(|book| book.color_policy != ColorPolicy::FirstEngineSente)
+    }
+
+    pub fn pairing_seed(&self) -> Option<u64> {
+        // Offset from the book seed so that the two RNGs don't produce the same stream.
+        const PAIRING_SEED_OFFSET: u64 = 0x9e37_79b9_7f4a_7c15;
+        self.pairing_seed.or(self
+            .rand_seed
+            .map(|seed| seed.wrapping_add(PAIRING_SEED_OFFSET)))
+    }
+
+    /// Starts each engine once to learn its name and the `id name` it reports, which also
+    /// checks that it completes the `usi` and `isready` handshakes before any game starts.
+    /// Engines ending up with the same name are told apart as by `disambiguate_names`.
+    pub fn identify_engines(&self) -> std::io::Result<Vec<EngineIdentity>> {
+        let mut identities = self
+            .engines
             .iter()
-            .map(|e| e.builder.init().unwrap().name().to_string())
-            .collect()
+            .map(|e| {
+                let mut engine = e.builder.init()?;
+                engine.isready()?;
+                Ok(EngineIdentity {
+                    name: engine.name().to_string(),
+                    usi_name: engine.usi_name().map(String::from),
+                    usi_author: engine.usi_author().map(String::from),
+                })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let names = identities.iter().map(|e| e.name.clone()).collect();
+        for (identity, name) in identities.iter_mut().zip(disambiguate_names(names)) {
+            if identity.name != name {
+                eprintln!(
+                    "Warning; More than one engine is named {}, calling this one {name}; use -engine name= to choose names",
+                    identity.name
+                );
+                identity.name = name;
+            }
+        }
+        Ok(identities)
     }
 }
 
+/// Makes engine names unique, so results and PGN tags can be told apart. The first engine with
+/// a name keeps it, later ones get a suffix counting from `#2`, skipping any that is taken.
+pub fn disambiguate_names(names: Vec<String>) -> Vec<String> {
+    let mut taken: std::collections::HashSet<String> = names.iter().cloned().collect();
+    let mut seen = std::collections::HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            if seen.insert(name.clone()) {
+                return name;
+            }
+            let unique = (2..)
+                .map(|i| format!("{name} #{i}"))
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap();
+            taken.insert(unique.clone());
+            unique
+        })
+        .collect()
+}
+
 impl Default for CliOptions {
     fn default() -> Self {
         CliOptions {
             engines: vec![],
             book: None,
-            games: None,
-            rounds: 2,
-            concurrency: 1,
+            rounds: None,
+            games: 2,
+            concurrency: Concurrency::Fixed(1),
             rand_seed: None,
+            random_pairings: false,
+            pairing_seed: None,
+            gauntlet_seeds: None,
+            variant: shogi::Variant::Standard,
+            impasse_rule: shogi::ImpasseRule::default(),
             meta: MetaDataOptions {
                 event_name: String::from("?"),
                 site_name: String::from("?"),
             },
             pgn: None,
+            events: None,
+            import_pgn: vec![],
             adjudication: AdjudicationOptions::default(),
-            report_interval: Some(10),
+            report_interval: ReportIntervalOptions::default(),
             sprt: None,
+            stats: StatsOptions::default(),
+            affinity: None,
+            strict: false,
+            exit_code: false,
+            dry_run: false,
         }
     }
 }
@@ -147,6 +454,17 @@ pub struct EngineOptions {
     pub time_control: tc::TimeControl,
     pub time_margin: Duration,
     pub restart: bool,
+    /// How many of the engine's centipawns make up one centipawn on the common scale used for
+    /// adjudication. None is the identity scale.
+    pub score_scale: Option<f64>,
+    /// Known absolute Elo of the engine, which the standings are shifted to agree with.
+    pub anchor_elo: Option<f64>,
+}
+
+impl EngineOptions {
+    pub fn score_scale(&self) -> f64 {
+        self.score_scale.unwrap_or(1.0)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -158,6 +476,10 @@ pub struct PgnOutOptions {
     pub track_hashfull: bool,
     pub track_timeleft: bool,
     pub track_latency: bool,
+    /// Continue an existing file instead of refusing to overwrite it.
+    pub resume: bool,
+    /// Sync each game to disk once it has been written.
+    pub fsync: bool,
 }
 
 impl Default for PgnOutOptions {
@@ -170,6 +492,8 @@ impl Default for PgnOutOptions {
             track_hashfull: false,
             track_timeleft: false,
             track_latency: false,
+            resume: false,
+            fsync: false,
         }
     }
 }
@@ -185,42 +509,66 @@ fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> b
         "cmd" => {
             engine.builder.cmd = String::from(value);
         }
-        "tc" => {
-            if engine.time_control != tc::TimeControl::None {
-                eprint!("Warning; Specifying multiple time controls!");
-            }
-            if let Some(tc) = tc::TimeControl::parse(value) {
-                engine.time_control = tc;
-            } else {
-                eprint!("Invalid time control specification {value}");
-                return false;
-            }
+        "args" => {
+            engine.builder.args = value.split_whitespace().map(String::from).collect();
         }
-        "st" => {
+        "host" => {
+            engine.builder.host = Some(String::from(value));
+        }
+        "tc" | "st" | "nodes" => {
             if engine.time_control != tc::TimeControl::None {
                 eprint!("Warning; Specifying multiple time controls!");
             }
-            match value.parse::<u64>() {
-                Ok(value) => {
-                    engine.time_control = tc::TimeControl::MoveTime(Duration::from_millis(value));
-                }
-                Err(_) => {
-                    eprintln!("Expected number for st option");
+            // `st` used to be read as milliseconds, so a large value is likely left over from that.
+            if name == "st" && value.parse::<f64>().is_ok_and(|secs| secs >= 1000.0) {
+                eprintln!("Warning; st={value} is in seconds, not milliseconds");
+            }
+            // `st` and `nodes` are spelled the same inside `tc=`, so share its parser.
+            let spec = match name {
+                "tc" => String::from(value),
+                _ => format!("{name}={value}"),
+            };
+            match tc::TimeControl::parse(&spec) {
+                Ok(tc) => engine.time_control = tc,
+                Err(error) => {
+                    eprintln!("{error}");
                     return false;
                 }
             }
         }
-        "nodes" => {
-            if engine.time_control != tc::TimeControl::None {
-                eprint!("Warning; Specifying multiple time controls!");
+        "keepalive" => match value.parse::<u64>() {
+            Ok(value) if value > 0 => {
+                engine.builder.keepalive = Some(Duration::from_secs(value));
             }
-            match value.parse::<u64>() {
-                Ok(value) => engine.time_control = tc::TimeControl::Nodes(value),
-                Err(_) => {
-                    eprintln!("Expected number for st option");
-                    return false;
-                }
+            _ => {
+                eprintln!("Expected positive number for keepalive option");
+                return false;
+            }
+        },
+        "readytimeout" => match value.parse::<f64>() {
+            Ok(value) if value > 0.0 && value.is_finite() => {
+                engine.builder.ready_timeout = Some(Duration::from_secs_f64(value));
+            }
+            _ => {
+                eprintln!("Expected positive number for readytimeout option");
+                return false;
             }
+        },
+        "warmup" => {
+            let warmup = if value == "bench" {
+                Some(engine::Warmup::Bench)
+            } else {
+                value
+                    .strip_prefix("depth:")
+                    .and_then(|depth| depth.parse::<u32>().ok())
+                    .filter(|&depth| depth > 0)
+                    .map(engine::Warmup::Depth)
+            };
+            let Some(warmup) = warmup else {
+                eprintln!("Invalid value {value} for warmup option (must be depth:D or bench)");
+                return false;
+            };
+            engine.builder.warmup = Some(warmup);
         }
         "timemargin" => match value.parse::<u64>() {
             Ok(value) => engine.time_margin = Duration::from_millis(value),
@@ -229,6 +577,20 @@ fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> b
                 return false;
             }
         },
+        "scorescale" => match value.parse::<f64>() {
+            Ok(value) if value > 0.0 && value.is_finite() => engine.score_scale = Some(value),
+            _ => {
+                eprintln!("Invalid value {value} for engine scorescale option (must be positive)");
+                return false;
+            }
+        },
+        "elo" => match value.parse::<f64>() {
+            Ok(value) if value.is_finite() => engine.anchor_elo = Some(value),
+            _ => {
+                eprintln!("Invalid value {value} for engine elo option");
+                return false;
+            }
+        },
         "restart" => match value {
             "on" => engine.restart = true,
             "off" => engine.restart = false,
@@ -237,6 +599,47 @@ fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> b
                 return false;
             }
         },
+        "logfile" => {
+            engine.builder.log_file = Some(String::from(value));
+        }
+        "logappend" => match value {
+            "on" => engine.builder.log_append = true,
+            "off" => engine.builder.log_append = false,
+            _ => {
+                eprintln!("Invalid value {value} for engine logappend option");
+                return false;
+            }
+        },
+        "newgame" => match value {
+            "true" => engine.builder.skip_usinewgame = false,
+            "false" => engine.builder.skip_usinewgame = true,
+            _ => {
+                eprintln!("Invalid value {value} for engine newgame option");
+                return false;
+            }
+        },
+        "readyeverymove" => match value {
+            "true" => engine.builder.ready_every_move = true,
+            "false" => engine.builder.ready_every_move = false,
+            _ => {
+                eprintln!("Invalid value {value} for engine readyeverymove option");
+                return false;
+            }
+        },
+        "maxline" => match value.parse::<usize>() {
+            Ok(value) if value > 0 => engine.builder.max_line_length = Some(value),
+            _ => {
+                eprintln!("Expected positive number for maxline option");
+                return false;
+            }
+        },
+        "startupretries" => match value.parse::<u32>() {
+            Ok(value) => engine.builder.startup_retries = value,
+            Err(_) => {
+                eprintln!("Expected number for startupretries option");
+                return false;
+            }
+        },
         "proto" => match value {
             "usi" => {}
             _ => {
@@ -244,6 +647,22 @@ fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> b
                 return false;
             }
         },
+        "lateoption" => {
+            let Some((optionname, value)) = value.split_once('=') else {
+                eprintln!("Expected NAME=VALUE for engine lateoption, got {value}");
+                return false;
+            };
+            engine
+                .builder
+                .late_options
+                .push((optionname.to_string(), value.to_string()));
+        }
+        name if let Some(variable) = name.strip_prefix("env.") => {
+            engine
+                .builder
+                .env
+                .push((variable.to_string(), value.to_string()));
+        }
         name if let Some(optionname) = name.strip_prefix("option.") => {
             engine
                 .builder
@@ -258,89 +677,647 @@ fn parse_engine_option(engine: &mut EngineOptions, name: &str, value: &str) -> b
     true
 }
 
-pub fn parse() -> Option<CliOptions> {
-    let args: Vec<String> = std::env::args().skip(1).collect();
+/// Reads the engines of an engine configuration file. In JSON, this is a list of engines as
+/// fastchess writes them, on its own or as the `engines` of a fastchess configuration file. In
+/// TOML, each `[[engine]]` table is an engine.
+fn read_engine_config(file: &str) -> Option<Vec<EngineOptions>> {
+    let value = match config::read(file) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            return None;
+        }
+    };
 
-    let mut options = CliOptions::default();
-    let mut each_options = Vec::<(String, String)>::new();
+    let engines = match &value {
+        serde_json::Value::Array(engines) => engines,
+        serde_json::Value::Object(tables) => match tables.get("engines").or(tables.get("engine")) {
+            Some(serde_json::Value::Array(engines)) => engines,
+            _ => {
+                eprintln!("No engines in engine configuration {file}");
+                return None;
+            }
+        },
+        _ => {
+            eprintln!("Expected a list of engines in engine configuration {file}");
+            return None;
+        }
+    };
+    engines
+        .iter()
+        .map(|engine| config_engine(engine, file))
+        .collect()
+}
 
-    let mut it = args.iter().peekable();
-    while let Some(flag) = it.next() {
-        match flag.as_str() {
-            "-version" | "--version" => {
-                println!("Shogitest version 0.1.1");
+/// Reads an engine of a configuration file, whose keys are read as by `config_engine_options`.
+fn config_engine(value: &serde_json::Value, file: &str) -> Option<EngineOptions> {
+    let Some(entries) = value.as_object() else {
+        eprintln!("Expected an engine table in {file}");
+        return None;
+    };
+    let mut engine = EngineOptions::default();
+    for (name, value) in entries {
+        // Kept apart, as an argument may contain spaces.
+        if name == "args"
+            && let Some(args) = value.as_array()
+        {
+            let Some(args) = args.iter().map(config::text).collect() else {
+                eprintln!("Invalid args for engine in {file}");
+                return None;
+            };
+            engine.builder.args = args;
+            continue;
+        }
+        let options = match config_engine_options(name, value) {
+            Ok(options) => options,
+            Err(err) => {
+                eprintln!("{err} in {file}");
+                return None;
+            }
+        };
+        for (name, value) in options {
+            if !parse_engine_option(&mut engine, &name, &value) {
                 return None;
             }
+        }
+    }
+    Some(engine)
+}
+
+/// Turns a key of an engine in a configuration file into the `-engine` options it stands for.
+/// Each key is an `-engine` option, except that `args` may be an array; `options`, `lateoptions`
+/// and `env` hold USI options, late USI options and environment variables, as a table or as
+/// fastchess's list of name and value pairs; `restart` and `logappend` may be booleans; and
+/// fastchess's `limit` is read as the time control. fastchess's `variant` is ignored.
+fn config_engine_options(
+    name: &str,
+    value: &serde_json::Value,
+) -> Result<Vec<(String, String)>, String> {
+    use serde_json::Value;
+
+    let invalid = || format!("Invalid value for engine option {name}");
+    match (name, value) {
+        ("options" | "lateoptions" | "env", _) => {
+            let pairs: Vec<(&str, &Value)> = match value {
+                Value::Object(entries) => entries.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item.as_array().map(Vec::as_slice) {
+                        Some([Value::String(key), value]) => Ok((key.as_str(), value)),
+                        _ => Err(invalid()),
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(invalid()),
+            };
+            pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = config::text(value).ok_or_else(invalid)?;
+                    Ok(match name {
+                        "options" => (format!("option.{key}"), value),
+                        "lateoptions" => (String::from("lateoption"), format!("{key}={value}")),
+                        _ => (format!("env.{key}"), value),
+                    })
+                })
+                .collect()
+        }
+        ("args", Value::Array(args)) => {
+            let args: Option<Vec<_>> = args.iter().map(config::text).collect();
+            Ok(vec![(
+                String::from(name),
+                args.ok_or_else(invalid)?.join(" "),
+            )])
+        }
+        ("restart" | "logappend", Value::Bool(on)) => Ok(vec![(
+            String::from(name),
+            String::from(if *on { "on" } else { "off" }),
+        )]),
+        ("limit", _) => fastchess_limit(value).ok_or_else(invalid),
+        ("variant", _) => Ok(vec![]),
+        _ => {
+            let value = config::text(value).ok_or_else(invalid)?;
+            Ok(vec![(String::from(name), value)])
+        }
+    }
+}
 
-            "-variant" => {
+/// Turns fastchess's `limit` of an engine, whose times are in milliseconds and zero when unset,
+/// into `-engine` time control options.
+fn fastchess_limit(limit: &serde_json::Value) -> Option<Vec<(String, String)>> {
+    let number = |value: Option<&serde_json::Value>| match value {
+        Some(value) => value.as_f64().filter(|value| *value >= 0.0),
+        None => Some(0.0),
+    };
+    let tc = limit.get("tc");
+    let time = number(tc.and_then(|tc| tc.get("time")))?;
+    let increment = number(tc.and_then(|tc| tc.get("increment")))?;
+    let fixed_time = number(tc.and_then(|tc| tc.get("fixed_time")))?;
+    let nodes = number(limit.get("nodes"))?;
+
+    let mut options = vec![];
+    if fixed_time > 0.0 {
+        options.push((String::from("st"), (fixed_time / 1000.0).to_string()));
+    } else if time > 0.0 || increment > 0.0 {
+        let tc = format!("{}+{}", time / 1000.0, increment / 1000.0);
+        options.push((String::from("tc"), tc));
+    }
+    if nodes > 0.0 {
+        options.push((String::from("nodes"), nodes.to_string()));
+    }
+    Some(options)
+}
+
+/// How a flag takes its arguments on the command line, which also decides how a configuration
+/// file sets it.
+#[derive(Debug, Clone, Copy)]
+enum FlagKind {
+    /// No argument. Set with `flag = true` in a configuration file.
+    Switch,
+    /// A single argument. Set with `flag = value`.
+    Value,
+    /// A single argument, and each use of the flag adds another. Set with `flag = value` or an
+    /// array of values.
+    Repeated,
+    /// `key=value` arguments, and the bare `switches`. Set with a `[flag]` table, in which the
+    /// switches are set with `true`. A bare first argument is taken as the `positional` key,
+    /// which a configuration file may also set with `flag = value`.
+    Table {
+        keys: &'static [&'static str],
+        switches: &'static [&'static str],
+        positional: Option<&'static str>,
+    },
+    /// `key=value` engine options. `-each` is set with an `[each]` table, and `[[engine]]`
+    /// tables declare engines.
+    Engine,
+}
+
+#[derive(Debug)]
+struct Flag {
+    name: &'static str,
+    kind: FlagKind,
+}
+
+const fn flag(name: &'static str, kind: FlagKind) -> Flag {
+    Flag { name, kind }
+}
+
+const fn table(name: &'static str, keys: &'static [&'static str]) -> Flag {
+    Flag {
+        name,
+        kind: FlagKind::Table {
+            keys,
+            switches: &[],
+            positional: None,
+        },
+    }
+}
+
+/// Every command line flag, apart from `-config` and `-version`, which are handled before the
+/// others are parsed.
+const FLAGS: &[Flag] = &[
+    flag("variant", FlagKind::Value),
+    flag("impasse", FlagKind::Value),
+    flag("event", FlagKind::Value),
+    flag("site", FlagKind::Value),
+    flag("engine", FlagKind::Engine),
+    flag("engineconf", FlagKind::Value),
+    flag("each", FlagKind::Engine),
+    flag(
+        "openings",
+        FlagKind::Table {
+            keys: &[
+                "file",
+                "format",
+                "depth",
+                "plies",
+                "order",
+                "start",
+                "colorpolicy",
+                "maxmaterial",
+                "minply",
+                "maxply",
+            ],
+            switches: &["norepeat", "dedup", "stride", "buckets"],
+            positional: None,
+        },
+    ),
+    flag("concurrency", FlagKind::Value),
+    flag("srand", FlagKind::Value),
+    flag("pairings", FlagKind::Value),
+    flag("tournament", FlagKind::Value),
+    flag("seeds", FlagKind::Value),
+    flag("pairingseed", FlagKind::Value),
+    flag("rounds", FlagKind::Value),
+    flag("games", FlagKind::Value),
+    flag("repeat", FlagKind::Switch),
+    flag("events", FlagKind::Value),
+    flag("importpgn", FlagKind::Repeated),
+    table(
+        "pgnout",
+        &[
+            "file", "nodes", "seldepth", "nps", "hashfull", "timeleft", "latency", "resume",
+            "fsync",
+        ],
+    ),
+    flag("maxmoves", FlagKind::Value),
+    flag("moveunit", FlagKind::Value),
+    table("draw", &["movenumber", "movecount", "score"]),
+    table("resign", &["movecount", "score", "twosided"]),
+    table("watchdog", &["maxnps", "minnps", "timefactor", "forfeit"]),
+    flag(
+        "ratinginterval",
+        FlagKind::Table {
+            keys: &["games", "time"],
+            switches: &[],
+            positional: Some("games"),
+        },
+    ),
+    flag("confidence", FlagKind::Value),
+    flag("elo", FlagKind::Value),
+    table(
+        "sprt",
+        &["elo0", "elo1", "alpha", "beta", "model", "pair", "drain"],
+    ),
+    table("affinity", &["cores"]),
+    flag("progress", FlagKind::Switch),
+    flag("perf", FlagKind::Switch),
+    flag("fishtest", FlagKind::Switch),
+    flag("testEnv", FlagKind::Switch),
+    flag("strict", FlagKind::Switch),
+    flag("exitcode", FlagKind::Switch),
+    flag("dryrun", FlagKind::Switch),
+    flag("recover", FlagKind::Switch),
+];
+
+fn find_flag(name: &str) -> Option<&'static Flag> {
+    FLAGS.iter().find(|flag| flag.name == name)
+}
+
+/// A flag and its arguments, from the command line or a configuration file.
+#[derive(Debug)]
+struct FlagArgs {
+    flag: &'static Flag,
+    args: Vec<String>,
+}
+
+/// Splits the command line into flags and their arguments, as the kind of each flag says.
+fn split_flags(args: &[String]) -> Option<Vec<FlagArgs>> {
+    let mut flags = vec![];
+    let mut it = args.iter().peekable();
+    while let Some(arg) = it.next() {
+        let Some(flag) = arg.strip_prefix('-').and_then(find_flag) else {
+            eprintln!("Unrecognised command line flag {arg}");
+            return None;
+        };
+        let mut flag_args = vec![];
+        match flag.kind {
+            FlagKind::Switch => {}
+            FlagKind::Value | FlagKind::Repeated => {
                 let Some(value) = it.next() else {
-                    eprintln!("No value for -variant");
+                    eprintln!("No value for -{}", flag.name);
                     return None;
                 };
-                if value != "standard" {
-                    eprintln!("Unrecognised value of -variant");
-                    return None;
+                flag_args.push(value.clone());
+            }
+            FlagKind::Table {
+                keys,
+                switches,
+                positional,
+            } => {
+                if let Some(key) = positional
+                    && let Some(value) =
+                        it.next_if(|arg| !arg.starts_with('-') && !arg.contains('='))
+                {
+                    flag_args.push(format!("{key}={value}"));
+                }
+                while let Some(option) = it.next_if(|arg| {
+                    !arg.starts_with('-') && (arg.contains('=') || switches.contains(&arg.as_str()))
+                }) {
+                    if let Some((key, _)) = option.split_once('=')
+                        && !keys.contains(&key)
+                    {
+                        eprintln!("Invalid key {key} for -{}", flag.name);
+                        return None;
+                    }
+                    flag_args.push(option.clone());
                 }
             }
+            FlagKind::Engine => {
+                while let Some(option) =
+                    it.next_if(|arg| !arg.starts_with('-') && arg.contains('='))
+                {
+                    flag_args.push(option.clone());
+                }
+            }
+        }
+        flags.push(FlagArgs {
+            flag,
+            args: flag_args,
+        });
+    }
+    Some(flags)
+}
 
-            "-event" => {
-                let Some(value) = it.next() else {
-                    eprintln!("No value for -event");
+/// A tournament configuration file, as the flags it sets and the engines it declares.
+#[derive(Debug, Default)]
+struct Config {
+    flags: Vec<FlagArgs>,
+    engines: Vec<EngineOptions>,
+}
+
+/// Reads a tournament configuration file, which sets each flag as its kind in `FLAGS` says.
+/// Arrays are joined with commas. Unknown flags and keys are skipped with a warning.
+fn read_tournament_config(file: &str) -> Option<Config> {
+    use serde_json::Value;
+
+    let tables = match config::read(file) {
+        Ok(Value::Object(tables)) => tables,
+        Ok(_) => {
+            eprintln!("Expected a table of flags in {file}");
+            return None;
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            return None;
+        }
+    };
+
+    let text = |name: &str, value: &Value| {
+        let text = config::text(value);
+        if text.is_none() {
+            eprintln!("Invalid value for {name} in {file}");
+        }
+        text
+    };
+    let boolean = |name: &str, value: &Value| {
+        let value = value.as_bool();
+        if value.is_none() {
+            eprintln!("Invalid value for {name} in {file} (must be a boolean)");
+        }
+        value
+    };
+
+    let mut config = Config::default();
+    for (name, value) in &tables {
+        if name == "engine" {
+            let engines = match value {
+                Value::Array(engines) => engines.as_slice(),
+                value => std::slice::from_ref(value),
+            };
+            for engine in engines {
+                config.engines.push(config_engine(engine, file)?);
+            }
+            continue;
+        }
+
+        let Some(flag) = find_flag(name) else {
+            eprintln!("Warning; Ignoring unknown key {name} in {file}");
+            continue;
+        };
+        let args = match (flag.kind, value) {
+            (FlagKind::Table { keys, switches, .. }, Value::Object(table)) => {
+                let mut args = vec![];
+                for (key, value) in table {
+                    if switches.contains(&key.as_str()) {
+                        if boolean(key, value)? {
+                            args.push(key.clone());
+                        }
+                    } else if keys.contains(&key.as_str()) {
+                        args.push(format!("{key}={}", text(key, value)?));
+                    } else {
+                        eprintln!("Warning; Ignoring unknown key {key} in [{name}] in {file}");
+                    }
+                }
+                args
+            }
+            (FlagKind::Engine, Value::Object(table)) => {
+                let mut args = vec![];
+                for (key, value) in table {
+                    match config_engine_options(key, value) {
+                        Ok(options) => args.extend(
+                            options
+                                .into_iter()
+                                .map(|(name, value)| format!("{name}={value}")),
+                        ),
+                        Err(err) => {
+                            eprintln!("{err} in [{name}] in {file}");
+                            return None;
+                        }
+                    }
+                }
+                args
+            }
+            (
+                FlagKind::Table {
+                    positional: Some(key),
+                    ..
+                },
+                value,
+            ) => vec![format!("{key}={}", text(name, value)?)],
+            (FlagKind::Table { .. } | FlagKind::Engine, _) => {
+                eprintln!("Invalid value for {name} in {file} (must be a table)");
+                return None;
+            }
+            (FlagKind::Switch, value) => {
+                if boolean(name, value)? {
+                    config.flags.push(FlagArgs { flag, args: vec![] });
+                }
+                continue;
+            }
+            (FlagKind::Repeated, Value::Array(values)) => {
+                for value in values {
+                    config.flags.push(FlagArgs {
+                        flag,
+                        args: vec![text(name, value)?],
+                    });
+                }
+                continue;
+            }
+            (FlagKind::Value | FlagKind::Repeated, value) => vec![text(name, value)?],
+        };
+        config.flags.push(FlagArgs { flag, args });
+    }
+    Some(config)
+}
+
+/// Reads each `-config FILE` and splits the remaining arguments into flags. A flag given on
+/// the command line replaces the files' use of it as a whole, apart from the engine options of
+/// `-each`, which are added to the files'.
+fn expand_config(args: Vec<String>) -> Option<(Vec<FlagArgs>, Vec<EngineOptions>)> {
+    let mut config = Config::default();
+    let mut rest = vec![];
+    let mut it = args.into_iter();
+    while let Some(arg) = it.next() {
+        if arg != "-config" {
+            rest.push(arg);
+            continue;
+        }
+        let Some(file) = it.next() else {
+            eprintln!("No value for -config");
+            return None;
+        };
+        let file_config = read_tournament_config(&file)?;
+        config.flags.extend(file_config.flags);
+        config.engines.extend(file_config.engines);
+    }
+
+    let rest = split_flags(&rest)?;
+    let mut flags: Vec<FlagArgs> = config
+        .flags
+        .into_iter()
+        .filter(|config_flag| {
+            matches!(config_flag.flag.kind, FlagKind::Engine)
+                || !rest
+                    .iter()
+                    .any(|flag| flag.flag.name == config_flag.flag.name)
+        })
+        .collect();
+    flags.extend(rest);
+    Some((flags, config.engines))
+}
+
+/// Adds engines read from a configuration file, keeping track of their names so that a later
+/// `-engine` with the same name amends them.
+fn add_config_engines(
+    options: &mut CliOptions,
+    config_engines: &mut std::collections::HashMap<String, usize>,
+    engines: Vec<EngineOptions>,
+) {
+    for engine in engines {
+        if let Some(name) = &engine.builder.name {
+            config_engines.insert(name.clone(), options.engines.len());
+        }
+        options.engines.push(engine);
+    }
+}
+
+/// Exit code for invalid command lines and setups that fail before any game is played. It is
+/// kept apart from the SPRT verdicts' codes so that scripts can tell a broken setup from a
+/// rejected patch, and 125 makes `git bisect run` skip the revision.
+pub const SETUP_FAILURE_EXIT_CODE: u8 = 125;
+
+/// What the command line asks shogitest to do.
+#[derive(Debug)]
+pub enum Command {
+    Run(Box<CliOptions>),
+    /// Print the version and exit successfully, whatever else is on the command line.
+    Version,
+}
+
+pub fn parse() -> Option<Command> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args
+        .iter()
+        .any(|arg| arg == "-version" || arg == "--version")
+    {
+        return Some(Command::Version);
+    }
+    parse_args(args).map(|options| Command::Run(Box::new(options)))
+}
+
+fn parse_args(args: Vec<String>) -> Option<CliOptions> {
+    let (flags, engines) = expand_config(args)?;
+    let mut options = CliOptions::default();
+    let mut each_options = Vec::<(String, String)>::new();
+    // Engines read by -engineconf or -config, by name, which a later -engine with that name
+    // amends.
+    let mut config_engines = std::collections::HashMap::<String, usize>::new();
+    add_config_engines(&mut options, &mut config_engines, engines);
+
+    for FlagArgs { flag, args } in &flags {
+        // Value flags always have exactly one argument, see `split_flags`.
+        match flag.name {
+            "variant" => {
+                let value = &args[0];
+                let Some(variant) = shogi::Variant::parse(value) else {
+                    eprintln!("Unrecognised value of -variant");
                     return None;
                 };
-                options.meta.event_name = value.to_string();
+                options.variant = variant;
             }
 
-            "-site" => {
-                let Some(value) = it.next() else {
-                    eprintln!("No value for -site");
+            "impasse" => {
+                let value = &args[0];
+                let Some(impasse_rule) = shogi::ImpasseRule::parse(value) else {
+                    eprintln!("Unrecognised value of -impasse");
                     return None;
                 };
+                options.impasse_rule = impasse_rule;
+            }
+
+            "event" => {
+                let value = &args[0];
+                options.meta.event_name = value.to_string();
+            }
+
+            "site" => {
+                let value = &args[0];
                 options.meta.site_name = value.to_string();
             }
 
-            "-engine" => {
-                let mut engine = EngineOptions::default();
-                while let Some(option) = it.peek()
-                    && !option.starts_with("-")
-                    && let Some((name, value)) = option.split_once('=')
-                {
-                    it.next(); // consume token
+            "engine" => {
+                let engine_options: Vec<_> = args
+                    .iter()
+                    .filter_map(|option| option.split_once('='))
+                    .collect();
 
-                    if !parse_engine_option(&mut engine, name, value) {
+                let configured = engine_options
+                    .iter()
+                    .find(|(name, _)| *name == "name")
+                    .and_then(|(_, value)| config_engines.get(*value));
+                let engine = match configured {
+                    Some(&index) => &mut options.engines[index],
+                    None => {
+                        options.engines.push(EngineOptions::default());
+                        options.engines.last_mut().unwrap()
+                    }
+                };
+                for (name, value) in engine_options {
+                    if !parse_engine_option(engine, name, value) {
                         return None;
                     }
                 }
-                options.engines.push(engine);
             }
 
-            "-each" => {
-                while let Some(option) = it.peek()
-                    && !option.starts_with("-")
-                    && let Some((name, value)) = option.split_once('=')
-                {
-                    it.next(); // consume token
+            "engineconf" => {
+                let engines = read_engine_config(&args[0])?;
+                add_config_engines(&mut options, &mut config_engines, engines);
+            }
 
+            "each" => {
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
                     each_options.push((name.to_string(), value.to_string()));
                 }
             }
 
-            "-openings" => {
-                if options.book.is_some() {
-                    eprintln!("Duplicate -openings flag");
-                    return None;
-                }
+            "openings" => {
+                // Repeating -openings adds more files to the same book.
+                let mut book = options.book.take().unwrap_or_default();
+                for option in args {
+                    if option.as_str() == "norepeat" {
+                        book.no_repeat = true;
+                        continue;
+                    }
+                    if option.as_str() == "dedup" {
+                        book.dedup = true;
+                        continue;
+                    }
+                    if option.as_str() == "stride" {
+                        book.stride = true;
+                        continue;
+                    }
+                    if option.as_str() == "buckets" {
+                        book.buckets = true;
+                        continue;
+                    }
 
-                let mut book = BookOptions::default();
-                while let Some(option) = it.peek()
-                    && !option.starts_with("-")
-                    && let Some((name, value)) = option.split_once('=')
-                {
-                    it.next(); // consume token
+                    let Some((name, value)) = option.split_once('=') else {
+                        unreachable!("split_flags only passes switches and key=value options");
+                    };
 
                     match name {
                         "file" => {
-                            book.file = String::from(value);
+                            book.files.extend(value.split(',').map(String::from));
                         }
                         "order" => {
                             book.random_order = value == "random";
@@ -361,8 +1338,59 @@ pub fn parse() -> Option<CliOptions> {
                                 return None;
                             }
                         }
+                        "depth" => {
+                            let Ok(value) = value.parse::<usize>() else {
+                                eprintln!(
+                                    "invalid openings depth {value} (must be unsigned integer)"
+                                );
+                                return None;
+                            };
+                            book.depth = value;
+                        }
+                        "plies" => {
+                            let Ok(value) = value.parse::<usize>() else {
+                                eprintln!(
+                                    "invalid openings plies {value} (must be unsigned integer)"
+                                );
+                                return None;
+                            };
+                            book.plies = Some(value);
+                        }
+                        "maxmaterial" => {
+                            let Ok(value) = value.parse::<u32>() else {
+                                eprintln!(
+                                    "invalid openings maxmaterial {value} (must be unsigned integer)"
+                                );
+                                return None;
+                            };
+                            book.max_material = Some(value);
+                        }
+                        "minply" | "maxply" => {
+                            let Ok(value) = value.parse::<usize>() else {
+                                eprintln!(
+                                    "invalid openings {name} {value} (must be unsigned integer)"
+                                );
+                                return None;
+                            };
+                            if name == "minply" {
+                                book.min_ply = Some(value);
+                            } else {
+                                book.max_ply = Some(value);
+                            }
+                        }
+                        "colorpolicy" => match value {
+                            "alternate" => book.color_policy = ColorPolicy::Alternate,
+                            "book" => book.color_policy = ColorPolicy::Book,
+                            "engineA-first" => book.color_policy = ColorPolicy::FirstEngineSente,
+                            _ => {
+                                eprintln!("Invalid value {value} for openings colorpolicy option");
+                                return None;
+                            }
+                        },
                         "format" => match value {
-                            "epd" => {}
+                            "epd" => book.format = Some(BookFormat::Epd),
+                            "csa" => book.format = Some(BookFormat::Csa),
+                            "bin" => book.format = Some(BookFormat::Bin),
                             _ => {
                                 eprintln!("Invalid value {value} for openings format option");
                                 return None;
@@ -377,22 +1405,24 @@ pub fn parse() -> Option<CliOptions> {
                 options.book = Some(book);
             }
 
-            "-concurrency" => {
-                let Some(option) = it.next() else { break };
-                if let Ok(option) = option.parse::<u64>() {
+            "concurrency" => {
+                let option = &args[0];
+                if option == "auto" {
+                    options.concurrency = Concurrency::Auto;
+                } else if let Ok(option) = option.parse::<u64>() {
                     if option == 0 {
                         eprint!("invalid concurrency value {option} (must be bigger than zero)");
                         return None;
                     }
-                    options.concurrency = option;
+                    options.concurrency = Concurrency::Fixed(option);
                 } else {
                     eprint!("invalid concurrency value {option} (must be unsigned integer)");
                     return None;
                 }
             }
 
-            "-srand" => {
-                let Some(option) = it.next() else { break };
+            "srand" => {
+                let option = &args[0];
                 if let Ok(option) = option.parse::<u64>() {
                     options.rand_seed = Some(option);
                 } else {
@@ -401,29 +1431,76 @@ pub fn parse() -> Option<CliOptions> {
                 }
             }
 
-            "-games" => {
-                let Some(option) = it.next() else { break };
+            "pairings" => {
+                let value = &args[0];
+                options.random_pairings = match value.as_str() {
+                    "sequential" => false,
+                    "random" => true,
+                    _ => {
+                        eprintln!("Invalid value {value} for -pairings");
+                        return None;
+                    }
+                };
+            }
+
+            "tournament" => {
+                let value = &args[0];
+                match value.as_str() {
+                    "roundrobin" => options.gauntlet_seeds = None,
+                    "gauntlet" => {
+                        options.gauntlet_seeds = Some(options.gauntlet_seeds.unwrap_or(1))
+                    }
+                    _ => {
+                        eprintln!("Invalid value {value} for -tournament");
+                        return None;
+                    }
+                }
+            }
+
+            "seeds" => {
+                let option = &args[0];
+                match option.parse::<usize>() {
+                    Ok(seeds) if seeds > 0 => options.gauntlet_seeds = Some(seeds),
+                    _ => {
+                        eprintln!("Invalid seed count {option} (must be a positive integer)");
+                        return None;
+                    }
+                }
+            }
+
+            "pairingseed" => {
+                let option = &args[0];
+                if let Ok(option) = option.parse::<u64>() {
+                    options.pairing_seed = Some(option);
+                } else {
+                    eprint!("invalid pairing seed {option} (must be unsigned integer)");
+                    return None;
+                }
+            }
+
+            "rounds" => {
+                let option = &args[0];
                 if let Ok(option) = option.parse::<u64>() {
                     if option == 0 {
-                        eprint!("invalid games value {option} (must be bigger than zero)");
+                        eprint!("invalid rounds value {option} (must be bigger than zero)");
                         return None;
                     }
-                    options.games = Some(option);
+                    options.rounds = Some(option);
                 } else {
-                    eprint!("invalid games value {option} (must be unsigned integer)");
+                    eprint!("invalid rounds value {option} (must be unsigned integer)");
                     return None;
                 }
             }
 
-            "-rounds" => {
-                let Some(option) = it.next() else { break };
+            "games" => {
+                let option = &args[0];
                 if let Ok(option) = option.parse::<u64>() {
                     if option == 0 {
-                        eprint!("invalid rounds value {option} (must be bigger than zero)");
+                        eprint!("invalid games value {option} (must be bigger than zero)");
                         return None;
                     }
                     if option % 2 != 0 {
-                        eprint!("odd value for rounds {option}! expected an even value.");
+                        eprint!("odd value for games {option}! expected an even value.");
                         return None;
                     }
                     if option > 2 {
@@ -431,25 +1508,30 @@ pub fn parse() -> Option<CliOptions> {
                             "Warning; There is often no good reason for a round to have more than two games. (Current value: {option})"
                         );
                     }
-                    options.rounds = option;
+                    options.games = option;
                 } else {
-                    eprint!("invalid rounds value {option} (must be unsigned integer)");
+                    eprint!("invalid games value {option} (must be unsigned integer)");
                     return None;
                 }
             }
 
-            "-repeat" => {
-                options.rounds = 2;
+            "repeat" => {
+                options.games = 2;
             }
 
-            "-pgnout" => {
-                let mut pgn_out = PgnOutOptions::default();
-                while let Some(option) = it.peek()
-                    && !option.starts_with("-")
-                    && let Some((name, value)) = option.split_once('=')
-                {
-                    it.next(); // consume token
+            "events" => {
+                let file = &args[0];
+                options.events = Some(file.to_string());
+            }
+
+            "importpgn" => {
+                let file = &args[0];
+                options.import_pgn.push(file.to_string());
+            }
 
+            "pgnout" => {
+                let mut pgn_out = PgnOutOptions::default();
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
                     let value_as_bool = || -> Option<bool> {
                         match value {
                             "true" => Some(true),
@@ -480,6 +1562,12 @@ pub fn parse() -> Option<CliOptions> {
                         "latency" => {
                             pgn_out.track_latency = value_as_bool()?;
                         }
+                        "resume" => {
+                            pgn_out.resume = value_as_bool()?;
+                        }
+                        "fsync" => {
+                            pgn_out.fsync = value_as_bool()?;
+                        }
                         _ => {
                             dbg!(&name);
                             dbg!(&value);
@@ -494,8 +1582,8 @@ pub fn parse() -> Option<CliOptions> {
                 options.pgn = Some(pgn_out);
             }
 
-            "-maxmoves" => {
-                let Some(value) = it.next() else { break };
+            "maxmoves" => {
+                let value = &args[0];
                 options.adjudication.max_moves = match value.to_lowercase().as_str() {
                     "inf" | "infinite" => None,
                     _ if let Ok(value) = value.parse::<u64>()
@@ -512,14 +1600,21 @@ pub fn parse() -> Option<CliOptions> {
                 };
             }
 
-            "-draw" => {
-                let mut draw = DrawAdjudicationOptions::default();
-                while let Some(option) = it.peek()
-                    && !option.starts_with("-")
-                    && let Some((name, value)) = option.split_once('=')
-                {
-                    it.next(); // consume token
+            "moveunit" => {
+                let value = &args[0];
+                options.adjudication.move_unit = match value.as_str() {
+                    "plies" => MoveUnit::Plies,
+                    "moves" => MoveUnit::FullMoves,
+                    _ => {
+                        eprintln!("Invalid value {value} for -moveunit (must be plies or moves)");
+                        return None;
+                    }
+                };
+            }
 
+            "draw" => {
+                let mut draw = DrawAdjudicationOptions::default();
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
                     match name {
                         "movenumber" => {
                             draw.move_number = match value.parse::<usize>() {
@@ -557,14 +1652,9 @@ pub fn parse() -> Option<CliOptions> {
                 options.adjudication.draw = Some(draw);
             }
 
-            "-resign" => {
+            "resign" => {
                 let mut resign = ResignAdjudicationOptions::default();
-                while let Some(option) = it.peek()
-                    && !option.starts_with("-")
-                    && let Some((name, value)) = option.split_once('=')
-                {
-                    it.next(); // consume token
-
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
                     match name {
                         "movecount" => {
                             resign.move_count = match value.parse::<usize>() {
@@ -603,27 +1693,120 @@ pub fn parse() -> Option<CliOptions> {
                 options.adjudication.resign = Some(resign);
             }
 
-            "-ratinginterval" => {
-                let Some(option) = it.next() else { break };
-                if let Ok(option) = option.parse::<u64>() {
-                    options.report_interval = if option == 0 { None } else { Some(option) };
-                } else {
-                    eprint!("invalid games value {option} (must be unsigned integer)");
-                    return None;
+            "watchdog" => {
+                let mut watchdog = WatchdogOptions::default();
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
+                    match name {
+                        "maxnps" | "minnps" => {
+                            let nps = match value.parse::<u64>() {
+                                Ok(value) if value > 0 => value,
+                                _ => {
+                                    eprintln!("Invalid {name} {value} for -watchdog");
+                                    return None;
+                                }
+                            };
+                            if name == "maxnps" {
+                                watchdog.max_nps = Some(nps);
+                            } else {
+                                watchdog.min_nps = Some(nps);
+                            }
+                        }
+                        "timefactor" => {
+                            watchdog.time_factor = match value.parse::<f64>() {
+                                Ok(value) if value >= 1.0 && value.is_finite() => value,
+                                _ => {
+                                    eprintln!(
+                                        "Invalid timefactor {value} for -watchdog (must be at least 1)"
+                                    );
+                                    return None;
+                                }
+                            };
+                        }
+                        "forfeit" => {
+                            watchdog.forfeit = match value.to_lowercase().as_ref() {
+                                "true" => true,
+                                "false" => false,
+                                _ => {
+                                    eprintln!("Invalid forfeit {value} for -watchdog");
+                                    return None;
+                                }
+                            };
+                        }
+                        _ => {
+                            eprintln!("Invalid key {name} for -watchdog");
+                            return None;
+                        }
+                    }
                 }
+                options.adjudication.watchdog = Some(watchdog);
             }
 
-            "-sprt" => {
-                let mut sprt = SprtOptions::default();
-                while let Some(option) = it.peek()
-                    && !option.starts_with("-")
-                    && let Some((name, value)) = option.split_once('=')
-                {
-                    it.next(); // consume token
+            "ratinginterval" => {
+                let parse_games = |value: &str| match value.parse::<u64>() {
+                    Ok(value) => Some(if value == 0 { None } else { Some(value) }),
+                    Err(_) => {
+                        eprintln!("invalid games value {value} (must be unsigned integer)");
+                        None
+                    }
+                };
 
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
+                    match name {
+                        "games" => options.report_interval.games = parse_games(value)?,
+                        "time" => {
+                            options.report_interval.time = match value.parse::<f64>() {
+                                Ok(0.0) => None,
+                                Ok(value) if let Ok(value) = Duration::try_from_secs_f64(value) => {
+                                    Some(value)
+                                }
+                                _ => {
+                                    eprintln!(
+                                        "invalid time value {value} for -ratinginterval (must be seconds)"
+                                    );
+                                    return None;
+                                }
+                            };
+                        }
+                        _ => {
+                            eprintln!("Invalid key {name} for -ratinginterval");
+                            return None;
+                        }
+                    }
+                }
+            }
+
+            "confidence" => {
+                let option = &args[0];
+                match option.parse::<f64>() {
+                    Ok(value) if value > 0.0 && value < 100.0 => {
+                        options.stats.confidence = value / 100.0
+                    }
+                    _ => {
+                        eprintln!("Invalid confidence level {option} (must be a percentage)");
+                        return None;
+                    }
+                }
+            }
+
+            "elo" => {
+                let option = &args[0];
+                options.stats.elo = match option.as_str() {
+                    "both" => EloReport::Both,
+                    "lelo" => EloReport::Logistic,
+                    "nelo" => EloReport::Normalized,
+                    _ => {
+                        eprintln!("Invalid value {option} for -elo (must be nelo, lelo or both)");
+                        return None;
+                    }
+                };
+            }
+
+            "sprt" => {
+                let mut sprt = SprtOptions::default();
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
                     match name {
                         "elo0" => {
-                            sprt.nelo0 = match value.parse::<f64>() {
+                            sprt.elo0 = match value.parse::<f64>() {
                                 Ok(value) => value,
                                 _ => {
                                     eprintln!("Invalid elo0 {value} for -sprt");
@@ -632,7 +1815,7 @@ pub fn parse() -> Option<CliOptions> {
                             };
                         }
                         "elo1" => {
-                            sprt.nelo1 = match value.parse::<f64>() {
+                            sprt.elo1 = match value.parse::<f64>() {
                                 Ok(value) => value,
                                 _ => {
                                     eprintln!("Invalid elo1 {value} for -sprt");
@@ -640,6 +1823,17 @@ pub fn parse() -> Option<CliOptions> {
                                 }
                             };
                         }
+                        "model" => {
+                            sprt.model = match value {
+                                "normalized" => sprt::SprtModel::Normalized,
+                                "logistic" => sprt::SprtModel::Logistic,
+                                "trinomial" => sprt::SprtModel::Trinomial,
+                                _ => {
+                                    eprintln!("Invalid model {value} for -sprt");
+                                    return None;
+                                }
+                            };
+                        }
                         "alpha" => {
                             sprt.alpha = match value.parse::<f64>() {
                                 Ok(value) => value,
@@ -658,6 +1852,28 @@ pub fn parse() -> Option<CliOptions> {
                                 }
                             };
                         }
+                        "drain" => {
+                            sprt.drain = match value {
+                                "complete" => SprtDrain::Complete,
+                                "cancel" => SprtDrain::Cancel,
+                                _ => {
+                                    eprintln!("Invalid drain {value} for -sprt");
+                                    return None;
+                                }
+                            };
+                        }
+                        "pair" => {
+                            let pair = value.split_once(',').and_then(|(a, b)| {
+                                Some((a.parse::<usize>().ok()?, b.parse::<usize>().ok()?))
+                            });
+                            sprt.pair = match pair {
+                                Some((a, b)) if a > 0 && b > 0 && a != b => Some([a - 1, b - 1]),
+                                _ => {
+                                    eprintln!("Invalid pair {value} for -sprt");
+                                    return None;
+                                }
+                            };
+                        }
                         _ => {
                             eprintln!("Invalid key {name} for -sprt");
                             return None;
@@ -667,18 +1883,64 @@ pub fn parse() -> Option<CliOptions> {
                 options.sprt = Some(sprt);
             }
 
-            "-testEnv" => {
-                options.report_interval = None;
+            "affinity" => {
+                let mut affinity = AffinityOptions::default();
+                for (name, value) in args.iter().filter_map(|option| option.split_once('=')) {
+                    match name {
+                        "cores" => {
+                            affinity.cores_per_engine = match value.parse::<usize>() {
+                                Ok(value) if value > 0 => value,
+                                _ => {
+                                    eprintln!("Invalid cores {value} for -affinity");
+                                    return None;
+                                }
+                            };
+                        }
+                        _ => {
+                            eprintln!("Invalid key {name} for -affinity");
+                            return None;
+                        }
+                    }
+                }
+                options.affinity = Some(affinity);
+            }
+
+            "progress" => {
+                options.stats.progress = true;
             }
 
-            "-recover" => {
-                // We always recover on disconnects
+            "perf" => {
+                options.stats.performance = true;
             }
 
-            _ => {
-                eprintln!("Unrecognised command line flag {flag}");
-                return None;
+            "fishtest" => {
+                options.stats.fishtest = true;
+            }
+
+            "testEnv" => {
+                options.report_interval = ReportIntervalOptions {
+                    games: None,
+                    time: None,
+                };
+            }
+
+            "strict" => {
+                options.strict = true;
+            }
+
+            "exitcode" => {
+                options.exit_code = true;
+            }
+
+            "dryrun" => {
+                options.dry_run = true;
             }
+
+            "recover" => {
+                // We always recover on disconnects
+            }
+
+            name => unreachable!("-{name} is in FLAGS but has no parser"),
         }
     }
 
@@ -690,10 +1952,392 @@ pub fn parse() -> Option<CliOptions> {
         }
     }
 
-    if options.sprt.is_some() && options.engines.len() != 2 {
-        eprintln!("SPRT can only be done on two engines");
+    if let Err(err) = validate(&options) {
+        eprintln!("{err}");
         return None;
     }
 
     Some(options)
 }
+
+/// Checks options that depend on each other, so that mistakes are reported before any engine
+/// is started.
+pub fn validate(options: &CliOptions) -> Result<(), String> {
+    // The engines of a report on imported games are only known once the games are read.
+    if options.engines.is_empty() && !options.import_pgn.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(sprt) = &options.sprt {
+        if sprt.pair.is_none() && options.engines.len() != 2 {
+            return Err(format!(
+                "SPRT requires exactly two engines, but {} were given; use -sprt pair=I,J to test two engines of a larger tournament",
+                options.engines.len()
+            ));
+        }
+        if sprt.pair().iter().any(|&i| i >= options.engines.len()) {
+            return Err(String::from(
+                "SPRT pair refers to an engine that does not exist",
+            ));
+        }
+    }
+
+    if let Some(seeds) = options.gauntlet_seeds
+        && seeds >= options.engines.len()
+    {
+        return Err(String::from("A gauntlet needs more engines than seeds"));
+    }
+
+    // A gauntlet only pairs seeds with the other engines, and an SPRT on a pair that is never
+    // played would run forever.
+    if let Some(sprt) = &options.sprt
+        && let Some(seeds) = options.gauntlet_seeds
+        && let [a, b] = sprt.pair()
+        && (a < seeds) == (b < seeds)
+    {
+        return Err(format!(
+            "SPRT pair {},{} is never played in a gauntlet with {seeds} seeds; one engine of the pair must be a seed",
+            a + 1,
+            b + 1
+        ));
+    }
+
+    if options.stats.fishtest && options.sprt.is_none() && options.engines.len() != 2 {
+        return Err(String::from(
+            "-fishtest reports a single pair; use -sprt pair=I,J to choose it when more than two engines play",
+        ));
+    }
+
+    // Resuming skips the games already recorded, which needs the same schedule as the original
+    // run.
+    if options.pgn.as_ref().is_some_and(|pgn| pgn.resume) {
+        if options.rand_seed.is_none()
+            && options.book.as_ref().is_some_and(|book| book.random_order)
+        {
+            return Err(String::from(
+                "-pgnout resume=true with -openings order=random requires -srand, to play the openings in the same order as the original run",
+            ));
+        }
+        if options.random_pairings && options.pairing_seed().is_none() {
+            return Err(String::from(
+                "-pgnout resume=true with -pairings random requires -srand or -pairingseed, to pair the engines as in the original run",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprt_engine_count() {
+        let args = |engines: usize, sprt: &str| {
+            let mut args = vec![];
+            for i in 0..engines {
+                args.extend([String::from("-engine"), format!("cmd=engine{i}")]);
+            }
+            args.push(String::from("-sprt"));
+            args.extend(sprt.split_whitespace().map(String::from));
+            args
+        };
+        let sprt = "elo0=0 elo1=5 alpha=0.05 beta=0.05";
+
+        assert!(parse_args(args(2, sprt)).is_some());
+        assert!(parse_args(args(3, sprt)).is_none());
+        let options = CliOptions {
+            engines: vec![EngineOptions::default(); 3],
+            sprt: Some(SprtOptions::default()),
+            ..CliOptions::default()
+        };
+        assert_eq!(
+            validate(&options),
+            Err(String::from(
+                "SPRT requires exactly two engines, but 3 were given; use -sprt pair=I,J to test two engines of a larger tournament"
+            ))
+        );
+
+        assert!(parse_args(args(3, &format!("{sprt} pair=1,3"))).is_some());
+        assert!(parse_args(args(3, &format!("{sprt} pair=1,4"))).is_none());
+
+        // Only pairs with exactly one seed are played in a gauntlet.
+        let options = CliOptions {
+            engines: vec![EngineOptions::default(); 4],
+            gauntlet_seeds: Some(2),
+            sprt: Some(SprtOptions {
+                pair: Some([2, 3]),
+                ..SprtOptions::default()
+            }),
+            ..CliOptions::default()
+        };
+        assert_eq!(
+            validate(&options),
+            Err(String::from(
+                "SPRT pair 3,4 is never played in a gauntlet with 2 seeds; one engine of the pair must be a seed"
+            ))
+        );
+        let gauntlet = |pair: &str| {
+            let mut args = args(4, &format!("{sprt} pair={pair}"));
+            args.extend(["-tournament", "gauntlet", "-seeds", "2"].map(String::from));
+            parse_args(args)
+        };
+        assert!(gauntlet("1,2").is_none());
+        assert!(gauntlet("3,4").is_none());
+        assert!(gauntlet("4,1").is_some());
+
+        // Without an SPRT, -fishtest has no pair to report in a larger tournament.
+        let fishtest = |engines: usize, sprt: &str| {
+            let mut args = args(engines, sprt);
+            args.push(String::from("-fishtest"));
+            if sprt.is_empty() {
+                args.retain(|arg| arg != "-sprt");
+            }
+            parse_args(args)
+        };
+        assert!(fishtest(2, "").is_some());
+        assert!(fishtest(3, "").is_none());
+        assert!(fishtest(3, &format!("{sprt} pair=1,3")).is_some());
+    }
+
+    #[test]
+    fn resume_needs_seed() {
+        let resume = |args: &str| {
+            let args = format!(
+                "-engine cmd=a -engine cmd=b -openings file=a.epd {args} -pgnout file=games.pgn resume=true"
+            );
+            parse_args(args.split_whitespace().map(String::from).collect())
+        };
+        assert!(resume("").is_some());
+        assert!(resume("order=random").is_none());
+        assert!(resume("order=random -srand 1").is_some());
+        assert!(resume("-pairings random").is_none());
+        assert!(resume("-pairings random -pairingseed 1").is_some());
+        assert!(resume("-pairings random -srand 1").is_some());
+    }
+
+    #[test]
+    fn setup_failure_exit_code() {
+        for verdict in [
+            sprt::SprtVerdict::AcceptH1,
+            sprt::SprtVerdict::AcceptH0,
+            sprt::SprtVerdict::Continue,
+        ] {
+            assert_ne!(verdict.exit_code(), SETUP_FAILURE_EXIT_CODE);
+        }
+        assert_eq!(SETUP_FAILURE_EXIT_CODE, 125);
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let names = |names: &[&str]| names.iter().map(|n| String::from(*n)).collect();
+        assert_eq!(
+            disambiguate_names(names(&["engine", "other", "engine", "engine"])),
+            ["engine", "other", "engine #2", "engine #3"]
+        );
+        // A suffix already used by another engine is skipped.
+        assert_eq!(
+            disambiguate_names(names(&["engine", "engine", "engine #2"])),
+            ["engine", "engine #3", "engine #2"]
+        );
+        assert_eq!(disambiguate_names(names(&["a", "b"])), ["a", "b"]);
+    }
+
+    #[test]
+    fn engine_config() {
+        let file =
+            std::env::temp_dir().join(format!("shogitest-engines-{}.json", std::process::id()));
+        std::fs::write(
+            &file,
+            r#"[
+                {
+                    "name": "dev",
+                    "cmd": "./dev",
+                    "dir": "engines",
+                    "args": ["--usi", "--threads=2"],
+                    "tc": "10+0.1",
+                    "options": {"Hash": 64, "Threads": 2},
+                    "lateoptions": {"EvalScale": 120},
+                    "env": {"RUST_BACKTRACE": 1}
+                },
+                {
+                    "name": "base",
+                    "cmd": "./base",
+                    "args": "",
+                    "options": [["Hash", "16"]],
+                    "limit": {"nodes": 0, "plies": 0, "tc": {"time": 10000, "increment": 100, "fixed_time": 0}},
+                    "restart": false,
+                    "variant": "standard"
+                }
+            ]"#,
+        )
+        .unwrap();
+        let args = format!(
+            "-engineconf {} -engine name=base option.Hash=32 -engine cmd=./extra",
+            file.display()
+        );
+        let options = parse_args(args.split_whitespace().map(String::from).collect()).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(options.engines.len(), 3);
+        let dev = &options.engines[0];
+        assert_eq!(dev.builder.name.as_deref(), Some("dev"));
+        assert_eq!(dev.builder.cmd, "./dev");
+        assert_eq!(dev.builder.dir, "engines");
+        assert_eq!(dev.builder.args, ["--usi", "--threads=2"]);
+        assert_eq!(
+            dev.builder.env,
+            [(String::from("RUST_BACKTRACE"), String::from("1"))]
+        );
+        assert_eq!(
+            dev.builder.usi_options,
+            [
+                (String::from("Hash"), String::from("64")),
+                (String::from("Threads"), String::from("2"))
+            ]
+        );
+        assert_eq!(
+            dev.builder.late_options,
+            [(String::from("EvalScale"), String::from("120"))]
+        );
+        assert_eq!(dev.time_control, tc::TimeControl::parse("10+0.1").unwrap());
+
+        // -engine name=base amends the engine from the file rather than adding another.
+        let base = &options.engines[1];
+        assert_eq!(base.builder.cmd, "./base");
+        assert_eq!(
+            base.builder.usi_options,
+            [
+                (String::from("Hash"), String::from("16")),
+                (String::from("Hash"), String::from("32"))
+            ]
+        );
+        // Written as fastchess does, with option pairs and the time control in milliseconds.
+        assert_eq!(base.time_control, dev.time_control);
+        assert!(!base.restart);
+        assert_eq!(options.engines[2].builder.cmd, "./extra");
+    }
+
+    #[test]
+    fn movetime_unit() {
+        let mut engine = EngineOptions::default();
+        assert!(parse_engine_option(&mut engine, "st", "2"));
+        assert_eq!(
+            engine.time_control,
+            tc::TimeControl::MoveTime(Duration::from_secs(2))
+        );
+        let mut engine = EngineOptions::default();
+        assert!(parse_engine_option(&mut engine, "st", "0.25"));
+        assert_eq!(
+            engine.time_control,
+            tc::TimeControl::MoveTime(Duration::from_millis(250))
+        );
+        let mut engine = EngineOptions::default();
+        assert!(parse_engine_option(&mut engine, "tc", "st=2"));
+        assert_eq!(
+            engine.time_control,
+            tc::TimeControl::MoveTime(Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn tournament_config() {
+        let file =
+            std::env::temp_dir().join(format!("shogitest-config-{}.toml", std::process::id()));
+        std::fs::write(
+            &file,
+            r#"
+            concurrency = 4
+            rounds = 100
+            tournament = "gauntlet"
+            event = "Config test"
+            progress = true
+            strict = false
+            importpgn = ["old1.pgn", "old2.pgn"]
+            unknown = 1
+
+            [openings]
+            file = ["a.epd", "b.epd"]
+            order = "random"
+            norepeat = true
+
+            [each]
+            tc = "10+0.1"
+            options = { USI_Hash = 16 }
+
+            [sprt]
+            elo0 = 0
+            elo1 = 5
+            alpha = 0.05
+            beta = 0.05
+            pair = [1, 2]
+            bogus = 3
+
+            [draw]
+            movenumber = 40
+            movecount = 8
+            score = 10
+
+            [pgnout]
+            file = "games.pgn"
+            nps = true
+
+            [[engine]]
+            name = "dev"
+            cmd = "./dev"
+
+            [[engine]]
+            name = "base"
+            cmd = "./base"
+            "#,
+        )
+        .unwrap();
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        let from_config = parse_args(args(&format!("-config {}", file.display()))).unwrap();
+        let overridden = parse_args(args(&format!(
+            "-config {} -concurrency 8 -engine name=base option.Threads=2 -openings file=c.epd",
+            file.display()
+        )))
+        .unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let mut flags = args(
+            "-concurrency 4 -rounds 100 -tournament gauntlet -event Config -progress \
+             -openings file=a.epd,b.epd order=random norepeat -each tc=10+0.1 option.USI_Hash=16 \
+             -sprt elo0=0 elo1=5 alpha=0.05 beta=0.05 pair=1,2 \
+             -draw movenumber=40 movecount=8 score=10 -pgnout file=games.pgn nps=true \
+             -engine name=dev cmd=./dev -engine name=base cmd=./base \
+             -importpgn old1.pgn -importpgn old2.pgn",
+        );
+        flags[7] = String::from("Config test");
+        let from_flags = parse_args(flags).unwrap();
+        assert_eq!(format!("{from_config:?}"), format!("{from_flags:?}"));
+
+        // Flags after -config override the file, and a table is replaced as a whole.
+        assert_eq!(overridden.concurrency, Concurrency::Fixed(8));
+        assert_eq!(overridden.book.as_ref().unwrap().files, ["c.epd"]);
+        assert!(!overridden.book.as_ref().unwrap().no_repeat);
+        assert_eq!(overridden.engines.len(), 2);
+        assert_eq!(
+            overridden.engines[1].builder.usi_options,
+            [
+                (String::from("Threads"), String::from("2")),
+                (String::from("USI_Hash"), String::from("16"))
+            ]
+        );
+    }
+
+    #[test]
+    fn every_flag_is_parsed() {
+        // parse_args panics on a flag of FLAGS without a parser. Whether the placeholder
+        // arguments are valid doesn't matter.
+        for flag in FLAGS {
+            let mut args = vec![format!("-{}", flag.name)];
+            if matches!(flag.kind, FlagKind::Value | FlagKind::Repeated) {
+                args.push(String::from("1"));
+            }
+            parse_args(args);
+        }
+    }
+}
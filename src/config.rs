@@ -0,0 +1,70 @@
+//! Reads configuration files, in JSON or TOML, into JSON values so that the command line
+//! parsers read either the same way.
+
+use serde_json::Value;
+
+/// Reads a TOML file if its name ends in `.toml`, and a JSON file otherwise.
+pub fn read(path: &str) -> Result<Value, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("Failed to read {path}: {err}"))?;
+    let value = if path.ends_with(".toml") {
+        text.parse::<toml::Table>()
+            .map_err(|err| err.to_string())
+            .and_then(|table| serde_json::to_value(table).map_err(|err| err.to_string()))
+    } else {
+        serde_json::from_str(&text).map_err(|err| err.to_string())
+    };
+    value.map_err(|err| format!("{path}: {err}"))
+}
+
+/// A value as it is written on the command line. Arrays are joined with commas, and tables
+/// have no such form.
+pub fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        Value::Array(items) => items
+            .iter()
+            .map(text)
+            .collect::<Option<Vec<_>>>()
+            .map(|items| items.join(",")),
+        Value::Null | Value::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_and_json() {
+        let dir = std::env::temp_dir();
+        let toml = dir.join(format!("shogitest-config-{}.toml", std::process::id()));
+        let json = dir.join(format!("shogitest-config-{}.json", std::process::id()));
+        std::fs::write(
+            &toml,
+            "rounds = 10\nalpha = 0.05\n\n[openings]\nfile = [\"a.epd\", \"b.epd\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &json,
+            r#"{"rounds": 10, "alpha": 0.05, "openings": {"file": ["a.epd", "b.epd"]}}"#,
+        )
+        .unwrap();
+        let from_toml = read(toml.to_str().unwrap());
+        let from_json = read(json.to_str().unwrap());
+        std::fs::remove_file(&toml).unwrap();
+        std::fs::remove_file(&json).unwrap();
+
+        let value = from_toml.unwrap();
+        assert_eq!(value, from_json.unwrap());
+        assert_eq!(text(&value["rounds"]).as_deref(), Some("10"));
+        assert_eq!(text(&value["alpha"]).as_deref(), Some("0.05"));
+        assert_eq!(
+            text(&value["openings"]["file"]).as_deref(),
+            Some("a.epd,b.epd")
+        );
+        assert_eq!(text(&value["openings"]), None);
+    }
+}
@@ -0,0 +1,415 @@
+// Reference: CSA standard file format, http://www2.computer-shogi.org/protocol/record_v22.html
+
+use crate::{
+    book::Opening,
+    shogi::{Color, Move, PieceType, Position, Square},
+};
+
+const HIRATE: [&str; 9] = [
+    "P1-KY-KE-GI-KI-OU-KI-GI-KE-KY",
+    "P2 * -HI *  *  *  *  * -KA * ",
+    "P3-FU-FU-FU-FU-FU-FU-FU-FU-FU",
+    "P4 *  *  *  *  *  *  *  *  * ",
+    "P5 *  *  *  *  *  *  *  *  * ",
+    "P6 *  *  *  *  *  *  *  *  * ",
+    "P7+FU+FU+FU+FU+FU+FU+FU+FU+FU",
+    "P8 * +KA *  *  *  *  * +HI * ",
+    "P9+KY+KE+GI+KI+OU+KI+GI+KE+KY",
+];
+
+const HAND_PIECES: [(PieceType, u8); 7] = [
+    (PieceType::Rook, 2),
+    (PieceType::Bishop, 2),
+    (PieceType::Gold, 4),
+    (PieceType::Silver, 4),
+    (PieceType::Knight, 4),
+    (PieceType::Lance, 4),
+    (PieceType::Pawn, 18),
+];
+
+/// Returns true if the line looks like the start of a CSA record.
+pub fn looks_like_csa(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    line.starts_with(['V', '\'', '$', '/'])
+        || line.starts_with("N+")
+        || line.starts_with("N-")
+        || line.starts_with("PI")
+        || (bytes.len() > 2 && bytes[0] == b'P' && matches!(bytes[2], b'+' | b'-' | b' '))
+}
+
+/// Parses every record in a CSA file into an opening. Records are separated by a `/` line.
+pub fn parse_openings<I>(lines: I) -> Option<Vec<Opening>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut openings = vec![];
+    let mut record = Record::default();
+
+    for line in lines {
+        for statement in line.trim_end().split(',') {
+            if statement == "/" {
+                openings.push(record.finish()?);
+                record = Record::default();
+            } else if !record.parse_statement(statement) {
+                eprintln!("Invalid CSA statement: {statement}");
+                return None;
+            }
+        }
+    }
+
+    if !record.is_empty() {
+        openings.push(record.finish()?);
+    }
+
+    Some(openings)
+}
+
+fn parse_piece(s: &str) -> Option<PieceType> {
+    Some(match s {
+        "FU" => PieceType::Pawn,
+        "KY" => PieceType::Lance,
+        "KE" => PieceType::Knight,
+        "GI" => PieceType::Silver,
+        "KI" => PieceType::Gold,
+        "KA" => PieceType::Bishop,
+        "HI" => PieceType::Rook,
+        "OU" => PieceType::King,
+        "TO" => PieceType::Tokin,
+        "NY" => PieceType::NariLance,
+        "NK" => PieceType::NariKnight,
+        "NG" => PieceType::NariSilver,
+        "UM" => PieceType::Horse,
+        "RY" => PieceType::Dragon,
+        _ => return None,
+    })
+}
+
+fn parse_color(c: u8) -> Option<Color> {
+    match c {
+        b'+' => Some(Color::Sente),
+        b'-' => Some(Color::Gote),
+        _ => None,
+    }
+}
+
+/// Parses a two digit CSA square (e.g. `77`) into a board index in sfen ordering.
+fn parse_board_index(s: &[u8]) -> Option<usize> {
+    if s.len() != 2 || !(b'1'..=b'9').contains(&s[0]) || !(b'1'..=b'9').contains(&s[1]) {
+        return None;
+    }
+    let file = (s[0] - b'1') as usize;
+    let rank = (s[1] - b'1') as usize;
+    Some(rank * 9 + (8 - file))
+}
+
+fn parse_square(s: &[u8]) -> Option<Square> {
+    if s.len() != 2 || !(b'1'..=b'9').contains(&s[1]) {
+        return None;
+    }
+    Square::parse(s[0], b'a' + (s[1] - b'1'))
+}
+
+#[derive(Debug)]
+struct Record {
+    board: [Option<(Color, PieceType)>; 81],
+    hand: [[u8; 7]; 2],
+    has_board: bool,
+    stm: Option<Color>,
+    position: Option<Position>,
+    moves: Vec<Move>,
+    finished: bool,
+}
+
+impl Default for Record {
+    fn default() -> Self {
+        Record {
+            board: [None; 81],
+            hand: [[0; 7]; 2],
+            has_board: false,
+            stm: None,
+            position: None,
+            moves: vec![],
+            finished: false,
+        }
+    }
+}
+
+impl Record {
+    fn is_empty(&self) -> bool {
+        !self.has_board && self.stm.is_none()
+    }
+
+    fn parse_statement(&mut self, s: &str) -> bool {
+        let bytes = s.as_bytes();
+        match bytes.first() {
+            None => true,
+            // Comments, version, game information and time consumption
+            Some(b'\'' | b'V' | b'N' | b'$' | b'T') => true,
+            // Special moves (e.g. %TORYO) end the record
+            Some(b'%') => {
+                self.finished = true;
+                true
+            }
+            Some(b'P') if self.stm.is_none() => self.parse_position_statement(s),
+            Some(b'+' | b'-') if bytes.len() == 1 && self.has_board && self.stm.is_none() => {
+                self.stm = parse_color(bytes[0]);
+                self.position = Position::parse(&self.to_sfen());
+                self.position.is_some()
+            }
+            Some(b'+' | b'-') if self.stm.is_some() => self.finished || self.parse_move(s),
+            _ => false,
+        }
+    }
+
+    fn parse_position_statement(&mut self, s: &str) -> bool {
+        let bytes = s.as_bytes();
+        match bytes.get(1) {
+            Some(b'I') => {
+                for line in HIRATE {
+                    assert!(self.parse_rank(line));
+                }
+                bytes[2..].chunks(4).all(|chunk| {
+                    let Some(i) = parse_board_index(&chunk[..2.min(chunk.len())]) else {
+                        return false;
+                    };
+                    let pt = chunk
+                        .get(2..4)
+                        .and_then(|pt| parse_piece(str::from_utf8(pt).ok()?));
+                    match self.board[i] {
+                        Some((_, board_pt)) if Some(board_pt) == pt => {
+                            self.board[i] = None;
+                            true
+                        }
+                        _ => false,
+                    }
+                })
+            }
+            Some(b'1'..=b'9') => self.parse_rank(s),
+            Some(&c @ (b'+' | b'-')) => {
+                let color = parse_color(c).unwrap();
+                self.has_board = true;
+                bytes[2..]
+                    .chunks(4)
+                    .all(|chunk| self.parse_placement(color, chunk))
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_rank(&mut self, s: &str) -> bool {
+        let bytes = s.as_bytes();
+        let rank = (bytes[1] - b'1') as usize;
+        let mut cells = bytes[2..].to_vec();
+        if cells.len() > 27 {
+            return false;
+        }
+        cells.resize(27, b' ');
+        for (i, cell) in cells.chunks(3).enumerate() {
+            self.board[rank * 9 + i] = match cell {
+                b" * " => None,
+                [c, pt @ ..] => match (
+                    parse_color(*c),
+                    str::from_utf8(pt).ok().and_then(parse_piece),
+                ) {
+                    (Some(color), Some(pt)) => Some((color, pt)),
+                    _ => return false,
+                },
+                _ => return false,
+            };
+        }
+        self.has_board = true;
+        true
+    }
+
+    fn parse_placement(&mut self, color: Color, chunk: &[u8]) -> bool {
+        if chunk.len() != 4 {
+            return false;
+        }
+        let Ok(pt) = str::from_utf8(&chunk[2..]) else {
+            return false;
+        };
+        if &chunk[..2] == b"00" {
+            if pt == "AL" {
+                self.add_remaining_to_hand(color);
+                return true;
+            }
+            match HAND_PIECES
+                .iter()
+                .position(|&(hand_pt, _)| Some(hand_pt) == parse_piece(pt))
+            {
+                Some(i) => {
+                    self.hand[color.to_index()][i] += 1;
+                    true
+                }
+                None => false,
+            }
+        } else {
+            match (parse_board_index(&chunk[..2]), parse_piece(pt)) {
+                (Some(i), Some(pt)) => {
+                    self.board[i] = Some((color, pt));
+                    true
+                }
+                _ => false,
+            }
+        }
+    }
+
+    fn add_remaining_to_hand(&mut self, color: Color) {
+        for (i, &(pt, total)) in HAND_PIECES.iter().enumerate() {
+            let on_board = self
+                .board
+                .iter()
+                .filter(|place| matches!(place, Some((_, board_pt)) if board_pt.demote() == pt))
+                .count() as u8;
+            let in_hand = self.hand[0][i] + self.hand[1][i];
+            self.hand[color.to_index()][i] += total.saturating_sub(on_board + in_hand);
+        }
+    }
+
+    fn parse_move(&mut self, s: &str) -> bool {
+        let bytes = s.as_bytes();
+        if bytes.len() != 7 {
+            return false;
+        }
+        let (Some(to), Some(pt)) = (
+            parse_square(&bytes[3..5]),
+            str::from_utf8(&bytes[5..7]).ok().and_then(parse_piece),
+        ) else {
+            return false;
+        };
+        let m = if &bytes[1..3] == b"00" {
+            Move::Drop(pt, to)
+        } else {
+            let Some(from) = parse_square(&bytes[1..3]) else {
+                return false;
+            };
+            let promo = pt.promoted()
+                && self
+                    .position
+                    .is_some_and(|position| !position.piece_type_at(from).promoted());
+            Move::Normal { from, to, promo }
+        };
+        self.moves.push(m);
+        // Illegal book moves are reported when the opening is validated, so we only need
+        // to keep track of the position for as long as we can.
+        self.position = self.position.and_then(|position| position.do_move(m));
+        true
+    }
+
+    fn to_sfen(&self) -> String {
+        let mut sfen = String::new();
+        for rank in 0..9 {
+            let mut blanks = 0;
+            for file in 0..9 {
+                match self.board[rank * 9 + file] {
+                    None => blanks += 1,
+                    Some((color, pt)) => {
+                        if blanks != 0 {
+                            sfen += &blanks.to_string();
+                            blanks = 0;
+                        }
+                        sfen += pt.to_str(color);
+                    }
+                }
+            }
+            if blanks != 0 {
+                sfen += &blanks.to_string();
+            }
+            if rank != 8 {
+                sfen += "/";
+            }
+        }
+
+        sfen += match self.stm {
+            Some(Color::Gote) => " w ",
+            _ => " b ",
+        };
+
+        let mut wrote_hand = false;
+        for color in [Color::Sente, Color::Gote] {
+            for (i, &(pt, _)) in HAND_PIECES.iter().enumerate() {
+                let count = self.hand[color.to_index()][i];
+                if count > 1 {
+                    sfen += &count.to_string();
+                }
+                if count > 0 {
+                    sfen += pt.to_str(color);
+                    wrote_hand = true;
+                }
+            }
+        }
+        if !wrote_hand {
+            sfen += "-";
+        }
+
+        sfen + " 1"
+    }
+
+    fn finish(self) -> Option<Opening> {
+        let Some(position) = Position::parse(&self.to_sfen()).filter(|_| self.stm.is_some()) else {
+            eprintln!("CSA record is missing a starting position or side to move");
+            return None;
+        };
+        Some(Opening {
+            position,
+            moves: self.moves,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn csa_hirate() {
+        let record = lines(
+            "V2.2\nN+sente\nN-gote\nP1-KY-KE-GI-KI-OU-KI-GI-KE-KY\nP2 * -HI *  *  *  *  * -KA *\nP3-FU-FU-FU-FU-FU-FU-FU-FU-FU\nP4 *  *  *  *  *  *  *  *  * \nP5 *  *  *  *  *  *  *  *  * \nP6 *  *  *  *  *  *  *  *  * \nP7+FU+FU+FU+FU+FU+FU+FU+FU+FU\nP8 * +KA *  *  *  *  * +HI *\nP9+KY+KE+GI+KI+OU+KI+GI+KE+KY\n+\n",
+        );
+        let openings = parse_openings(record).unwrap();
+        assert_eq!(openings.len(), 1);
+        assert_eq!(openings[0].position, Position::default());
+        assert!(openings[0].moves.is_empty());
+    }
+
+    #[test]
+    fn csa_opening_with_moves() {
+        let record = lines("'opening\nPI\n+\n+7776FU\n-3334FU,T1\n+8822UM\n/\nPI82HI22KA\n-\n");
+        let openings = parse_openings(record).unwrap();
+        assert_eq!(openings.len(), 2);
+
+        let game = openings[0].to_game().unwrap();
+        assert_eq!(
+            game.position().to_string(),
+            "lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4"
+        );
+
+        assert_eq!(
+            openings[1].position.to_string(),
+            "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+        );
+    }
+
+    #[test]
+    fn csa_hand_pieces() {
+        let record = lines(
+            "P1 *  *  *  * -OU *  *  *  * \nP9 *  *  *  * +OU *  *  *  * \nP+00KI00FU\nP-00AL\n+\n",
+        );
+        let openings = parse_openings(record).unwrap();
+        assert_eq!(
+            openings[0].position.to_string(),
+            "4k4/9/9/9/9/9/9/9/4K4 b GP2r2b3g4s4n4l17p 1"
+        );
+    }
+
+    #[test]
+    fn csa_mixed_with_sfen() {
+        let record =
+            lines("PI\n+\nlnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1\n");
+        assert!(parse_openings(record).is_none());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    cli, shogi,
+    tournament::{MatchResult, Tournament, TournamentState},
+};
+use chrono::Utc;
+
+/// Most games listed for a tournament without a fixed length.
+const MAX_LISTED_GAMES: u64 = 1000;
+
+/// Lists the games `tournament` would hand out, without starting any engines. Every game is
+/// completed as soon as it is handed out, with a stub result.
+pub fn schedule(tournament: &mut dyn Tournament, options: &cli::CliOptions) -> Vec<String> {
+    let names = cli::disambiguate_names(
+        options
+            .engines
+            .iter()
+            .map(|engine| engine.builder.display_name())
+            .collect(),
+    );
+
+    let expected = tournament.expected_maximum_match_count();
+    let mut lines = vec![match expected {
+        Some(count) => format!("Schedule of {count} games:"),
+        None => format!("Schedule of unlimited games, first {MAX_LISTED_GAMES} shown:"),
+    }];
+    for (name, engine) in names.iter().zip(&options.engines) {
+        lines.push(format!("Time control of {name}: {}", engine.time_control));
+    }
+
+    let mut listed = 0;
+    while listed < MAX_LISTED_GAMES
+        && let Some(ticket) = tournament.next()
+    {
+        listed += 1;
+        let [sente, gote] = ticket.engines;
+        lines.push(format!(
+            "Game {}: {} (sente) vs {} (gote), pairing {}: {}",
+            ticket.id + 1,
+            names[sente],
+            names[gote],
+            ticket.id / options.games + 1,
+            ticket.opening
+        ));
+
+        tournament.match_started(ticket.clone());
+        let now = Utc::now();
+        let result = MatchResult {
+            ticket,
+            game_start: now,
+            game_end: now,
+            opening_plies: 0,
+            outcome: shogi::GameOutcome::Undetermined,
+            moves: vec![],
+        };
+        if tournament.match_complete(result) == TournamentState::Stop {
+            break;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{book, engine, tournament::RoundRobin};
+
+    #[test]
+    fn three_engine_schedule() {
+        // Engines that cannot be started, so any attempt to start one would fail the test.
+        let engine = |name: &str| cli::EngineOptions {
+            builder: engine::EngineBuilder {
+                cmd: String::from("/nonexistent/engine"),
+                name: Some(String::from(name)),
+                ..engine::EngineBuilder::default()
+            },
+            ..cli::EngineOptions::default()
+        };
+        let options = cli::CliOptions {
+            engines: vec![engine("a"), engine("b"), engine("c")],
+            rounds: Some(1),
+            ..cli::CliOptions::default()
+        };
+        let openings = [
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+            "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+        ];
+        let positions = openings.map(|sfen| shogi::Position::parse(sfen).unwrap());
+        let book = book::OpeningBook::from_positions(positions.to_vec(), false);
+        let mut round_robin = RoundRobin::new(&options, book).unwrap();
+
+        let lines = schedule(&mut round_robin, &options);
+        let [first, second, third] = openings;
+        assert_eq!(
+            lines,
+            [
+                String::from("Schedule of 6 games:"),
+                String::from("Time control of a: infinite"),
+                String::from("Time control of b: infinite"),
+                String::from("Time control of c: infinite"),
+                format!("Game 1: a (sente) vs b (gote), pairing 1: {first}"),
+                format!("Game 2: b (sente) vs a (gote), pairing 1: {first}"),
+                format!("Game 3: a (sente) vs c (gote), pairing 2: {second}"),
+                format!("Game 4: c (sente) vs a (gote), pairing 2: {second}"),
+                format!("Game 5: b (sente) vs c (gote), pairing 3: {third}"),
+                format!("Game 6: c (sente) vs b (gote), pairing 3: {third}"),
+            ]
+        );
+    }
+}
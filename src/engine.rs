@@ -1,12 +1,16 @@
-use crate::shogi;
+use crate::{
+    affinity,
+    engine_log::EngineLog,
+    shogi,
+    transport::{ChildTransport, TcpTransport, Transport},
+};
 use log::{error, info, trace};
 use std::{
-    path::Path,
     io::{Result, Write},
-    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
-    time::Duration,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
 };
-use wait_timeout::ChildExt;
 
 #[derive(Debug, Clone, Default)]
 pub enum Score {
@@ -16,6 +20,14 @@ pub enum Score {
     Mate(i32),
 }
 
+/// Marks a score that is only a bound on the true evaluation, as reported during aspiration
+/// window re-searches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreBound {
+    Lower,
+    Upper,
+}
+
 #[derive(Debug)]
 pub enum EngineResult<T> {
     Ok(T),
@@ -36,67 +48,372 @@ pub struct MoveRecord {
     pub m: shogi::Move,
     pub mstr: String,
     pub score: Score,
+    /// Set if `score` is a bound rather than an exact evaluation.
+    pub score_bound: Option<ScoreBound>,
+    /// The last exact score seen during the search.
+    pub exact_score: Score,
+    /// The last principal variation reported.
+    pub pv: Vec<shogi::Move>,
     pub depth: u32,
     pub seldepth: u32,
     pub nodes: u64,
     pub nps: u64,
     pub engine_time: u64,
     pub hashfull: u32,
+    /// Tablebase hits, for engines that probe endgame tablebases.
+    pub tbhits: u64,
+    /// The move currently being searched and its number among the root moves, if reported.
+    pub currmove: Option<shogi::Move>,
+    pub currmovenumber: u32,
     pub measured_time: Duration,
     pub time_left: Option<Duration>,
 }
 
+impl MoveRecord {
+    /// Updates the record from a line of search output. Stops at `bestmove`.
+    fn update(&mut self, line: &str) -> ReadState {
+        let mut it = line.split_ascii_whitespace();
+        match it.next() {
+            Some("info") => {
+                let mut score = None;
+                let mut bound = None;
+                while let Some(tok) = it.next() {
+                    match tok {
+                        "string" => break,
+                        "depth" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u32>()
+                            {
+                                self.depth = value;
+                            }
+                        }
+                        "seldepth" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u32>()
+                            {
+                                self.seldepth = value;
+                            }
+                        }
+                        "nodes" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u64>()
+                            {
+                                self.nodes = value;
+                            }
+                        }
+                        "nps" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u64>()
+                            {
+                                self.nps = value;
+                            }
+                        }
+                        "time" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u64>()
+                            {
+                                self.engine_time = value;
+                            }
+                        }
+                        "hashfull" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u32>()
+                            {
+                                self.hashfull = value;
+                            }
+                        }
+                        "tbhits" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u64>()
+                            {
+                                self.tbhits = value;
+                            }
+                        }
+                        "currmove" => {
+                            if let Some(value) = it.next()
+                                && let Some(m) = shogi::Move::parse(value)
+                            {
+                                self.currmove = Some(m);
+                            }
+                        }
+                        "currmovenumber" => {
+                            if let Some(value) = it.next()
+                                && let Ok(value) = value.parse::<u32>()
+                            {
+                                self.currmovenumber = value;
+                            }
+                        }
+                        "score" => match it.next() {
+                            Some(x) => match x {
+                                "cp" => {
+                                    if let Some(value) = it.next()
+                                        && let Ok(value) = value.parse::<i32>()
+                                    {
+                                        score = Some(Score::Cp(value));
+                                    }
+                                }
+                                "mate" => {
+                                    if let Some(value) = it.next()
+                                        && let Ok(value) = value.parse::<i32>()
+                                    {
+                                        score = Some(Score::Mate(value));
+                                    }
+                                }
+                                _ => continue,
+                            },
+                            None => continue,
+                        },
+                        "pv" => self.pv = it.by_ref().map_while(shogi::Move::parse).collect(),
+                        "lowerbound" => bound = Some(ScoreBound::Lower),
+                        "upperbound" => bound = Some(ScoreBound::Upper),
+                        _ => continue,
+                    }
+                }
+                if let Some(score) = score {
+                    if bound.is_none() {
+                        self.exact_score = score.clone();
+                    }
+                    self.score = score;
+                    self.score_bound = bound;
+                }
+                ReadState::Continue
+            }
+            Some("bestmove") => {
+                let mstr = it.next().unwrap_or("");
+                self.mstr = mstr.to_string();
+                if let Some(m) = shogi::Move::parse(mstr) {
+                    self.m = m;
+                }
+                ReadState::Stop
+            }
+            _ => ReadState::Continue,
+        }
+    }
+
+    /// Whether the engine sent `bestmove` without a move, as `bestmove` or `bestmove (none)`.
+    pub fn is_no_move(&self) -> bool {
+        matches!(self.mstr.as_str(), "" | "(none)")
+    }
+
+    /// The score adjudication goes by, which ignores bounded scores in favour of the last exact
+    /// one.
+    pub fn adjudication_score(&self) -> &Score {
+        match self.score_bound {
+            Some(_) => &self.exact_score,
+            None => &self.score,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
 pub struct EngineBuilder {
     pub dir: String,
     pub cmd: String,
+    /// Arguments passed to `cmd`.
+    pub args: Vec<String>,
+    /// Environment variables set for `cmd`, on top of shogitest's own.
+    pub env: Vec<(String, String)>,
+    /// Connect to an engine listening on this address instead of spawning `cmd`.
+    pub host: Option<String>,
     pub name: Option<String>,
     pub usi_options: Vec<(String, String)>,
+    /// USI options sent once the engine has answered its first `isready`, for engines whose
+    /// valid values depend on what they load then, such as their network.
+    pub late_options: Vec<(String, String)>,
+    pub affinity: Vec<usize>,
+    /// Number of extra attempts at starting the engine before giving up.
+    pub startup_retries: u32,
+    /// Also write this engine's USI traffic to this file.
+    pub log_file: Option<String>,
+    /// Keep the existing contents of `log_file` rather than truncating it at startup.
+    pub log_append: bool,
+    /// Longest line the engine may send, see `MAX_LINE_LENGTH`.
+    pub max_line_length: Option<usize>,
+    /// Don't send `usinewgame` between games, for engines that misbehave on it.
+    pub skip_usinewgame: bool,
+    /// Round-trip `isready` before every move, not just before each game.
+    pub ready_every_move: bool,
+    /// Send `isready` whenever the engine has been idle for this long between games.
+    pub keepalive: Option<Duration>,
+    /// Search to warm the engine up with before its first game.
+    pub warmup: Option<Warmup>,
+    /// How long the engine may take to answer `isready` before a game, see `READY_TIMEOUT`.
+    pub ready_timeout: Option<Duration>,
+}
+
+/// An untimed search run before an engine's first game, so that cold caches or a network still
+/// being loaded don't skew the timing of that game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Warmup {
+    /// `go depth D` on the starting position.
+    Depth(u32),
+    /// The engine's own `bench` command, which is assumed to be finished once the engine
+    /// answers a following `isready`.
+    Bench,
 }
 
+/// Default for how long an engine may take to answer `isready`.
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a warmup may take before the engine is considered unresponsive.
+const WARMUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Default for the longest line an engine may send. An engine that sends more without a newline
+/// is treated as disconnected, rather than buffering its output without bound.
+pub const MAX_LINE_LENGTH: usize = 1 << 20;
+
+/// How long a stopped engine has to send its `bestmove` before being reused, see
+/// `Engine::drain`.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delay before the first startup retry, doubled for every further attempt.
+const STARTUP_RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// USI options naming an evaluation network file or directory, which must exist.
+const NET_FILE_OPTIONS: [&str; 2] = ["EvalFile", "EvalDir"];
+
 impl EngineBuilder {
     pub fn init(&self) -> Result<Engine> {
-        let cmd = if self.dir.is_empty() {
+        let mut backoff = STARTUP_RETRY_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            // A failed attempt's engine is dropped before retrying, which quits or kills it.
+            let err = match self.try_init() {
+                Ok(engine) => return Ok(engine),
+                Err(err) => err,
+            };
+            if attempt == self.startup_retries {
+                if self.startup_retries == 0 {
+                    return Err(err);
+                }
+                return Err(std::io::Error::new(
+                    err.kind(),
+                    format!(
+                        "Failed to start engine {} after {} attempts: {err}",
+                        self.display_name(),
+                        attempt + 1
+                    ),
+                ));
+            }
+            attempt += 1;
+            error!(
+                "Failed to start engine {} ({err}), retrying in {}ms ({attempt}/{})",
+                self.display_name(),
+                backoff.as_millis(),
+                self.startup_retries
+            );
+            std::thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+
+    /// The engine's name before the engine itself has reported one.
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .or(self.host.clone())
+            .unwrap_or(self.cmd.to_string())
+    }
+
+    /// Checks that the engine binary exists and is executable, and that any network file given
+    /// with `NET_FILE_OPTIONS` exists relative to `dir`, so that a typo is reported before the
+    /// tournament starts rather than as a failure to start the engine. Remote engines are not
+    /// checked.
+    pub fn check_files(&self) -> Result<()> {
+        if self.host.is_some() {
+            return Ok(());
+        }
+        let cmd = self.command_path();
+        let found = if cmd.components().count() == 1 {
+            std::env::var_os("PATH").is_some_and(|paths| {
+                std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(&cmd)))
+            })
+        } else {
+            is_executable(&cmd)
+        };
+        if !found {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "Engine {}: {} is not an executable file",
+                    self.display_name(),
+                    cmd.display()
+                ),
+            ));
+        }
+
+        for (name, value) in self.usi_options.iter().chain(&self.late_options) {
+            if value.is_empty()
+                || !NET_FILE_OPTIONS
+                    .iter()
+                    .any(|option| option.eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+            let path = Path::new(&self.dir).join(value);
+            if !path.exists() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!(
+                        "Engine {}: {name} {} does not exist",
+                        self.display_name(),
+                        path.display()
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn command_path(&self) -> PathBuf {
+        if self.dir.is_empty() {
             Path::new(&self.cmd).to_path_buf()
         } else {
             Path::new(&self.dir).join(&self.cmd)
-        };
+        }
+    }
 
-        let mut child = Command::new(&cmd)
-            .stdout(Stdio::piped())
-            .stdin(Stdio::piped())
-            .spawn()?;
+    fn try_init(&self) -> Result<Engine> {
+        let transport: Box<dyn Transport> = match &self.host {
+            Some(host) => Box::new(TcpTransport::connect(host)?),
+            None => Box::new(self.spawn()?),
+        };
 
-        let stdout = child.stdout.take().unwrap();
-        let stdin = child.stdin.take().unwrap();
+        let log = match &self.log_file {
+            Some(path) => Some(EngineLog::open(path)?),
+            None => None,
+        };
 
         let mut engine = Engine {
-            child,
-            stdout,
+            transport,
+            log,
             read_buf: Vec::new(),
-            stdin,
-            name: self.name.clone().unwrap_or(self.cmd.to_string()),
+            name: self.display_name(),
+            usi_name: None,
+            usi_author: None,
             builder: self.clone(),
+            killed: false,
+            last_sent: Instant::now(),
+            awaiting_bestmove: false,
+            warmed_up: false,
         };
 
         engine.write_line("usi")?;
 
         let mut usi_name: Option<String> = None;
+        let mut usi_author: Option<String> = None;
         match engine.read_with_timeout(Some(5 * Duration::SECOND), |line| {
+            // Anything else, such as a banner, `info string` or `option` lines, is skipped.
             let mut it = line.split_whitespace();
             match it.next() {
                 Some("usiok") => ReadState::Stop,
                 Some("id") => {
-                    match it.next() {
-                        Some("name") => {
-                            if let Some(name) = it.remainder() {
-                                usi_name = Some(name.trim().to_string());
-                            }
-                        }
-                        Some("author") => {}
-                        s => {
-                            dbg!(s);
-                        }
+                    let field = it.next();
+                    let value = it.remainder().map(str::trim).filter(|v| !v.is_empty());
+                    match (field, value) {
+                        (Some("name"), Some(name)) => usi_name = Some(name.to_string()),
+                        (Some("author"), Some(author)) => usi_author = Some(author.to_string()),
+                        _ => {}
                     }
                     ReadState::Continue
                 }
@@ -122,58 +439,126 @@ impl EngineBuilder {
             }
         }
 
-        if let Some(usi_name) = usi_name
+        if let Some(usi_name) = &usi_name
             && self.name.is_none()
         {
-            engine.name = usi_name;
+            engine.name = usi_name.clone();
         }
+        engine.usi_name = usi_name;
+        engine.usi_author = usi_author;
 
         for (k, v) in &self.usi_options {
             engine.write_line(&format!("setoption name {k} value {v}"))?;
         }
 
+        if !self.late_options.is_empty() {
+            engine.isready_with_timeout(self.ready_timeout.unwrap_or(READY_TIMEOUT))?;
+            for (k, v) in &self.late_options {
+                engine.write_line(&format!("setoption name {k} value {v}"))?;
+            }
+        }
+
         info!("Engine {} started", engine.name);
 
         Ok(engine)
     }
+    fn spawn(&self) -> Result<ChildTransport> {
+        let cmd = self.command_path();
+
+        let child = Command::new(&cmd)
+            .args(&self.args)
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .stdout(Stdio::piped())
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        if !self.affinity.is_empty()
+            && let Err(err) = affinity::pin(&child, &self.affinity)
+        {
+            eprintln!(
+                "Warning; Failed to set CPU affinity for {}: {err}",
+                self.cmd
+            );
+        }
+
+        Ok(ChildTransport::new(child))
+    }
     pub fn get_usi_option_value(&self, key: &str) -> Option<&str> {
         self.usi_options
             .iter()
+            .chain(&self.late_options)
             .filter_map(|(k, v)| if k == key { Some(v.as_ref()) } else { None })
             .next_back()
     }
+    /// The `Threads` USI option, defaulting to a single thread.
+    pub fn thread_count(&self) -> usize {
+        self.get_usi_option_value("Threads")
+            .and_then(|threads| threads.parse().ok())
+            .unwrap_or(1)
+            .max(1)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Like `Command::new`, accepts a command given without its `.exe` or other `PATHEXT` suffix.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    if path.is_file() {
+        return true;
+    }
+    if path.extension().is_some() {
+        return false;
+    }
+    let extensions =
+        std::env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+    extensions
+        .split(';')
+        .filter(|extension| !extension.is_empty())
+        .any(|extension| {
+            let mut file = path.as_os_str().to_owned();
+            file.push(extension);
+            Path::new(&file).is_file()
+        })
 }
 
 #[derive(Debug)]
 pub struct Engine {
-    child: Child,
-    stdout: ChildStdout,
+    transport: Box<dyn Transport>,
+    log: Option<EngineLog>,
     read_buf: Vec<u8>,
-    stdin: ChildStdin,
     name: String,
+    /// The name the engine reported with `id name`, which usually includes its version.
+    usi_name: Option<String>,
+    /// The author the engine reported with `id author`.
+    usi_author: Option<String>,
     builder: EngineBuilder,
+    /// Set once the engine has been killed, so it is not asked to quit.
+    killed: bool,
+    /// When the engine was last sent a command, for keepalives.
+    last_sent: Instant,
+    /// Set when the engine was told to stop but its `bestmove` has not been read yet.
+    awaiting_bestmove: bool,
+    /// Set once the warmup has run, so it runs once per engine process.
+    warmed_up: bool,
 }
 
 impl Drop for Engine {
     fn drop(&mut self) {
+        if self.killed {
+            return;
+        }
         info!("Quitting engine {}...", self.name);
-        match self.write_line("quit") {
+        match self.write_line("quit").and_then(|_| self.flush()) {
             Ok(_) => {}
             Err(_) => error!("Failed to write quit to engine {}", self.name),
         };
-        match self.child.wait_timeout(Duration::from_secs(10)) {
-            Ok(Some(_)) => info!("Quit engine {} successfully", self.name),
-            Ok(None) | Err(_) => {
-                info!(
-                    "Timed out quitting engine {}, attempting to kill...",
-                    self.name
-                );
-                match self.child.kill() {
-                    Ok(_) => info!("Engine {} killed", self.name),
-                    Err(_) => info!("Failed to kill engine {}, giving up", self.name),
-                }
-            }
-        }
+        self.transport.close(&self.name);
     }
 }
 
@@ -182,21 +567,91 @@ impl Engine {
         &self.name
     }
 
+    pub fn usi_name(&self) -> Option<&str> {
+        self.usi_name.as_deref()
+    }
+
+    pub fn usi_author(&self) -> Option<&str> {
+        self.usi_author.as_deref()
+    }
+
     pub fn restart(&mut self) -> Result<()> {
         *self = self.builder.init()?;
         Ok(())
     }
 
+    /// Kills an engine that stopped responding. It must be restarted before further use.
+    pub fn kill(&mut self) {
+        error!("Killing unresponsive engine {}", self.name);
+        self.transport.kill(&self.name);
+        self.killed = true;
+    }
+
     pub fn write_line(&mut self, line: &str) -> Result<()> {
         trace!("{} < {line}", self.name());
-        writeln!(self.stdin, "{line}")
+        self.last_sent = Instant::now();
+        self.log_line(true, line);
+        writeln!(self.transport, "{line}")
+    }
+
+    /// Records a line sent to (`sent`) or received from the engine in its log, if it has one. A
+    /// log that cannot be written to is dropped with a warning, rather than ending the game.
+    fn log_line(&mut self, sent: bool, line: &str) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        let result = if sent {
+            log.sent(&self.name, line)
+        } else {
+            log.received(&self.name, line)
+        };
+        if let Err(err) = result {
+            eprintln!(
+                "Warning; Failed to write to the log of {}, no longer logging it: {err}",
+                self.name
+            );
+            self.log = None;
+        }
     }
 
     pub fn isready(&mut self) -> Result<()> {
+        self.isready_with_timeout(READY_TIMEOUT)
+    }
+
+    fn isready_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.drain()?;
         self.write_line("isready")?;
         self.flush()?;
-        match self.read_with_timeout(Some(5 * Duration::SECOND), |line| {
-            if line.trim().eq_ignore_ascii_case("readyok") {
+        self.wait_for_readyok(timeout)
+    }
+
+    /// The `isready` before a game, which may take longer as the engine clears its hash. It
+    /// gets the engine's own timeout, and an engine that misses it is given that long again
+    /// before it is considered unresponsive.
+    fn isready_between_games(&mut self) -> Result<()> {
+        let timeout = self.builder.ready_timeout.unwrap_or(READY_TIMEOUT);
+        match self.isready_with_timeout(timeout) {
+            Err(err) if err.kind() == std::io::ErrorKind::TimedOut => {
+                eprintln!(
+                    "Warning; {} did not answer isready within {:.1}s, waiting once more",
+                    self.name,
+                    timeout.as_secs_f64()
+                );
+                // The first `isready` is still outstanding, so wait for its reply rather than
+                // sending another, which would leave a stray `readyok` behind.
+                self.wait_for_readyok(timeout)
+            }
+            result => result,
+        }
+    }
+
+    fn wait_for_readyok(&mut self, timeout: Duration) -> Result<()> {
+        match self.read_with_timeout(Some(timeout), |line| {
+            if line
+                .split_whitespace()
+                .next()
+                .is_some_and(|token| token.eq_ignore_ascii_case("readyok"))
+            {
                 ReadState::Stop
             } else {
                 ReadState::Continue
@@ -224,107 +679,146 @@ impl Engine {
         Ok(())
     }
 
+    /// Prepares the engine for a new game: waits for it to be ready and, unless disabled, sends
+    /// `usinewgame`.
+    pub fn new_game(&mut self) -> Result<()> {
+        self.isready_between_games()?;
+        if !self.builder.skip_usinewgame {
+            self.usinewgame()?;
+        }
+        Ok(())
+    }
+
+    /// Runs the configured warmup, if any, the first time it is called for this engine process.
+    /// The result of the search is discarded.
+    pub fn warmup(&mut self) -> Result<()> {
+        if self.warmed_up {
+            return Ok(());
+        }
+        self.warmed_up = true;
+        match self.builder.warmup {
+            None => Ok(()),
+            Some(Warmup::Bench) => {
+                self.write_line("bench")?;
+                self.isready_with_timeout(WARMUP_TIMEOUT)
+            }
+            Some(Warmup::Depth(depth)) => {
+                self.drain()?;
+                self.write_line("position startpos")?;
+                self.write_line(&format!("go depth {depth}"))?;
+                self.flush()?;
+                match self.wait_for_bestmove(shogi::Color::Sente, Some(WARMUP_TIMEOUT), None) {
+                    EngineResult::Ok(_) => Ok(()),
+                    EngineResult::Err(err) => Err(err),
+                    EngineResult::Timeout => {
+                        self.kill();
+                        Err(std::io::Error::new(
+                            std::io::ErrorKind::TimedOut,
+                            format!("{} did not finish its warmup search", self.name),
+                        ))
+                    }
+                    EngineResult::Disconnected => Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("Engine {} disconnected during its warmup", self.name),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// How often an idle engine should be pinged, if at all.
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.builder.keepalive
+    }
+
+    /// Pings an idle engine with `isready` if it has not been sent anything for its keepalive
+    /// interval. Must only be called between games, never while the engine is searching.
+    pub fn keepalive(&mut self) -> Result<()> {
+        match self.builder.keepalive {
+            Some(interval) if self.last_sent.elapsed() >= interval => self.isready(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Waits for the engine to be ready before it is sent a position to search, if it was
+    /// configured to need this.
+    pub fn ready_for_move(&mut self) -> Result<()> {
+        if self.builder.ready_every_move {
+            self.isready()?;
+        }
+        Ok(())
+    }
+
     pub fn position(&mut self, game: &shogi::Game) -> Result<()> {
+        self.drain()?;
         let position = format!("position {}", game.usi_string());
         self.write_line(&position)?;
         self.flush()?;
         Ok(())
     }
 
+    /// Sets up an arbitrary position for analysis. The sfen is validated before anything is sent
+    /// to the engine, and the parsed position is returned.
+    pub fn position_sfen(&mut self, sfen: &str) -> Result<shogi::Position> {
+        let Some(position) = shogi::Position::parse(sfen.trim()) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid sfen: {sfen}"),
+            ));
+        };
+        self.drain()?;
+        self.write_line(&format!("position sfen {position}"))?;
+        self.flush()?;
+        Ok(position)
+    }
+
+    /// Discards the output of a search that was stopped without its `bestmove` being read, up to
+    /// and including that `bestmove`, so that it isn't mistaken for the reply to the next `go`.
+    /// An engine that doesn't finish within `DRAIN_TIMEOUT` is killed.
+    fn drain(&mut self) -> Result<()> {
+        if !self.awaiting_bestmove {
+            return Ok(());
+        }
+        let result = self.read_with_timeout(Some(DRAIN_TIMEOUT), |line| {
+            if line.split_whitespace().next() == Some("bestmove") {
+                ReadState::Stop
+            } else {
+                ReadState::Continue
+            }
+        });
+        match result {
+            EngineResult::Ok(()) => {
+                self.awaiting_bestmove = false;
+                Ok(())
+            }
+            EngineResult::Err(err) => Err(err),
+            EngineResult::Timeout => {
+                self.kill();
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("{} did not send bestmove after stop", self.name),
+                ))
+            }
+            EngineResult::Disconnected => Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                format!("{} disconnected", self.name),
+            )),
+        }
+    }
+
+    /// Waits for `bestmove`, giving up once `timeout` has passed in total or once
+    /// `silence_timeout` passes without the engine sending a single line.
     pub fn wait_for_bestmove(
         &mut self,
         stm: crate::shogi::Color,
         timeout: Option<Duration>,
+        silence_timeout: Option<Duration>,
     ) -> EngineResult<MoveRecord> {
         let mut mr = MoveRecord {
             stm: Some(stm),
             ..MoveRecord::default()
         };
-        match self.read_with_timeout(timeout, |line| {
-            let mut it = line.split_ascii_whitespace();
-            match it.next() {
-                Some("info") => {
-                    while let Some(tok) = it.next() {
-                        match tok {
-                            "string" => break,
-                            "depth" => {
-                                if let Some(value) = it.next()
-                                    && let Ok(value) = value.parse::<u32>()
-                                {
-                                    mr.depth = value;
-                                }
-                            }
-                            "seldepth" => {
-                                if let Some(value) = it.next()
-                                    && let Ok(value) = value.parse::<u32>()
-                                {
-                                    mr.seldepth = value;
-                                }
-                            }
-                            "nodes" => {
-                                if let Some(value) = it.next()
-                                    && let Ok(value) = value.parse::<u64>()
-                                {
-                                    mr.nodes = value;
-                                }
-                            }
-                            "nps" => {
-                                if let Some(value) = it.next()
-                                    && let Ok(value) = value.parse::<u64>()
-                                {
-                                    mr.nps = value;
-                                }
-                            }
-                            "time" => {
-                                if let Some(value) = it.next()
-                                    && let Ok(value) = value.parse::<u64>()
-                                {
-                                    mr.engine_time = value;
-                                }
-                            }
-                            "hashfull" => {
-                                if let Some(value) = it.next()
-                                    && let Ok(value) = value.parse::<u32>()
-                                {
-                                    mr.hashfull = value;
-                                }
-                            }
-                            "score" => match it.next() {
-                                Some(x) => match x {
-                                    "cp" => {
-                                        if let Some(value) = it.next()
-                                            && let Ok(value) = value.parse::<i32>()
-                                        {
-                                            mr.score = Score::Cp(value);
-                                        }
-                                    }
-                                    "mate" => {
-                                        if let Some(value) = it.next()
-                                            && let Ok(value) = value.parse::<i32>()
-                                        {
-                                            mr.score = Score::Mate(value);
-                                        }
-                                    }
-                                    _ => continue,
-                                },
-                                None => continue,
-                            },
-                            _ => continue,
-                        }
-                    }
-                    ReadState::Continue
-                }
-                Some("bestmove") => {
-                    let mstr = it.next().unwrap_or("");
-                    mr.mstr = mstr.to_string();
-                    if let Some(m) = shogi::Move::parse(mstr) {
-                        mr.m = m;
-                    }
-                    ReadState::Stop
-                }
-                _ => ReadState::Continue,
-            }
-        }) {
+        match self.read_with_timeouts(timeout, silence_timeout, |line| mr.update(&line)) {
             EngineResult::Ok(()) => EngineResult::Ok(mr),
             EngineResult::Err(err) => EngineResult::Err(err),
             EngineResult::Timeout => EngineResult::Timeout,
@@ -332,186 +826,1092 @@ impl Engine {
         }
     }
 
+    /// Starts searching the position last sent with `position` until told to stop.
+    pub fn go_infinite(&mut self, stm: shogi::Color) -> Result<Analysis<'_>> {
+        self.write_line("go infinite")?;
+        self.flush()?;
+        Ok(Analysis {
+            engine: self,
+            record: MoveRecord {
+                stm: Some(stm),
+                ..MoveRecord::default()
+            },
+            finished: false,
+        })
+    }
+
     pub fn flush(&mut self) -> Result<()> {
-        self.stdin.flush()
+        self.transport.flush()
     }
 
-    #[cfg(unix)]
-    pub fn read_with_timeout<F>(&mut self, timeout: Option<Duration>, mut f: F) -> EngineResult<()>
+    pub fn read_with_timeout<F>(&mut self, timeout: Option<Duration>, f: F) -> EngineResult<()>
     where
         F: FnMut(String) -> ReadState,
     {
-        use std::io::Read;
-        use std::os::fd::AsRawFd;
-
-        let timeout_ms = match timeout {
-            Some(timeout) => timeout.as_millis().clamp(0, i32::MAX as u128) as i32,
-            None => -1,
-        };
+        self.read_with_timeouts(timeout, None, f)
+    }
 
+    /// Feeds lines to `f` until it stops. `timeout` caps the total wait, while
+    /// `silence_timeout` caps the wait for each line, so an engine that keeps talking is only
+    /// bounded by the former.
+    fn read_with_timeouts<F>(
+        &mut self,
+        timeout: Option<Duration>,
+        silence_timeout: Option<Duration>,
+        mut f: F,
+    ) -> EngineResult<()>
+    where
+        F: FnMut(String) -> ReadState,
+    {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut silence_deadline = silence_timeout.map(|timeout| Instant::now() + timeout);
+        let mut buf = [0u8; 4096];
         loop {
-            let mut fds: [libc::pollfd; 1] = unsafe { std::mem::zeroed() };
-            fds[0].fd = self.stdout.as_raw_fd();
-            fds[0].events = libc::POLLIN;
-
-            let ready_count = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as u64, timeout_ms) };
-            if ready_count < 0 {
-                let err = std::io::Error::last_os_error();
-                match err.raw_os_error() {
-                    Some(libc::EINTR) | Some(libc::EAGAIN) => continue,
-                    _ => return EngineResult::Err(err),
-                }
+            // Lines left over from an earlier read that stopped early come first, as they may
+            // already hold what `f` is waiting for.
+            match self.process_read_buf(&mut f) {
+                Ok(ReadState::Continue) => {}
+                Ok(ReadState::Stop) => return EngineResult::Ok(()),
+                Err(err) => return EngineResult::Err(err),
             }
 
-            assert!(ready_count as usize <= fds.len());
-
-            if ready_count == 0 {
-                return EngineResult::Timeout;
+            let max_line_length = self.builder.max_line_length.unwrap_or(MAX_LINE_LENGTH);
+            if self.read_buf.len() > max_line_length {
+                error!(
+                    "Engine {} sent more than {max_line_length} bytes without a newline: {}...",
+                    self.name,
+                    String::from_utf8_lossy(&self.read_buf[..self.read_buf.len().min(80)])
+                );
+                self.read_buf.clear();
+                self.kill();
+                return EngineResult::Disconnected;
             }
 
-            let count = {
-                self.read_buf.reserve(4096);
-                let old_len = self.read_buf.len();
-                let spare_cap = self.read_buf.spare_capacity_mut();
-                let spare_cap = unsafe {
-                    std::slice::from_raw_parts_mut(
-                        spare_cap.as_mut_ptr() as *mut u8,
-                        spare_cap.len(),
-                    )
-                };
-                match self.stdout.read(spare_cap) {
-                    Err(err) => return EngineResult::Err(err),
-                    Ok(count) => {
-                        unsafe { self.read_buf.set_len(old_len + count) };
-                        count
-                    }
-                }
+            let read_deadline = match (deadline, silence_deadline) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
             };
-
-            if count == 0 {
-                return EngineResult::Disconnected;
+            let read_timeout = read_deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            match self.transport.read_with_timeout(&mut buf, read_timeout) {
+                EngineResult::Ok(count) => self.read_buf.extend_from_slice(&buf[..count]),
+                EngineResult::Timeout => return EngineResult::Timeout,
+                EngineResult::Disconnected => return EngineResult::Disconnected,
+                EngineResult::Err(err) => return EngineResult::Err(err),
             }
 
-            match self.process_read_buf(&mut f) {
-                Ok(ReadState::Continue) => {}
-                Ok(ReadState::Stop) => return EngineResult::Ok(()),
-                Err(err) => return EngineResult::Err(err),
+            if let Some(silence_timeout) = silence_timeout
+                && memchr::memchr(b'\n', &self.read_buf).is_some()
+            {
+                silence_deadline = Some(Instant::now() + silence_timeout);
             }
         }
     }
 
-    #[cfg(windows)]
-    pub fn read_with_timeout<F>(&mut self, timeout: Option<Duration>, mut f: F) -> EngineResult<()>
+    fn process_read_buf<F>(&mut self, mut f: F) -> Result<ReadState>
     where
         F: FnMut(String) -> ReadState,
     {
-        use std::os::windows::io::AsRawHandle;
-        use windows::{
-            Win32::Foundation::*, Win32::Storage::FileSystem::*, Win32::System::IO::*,
-            Win32::System::Threading::*,
-        };
+        while let Some(i) = memchr::memchr(b'\n', self.read_buf.as_slice()) {
+            let line = {
+                let line = self.read_buf.drain(0..(i + 1));
+                // Banners and `info string` lines may be in another encoding, such as Shift_JIS,
+                // but the USI keywords we look for are always ASCII.
+                String::from_utf8_lossy(line.as_slice()).into_owned()
+            };
 
-        let timeout_ms = match timeout {
-            Some(timeout) => timeout.as_millis().clamp(0, i32::MAX as u128) as u32,
-            None => INFINITE,
-        };
+            trace!("{} > {}", self.name(), line.trim());
+            self.log_line(false, line.trim());
 
-        loop {
-            unsafe {
-                let handle = HANDLE(self.stdout.as_raw_handle());
-
-                let mut overlapped = OVERLAPPED::default();
-                overlapped.hEvent =
-                    CreateEventW(None, true, false, None).expect("Could not create event");
-
-                let old_read_buf_len = self.read_buf.len();
-
-                let write_buf = {
-                    self.read_buf.reserve(4096);
-                    let spare_cap = self.read_buf.spare_capacity_mut();
-                    std::slice::from_raw_parts_mut(
-                        spare_cap.as_mut_ptr() as *mut u8,
-                        spare_cap.len(),
-                    )
-                };
+            // Engines built on Windows may end lines with `\r\n`, and start their output with a
+            // byte order mark.
+            let line = String::from(line.trim_start_matches('\u{feff}').trim_end());
+            match f(line) {
+                ReadState::Continue => {}
+                ReadState::Stop => return Ok(ReadState::Stop),
+            }
+        }
 
-                if let Err(err) = ReadFile(handle, Some(write_buf), None, Some(&mut overlapped))
-                    && err.code() != ERROR_IO_PENDING.into()
-                {
-                    let _ = CloseHandle(overlapped.hEvent);
-                    return EngineResult::Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("ReadFile Failed: {:?}", err),
-                    ));
+        Ok(ReadState::Continue)
+    }
+}
+
+/// Bounds on an `Analysis::run`, so that it ends even if the engine never sends `bestmove`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnalysisLimits {
+    /// Stop after this many `info` lines.
+    pub max_infos: Option<u64>,
+    /// Stop after searching for this long.
+    pub max_time: Option<Duration>,
+}
+
+/// A search started with `Engine::go_infinite`. The search state is only updated as output is
+/// read with `poll`, so the caller decides how long to wait between updates.
+#[derive(Debug)]
+pub struct Analysis<'a> {
+    engine: &'a mut Engine,
+    record: MoveRecord,
+    /// Set once the engine sent `bestmove` without being told to stop.
+    finished: bool,
+}
+
+impl Analysis<'_> {
+    /// Reads the search output that arrives within `timeout` and returns the latest state.
+    pub fn poll(&mut self, timeout: Duration) -> EngineResult<&MoveRecord> {
+        if self.finished {
+            return EngineResult::Ok(&self.record);
+        }
+        let record = &mut self.record;
+        match self
+            .engine
+            .read_with_timeout(Some(timeout), |line| record.update(&line))
+        {
+            EngineResult::Ok(()) => {
+                self.finished = true;
+                EngineResult::Ok(&self.record)
+            }
+            EngineResult::Timeout => EngineResult::Ok(&self.record),
+            EngineResult::Err(err) => EngineResult::Err(err),
+            EngineResult::Disconnected => EngineResult::Disconnected,
+        }
+    }
+
+    /// Sends `stop` and waits up to `timeout` for the final `bestmove`. If it doesn't arrive in
+    /// time, it is discarded before the engine is next used.
+    pub fn stop(mut self, timeout: Duration) -> EngineResult<MoveRecord> {
+        match self.finish(timeout) {
+            EngineResult::Ok(()) => EngineResult::Ok(std::mem::take(&mut self.record)),
+            EngineResult::Timeout => EngineResult::Timeout,
+            EngineResult::Err(err) => EngineResult::Err(err),
+            EngineResult::Disconnected => EngineResult::Disconnected,
+        }
+    }
+
+    /// Lets the search run until the engine sends `bestmove` or one of `limits` is reached,
+    /// then stops it as with `stop`. An engine that ignores `stop` for `stop_timeout` doesn't
+    /// hold up the caller: the line searched so far is returned all the same.
+    pub fn run(
+        mut self,
+        limits: AnalysisLimits,
+        stop_timeout: Duration,
+    ) -> EngineResult<MoveRecord> {
+        if !self.finished {
+            let mut infos = 0;
+            let mut bestmove = false;
+            let record = &mut self.record;
+            let result = self.engine.read_with_timeout(limits.max_time, |line| {
+                let state = record.update(&line);
+                bestmove = matches!(state, ReadState::Stop);
+                if line.starts_with("info") {
+                    infos += 1;
+                    if limits.max_infos.is_some_and(|max_infos| infos >= max_infos) {
+                        return ReadState::Stop;
+                    }
                 }
+                state
+            });
+            match result {
+                EngineResult::Ok(()) | EngineResult::Timeout => self.finished = bestmove,
+                EngineResult::Err(err) => return EngineResult::Err(err),
+                EngineResult::Disconnected => return EngineResult::Disconnected,
+            }
+        }
+        match self.finish(stop_timeout) {
+            EngineResult::Ok(()) | EngineResult::Timeout => {
+                EngineResult::Ok(std::mem::take(&mut self.record))
+            }
+            EngineResult::Err(err) => EngineResult::Err(err),
+            EngineResult::Disconnected => EngineResult::Disconnected,
+        }
+    }
 
-                match WaitForSingleObject(overlapped.hEvent, timeout_ms) {
-                    WAIT_TIMEOUT => {
-                        let _ = CancelIo(handle);
-                        let _ = CloseHandle(overlapped.hEvent);
-                        return EngineResult::Timeout;
+    fn finish(&mut self, timeout: Duration) -> EngineResult<()> {
+        if self.finished {
+            return EngineResult::Ok(());
+        }
+        self.finished = true;
+        if let Err(err) = self.send_stop() {
+            return EngineResult::Err(err);
+        }
+        let record = &mut self.record;
+        match self
+            .engine
+            .read_with_timeout(Some(timeout), |line| record.update(&line))
+        {
+            EngineResult::Ok(()) => {
+                self.engine.awaiting_bestmove = false;
+                EngineResult::Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn send_stop(&mut self) -> Result<()> {
+        self.engine.awaiting_bestmove = true;
+        self.engine.write_line("stop")?;
+        self.engine.flush()
+    }
+}
+
+impl Drop for Analysis<'_> {
+    /// Stops a search that is abandoned without calling `stop`. Its output is discarded before
+    /// the engine is next used.
+    fn drop(&mut self) {
+        if !self.finished && self.send_stop().is_err() {
+            error!("Failed to write stop to engine {}", self.engine.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    /// How a loopback engine answers `go`.
+    #[derive(Clone, Copy)]
+    enum GoReply {
+        Bestmove(&'static str),
+        Disconnect,
+        /// Never answers, as if hung.
+        Silent,
+        /// Sends `lines` info lines `interval` apart before the bestmove.
+        SlowBestmove {
+            bestmove: &'static str,
+            lines: usize,
+            interval: Duration,
+        },
+    }
+
+    /// Serves a single connection with a minimal USI engine that always plays `bestmove`.
+    fn loopback_engine(bestmove: &'static str, disconnect_on_go: bool) -> String {
+        let reply = if disconnect_on_go {
+            GoReply::Disconnect
+        } else {
+            GoReply::Bestmove(bestmove)
+        };
+        flaky_loopback_engine(0, reply)
+    }
+
+    /// Like `loopback_engine`, but first drops `failures` connections without a handshake.
+    fn flaky_loopback_engine(failures: usize, go_reply: GoReply) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for _ in 0..failures {
+                drop(listener.accept().unwrap());
+            }
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            for line in BufReader::new(stream).lines() {
+                let line = line.unwrap();
+                let reply = match (line.split_whitespace().next(), go_reply) {
+                    (Some("usi"), _) => "id name loopback\nusiok\n".to_string(),
+                    (Some("isready"), _) => "readyok\n".to_string(),
+                    (Some("go"), GoReply::Disconnect) => return,
+                    (Some("go"), GoReply::Silent) => continue,
+                    (Some("go"), GoReply::Bestmove(bestmove)) => {
+                        format!("info depth 3 score cp 42\nbestmove {bestmove}\n")
                     }
-                    WAIT_OBJECT_0 => {}
-                    _ => {
-                        let _ = CloseHandle(overlapped.hEvent);
-                        return EngineResult::Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "WaitForSingleObject Failed",
-                        ));
+                    (
+                        Some("go"),
+                        GoReply::SlowBestmove {
+                            bestmove,
+                            lines,
+                            interval,
+                        },
+                    ) => {
+                        for depth in 1..=lines {
+                            std::thread::sleep(interval);
+                            let info = format!("info depth {depth}\n");
+                            if writer.write_all(info.as_bytes()).is_err() {
+                                return;
+                            }
+                        }
+                        format!("bestmove {bestmove}\n")
                     }
+                    (Some("quit"), _) => return,
+                    _ => continue,
+                };
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+        addr
+    }
+
+    /// Serves a single connection, answering each command with whatever `respond` returns.
+    fn stub_engine(respond: impl Fn(&str) -> Option<&'static str> + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { return };
+                if let Some(reply) = respond(&line)
+                    && writer.write_all(reply.as_bytes()).is_err()
+                {
+                    return;
                 }
+            }
+        });
+        addr
+    }
+
+    fn tcp_builder(host: String) -> EngineBuilder {
+        EngineBuilder {
+            host: Some(host),
+            ..EngineBuilder::default()
+        }
+    }
+
+    #[test]
+    fn tcp_engine() {
+        let mut engine = tcp_builder(loopback_engine("7g7f", false)).init().unwrap();
+        assert_eq!(engine.name(), "loopback");
+        engine.isready().unwrap();
+
+        let game = shogi::Game::new(shogi::Position::default());
+        engine.position(&game).unwrap();
+        engine.write_line("go btime 1000 wtime 1000").unwrap();
+        engine.flush().unwrap();
+        let EngineResult::Ok(record) =
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None)
+        else {
+            panic!("expected a bestmove");
+        };
+        assert_eq!(record.mstr, "7g7f");
+        assert_eq!(record.depth, 3);
+    }
+
+    /// Writes to `/dev/full` fail, as they would on a full disk.
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn unwritable_log() {
+        let builder = EngineBuilder {
+            log_file: Some(String::from("/dev/full")),
+            ..tcp_builder(loopback_engine("7g7f", false))
+        };
+        let mut engine = builder.init().unwrap();
+        assert!(engine.log.is_none());
+        engine.isready().unwrap();
+        engine.write_line("go btime 1000 wtime 1000").unwrap();
+        engine.flush().unwrap();
+        assert!(matches!(
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None),
+            EngineResult::Ok(_)
+        ));
+    }
+
+    #[test]
+    fn tcp_engine_disconnect() {
+        let mut engine = tcp_builder(loopback_engine("7g7f", true)).init().unwrap();
+        engine.write_line("go btime 1000 wtime 1000").unwrap();
+        engine.flush().unwrap();
+        assert!(matches!(
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None),
+            EngineResult::Disconnected
+        ));
+    }
+
+    fn go(engine: &mut Engine) {
+        engine.write_line("go").unwrap();
+        engine.flush().unwrap();
+    }
+
+    #[test]
+    fn overlong_line() {
+        let flood: &'static str = "x".repeat(64 * 1024).leak();
+        let addr = stub_engine(move |command| match command {
+            "usi" => Some("id name chatty\nusiok\n"),
+            "go" => Some(flood),
+            _ => None,
+        });
+        let mut engine = EngineBuilder {
+            max_line_length: Some(4096),
+            ..tcp_builder(addr)
+        }
+        .init()
+        .unwrap();
+        go(&mut engine);
+        assert!(matches!(
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None),
+            EngineResult::Disconnected
+        ));
+    }
+
+    #[test]
+    fn silent_engine_times_out() {
+        let mut engine = tcp_builder(flaky_loopback_engine(0, GoReply::Silent))
+            .init()
+            .unwrap();
+        go(&mut engine);
+        let start = Instant::now();
+        assert!(matches!(
+            engine.wait_for_bestmove(shogi::Color::Sente, None, Some(100 * Duration::MILLISECOND)),
+            EngineResult::Timeout
+        ));
+        assert!(start.elapsed() < Duration::SECOND);
+        engine.kill();
+    }
+
+    #[test]
+    fn slow_engine_is_not_silent() {
+        let slow = GoReply::SlowBestmove {
+            bestmove: "7g7f",
+            lines: 5,
+            interval: 50 * Duration::MILLISECOND,
+        };
+
+        // Each line arrives well within the silence timeout, although the whole search doesn't.
+        let mut engine = tcp_builder(flaky_loopback_engine(0, slow)).init().unwrap();
+        go(&mut engine);
+        let EngineResult::Ok(record) =
+            engine.wait_for_bestmove(shogi::Color::Sente, None, Some(150 * Duration::MILLISECOND))
+        else {
+            panic!("expected a bestmove");
+        };
+        assert_eq!(record.mstr, "7g7f");
+        assert_eq!(record.depth, 5);
+
+        // The overall timeout still applies to an engine that keeps talking.
+        let mut engine = tcp_builder(flaky_loopback_engine(0, slow)).init().unwrap();
+        go(&mut engine);
+        assert!(matches!(
+            engine.wait_for_bestmove(
+                shogi::Color::Sente,
+                Some(120 * Duration::MILLISECOND),
+                Some(150 * Duration::MILLISECOND)
+            ),
+            EngineResult::Timeout
+        ));
+    }
+
+    #[test]
+    fn version_handshake() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some("id name Engine 1.3-dev (a1b2c3)\nid author A. Author\nusiok\n"),
+            _ => None,
+        });
+        let engine = tcp_builder(addr).init().unwrap();
+        assert_eq!(engine.name(), "Engine 1.3-dev (a1b2c3)");
+        assert_eq!(engine.usi_name(), Some("Engine 1.3-dev (a1b2c3)"));
+        assert_eq!(engine.usi_author(), Some("A. Author"));
+    }
+
+    #[test]
+    fn noisy_startup() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some(
+                "\u{feff}Noisy 2.1 Copyright (C) 2024 The Noisy Authors\n\
+                 \n\
+                 info string Loading eval.bin\n\
+                 id name Noisy 2.1\n\
+                 Unknown command: usi?\n\
+                 id\n\
+                 id author  \n\
+                 id author The Noisy Authors\n\
+                 id version 2.1\n\
+                 option name id type string default name\n\
+                 info string usiok soon\n\
+                 usiok\n",
+            ),
+            "isready" => Some("readyok\n"),
+            _ => None,
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        assert_eq!(engine.name(), "Noisy 2.1");
+        assert_eq!(engine.usi_author(), Some("The Noisy Authors"));
+        engine.isready().unwrap();
 
-                let mut bytes_read: u32 = 0;
-                if let Err(err) = GetOverlappedResult(handle, &overlapped, &mut bytes_read, false) {
-                    let _ = CloseHandle(overlapped.hEvent);
-                    return EngineResult::Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("GetOverlappedResult Failed: {:?}", err),
-                    ));
+        // A banner in Shift_JIS is not valid UTF-8.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { return };
+                let reply: &[u8] = match line.as_str() {
+                    "usi" => b"\x8f\xab\x8a\xfb\nid name sjis\nusiok\n",
+                    "isready" => b"readyok\n",
+                    _ => continue,
+                };
+                if writer.write_all(reply).is_err() {
+                    return;
                 }
+            }
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        assert_eq!(engine.name(), "sjis");
+        engine.isready().unwrap();
+    }
 
-                let _ = CloseHandle(overlapped.hEvent);
+    #[test]
+    fn crlf_line_endings() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some("id name  crlf engine \r\nusiok\r\n"),
+            "isready" => Some("readyok\r\n"),
+            "go" => Some("info depth 7 score cp 42\r\nbestmove  7g7f \r\n"),
+            _ => None,
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        assert_eq!(engine.name(), "crlf engine");
+        assert_eq!(engine.usi_name(), Some("crlf engine"));
+        assert_eq!(engine.usi_author(), None);
+        engine.isready().unwrap();
+        go(&mut engine);
+        let EngineResult::Ok(record) =
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None)
+        else {
+            panic!("expected a bestmove");
+        };
+        assert_eq!(record.mstr, "7g7f");
+        assert_eq!(record.m, shogi::Move::parse("7g7f").unwrap());
+        assert_eq!(record.depth, 7);
+    }
 
-                self.read_buf
-                    .set_len(old_read_buf_len + bytes_read as usize);
+    #[test]
+    fn bounded_scores() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some("id name bounded\nusiok\n"),
+            "go" => Some(
+                "info depth 9 score cp 35\n\
+                 info depth 10 score cp 80 lowerbound\n\
+                 info depth 10 score mate -3 upperbound nodes 100\n\
+                 bestmove 7g7f\n",
+            ),
+            _ => None,
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        go(&mut engine);
+        let EngineResult::Ok(record) =
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None)
+        else {
+            panic!("expected a bestmove");
+        };
+        assert!(matches!(record.score, Score::Mate(-3)));
+        assert_eq!(record.score_bound, Some(ScoreBound::Upper));
+        assert!(matches!(record.exact_score, Score::Cp(35)));
+        assert!(matches!(record.adjudication_score(), Score::Cp(35)));
+        assert_eq!(record.nodes, 100);
+    }
+
+    #[test]
+    fn tablebase_and_current_move_info() {
+        let mut record = MoveRecord::default();
+        record.update("info depth 5 currmove 2g2f currmovenumber 3 tbhits 12 nodes 500");
+        assert_eq!(record.currmove, shogi::Move::parse("2g2f"));
+        assert_eq!(record.currmovenumber, 3);
+        assert_eq!(record.tbhits, 12);
+        assert_eq!(record.depth, 5);
+        assert_eq!(record.nodes, 500);
+
+        // In any order, and without disturbing the fields around them.
+        record.update("info tbhits 40 score cp 15 currmovenumber 7 currmove 7g7f depth 6");
+        assert_eq!(record.currmove, shogi::Move::parse("7g7f"));
+        assert_eq!(record.currmovenumber, 7);
+        assert_eq!(record.tbhits, 40);
+        assert_eq!(record.depth, 6);
+        assert!(matches!(record.score, Score::Cp(15)));
+    }
 
-                if bytes_read == 0 {
-                    return EngineResult::Disconnected;
+    #[test]
+    fn slow_readyok() {
+        let delayed_engine = |delay: Duration| {
+            stub_engine(move |command| match command {
+                "usi" => Some("id name slow\nusiok\n"),
+                "isready" => {
+                    std::thread::sleep(delay);
+                    Some("readyok\n")
                 }
+                _ => None,
+            })
+        };
+        let builder = |delay, ready_timeout| EngineBuilder {
+            ready_timeout: Some(Duration::from_millis(ready_timeout)),
+            ..tcp_builder(delayed_engine(Duration::from_millis(delay)))
+        };
+
+        // Slower than the timeout, but within the retry.
+        let mut engine = builder(300, 200).init().unwrap();
+        engine.new_game().unwrap();
+        // Nothing is left over to be mistaken for the reply to the next `isready`.
+        engine.new_game().unwrap();
+
+        let mut engine = builder(300, 1000).init().unwrap();
+        engine.new_game().unwrap();
+
+        let mut engine = builder(1000, 200).init().unwrap();
+        let err = engine.new_game().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn position_startpos() {
+        let (send, recv) = std::sync::mpsc::channel();
+        let addr = stub_engine(move |command| {
+            send.send(String::from(command)).unwrap();
+            match command {
+                "usi" => Some("id name analyser\nusiok\n"),
+                _ => None,
+            }
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        assert_eq!(recv.recv().unwrap(), "usi");
+
+        let mut game = shogi::Game::new(shogi::Position::default());
+        game.do_move(shogi::Move::parse("2g2f").unwrap());
+        engine.position(&game).unwrap();
+        assert_eq!(recv.recv().unwrap(), "position startpos moves 2g2f");
+    }
+
+    #[test]
+    fn position_sfen() {
+        let (send, recv) = std::sync::mpsc::channel();
+        let addr = stub_engine(move |command| {
+            send.send(String::from(command)).unwrap();
+            match command {
+                "usi" => Some("id name analyser\nusiok\n"),
+                _ => None,
+            }
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        assert_eq!(recv.recv().unwrap(), "usi");
+
+        let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2";
+        let position = engine.position_sfen(sfen).unwrap();
+        assert_eq!(position.stm(), shogi::Color::Gote);
+        assert_eq!(recv.recv().unwrap(), format!("position sfen {sfen}"));
+
+        for invalid in [
+            "",
+            "startpos",
+            "lnsgkgsnl/9 b - 1",
+            &sfen.replace(" w ", " x "),
+        ] {
+            let err = engine.position_sfen(invalid).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        engine.write_line("isready").unwrap();
+        engine.flush().unwrap();
+        assert_eq!(recv.recv().unwrap(), "isready");
+    }
 
-                match self.process_read_buf(&mut f) {
-                    Ok(ReadState::Continue) => {}
-                    Ok(ReadState::Stop) => return EngineResult::Ok(()),
-                    Err(err) => return EngineResult::Err(err),
+    #[test]
+    fn game_and_move_handshakes() {
+        for (skip_usinewgame, ready_every_move) in
+            [(false, false), (true, false), (false, true), (true, true)]
+        {
+            let (send, recv) = std::sync::mpsc::channel();
+            let addr = stub_engine(move |command| {
+                send.send(String::from(command)).unwrap();
+                match command {
+                    "usi" => Some("id name handshakes\nusiok\n"),
+                    "isready" => Some("readyok\n"),
+                    _ => None,
                 }
+            });
+            let mut engine = EngineBuilder {
+                skip_usinewgame,
+                ready_every_move,
+                ..tcp_builder(addr)
             }
+            .init()
+            .unwrap();
+            engine.new_game().unwrap();
+            engine.ready_for_move().unwrap();
+            engine.write_line("quit").unwrap();
+            engine.flush().unwrap();
+
+            let mut expected = vec!["usi", "isready"];
+            if !skip_usinewgame {
+                expected.push("usinewgame");
+            }
+            if ready_every_move {
+                expected.push("isready");
+            }
+            expected.push("quit");
+            let commands: Vec<String> = expected.iter().map(|_| recv.recv().unwrap()).collect();
+            assert_eq!(commands, expected);
         }
     }
 
-    fn process_read_buf<F>(&mut self, mut f: F) -> Result<ReadState>
-    where
-        F: FnMut(String) -> ReadState,
-    {
-        while let Some(i) = memchr::memchr(b'\n', self.read_buf.as_slice()) {
-            let line = {
-                let line = self.read_buf.drain(0..(i + 1));
-                let Ok(line) = str::from_utf8(line.as_slice()) else {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "Received Invalid UTF-8",
-                    ));
-                };
-                line.to_string()
+    #[test]
+    fn keepalive_pings_idle_engine() {
+        let (send, recv) = std::sync::mpsc::channel();
+        let addr = stub_engine(move |command| {
+            send.send(String::from(command)).unwrap();
+            match command {
+                "usi" => Some("id name sleepy\nusiok\n"),
+                "isready" => Some("readyok\n"),
+                _ => None,
+            }
+        });
+        let mut engine = EngineBuilder {
+            keepalive: Some(Duration::from_millis(50)),
+            ..tcp_builder(addr)
+        }
+        .init()
+        .unwrap();
+        assert_eq!(recv.recv().unwrap(), "usi");
+
+        // Not idle for long enough yet.
+        engine.keepalive().unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        engine.keepalive().unwrap();
+        assert_eq!(recv.recv().unwrap(), "isready");
+        // The `readyok` was consumed, and the ping resets the idle timer.
+        assert!(engine.read_buf.is_empty());
+        engine.keepalive().unwrap();
+        engine.write_line("quit").unwrap();
+        engine.flush().unwrap();
+        assert_eq!(recv.recv().unwrap(), "quit");
+    }
+
+    #[test]
+    fn late_options_after_readyok() {
+        let (send, recv) = std::sync::mpsc::channel();
+        let addr = stub_engine(move |command| {
+            send.send(String::from(command)).unwrap();
+            match command {
+                "usi" => Some("id name netloader\nusiok\n"),
+                "isready" => Some("readyok\n"),
+                _ => None,
+            }
+        });
+        let mut engine = EngineBuilder {
+            usi_options: vec![(String::from("EvalFile"), String::from("nn.bin"))],
+            late_options: vec![(String::from("EvalScale"), String::from("120"))],
+            ..tcp_builder(addr)
+        }
+        .init()
+        .unwrap();
+        engine.isready().unwrap();
+
+        let commands: Vec<String> = (0..5).map(|_| recv.recv().unwrap()).collect();
+        assert_eq!(
+            commands,
+            [
+                "usi",
+                "setoption name EvalFile value nn.bin",
+                "isready",
+                "setoption name EvalScale value 120",
+                "isready",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_engine_files() {
+        let dir = std::env::temp_dir().join(format!("shogitest-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = std::env::current_exe().unwrap();
+        let builder = EngineBuilder {
+            dir: dir.to_string_lossy().to_string(),
+            cmd: exe.to_string_lossy().to_string(),
+            usi_options: vec![
+                (String::from("Threads"), String::from("1")),
+                (String::from("EvalFile"), String::from("nn.bin")),
+            ],
+            ..EngineBuilder::default()
+        };
+
+        let err = builder.check_files().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("EvalFile"), "{err}");
+        std::fs::write(dir.join("nn.bin"), "").unwrap();
+        builder.check_files().unwrap();
+
+        let missing = EngineBuilder {
+            cmd: String::from("no-such-engine"),
+            ..builder.clone()
+        };
+        let err = missing.check_files().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("no-such-engine"), "{err}");
+
+        // A file that exists but can't be run is rejected too.
+        #[cfg(unix)]
+        {
+            let not_executable = EngineBuilder {
+                cmd: String::from("nn.bin"),
+                ..builder.clone()
             };
+            assert!(not_executable.check_files().is_err());
+        }
 
-            trace!("{} > {}", self.name(), line.trim());
+        let remote = EngineBuilder {
+            host: Some(String::from("127.0.0.1:1")),
+            ..missing
+        };
+        remote.check_files().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-            match f(line) {
-                ReadState::Continue => {}
-                ReadState::Stop => return Ok(ReadState::Stop),
+    #[test]
+    fn warmup_runs_once() {
+        for (warmup, expected) in [
+            (Warmup::Depth(15), &["position startpos", "go depth 15"][..]),
+            (Warmup::Bench, &["bench", "isready"][..]),
+        ] {
+            let (send, recv) = std::sync::mpsc::channel();
+            let addr = stub_engine(move |command| {
+                send.send(String::from(command)).unwrap();
+                match command {
+                    "usi" => Some("id name cold\nusiok\n"),
+                    "isready" => Some("readyok\n"),
+                    "go depth 15" => Some("info depth 15 score cp 30\nbestmove 7g7f\n"),
+                    _ => None,
+                }
+            });
+            let mut engine = EngineBuilder {
+                warmup: Some(warmup),
+                ..tcp_builder(addr)
             }
+            .init()
+            .unwrap();
+            assert_eq!(recv.recv().unwrap(), "usi");
+
+            engine.warmup().unwrap();
+            for command in expected {
+                assert_eq!(recv.recv().unwrap(), *command);
+            }
+            assert!(engine.read_buf.is_empty());
+            // Later games are not preceded by another warmup.
+            engine.warmup().unwrap();
+            engine.write_line("quit").unwrap();
+            engine.flush().unwrap();
+            assert_eq!(recv.recv().unwrap(), "quit");
         }
+    }
 
-        Ok(ReadState::Continue)
+    #[test]
+    fn infinite_analysis() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some("id name analyser\nusiok\n"),
+            "go infinite" => Some(
+                "info depth 1 score cp 10 pv 7g7f\n\
+                 info depth 2 score cp 25 pv 2g2f 8c8d\n\
+                 info depth 3 score cp 30 pv 2g2f 8c8d 2f2e\n",
+            ),
+            "stop" => Some("bestmove 2g2f ponder 8c8d\n"),
+            _ => None,
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        engine
+            .position(&shogi::Game::new(shogi::Position::default()))
+            .unwrap();
+        let mut analysis = engine.go_infinite(shogi::Color::Sente).unwrap();
+
+        let start = Instant::now();
+        loop {
+            let EngineResult::Ok(record) = analysis.poll(10 * Duration::MILLISECOND) else {
+                panic!("expected search output");
+            };
+            if record.depth == 3 {
+                break;
+            }
+            assert!(start.elapsed() < Duration::SECOND);
+        }
+
+        let EngineResult::Ok(record) = analysis.stop(Duration::SECOND) else {
+            panic!("expected a bestmove");
+        };
+        assert_eq!(record.mstr, "2g2f");
+        assert!(matches!(record.score, Score::Cp(30)));
+        let pv: Vec<String> = record.pv.iter().map(|m| m.to_string()).collect();
+        assert_eq!(pv, ["2g2f", "8c8d", "2f2e"]);
+    }
+
+    #[test]
+    fn analysis_limits() {
+        // The engine never answers `stop`, so only the limits end the search.
+        let runaway = || {
+            stub_engine(|command| match command {
+                "usi" => Some("id name runaway\nusiok\n"),
+                "go infinite" => Some(
+                    "info depth 1 score cp 10 pv 7g7f\n\
+                 info depth 2 score cp 20 pv 2g2f\n\
+                 info depth 3 score cp 30 pv 2g2f 8c8d\n\
+                 info depth 4 score cp 40 pv 2g2f 8c8d 2f2e\n",
+                ),
+                _ => None,
+            })
+        };
+        let game = shogi::Game::new(shogi::Position::default());
+
+        let mut engine = tcp_builder(runaway()).init().unwrap();
+        engine.position(&game).unwrap();
+        let analysis = engine.go_infinite(shogi::Color::Sente).unwrap();
+        let limits = AnalysisLimits {
+            max_infos: Some(3),
+            max_time: Some(Duration::SECOND),
+        };
+        let start = Instant::now();
+        let EngineResult::Ok(record) = analysis.run(limits, 10 * Duration::MILLISECOND) else {
+            panic!("expected the line so far");
+        };
+        assert!(start.elapsed() < Duration::SECOND);
+        assert!(record.mstr.is_empty());
+        // The info sent past the limit is still read while waiting for the engine to stop.
+        assert!(matches!(record.score, Score::Cp(40)));
+        let pv: Vec<String> = record.pv.iter().map(|m| m.to_string()).collect();
+        assert_eq!(pv, ["2g2f", "8c8d", "2f2e"]);
+
+        let mut engine = tcp_builder(runaway()).init().unwrap();
+        engine.position(&game).unwrap();
+        let analysis = engine.go_infinite(shogi::Color::Sente).unwrap();
+        let limits = AnalysisLimits {
+            max_infos: None,
+            max_time: Some(50 * Duration::MILLISECOND),
+        };
+        let EngineResult::Ok(record) = analysis.run(limits, 10 * Duration::MILLISECOND) else {
+            panic!("expected the line so far");
+        };
+        assert_eq!(record.depth, 4);
+
+        // The bestmove arrives with the infos past the limit, and is read once the search is
+        // stopped, so that the engine can be used again.
+        let mut engine = tcp_builder(stub_engine(|command| match command {
+            "usi" => Some("id name prompt\nusiok\n"),
+            "go infinite" => Some(
+                "info depth 1 score cp 10 pv 7g7f\n\
+                 info depth 2 score cp 20 pv 2g2f\n\
+                 info depth 3 score cp 30 pv 2g2f 8c8d\n\
+                 info depth 4 score cp 40 pv 2g2f 8c8d 2f2e\n\
+                 bestmove 2g2f\n",
+            ),
+            "go btime 1000 wtime 1000" => Some("info depth 5 score cp 5\nbestmove 7g7f\n"),
+            _ => None,
+        }))
+        .init()
+        .unwrap();
+        engine.position(&game).unwrap();
+        let analysis = engine.go_infinite(shogi::Color::Sente).unwrap();
+        let limits = AnalysisLimits {
+            max_infos: Some(3),
+            max_time: None,
+        };
+        let start = Instant::now();
+        let EngineResult::Ok(record) = analysis.run(limits, Duration::SECOND) else {
+            panic!("expected the line so far");
+        };
+        assert!(start.elapsed() < Duration::SECOND);
+        assert_eq!(record.depth, 4);
+        assert_eq!(record.mstr, "2g2f");
+        assert!(!engine.awaiting_bestmove);
+
+        engine.position(&game).unwrap();
+        engine.write_line("go btime 1000 wtime 1000").unwrap();
+        engine.flush().unwrap();
+        let EngineResult::Ok(record) =
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None)
+        else {
+            panic!("expected a bestmove");
+        };
+        assert_eq!(record.mstr, "7g7f");
+        assert_eq!(record.depth, 5);
+    }
+
+    #[test]
+    fn late_bestmove_after_stop() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some("id name laggard\nusiok\n"),
+            "go infinite" => Some("info depth 1 score cp 10 pv 7g7f\n"),
+            "stop" => {
+                // Answer well after the caller has given up waiting.
+                std::thread::sleep(Duration::from_millis(100));
+                Some("info depth 2 score cp 20 pv 7g7f\ninfo string late\nbestmove 7g7f\n")
+            }
+            "go btime 1000 wtime 1000" => Some("info depth 4 score cp 5\nbestmove 2g2f\n"),
+            _ => None,
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        let game = shogi::Game::new(shogi::Position::default());
+        engine.position(&game).unwrap();
+        let analysis = engine.go_infinite(shogi::Color::Sente).unwrap();
+        assert!(matches!(
+            analysis.stop(10 * Duration::MILLISECOND),
+            EngineResult::Timeout
+        ));
+
+        // The stray output is discarded, so the next search gets its own bestmove.
+        engine.position(&game).unwrap();
+        engine.write_line("go btime 1000 wtime 1000").unwrap();
+        engine.flush().unwrap();
+        let EngineResult::Ok(record) =
+            engine.wait_for_bestmove(shogi::Color::Sente, Some(Duration::SECOND), None)
+        else {
+            panic!("expected a bestmove");
+        };
+        assert_eq!(record.mstr, "2g2f");
+        assert_eq!(record.depth, 4);
+    }
+
+    #[test]
+    fn missing_usiok() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some("id name mute\nid author nobody\n"),
+            _ => None,
+        });
+        let err = tcp_builder(addr.clone()).init().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(
+            err.to_string(),
+            format!("Timed-out waiting for usiok for {addr}")
+        );
+    }
+
+    #[test]
+    fn missing_readyok() {
+        let addr = stub_engine(|command| match command {
+            "usi" => Some("id name sleepy\nusiok\n"),
+            _ => None,
+        });
+        let mut engine = tcp_builder(addr).init().unwrap();
+        let err = engine.isready().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(err.to_string(), "Timed-out waiting for readyok for sleepy");
+    }
+
+    #[test]
+    fn startup_retry() {
+        let builder = tcp_builder(flaky_loopback_engine(1, GoReply::Bestmove("7g7f")));
+        assert!(builder.init().is_err());
+
+        let builder = EngineBuilder {
+            startup_retries: 1,
+            ..tcp_builder(flaky_loopback_engine(1, GoReply::Bestmove("7g7f")))
+        };
+        let mut engine = builder.init().unwrap();
+        assert_eq!(engine.name(), "loopback");
+        engine.isready().unwrap();
+    }
+
+    #[test]
+    fn startup_retry_gives_up() {
+        let addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        let builder = EngineBuilder {
+            startup_retries: 2,
+            ..tcp_builder(addr.clone())
+        };
+        let err = builder.init().unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with(&format!("Failed to start engine {addr} after 3 attempts: "))
+        );
+    }
+
+    #[test]
+    fn tcp_engine_connect_failure() {
+        let addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        assert!(tcp_builder(addr).init().is_err());
     }
 }
@@ -0,0 +1,71 @@
+use chrono::{SecondsFormat, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::{Result, Write};
+
+/// A per-engine transcript of the USI lines sent to and received from an engine.
+///
+/// Every engine process opens the file in append mode, so the engines of all concurrency slots
+/// can share one log file. Lines are written whole, prefixed with a timestamp.
+#[derive(Debug)]
+pub struct EngineLog {
+    file: File,
+}
+
+impl EngineLog {
+    pub fn open(path: &str) -> Result<EngineLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(EngineLog { file })
+    }
+
+    /// Empties the log file at `path`, for logs that should only cover the current run.
+    pub fn truncate(path: &str) -> Result<()> {
+        File::create(path).map(|_| ())
+    }
+
+    pub fn sent(&mut self, name: &str, line: &str) -> Result<()> {
+        self.write_line(name, '<', line)
+    }
+
+    pub fn received(&mut self, name: &str, line: &str) -> Result<()> {
+        self.write_line(name, '>', line)
+    }
+
+    fn write_line(&mut self, name: &str, direction: char, line: &str) -> Result<()> {
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        self.file
+            .write_all(format!("{timestamp} {name} {direction} {line}\n").as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_timestamped_lines() {
+        let path =
+            std::env::temp_dir().join(format!("shogitest-engine-log-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "previous run\n").unwrap();
+
+        EngineLog::truncate(path).unwrap();
+        let mut log = EngineLog::open(path).unwrap();
+        log.sent("a", "usi").unwrap();
+        log.received("a", "usiok").unwrap();
+        let mut other = EngineLog::open(path).unwrap();
+        other.sent("b", "isready").unwrap();
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let lines: Vec<_> = contents
+            .lines()
+            .map(|line| {
+                let (timestamp, rest) = line.split_once(' ').unwrap();
+                assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+                rest
+            })
+            .collect();
+        assert_eq!(lines, ["a < usi", "a > usiok", "b < isready"]);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{cli, engine, engine::Score, shogi, stats::Wdl, tournament};
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Error, Write};
+
+/// Writes tournament events as newline-delimited JSON, one object per line.
+pub struct EventWriter {
+    out: RefCell<Box<dyn Write>>,
+    engine_names: Vec<String>,
+}
+
+impl EventWriter {
+    /// Opens the event stream. A path of `-` writes to stdout.
+    pub fn new(path: &str, engine_names: Vec<String>) -> Result<EventWriter, Error> {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(std::io::stdout())
+        } else {
+            Box::new(File::create_new(path)?)
+        };
+        Ok(EventWriter::from_writer(out, engine_names))
+    }
+
+    pub fn from_writer(out: Box<dyn Write>, engine_names: Vec<String>) -> EventWriter {
+        EventWriter {
+            out: RefCell::new(out),
+            engine_names,
+        }
+    }
+
+    fn emit(&self, event: Value) -> Result<(), Error> {
+        let mut out = self.out.borrow_mut();
+        serde_json::to_writer(&mut *out, &event)?;
+        writeln!(out)?;
+        out.flush()
+    }
+
+    /// Lists the engines with the `id name` and `id author` they reported.
+    pub fn engines(&self, identities: &[cli::EngineIdentity]) -> Result<(), Error> {
+        self.emit(json!({
+            "event": "engines",
+            "engines": identities.iter().map(json_identity).collect::<Vec<_>>(),
+        }))
+    }
+
+    pub fn match_started(&self, ticket: &tournament::MatchTicket) -> Result<(), Error> {
+        self.emit(json!({
+            "event": "match_started",
+            "id": ticket.id,
+            "sente": self.engine_names[ticket.engines[0]],
+            "gote": self.engine_names[ticket.engines[1]],
+            "position": ticket.opening.position.to_string(),
+            "moves": ticket.opening.moves.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
+        }))
+    }
+
+    pub fn move_played(
+        &self,
+        id: u64,
+        ply: usize,
+        record: &engine::MoveRecord,
+    ) -> Result<(), Error> {
+        self.emit(json!({
+            "event": "move",
+            "id": id,
+            "ply": ply,
+            "stm": record.stm.map(|c| c.to_string()),
+            "m": record.m.to_string(),
+            "mstr": record.mstr,
+            "score": json_score(&record.score),
+            "depth": record.depth,
+            "seldepth": record.seldepth,
+            "nodes": record.nodes,
+            "nps": record.nps,
+            "engine_time": record.engine_time,
+            "hashfull": record.hashfull,
+            "tbhits": record.tbhits,
+            "measured_time_ms": record.measured_time.as_millis() as u64,
+            "time_left_ms": record.time_left.map(|t| t.as_millis() as u64),
+        }))
+    }
+
+    pub fn match_complete(&self, result: &tournament::MatchResult) -> Result<(), Error> {
+        let result_str = match result.outcome.winner() {
+            Some(shogi::Color::Sente) => "1-0",
+            Some(shogi::Color::Gote) => "0-1",
+            None if result.outcome.is_draw() => "1/2-1/2",
+            None => "undetermined",
+        };
+        self.emit(json!({
+            "event": "match_complete",
+            "id": result.ticket.id,
+            "result": result_str,
+            "reason": result.outcome.to_string(),
+            "termination": result.outcome.to_pgn_termination_string(),
+            "termination_reason": result.reason().map(|reason| reason.as_str()),
+            "opening_plies": result.opening_plies,
+            "plies": result.moves.len(),
+        }))
+    }
+
+    pub fn engine_count(&self) -> usize {
+        self.engine_names.len()
+    }
+
+    /// `color_wdl` holds each engine's results when playing sente and when playing gote.
+    pub fn interval_report(
+        &self,
+        match_complete_count: u64,
+        color_wdl: &[[Wdl; 2]],
+    ) -> Result<(), Error> {
+        self.emit(json!({
+            "event": "interval_report",
+            "match_complete_count": match_complete_count,
+            "engines": self.json_standings(color_wdl),
+        }))
+    }
+
+    pub fn tournament_complete(
+        &self,
+        match_complete_count: u64,
+        color_wdl: &[[Wdl; 2]],
+    ) -> Result<(), Error> {
+        self.emit(json!({
+            "event": "tournament_complete",
+            "match_complete_count": match_complete_count,
+            "engines": self.json_standings(color_wdl),
+        }))
+    }
+
+    fn json_standings(&self, color_wdl: &[[Wdl; 2]]) -> Value {
+        self.engine_names
+            .iter()
+            .zip(color_wdl)
+            .map(|(name, [sente, gote])| {
+                json!({
+                    "name": name,
+                    "sente_wdl": json_wdl(sente),
+                    "gote_wdl": json_wdl(gote),
+                })
+            })
+            .collect()
+    }
+}
+
+fn json_identity(identity: &cli::EngineIdentity) -> Value {
+    json!({
+        "name": identity.name,
+        "usi_name": identity.usi_name,
+        "usi_author": identity.usi_author,
+    })
+}
+
+fn json_wdl(wdl: &Wdl) -> Value {
+    json!({ "w": wdl.w, "d": wdl.d, "l": wdl.l })
+}
+
+fn json_score(score: &Score) -> Value {
+    match *score {
+        Score::None => Value::Null,
+        Score::Cp(cp) => json!({ "cp": cp }),
+        Score::Mate(mate) => json!({ "mate": mate }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_versions() {
+        let identity = cli::EngineIdentity {
+            name: String::from("dev"),
+            usi_name: Some(String::from("Engine 1.3-dev (a1b2c3)")),
+            usi_author: None,
+        };
+        assert_eq!(
+            json_identity(&identity).to_string(),
+            r#"{"name":"dev","usi_name":"Engine 1.3-dev (a1b2c3)","usi_author":null}"#
+        );
+    }
+}
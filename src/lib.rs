@@ -0,0 +1,25 @@
+#![feature(duration_constants)]
+#![feature(if_let_guard)]
+#![feature(str_split_whitespace_remainder)]
+
+pub mod affinity;
+pub mod binbook;
+pub mod book;
+pub mod cli;
+pub mod config;
+pub mod csa;
+pub mod dryrun;
+pub mod engine;
+pub mod engine_log;
+pub mod events;
+pub mod pause;
+pub mod pgn;
+pub mod progress;
+pub mod runner;
+pub mod shogi;
+pub mod sprt;
+pub mod stats;
+pub mod tc;
+pub mod tournament;
+pub mod transport;
+pub mod util;
@@ -1,52 +1,118 @@
-#![feature(duration_constants)]
-#![feature(if_let_guard)]
-#![feature(str_split_whitespace_remainder)]
-
 use log::info;
 use rand::SeedableRng;
+use shogitest::{book, cli, dryrun, engine_log, events, pause, pgn, runner, sprt, tournament};
+use std::io::IsTerminal;
+use std::process::ExitCode;
 
-mod book;
-mod cli;
-mod engine;
-mod pgn;
-mod runner;
-mod shogi;
-mod sprt;
-mod stats;
-mod tc;
-mod tournament;
-mod util;
-
-fn main() -> std::io::Result<()> {
+fn main() -> ExitCode {
     flexi_logger::Logger::try_with_env().unwrap().start().ok();
 
-    let Some(cli_options) = cli::parse() else {
-        return Ok(());
+    // I/O errors only escape while the tournament is being set up.
+    run().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        setup_failure()
+    })
+}
+
+fn run() -> std::io::Result<ExitCode> {
+    let cli_options = match cli::parse() {
+        Some(cli::Command::Run(cli_options)) => *cli_options,
+        Some(cli::Command::Version) => {
+            println!("Shogitest version 0.1.1");
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => return Ok(setup_failure()),
     };
     info!("{:#?}", &cli_options);
 
+    let Some(imported_games) = read_imported_games(&cli_options.import_pgn) else {
+        return Ok(setup_failure());
+    };
+    if cli_options.engines.is_empty() && !imported_games.is_empty() {
+        return Ok(report_imported_games(cli_options, &imported_games));
+    }
+
     if cli_options.engines.len() < 2 {
         eprintln!("We require at least two engines to be supplied.");
-        return Ok(());
+        return Ok(setup_failure());
     }
 
-    if cli_options.book.is_none() {
+    if cli_options
+        .book
+        .as_ref()
+        .is_none_or(|book| book.files.is_empty())
+    {
         eprintln!("Openings file required.");
-        return Ok(());
+        return Ok(setup_failure());
+    }
+
+    for engine in &cli_options.engines {
+        if let Err(err) = engine.builder.check_files() {
+            eprintln!("{err}");
+            return Ok(setup_failure());
+        }
     }
 
-    let engine_names = cli_options.engine_names();
+    let r = runner::Runner::new(
+        cli_options.engines.clone(),
+        cli_options.concurrency,
+        cli_options.adjudication.clone(),
+        cli_options.impasse_rule,
+        cli_options.report_interval,
+        cli_options.affinity.clone(),
+    );
+    if !r.check_oversubscription(cli_options.strict) {
+        return Ok(setup_failure());
+    }
 
     let opening_book = {
         let mut rng = match cli_options.rand_seed {
             Some(seed) => rand_chacha::ChaCha8Rng::seed_from_u64(seed),
             None => rand_chacha::ChaCha8Rng::from_os_rng(),
         };
-        book::OpeningBook::new(cli_options.book.as_ref().unwrap(), &mut rng).unwrap()
+        book::OpeningBook::new(
+            cli_options.book.as_ref().unwrap(),
+            cli_options.variant,
+            &mut rng,
+        )
+    };
+    let Some(mut opening_book) = opening_book else {
+        return Ok(setup_failure());
+    };
+    if cli_options.book.as_ref().unwrap().stride {
+        opening_book.set_stride(r.concurrency() as usize);
+    }
+
+    let Some(mut round_robin) = tournament::RoundRobin::new(&cli_options, opening_book) else {
+        return Ok(setup_failure());
     };
 
-    let mut tournament: Box<dyn tournament::Tournament> =
-        Box::new(tournament::RoundRobin::new(&cli_options, opening_book));
+    if cli_options.dry_run {
+        for line in dryrun::schedule(&mut round_robin, &cli_options) {
+            println!("{line}");
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for engine in &cli_options.engines {
+        if let Some(path) = &engine.builder.log_file
+            && !engine.builder.log_append
+        {
+            engine_log::EngineLog::truncate(path)?;
+        }
+    }
+
+    let engine_identities = match cli_options.identify_engines() {
+        Ok(engine_identities) => engine_identities,
+        Err(err) => {
+            eprintln!("{err}");
+            return Ok(setup_failure());
+        }
+    };
+    let engine_names: Vec<String> = engine_identities.iter().map(|e| e.name.clone()).collect();
+
+    let paired = cli_options.pairs_games();
+    let mut tournament: Box<dyn tournament::Tournament> = Box::new(round_robin);
 
     if let Some(pgn) = cli_options.pgn {
         tournament = Box::new(tournament::PgnOutWrapper::new(
@@ -54,34 +120,150 @@ fn main() -> std::io::Result<()> {
             &pgn,
             &cli_options.meta,
             cli_options.engines.clone(),
-            engine_names.clone(),
+            engine_identities.clone(),
         )?);
     }
 
-    let sprt_parameters = cli_options
-        .sprt
-        .map(|sprt| sprt::SprtParameters::new(sprt.nelo0, sprt.nelo1, sprt.alpha, sprt.beta));
+    if let Some(events) = &cli_options.events {
+        let writer = events::EventWriter::new(events, engine_names.clone())?;
+        writer.engines(&engine_identities)?;
+        tournament = Box::new(tournament::EventsWrapper::new(tournament, writer));
+    }
 
-    tournament = Box::new(tournament::StatsWrapper::new(
+    let mut stats = tournament::StatsWrapper::new(
         tournament,
         engine_names.clone(),
         cli_options.engines.clone(),
-        cli_options.book.map(|b| b.file.clone()),
-        sprt_parameters,
-    ));
+        cli_options.book.map_or(vec![], |b| b.files),
+        cli_options.sprt.map(|sprt| sprt_parameters(sprt, paired)),
+        cli_options.gauntlet_seeds,
+        cli::StatsOptions {
+            progress: cli_options.stats.progress && std::io::stdout().is_terminal(),
+            ..cli_options.stats
+        },
+    );
+    stats.set_engine_versions(
+        engine_identities
+            .iter()
+            .map(|e| e.usi_name.clone())
+            .collect(),
+    );
+    for games in &imported_games {
+        stats.import_games(games);
+    }
+    tournament = Box::new(stats);
 
     tournament = Box::new(tournament::ReporterWrapper::new(
         tournament,
         engine_names.clone(),
     ));
 
-    let r = runner::Runner::new(
-        cli_options.engines,
-        cli_options.concurrency,
-        cli_options.adjudication,
-        cli_options.report_interval,
+    pause::install_signal_handlers();
+    r.run(tournament.as_mut());
+
+    Ok(exit_code(tournament.as_ref(), cli_options.exit_code))
+}
+
+/// The SPRT of the tournament, whose model is chosen here once and for all: from game pairs
+/// unless `model=trinomial` is asked for, or `paired` says the schedule has no pairs.
+fn sprt_parameters(
+    sprt: cli::SprtOptions,
+    paired: bool,
+) -> (sprt::SprtParameters, [usize; 2], cli::SprtDrain) {
+    let parameters = match sprt.model {
+        sprt::SprtModel::Normalized => {
+            sprt::SprtParameters::new(sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta)
+        }
+        sprt::SprtModel::Logistic => {
+            sprt::SprtParameters::new_logistic(sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta)
+        }
+        sprt::SprtModel::Trinomial => {
+            sprt::SprtParameters::new_trinomial(sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta)
+        }
+    };
+    let parameters = if paired {
+        parameters
+    } else {
+        parameters.unpaired()
+    };
+    (parameters, sprt.pair(), sprt.drain)
+}
+
+fn setup_failure() -> ExitCode {
+    ExitCode::from(cli::SETUP_FAILURE_EXIT_CODE)
+}
+
+fn exit_code(tournament: &dyn tournament::Tournament, enabled: bool) -> ExitCode {
+    if !enabled {
+        return ExitCode::SUCCESS;
+    }
+    let verdict = tournament.sprt_verdict();
+    println!(
+        "RESULT: {}",
+        verdict.map_or("none", |verdict| verdict.to_result_string())
     );
-    r.run(tournament);
+    ExitCode::from(verdict.map_or(0, |verdict| verdict.exit_code()))
+}
+
+/// Reads the games of each `-importpgn` file, keeping the files apart so that their rounds
+/// are paired separately.
+fn read_imported_games(files: &[String]) -> Option<Vec<Vec<pgn::ImportedGame>>> {
+    let mut imported_games = vec![];
+    for file in files {
+        match pgn::read_results(file) {
+            Ok(games) if games.is_empty() => {
+                eprintln!("Warning; No completed games in {file}");
+            }
+            Ok(games) => imported_games.push(games),
+            Err(err) => {
+                eprintln!("Failed to read {file}: {err}");
+                return None;
+            }
+        }
+    }
+    Some(imported_games)
+}
+
+/// Prints the stats of imported games without playing any, taking the engines from the games.
+fn report_imported_games(
+    cli_options: cli::CliOptions,
+    imported_games: &[Vec<pgn::ImportedGame>],
+) -> ExitCode {
+    let mut engine_names: Vec<String> = vec![];
+    for game in imported_games.iter().flatten() {
+        for name in [&game.sente, &game.gote] {
+            if !engine_names.contains(name) {
+                engine_names.push(name.clone());
+            }
+        }
+    }
 
-    Ok(())
+    // Checked as if the engines of the games had been given on the command line.
+    let cli_options = cli::CliOptions {
+        engines: vec![cli::EngineOptions::default(); engine_names.len()],
+        ..cli_options
+    };
+    if let Err(err) = cli::validate(&cli_options) {
+        eprintln!("{err}");
+        return setup_failure();
+    }
+
+    let mut stats = tournament::StatsWrapper::new(
+        Box::new(tournament::NoGames),
+        engine_names.clone(),
+        vec![cli::EngineOptions::default(); engine_names.len()],
+        vec![],
+        // Imported games are paired by their rounds.
+        cli_options.sprt.map(|sprt| sprt_parameters(sprt, true)),
+        cli_options.gauntlet_seeds,
+        cli::StatsOptions {
+            progress: false,
+            ..cli_options.stats
+        },
+    );
+    for games in imported_games {
+        stats.import_games(games);
+    }
+    tournament::Tournament::tournament_complete(&stats);
+    exit_code(&stats, cli_options.exit_code)
 }
@@ -1,14 +1,20 @@
-use crate::{cli, engine::Score, shogi, tournament};
-use std::fs::File;
-use std::io::{Error, Write};
+use crate::{cli, engine::Score, shogi, tournament, util};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, Read, Seek, SeekFrom, Write};
 
 #[derive(Debug)]
 pub struct PgnWriter {
     file: File,
     engine_options: Vec<cli::EngineOptions>,
-    engine_names: Vec<String>,
+    engines: Vec<cli::EngineIdentity>,
     options: cli::PgnOutOptions,
     meta: cli::MetaDataOptions,
+    /// Games found in the file when resuming that have not been skipped yet, by round.
+    recorded_games: HashMap<u64, RecordedRound>,
+    /// Results of the games skipped since the last call to `take_resumed_games`.
+    resumed_games: Vec<ImportedGame>,
 }
 
 impl PgnWriter {
@@ -16,25 +22,103 @@ impl PgnWriter {
         options: &cli::PgnOutOptions,
         meta: &cli::MetaDataOptions,
         engine_options: Vec<cli::EngineOptions>,
-        engine_names: Vec<String>,
+        engines: Vec<cli::EngineIdentity>,
     ) -> Result<PgnWriter, Error> {
+        let (file, recorded_games) = if options.resume {
+            Self::open_for_resume(&options.file)?
+        } else {
+            (File::create_new(&options.file)?, HashMap::new())
+        };
         Ok(PgnWriter {
-            file: File::create_new(&options.file)?,
+            file,
             engine_options,
-            engine_names,
+            engines,
             options: options.clone(),
             meta: meta.clone(),
+            recorded_games,
+            resumed_games: vec![],
         })
     }
 
-    fn write_header(file: &mut File, key: &str, value: &str) -> Result<(), Error> {
-        writeln!(file, "[{} {:?}]", key, value)?;
+    /// Opens an existing file to append to, dropping any partially written game at its end.
+    fn open_for_resume(path: &str) -> Result<(File, HashMap<u64, RecordedRound>), Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let (recorded_games, complete_len) = scan_recorded_games(&contents);
+        if complete_len < contents.len() {
+            eprintln!(
+                "Warning; Removing partially written game at the end of {path} ({} bytes)",
+                contents.len() - complete_len
+            );
+            file.set_len(complete_len as u64)?;
+        }
+        file.seek(SeekFrom::Start(complete_len as u64))?;
+
+        println!(
+            "Resuming {path}: {} games already recorded",
+            recorded_games.len()
+        );
+        Ok((file, recorded_games))
+    }
+
+    fn write_header(f: &mut Vec<u8>, key: &str, value: &str) -> Result<(), Error> {
+        writeln!(f, "[{} {:?}]", key, value)?;
         Ok(())
     }
 
+    /// Whether the game of a ticket is already recorded in the file being resumed, so that it
+    /// need not be played again. The recorded game must have been played by the same engines on
+    /// the same opening, otherwise the schedule has changed since and the game is played anyway.
+    /// The result of a skipped game is kept for `take_resumed_games`.
+    pub fn take_recorded(&mut self, ticket: &tournament::MatchTicket) -> bool {
+        let Some(recorded) = self.recorded_games.remove(&ticket.id) else {
+            return false;
+        };
+        let key = game_key(
+            &self.engines[ticket.engines[0]].name,
+            &self.engines[ticket.engines[1]].name,
+            &ticket.opening.to_string(),
+        );
+        if recorded.key != key {
+            eprintln!(
+                "Warning; Round {} in {} was played by other engines or on another opening, playing it again",
+                ticket.id, self.options.file
+            );
+            return false;
+        }
+        self.resumed_games.extend(recorded.result);
+        true
+    }
+
+    /// The results of the games skipped by `take_recorded` since the last call, leaving out
+    /// games recorded without one.
+    pub fn take_resumed_games(&mut self) -> Vec<ImportedGame> {
+        std::mem::take(&mut self.resumed_games)
+    }
+
     pub fn write(&mut self, match_result: &tournament::MatchResult) -> Result<(), Error> {
-        let f = &mut self.file;
+        // The game is written with a single write, so that a crash leaves at most one partially
+        // written game at the end of the file, which resuming removes.
+        let game = self.format_game(match_result)?;
+        self.file.write_all(&game)?;
+        self.file.flush()?;
+        if self.options.fsync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn format_game(&self, match_result: &tournament::MatchResult) -> Result<Vec<u8>, Error> {
         let ticket = &match_result.ticket;
+        let mut game = Vec::new();
+        let f = &mut game;
         let date_str = match_result.game_start.format("%Y-%m-%d").to_string();
         let result_str = match match_result.outcome.winner() {
             Some(shogi::Color::Sente) => "1-0",
@@ -47,35 +131,69 @@ impl PgnWriter {
         Self::write_header(f, "Site", &self.meta.site_name)?;
         Self::write_header(f, "Date", &date_str)?;
         Self::write_header(f, "Round", &ticket.id.to_string())?;
-        Self::write_header(f, "Black", &self.engine_names[ticket.engines[0]])?;
-        Self::write_header(f, "Sente", &self.engine_names[ticket.engines[0]])?;
-        Self::write_header(f, "White", &self.engine_names[ticket.engines[1]])?;
-        Self::write_header(f, "Gote", &self.engine_names[ticket.engines[1]])?;
+        Self::write_header(f, "Black", &self.engines[ticket.engines[0]].name)?;
+        Self::write_header(f, "Sente", &self.engines[ticket.engines[0]].name)?;
+        Self::write_header(f, "White", &self.engines[ticket.engines[1]].name)?;
+        Self::write_header(f, "Gote", &self.engines[ticket.engines[1]].name)?;
         Self::write_header(f, "Result", result_str)?;
-        if match_result.ticket.opening != shogi::Position::default() {
-            Self::write_header(f, "FEN", &match_result.ticket.opening.to_string())?;
+        let opening = &match_result.ticket.opening;
+        if opening.position.variant() != shogi::Variant::Standard {
+            Self::write_header(f, "Variant", &opening.position.variant().to_string())?;
+        }
+        if opening.position != shogi::Position::default() {
+            Self::write_header(f, "FEN", &opening.position.to_string())?;
             Self::write_header(f, "SetUp", "1")?;
         }
-        Self::write_header(f, "PlyCount", &match_result.moves.len().to_string())?;
-        Self::write_header(f, "Termination", match_result.outcome.to_pgn_termination_string())?;
-        Self::write_header(f, "GameStartTime", &match_result.game_start.to_rfc3339())?;
         Self::write_header(
             f,
-            "BlackTimeControl",
-            &self.engine_options[ticket.engines[0]]
-                .time_control
-                .to_string(),
+            "PlyCount",
+            &(match_result.opening_plies + match_result.moves.len()).to_string(),
         )?;
+        if match_result.opening_plies > 0 {
+            Self::write_header(f, "OpeningPlies", &match_result.opening_plies.to_string())?;
+        }
         Self::write_header(
             f,
-            "WhiteTimeControl",
-            &self.engine_options[ticket.engines[1]]
-                .time_control
-                .to_string(),
+            "Termination",
+            match_result.outcome.to_pgn_termination_string(),
         )?;
+        if let Some(reason) = match_result.outcome.to_pgn_reason_string() {
+            Self::write_header(f, "Reason", reason)?;
+        }
+        Self::write_header(f, "GameStartTime", &match_result.game_start.to_rfc3339())?;
+        Self::write_header(f, "GameEndTime", &match_result.game_end.to_rfc3339())?;
+        Self::write_header(
+            f,
+            "GameDuration",
+            &util::format_duration(match_result.duration()),
+        )?;
+        let time_controls = ticket
+            .engines
+            .map(|i| self.engine_options[i].time_control.to_string());
+        if time_controls[0] == time_controls[1] {
+            Self::write_header(f, "TimeControl", &time_controls[0])?;
+        }
+        Self::write_header(f, "BlackTimeControl", &time_controls[0])?;
+        Self::write_header(f, "WhiteTimeControl", &time_controls[1])?;
+        if let Some(usi_name) = &self.engines[ticket.engines[0]].usi_name {
+            Self::write_header(f, "BlackVersion", usi_name)?;
+        }
+        if let Some(usi_name) = &self.engines[ticket.engines[1]].usi_name {
+            Self::write_header(f, "WhiteVersion", usi_name)?;
+        }
+        if let Some(usi_author) = &self.engines[ticket.engines[0]].usi_author {
+            Self::write_header(f, "BlackAuthor", usi_author)?;
+        }
+        if let Some(usi_author) = &self.engines[ticket.engines[1]].usi_author {
+            Self::write_header(f, "WhiteAuthor", usi_author)?;
+        }
 
         writeln!(f)?;
 
+        for m in &opening.moves {
+            writeln!(f, "{m} {{book}}")?;
+        }
+
         for (i, m) in match_result.moves.iter().enumerate() {
             let mstr = if m.mstr.is_empty() {
                 "output-was-empty"
@@ -121,6 +239,382 @@ impl PgnWriter {
         writeln!(f, "{result_str}")?;
         writeln!(f)?;
 
-        Ok(())
+        Ok(game)
+    }
+}
+
+fn game_key(sente: &str, gote: &str, opening: &str) -> String {
+    format!("{sente}\n{gote}\n{opening}")
+}
+
+/// A complete game in a file written by `PgnWriter`.
+struct RecordedGame<'a> {
+    headers: HashMap<&'a str, Cow<'a, str>>,
+    book_moves: Vec<&'a str>,
+    result: &'a str,
+}
+
+impl RecordedGame<'_> {
+    fn header(&self, key: &str) -> &str {
+        self.headers.get(key).map_or("", |value| value)
+    }
+
+    /// The result of the game, or None if it has no `Round` or was cut short without one.
+    fn imported_game(&self) -> Option<ImportedGame> {
+        let winner = match self.result {
+            "1-0" => Some(shogi::Color::Sente),
+            "0-1" => Some(shogi::Color::Gote),
+            "1/2-1/2" => None,
+            _ => return None,
+        };
+        Some(ImportedGame {
+            round: self.header("Round").parse().ok()?,
+            sente: self.header("Sente").to_string(),
+            gote: self.header("Gote").to_string(),
+            winner,
+        })
+    }
+}
+
+/// A game recorded in a file being resumed.
+#[derive(Debug)]
+struct RecordedRound {
+    /// The pairing and opening of the game, as given by `game_key`.
+    key: String,
+    result: Option<ImportedGame>,
+}
+
+/// Reads back a header value written by `write_header`, undoing the quoting and escaping of
+/// `{:?}`.
+fn unescape_header(value: &str) -> Cow<'_, str> {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some('0') => unescaped.push('\0'),
+            Some('u') => {
+                let code_point = chars
+                    .as_str()
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .and_then(|(hex, rest)| {
+                        let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+                        Some((c, rest))
+                    });
+                match code_point {
+                    Some((c, rest)) => {
+                        unescaped.push(c);
+                        chars = rest.chars();
+                    }
+                    None => unescaped.push_str("\\u"),
+                }
+            }
+            // `\\`, `\"` and `\'` stand for the character itself.
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Reads the complete games in a file written by `PgnWriter`. Also returns the length of the
+/// file up to the end of the last complete game.
+fn parse_games(contents: &str) -> (Vec<RecordedGame<'_>>, usize) {
+    let mut games = vec![];
+    let mut complete_len = 0;
+
+    let mut headers = HashMap::new();
+    let mut book_moves = vec![];
+    let mut result = None;
+    let mut offset = 0;
+    for line in contents.split_inclusive('\n') {
+        offset += line.len();
+        let complete_line = line.ends_with('\n');
+        let line = line.trim();
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if let Some((key, value)) = header.split_once(' ') {
+                headers.insert(key, unescape_header(value));
+            }
+        } else if let Some(m) = line.strip_suffix(" {book}") {
+            book_moves.push(m);
+        } else if matches!(line, "1-0" | "0-1" | "1/2-1/2" | "undetermined") {
+            result = Some(line);
+        } else if line.is_empty()
+            && complete_line
+            && let Some(result) = result.take()
+        {
+            games.push(RecordedGame {
+                headers: std::mem::take(&mut headers),
+                book_moves: std::mem::take(&mut book_moves),
+                result,
+            });
+            complete_len = offset;
+        }
+    }
+
+    (games, complete_len)
+}
+
+/// Finds the pairing, opening and result of each complete game in a file written by
+/// `PgnWriter`, by round. Also returns the length of the file up to the end of the last complete
+/// game.
+fn scan_recorded_games(contents: &str) -> (HashMap<u64, RecordedRound>, usize) {
+    let (games, complete_len) = parse_games(contents);
+    let mut recorded_games = HashMap::new();
+    for game in games {
+        let Ok(round) = game.header("Round").parse() else {
+            continue;
+        };
+        let mut opening = match game.headers.get("FEN") {
+            Some(fen) => fen.to_string(),
+            None => shogi::Position::default().to_string(),
+        };
+        if !game.book_moves.is_empty() {
+            opening += " moves ";
+            opening += &game.book_moves.join(" ");
+        }
+        let key = game_key(game.header("Sente"), game.header("Gote"), &opening);
+        let result = game.imported_game();
+        recorded_games.insert(round, RecordedRound { key, result });
+    }
+    (recorded_games, complete_len)
+}
+
+/// The result of a game read back from a PGN file, for seeding the stats of a new run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedGame {
+    /// The game's `Round`, which is the id of its ticket, so that the two games on an opening
+    /// can be paired again.
+    pub round: u64,
+    pub sente: String,
+    pub gote: String,
+    /// None for a draw.
+    pub winner: Option<shogi::Color>,
+}
+
+/// Reads the results of the decided games in a PGN file written by shogitest. Games that were
+/// cut short without a result are skipped.
+pub fn read_results(path: &str) -> Result<Vec<ImportedGame>, Error> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(parse_results(&contents))
+}
+
+fn parse_results(contents: &str) -> Vec<ImportedGame> {
+    let (games, _) = parse_games(contents);
+    games
+        .iter()
+        .filter_map(RecordedGame::imported_game)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{book, engine};
+
+    fn game(id: u64, engines: [usize; 2], opening: &str) -> tournament::MatchResult {
+        let m = shogi::Move::parse("2g2f").unwrap();
+        tournament::MatchResult {
+            ticket: tournament::MatchTicket {
+                id,
+                engines,
+                bucket: None,
+                opening: book::Opening::parse(opening, shogi::Variant::Standard).unwrap(),
+            },
+            game_start: chrono::Utc::now(),
+            game_end: chrono::Utc::now(),
+            opening_plies: 0,
+            outcome: shogi::GameOutcome::Resignation(shogi::Color::Gote),
+            moves: vec![engine::MoveRecord {
+                stm: Some(shogi::Color::Sente),
+                m,
+                mstr: m.to_string(),
+                ..engine::MoveRecord::default()
+            }],
+        }
+    }
+
+    fn writer(path: &str, resume: bool) -> PgnWriter {
+        let options = cli::PgnOutOptions {
+            file: String::from(path),
+            resume,
+            ..cli::PgnOutOptions::default()
+        };
+        let meta = cli::MetaDataOptions {
+            event_name: String::from("?"),
+            site_name: String::from("?"),
+        };
+        PgnWriter::new(
+            &options,
+            &meta,
+            vec![cli::EngineOptions::default(); 2],
+            vec![
+                cli::EngineIdentity {
+                    name: String::from("a"),
+                    usi_name: Some(String::from("Engine A 1.2")),
+                    usi_author: None,
+                },
+                cli::EngineIdentity {
+                    name: String::from("b"),
+                    usi_name: None,
+                    usi_author: Some(String::from("B. Author")),
+                },
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn header_escapes() {
+        for value in [
+            "plain",
+            "Say \"hi\"",
+            "back\\slash\\",
+            "tab\tnew\nline\r",
+            "bell\u{7} nul\0",
+            "it's café 将棋",
+        ] {
+            assert_eq!(unescape_header(&format!("{value:?}")), value);
+        }
+
+        let contents =
+            "[Round \"1\"]\n[Sente \"a \\\"quoted\\\" name\"]\n[Gote \"b\\\\c\"]\n\n1-0\n\n";
+        let games = parse_results(contents);
+        assert_eq!(games[0].sente, "a \"quoted\" name");
+        assert_eq!(games[0].gote, "b\\c");
+    }
+
+    #[test]
+    fn resume() {
+        let path =
+            std::env::temp_dir().join(format!("shogitest-resume-{}.pgn", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let games = [
+            game(0, [0, 1], "startpos moves 7g7f"),
+            game(1, [1, 0], "startpos moves 7g7f"),
+            game(2, [0, 1], "startpos"),
+        ];
+
+        let mut first_run = writer(path, false);
+        first_run.write(&games[0]).unwrap();
+        first_run.write(&games[1]).unwrap();
+        drop(first_run);
+        let two_games = std::fs::read_to_string(path).unwrap();
+
+        // Simulate a crash in the middle of writing a game.
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        write!(file, "[Event \"?\"]\n[Site").unwrap();
+        drop(file);
+
+        let mut second_run = writer(path, true);
+        let mut played = vec![];
+        for game in &games {
+            if !second_run.take_recorded(&game.ticket) {
+                played.push(game.ticket.id);
+                second_run.write(game).unwrap();
+            }
+        }
+        // A round is only skipped once, and only if the same engines played the same opening.
+        assert!(!second_run.take_recorded(&games[0].ticket));
+        let resumed = second_run.take_resumed_games();
+        assert_eq!(
+            resumed.iter().map(|game| game.round).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert!(second_run.take_resumed_games().is_empty());
+        drop(second_run);
+        assert_eq!(played, [2]);
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(contents.starts_with(&two_games));
+        let (recorded_games, complete_len) = scan_recorded_games(&contents);
+        assert_eq!(complete_len, contents.len());
+        assert_eq!(recorded_games.len(), 3);
+        assert_eq!(contents.matches("[Event ").count(), 3);
+
+        let mut third_run = writer(path, true);
+        assert!(!third_run.take_recorded(&game(1, [0, 1], "startpos moves 7g7f").ticket));
+        assert!(!third_run.take_recorded(&game(2, [0, 1], "startpos moves 7g7f").ticket));
+        assert!(third_run.take_recorded(&games[0].ticket));
+        // Only the result of the game skipped is resumed.
+        let resumed = third_run.take_resumed_games();
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].round, 0);
+        drop(third_run);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn interrupted_write() {
+        let path =
+            std::env::temp_dir().join(format!("shogitest-interrupted-{}.pgn", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut pgn = writer(path, false);
+        pgn.write(&game(0, [0, 1], "startpos")).unwrap();
+        pgn.write(&game(1, [1, 0], "startpos")).unwrap();
+        let third = pgn.format_game(&game(2, [0, 1], "startpos")).unwrap();
+        drop(pgn);
+        let two_games = std::fs::read(path).unwrap();
+
+        // Wherever the third game was cut off, resuming keeps exactly the first two.
+        for cut in (1..third.len()).step_by(7) {
+            let mut contents = two_games.clone();
+            contents.extend_from_slice(&third[..cut]);
+            std::fs::write(path, &contents).unwrap();
+            drop(writer(path, true));
+            assert_eq!(std::fs::read(path).unwrap(), two_games, "cut at {cut}");
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn resigned_game_headers() {
+        let path =
+            std::env::temp_dir().join(format!("shogitest-headers-{}.pgn", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let opening = "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2";
+        let mut pgn = writer(path, false);
+        pgn.write(&game(0, [0, 1], opening)).unwrap();
+        drop(pgn);
+        let contents = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let headers: Vec<&str> = contents.lines().filter(|l| l.starts_with('[')).collect();
+        for header in [
+            format!("[FEN \"{opening}\"]"),
+            String::from("[Result \"1-0\"]"),
+            String::from("[Termination \"normal\"]"),
+            String::from("[Reason \"Resign\"]"),
+            String::from("[TimeControl \"infinite\"]"),
+            String::from("[BlackVersion \"Engine A 1.2\"]"),
+            String::from("[WhiteAuthor \"B. Author\"]"),
+        ] {
+            assert!(headers.contains(&header.as_str()), "missing {header}");
+        }
+        assert!(!headers.iter().any(|h| h.starts_with("[WhiteVersion ")));
+        assert!(!headers.iter().any(|h| h.starts_with("[BlackAuthor ")));
+        // The starting position is recorded once.
+        assert_eq!(contents.matches(opening).count(), 1);
     }
 }
@@ -0,0 +1,43 @@
+use std::io::Write;
+use std::sync::Mutex;
+
+/// The live progress line currently drawn at the bottom of the terminal, if any.
+static LINE: Mutex<Option<String>> = Mutex::new(None);
+
+fn draw(line: &str) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = write!(stdout, "\r{line}\x1b[K");
+    let _ = stdout.flush();
+}
+
+fn erase() {
+    let mut stdout = std::io::stdout().lock();
+    let _ = write!(stdout, "\r\x1b[K");
+    let _ = stdout.flush();
+}
+
+/// Replaces the progress line in place.
+pub fn show(line: String) {
+    draw(&line);
+    *LINE.lock().unwrap() = Some(line);
+}
+
+/// Erases the progress line while `f` prints regular output, then draws it again below.
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    let line = LINE.lock().unwrap().clone();
+    if line.is_some() {
+        erase();
+    }
+    let result = f();
+    if let Some(line) = line {
+        draw(&line);
+    }
+    result
+}
+
+/// Removes the progress line for good.
+pub fn finish() {
+    if LINE.lock().unwrap().take().is_some() {
+        erase();
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
-    cli,
+    affinity, book, cli,
     engine::{self, EngineResult, Score},
-    shogi,
+    pause, progress, shogi,
     shogi::GameOutcome,
     tc,
     tc::StepResult,
@@ -9,85 +9,195 @@ use crate::{
 };
 use chrono::Utc;
 use log::info;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct Runner {
     engines: Vec<cli::EngineOptions>,
     concurrency: u64,
     adjudication: cli::AdjudicationOptions,
-    report_interval: Option<u64>,
+    impasse_rule: shogi::ImpasseRule,
+    report_interval: cli::ReportIntervalOptions,
+    affinity: Option<cli::AffinityOptions>,
 }
 
 impl Runner {
     pub fn new(
         engines: Vec<cli::EngineOptions>,
-        concurrency: u64,
+        concurrency: cli::Concurrency,
         adjudication: cli::AdjudicationOptions,
-        report_interval: Option<u64>,
+        impasse_rule: shogi::ImpasseRule,
+        report_interval: cli::ReportIntervalOptions,
+        affinity: Option<cli::AffinityOptions>,
     ) -> Runner {
+        let concurrency = resolve_concurrency(concurrency, &engines, affinity::total_cores());
         Runner {
             engines,
             concurrency,
             adjudication,
+            impasse_rule,
             report_interval,
+            affinity,
         }
     }
 
-    pub fn run(&self, mut tournament: Box<dyn Tournament>) {
-        let tournament = tournament.as_mut();
+    /// Number of games played concurrently, with `-concurrency auto` resolved.
+    pub fn concurrency(&self) -> u64 {
+        self.concurrency
+    }
 
+    /// Warns when the engines of all concurrent games together want more threads than there
+    /// are CPUs. With `strict`, this is an error instead and false is returned.
+    pub fn check_oversubscription(&self, strict: bool) -> bool {
+        let Some((threads, safe_concurrency)) =
+            oversubscription(self.concurrency, &self.engines, affinity::total_cores())
+        else {
+            return true;
+        };
+        let message = format!(
+            "{} concurrent games need {threads} engine threads but only {} CPUs are available, which skews results; use -concurrency {safe_concurrency} or fewer",
+            self.concurrency,
+            affinity::total_cores()
+        );
+        if strict {
+            eprintln!("Error; {message}");
+            false
+        } else {
+            eprintln!("Warning; {message}");
+            true
+        }
+    }
+
+    /// Engine options for a concurrency slot, pinned to that slot's cores if requested.
+    fn engines_for_slot(&self, slot: u64) -> Vec<cli::EngineOptions> {
+        let mut engines = self.engines.clone();
+        if let Some(affinity) = &self.affinity
+            && affinity::SUPPORTED
+        {
+            let engines_per_slot = engines.len();
+            for (i, engine) in engines.iter_mut().enumerate() {
+                engine.builder.affinity = affinity::cores_for(
+                    slot as usize,
+                    i,
+                    engines_per_slot,
+                    affinity.cores_per_engine,
+                    affinity::total_cores(),
+                );
+            }
+        }
+        engines
+    }
+
+    pub fn run(&self, tournament: &mut dyn Tournament) {
         let (send_ticket, recv_ticket) = crossbeam_channel::bounded(0);
         let (send_result, recv_result) = crossbeam_channel::bounded(0);
 
         let mut thread_handles = vec![];
+        // Set once the tournament stops, to abort the games still being played.
+        let abort = Arc::new(AtomicBool::new(false));
+
+        if let Some(affinity) = &self.affinity {
+            let required_cores =
+                self.concurrency as usize * self.engines.len() * affinity.cores_per_engine;
+            if !affinity::SUPPORTED {
+                eprintln!("Warning; CPU affinity is not supported on this platform, ignoring");
+            } else if required_cores > affinity::total_cores() {
+                eprintln!(
+                    "Warning; -affinity requires {required_cores} cores but only {} are available, engines will share cores",
+                    affinity::total_cores()
+                );
+            }
+        }
 
         for i in 0..self.concurrency {
             let recv_ticket = recv_ticket.clone();
             let send_result = send_result.clone();
-            let engines = self.engines.clone();
+            let engines = self.engines_for_slot(i);
             let adjudication = self.adjudication.clone();
+            let impasse_rule = self.impasse_rule;
+            let abort = abort.clone();
             thread_handles.push(thread::spawn(move || {
-                runner_thread_main(engines, adjudication, i, recv_ticket, send_result);
+                runner_thread_main(
+                    engines,
+                    adjudication,
+                    impasse_rule,
+                    i,
+                    (recv_ticket, send_result),
+                    &abort,
+                );
             }));
         }
 
         let mut state = TournamentState::Continue;
         let mut ticket = None;
-        let mut match_count = 0;
+        let mut running_games = 0;
+        let mut report_trigger = ReportTrigger::new(self.report_interval, Instant::now());
 
         let mut match_complete = |tournament: &mut dyn Tournament, result: MatchResult| {
             let state = tournament.match_complete(result);
 
-            match_count += 1;
-            if let Some(report_interval) = self.report_interval
-                && match_count % report_interval == 0
-            {
-                println!("--------------------------------------------------------------");
-                tournament.print_interval_report();
-                println!("--------------------------------------------------------------");
+            if report_trigger.match_complete(Instant::now()) {
+                progress::suspend(|| {
+                    println!("--------------------------------------------------------------");
+                    tournament.print_interval_report();
+                    println!("--------------------------------------------------------------");
+                });
             }
 
             state
         };
 
+        let mut was_paused = false;
         while state != TournamentState::Stop {
-            if ticket.is_none() {
-                ticket = tournament.next();
+            let paused = pause::is_paused();
+            if paused != was_paused {
+                progress::suspend(|| {
+                    if paused {
+                        println!("Paused; running games will finish, but no new games will start");
+                    } else {
+                        println!("Resumed");
+                    }
+                });
+                was_paused = paused;
             }
+
+            pull_ticket(tournament, &mut ticket, paused);
             match ticket {
+                // Wake up now and then to notice being resumed.
+                _ if paused => {
+                    crossbeam_channel::select! {
+                        recv(recv_result) -> result => {
+                            running_games -= 1;
+                            state = match_complete(tournament, result.unwrap());
+                        }
+                        default(PAUSE_POLL_INTERVAL) => {}
+                    }
+                }
+                // Nothing is left to play, e.g. when a resumed run had recorded every game.
+                None if running_games == 0 => state = TournamentState::Stop,
                 None => {
                     crossbeam_channel::select! {
-                        recv(recv_result) -> result => state = match_complete(tournament, result.unwrap()),
+                        recv(recv_result) -> result => {
+                            running_games -= 1;
+                            state = match_complete(tournament, result.unwrap());
+                        }
                     }
                 }
                 Some(ref t) => {
                     crossbeam_channel::select! {
-                        recv(recv_result) -> result => state = match_complete(tournament, result.unwrap()),
+                        recv(recv_result) -> result => {
+                            running_games -= 1;
+                            state = match_complete(tournament, result.unwrap());
+                        }
                         send(send_ticket, Some(t.clone())) -> result => {
                             assert!(result.is_ok());
                             tournament.match_started(t.clone());
+                            running_games += 1;
                             ticket = None;
                         }
                     }
@@ -95,8 +205,20 @@ impl Runner {
             }
         }
 
-        for _ in 0..self.concurrency {
-            send_ticket.send(None).unwrap();
+        // Only games cancelled by the tournament can still be running here. They are aborted at
+        // their next move, and their results left out.
+        abort.store(true, Ordering::Relaxed);
+        let mut stopped_threads = 0;
+        while stopped_threads < self.concurrency {
+            crossbeam_channel::select! {
+                send(send_ticket, None) -> result => {
+                    assert!(result.is_ok());
+                    stopped_threads += 1;
+                }
+                recv(recv_result) -> result => {
+                    info!("Discarding aborted game: {:?}", result.unwrap().ticket);
+                }
+            }
         }
 
         while let Some(h) = thread_handles.pop() {
@@ -107,60 +229,216 @@ impl Runner {
     }
 }
 
+/// How often a paused runner checks whether it has been resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Takes the next ticket from the tournament unless one is already waiting to be started, or
+/// the tournament is paused.
+fn pull_ticket(tournament: &mut dyn Tournament, ticket: &mut Option<MatchTicket>, paused: bool) {
+    if ticket.is_none() && !paused {
+        *ticket = tournament.next();
+    }
+}
+
+/// How long an engine without a clock may go without sending a single line while thinking
+/// before it is considered hung. Engines on a clock are bounded by their remaining time instead.
+const SILENT_ENGINE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Logical CPUs left free for the tournament manager itself when using `-concurrency auto`.
+const AUTO_CONCURRENCY_RESERVE: usize = 1;
+
+/// Resolves `-concurrency` to a number of concurrent games. In auto mode, the available CPUs less
+/// a reserve are divided by the largest `Threads` setting among the engines, so that each game
+/// has a thread per CPU for whichever engine is thinking.
+fn resolve_concurrency(
+    concurrency: cli::Concurrency,
+    engines: &[cli::EngineOptions],
+    available_cpus: usize,
+) -> u64 {
+    match concurrency {
+        cli::Concurrency::Fixed(concurrency) => concurrency,
+        cli::Concurrency::Auto => {
+            let threads = engines
+                .iter()
+                .map(|engine| engine.builder.thread_count())
+                .max()
+                .unwrap_or(1);
+            let concurrency =
+                (available_cpus.saturating_sub(AUTO_CONCURRENCY_RESERVE) / threads).max(1) as u64;
+            info!(
+                "Resolved -concurrency auto to {concurrency} ({available_cpus} CPUs, {threads} threads per engine)"
+            );
+            concurrency
+        }
+    }
+}
+
+/// Checks whether `concurrency` games oversubscribe `available_cpus`. Only one engine thinks at
+/// a time in each game, so each game needs as many threads as the largest `Threads` option.
+/// Returns the number of threads needed and the largest concurrency that fits, if oversubscribed.
+fn oversubscription(
+    concurrency: u64,
+    engines: &[cli::EngineOptions],
+    available_cpus: usize,
+) -> Option<(usize, u64)> {
+    let threads = engines
+        .iter()
+        .map(|engine| engine.builder.thread_count())
+        .max()
+        .unwrap_or(1);
+    let required = concurrency as usize * threads;
+    (required > available_cpus).then(|| (required, (available_cpus / threads).max(1) as u64))
+}
+
+/// Decides when to print an interval report: after a number of games or an amount of
+/// wall-clock time, whichever comes first. Both counters restart whenever a report fires.
+#[derive(Debug)]
+struct ReportTrigger {
+    games: Option<u64>,
+    time: Option<Duration>,
+    games_since_report: u64,
+    last_report: Instant,
+}
+
+impl ReportTrigger {
+    fn new(options: cli::ReportIntervalOptions, now: Instant) -> ReportTrigger {
+        ReportTrigger {
+            games: options.games,
+            time: options.time,
+            games_since_report: 0,
+            last_report: now,
+        }
+    }
+
+    fn match_complete(&mut self, now: Instant) -> bool {
+        self.games_since_report += 1;
+
+        let games_due = self
+            .games
+            .is_some_and(|games| self.games_since_report >= games);
+        let time_due = self
+            .time
+            .is_some_and(|time| now.duration_since(self.last_report) >= time);
+
+        if games_due || time_due {
+            self.games_since_report = 0;
+            self.last_report = now;
+        }
+        games_due || time_due
+    }
+}
+
 fn runner_thread_main(
     engine_options: Vec<cli::EngineOptions>,
     adjudication: cli::AdjudicationOptions,
+    impasse_rule: shogi::ImpasseRule,
     thread_index: u64,
-    recv: crossbeam_channel::Receiver<Option<MatchTicket>>,
-    send: crossbeam_channel::Sender<MatchResult>,
+    (recv, send): (
+        crossbeam_channel::Receiver<Option<MatchTicket>>,
+        crossbeam_channel::Sender<MatchResult>,
+    ),
+    abort: &AtomicBool,
 ) {
     let mut engines: Vec<_> = engine_options
         .iter()
         .map(|o| o.builder.init().unwrap())
         .collect();
 
-    while let Some(ticket) = recv.recv().unwrap() {
+    let keepalive = engines.iter().filter_map(|e| e.keepalive_interval()).min();
+
+    while let Some(ticket) = next_ticket(&recv, keepalive, &mut engines).unwrap() {
         assert!(ticket.engines[0] != ticket.engines[1]);
         info!("Thread {thread_index} received ticket: {:?}", &ticket);
 
-        let result = run_match(&engine_options, &adjudication, &mut engines, &ticket).unwrap();
+        let result = play_ticket(
+            &engine_options,
+            &adjudication,
+            impasse_rule,
+            &mut engines,
+            &ticket,
+            abort,
+        )
+        .unwrap();
 
         info!("Thread {thread_index} sending result: {:?}", &result);
         send.send(result).unwrap();
     }
 }
 
+/// Waits for the next ticket. Engines with a keepalive are pinged whenever they have been idle
+/// for long enough in the meantime. An engine that fails the ping is restarted, and an error is
+/// only returned if that fails too.
+fn next_ticket(
+    recv: &crossbeam_channel::Receiver<Option<MatchTicket>>,
+    keepalive: Option<Duration>,
+    engines: &mut [engine::Engine],
+) -> std::io::Result<Option<MatchTicket>> {
+    let Some(keepalive) = keepalive else {
+        return Ok(recv.recv().unwrap());
+    };
+    loop {
+        match recv.recv_timeout(keepalive) {
+            Ok(ticket) => return Ok(ticket),
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
+                for engine in engines.iter_mut() {
+                    if let Err(err) = engine.keepalive() {
+                        let name = engine.name();
+                        progress::suspend(|| {
+                            eprintln!("Warning; Keepalive failed for {name}, restarting: {err}")
+                        });
+                        engine.restart()?;
+                    }
+                }
+            }
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => panic!("runner disconnected"),
+        }
+    }
+}
+
+/// Converts an engine's centipawn score to the common scale adjudication thresholds are given in,
+/// using the `scorescale` of the engine that played the move.
+fn adjudication_cp(cp: i32, stm: Option<shogi::Color>, score_scales: [f64; 2]) -> i32 {
+    let scale = stm.map_or(1.0, |stm| score_scales[stm.to_index()]);
+    (cp as f64 / scale).round() as i32
+}
+
+/// `score_scales` are the score scales of the engines playing sente and gote respectively.
 fn do_adjudication(
     stm: shogi::Color,
     adjudication: &cli::AdjudicationOptions,
+    score_scales: [f64; 2],
     match_result: &mut MatchResult,
 ) {
     if match_result.outcome.is_determined() {
         return;
     }
 
+    let plies = |count| adjudication.move_unit.plies(count);
+
     if let Some(max_moves) = adjudication.max_moves
-        && match_result.moves.len() as u64 >= max_moves
+        && match_result.moves.len() >= plies(max_moves as usize)
     {
         match_result.outcome = GameOutcome::DrawByMoveLimit;
     }
 
     if let Some(ref draw) = adjudication.draw
-        && match_result.moves.len() >= draw.move_number
+        && match_result.moves.len() >= plies(draw.move_number)
         && match_result
             .moves
             .iter()
             .rev()
-            .take_while(|m| match m.score {
-                Score::Cp(cp) => cp.abs() <= draw.score,
+            .take_while(|m| match *m.adjudication_score() {
+                Score::Cp(cp) => adjudication_cp(cp, m.stm, score_scales).abs() <= draw.score,
                 _ => false,
             })
             .count()
-            >= draw.move_count
+            >= plies(draw.move_count)
     {
         match_result.outcome = GameOutcome::DrawByAdjudication;
     }
 
+    // One-sided resign adjudication counts only the losing side's moves, which are the same in
+    // either unit.
     if let Some(ref resign) = adjudication.resign
         && !resign.two_sided
         && match_result
@@ -168,9 +446,9 @@ fn do_adjudication(
             .iter()
             .rev()
             .filter(|m| m.stm == Some(stm))
-            .take_while(|m| match m.score {
+            .take_while(|m| match *m.adjudication_score() {
                 Score::None => false,
-                Score::Cp(cp) => cp <= -resign.score,
+                Score::Cp(cp) => adjudication_cp(cp, m.stm, score_scales) <= -resign.score,
                 Score::Mate(ply) => ply < 0,
             })
             .count()
@@ -186,9 +464,10 @@ fn do_adjudication(
             .moves
             .iter()
             .rev()
-            .take_while(|m| match m.score {
+            .take_while(|m| match *m.adjudication_score() {
                 Score::None => false,
                 Score::Cp(cp) => {
+                    let cp = adjudication_cp(cp, m.stm, score_scales);
                     if Some(stm) == m.stm {
                         cp <= -resign.score
                     } else {
@@ -204,25 +483,159 @@ fn do_adjudication(
                 }
             })
             .count()
-            >= resign.move_count
+            >= plies(resign.move_count)
     {
         assert!(Some(stm) == match_result.moves.last().and_then(|m| m.stm));
         match_result.outcome = GameOutcome::WinByAdjudication(!stm);
     }
 }
 
-fn run_match(
+/// Plays the engine's reply in `game`. `bestmove resign` and `bestmove win` are handled by
+/// `Game::do_move`, while a reply without a move loses unless the game was already over.
+fn bestmove_outcome(game: &mut shogi::Game, move_record: &engine::MoveRecord) -> GameOutcome {
+    if !move_record.is_no_move() {
+        return game.do_move(move_record.m);
+    }
+    if game.is_checkmate() {
+        GameOutcome::Checkmated(game.stm())
+    } else if game.is_stalemate() {
+        GameOutcome::Stalemated(game.stm())
+    } else {
+        GameOutcome::LossByNoMove(game.stm())
+    }
+}
+
+/// Checks a move against the watchdog's sanity limits, returning what is wrong with it. `allotted`
+/// is the engine's fair share of its clock for the move, see `tc::EngineTime::move_budget`.
+fn watchdog_check(
+    watchdog: &cli::WatchdogOptions,
+    move_record: &engine::MoveRecord,
+    allotted: Option<Duration>,
+    time_outcome: StepResult,
+) -> Option<String> {
+    let nps = move_record.nps;
+    if let Some(max_nps) = watchdog.max_nps
+        && nps > max_nps
+    {
+        return Some(format!("reported {nps} nps, above the limit of {max_nps}"));
+    }
+    // Engines that report no nps at all are given the benefit of the doubt.
+    if let Some(min_nps) = watchdog.min_nps
+        && nps > 0
+        && nps < min_nps
+    {
+        return Some(format!("reported {nps} nps, below the limit of {min_nps}"));
+    }
+    if let Some(allotted) = allotted
+        && time_outcome == StepResult::Ok
+        && move_record.measured_time.as_secs_f64() > allotted.as_secs_f64() * watchdog.time_factor
+    {
+        return Some(format!(
+            "took {:.3}s for a move with a budget of {:.3}s",
+            move_record.measured_time.as_secs_f64(),
+            allotted.as_secs_f64()
+        ));
+    }
+    None
+}
+
+/// Charges the time an engine took for a move to its clock and records it on the move.
+fn charge_clock(
+    clock: &mut tc::EngineTime,
+    move_record: &mut engine::MoveRecord,
+    duration: Duration,
+) -> StepResult {
+    let time_outcome = clock.step(duration);
+    move_record.measured_time = duration;
+    move_record.time_left = clock.remaining();
+    time_outcome
+}
+
+/// Plays a single game between two freshly started engines, `sente` moving first unless the
+/// opening has gote to move, both on the clock `time_control`. All the rules of a tournament game
+/// apply: illegal moves, time forfeits and adjudication. For use as a library, without any
+/// tournament around the game; the result's ticket has id 0 and engines `[0, 1]`.
+pub fn run_match(
+    sente: &engine::EngineBuilder,
+    gote: &engine::EngineBuilder,
+    opening: book::Opening,
+    time_control: tc::TimeControl,
+    adjudication: &cli::AdjudicationOptions,
+) -> Result<MatchResult, std::io::Error> {
+    if opening.to_game().is_none() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid opening: {}", opening.position),
+        ));
+    }
+    let engine_options = [sente, gote].map(|builder| cli::EngineOptions {
+        builder: builder.clone(),
+        time_control,
+        ..cli::EngineOptions::default()
+    });
+    let mut engines = vec![sente.init()?, gote.init()?];
+    let ticket = MatchTicket {
+        id: 0,
+        engines: [0, 1],
+        bucket: None,
+        opening,
+    };
+    play_ticket(
+        &engine_options,
+        adjudication,
+        shogi::ImpasseRule::default(),
+        &mut engines,
+        &ticket,
+        &AtomicBool::new(false),
+    )
+}
+
+/// Plays the game of `ticket`. If `abort` is set, the game is abandoned before the next move and
+/// returned with an undetermined outcome.
+fn play_ticket(
     engine_options: &[cli::EngineOptions],
     adjudication: &cli::AdjudicationOptions,
+    impasse_rule: shogi::ImpasseRule,
     engines: &mut [engine::Engine],
     ticket: &MatchTicket,
+    abort: &AtomicBool,
 ) -> Result<MatchResult, std::io::Error> {
+    let game_start = Utc::now();
     let mut match_result = MatchResult {
         ticket: ticket.clone(),
-        game_start: Utc::now(),
+        game_start,
+        game_end: game_start,
+        opening_plies: ticket.opening.moves.len(),
         outcome: shogi::GameOutcome::Undetermined,
         moves: vec![],
     };
+    play_game(
+        engine_options,
+        adjudication,
+        impasse_rule,
+        engines,
+        ticket,
+        abort,
+        &mut match_result,
+    )?;
+    match_result.game_end = Utc::now();
+    Ok(match_result)
+}
+
+/// Plays out the game of `ticket`, recording its moves and outcome in `match_result` as it goes.
+fn play_game(
+    engine_options: &[cli::EngineOptions],
+    adjudication: &cli::AdjudicationOptions,
+    impasse_rule: shogi::ImpasseRule,
+    engines: &mut [engine::Engine],
+    ticket: &MatchTicket,
+    abort: &AtomicBool,
+    match_result: &mut MatchResult,
+) -> Result<(), std::io::Error> {
+    let score_scales = [
+        engine_options[ticket.engines[0]].score_scale(),
+        engine_options[ticket.engines[1]].score_scale(),
+    ];
 
     let mut engine_time = [
         tc::EngineTime::new(
@@ -239,18 +652,38 @@ fn run_match(
         if engine_options[ticket.engines[i]].restart {
             engines[ticket.engines[i]].restart()?;
         }
-        engines[ticket.engines[i]].isready()?;
-        engines[ticket.engines[i]].usinewgame()?;
+        engines[ticket.engines[i]].warmup()?;
+        engines[ticket.engines[i]].new_game()?;
+    }
+
+    let mut game = ticket
+        .opening
+        .to_game()
+        .expect("opening book lines are validated on load");
+    game.set_impasse_rule(impasse_rule);
+    if game.is_checkmate() {
+        match_result.outcome = GameOutcome::Checkmated(game.stm());
+        return Ok(());
+    }
+    if game.is_stalemate() {
+        match_result.outcome = GameOutcome::Stalemated(game.stm());
+        return Ok(());
     }
 
-    let mut game = shogi::Game::new(ticket.opening);
     loop {
+        if abort.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
         let stm = game.stm();
         let current_engine = &mut engines[ticket.engines[stm.to_index()]];
 
         let bestmove_timeout = engine_time[stm.to_index()].bestmove_timeout();
+        let move_budget = engine_time[stm.to_index()].move_budget();
+        let silence_timeout = bestmove_timeout.is_none().then_some(SILENT_ENGINE_TIMEOUT);
 
         // TODO: Improve time measurement here
+        current_engine.ready_for_move()?;
         let now = Instant::now();
         current_engine.position(&game)?;
 
@@ -260,28 +693,48 @@ fn run_match(
         ))?;
         current_engine.flush()?;
 
-        match current_engine.wait_for_bestmove(stm, bestmove_timeout) {
+        match current_engine.wait_for_bestmove(stm, bestmove_timeout, silence_timeout) {
             EngineResult::Err(err) => return Err(err),
 
             EngineResult::Ok(mut move_record) => {
                 let duration = Instant::now() - now;
-                let time_outcome = engine_time[stm.to_index()].step(duration);
-                move_record.measured_time = duration;
-                move_record.time_left = engine_time[stm.to_index()].remaining();
+                let time_outcome =
+                    charge_clock(&mut engine_time[stm.to_index()], &mut move_record, duration);
+
+                let suspect = adjudication.watchdog.as_ref().and_then(|watchdog| {
+                    watchdog_check(watchdog, &move_record, move_budget, time_outcome)
+                        .map(|problem| (watchdog.forfeit, problem))
+                });
 
-                let m = move_record.m;
+                match_result.outcome = bestmove_outcome(&mut game, &move_record);
                 match_result.moves.push(move_record);
-                match_result.outcome = game.do_move(m);
 
                 if time_outcome == StepResult::TimeElapsed {
                     match_result.outcome = GameOutcome::LossByClock(stm);
                 }
 
-                do_adjudication(stm, adjudication, &mut match_result);
+                if let Some((forfeit, problem)) = suspect {
+                    let name = current_engine.name();
+                    progress::suspend(|| {
+                        eprintln!(
+                            "Warning; watchdog: {name} {problem} on move {} of game {}",
+                            match_result.moves.len(),
+                            ticket.id + 1
+                        )
+                    });
+                    if forfeit && !match_result.outcome.is_determined() {
+                        match_result.outcome = GameOutcome::LossByWatchdog(stm);
+                    }
+                }
+
+                do_adjudication(stm, adjudication, score_scales, match_result);
             }
 
             EngineResult::Timeout => {
+                // The engine may be wedged rather than merely slow, so don't wait for it.
                 match_result.outcome = GameOutcome::LossByClock(stm);
+                current_engine.kill();
+                current_engine.restart()?;
             }
 
             EngineResult::Disconnected => {
@@ -291,7 +744,7 @@ fn run_match(
         };
 
         if match_result.outcome.is_determined() {
-            return Ok(match_result);
+            return Ok(());
         }
     }
 }
@@ -300,15 +753,23 @@ fn run_match(
 mod tests {
     use super::*;
     use crate::shogi::Color;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
 
     fn new_mr() -> MatchResult {
         MatchResult {
             ticket: MatchTicket {
                 id: 0,
                 engines: [0, 1],
-                opening: shogi::Position::default(),
+                bucket: None,
+                opening: book::Opening {
+                    position: shogi::Position::default(),
+                    moves: vec![],
+                },
             },
             game_start: Utc::now(),
+            game_end: Utc::now(),
+            opening_plies: 0,
             outcome: GameOutcome::Undetermined,
             moves: vec![],
         }
@@ -322,6 +783,700 @@ mod tests {
         });
     }
 
+    fn engine_with_threads(threads: Option<&str>) -> cli::EngineOptions {
+        let mut engine = cli::EngineOptions::default();
+        if let Some(threads) = threads {
+            engine
+                .builder
+                .usi_options
+                .push((String::from("Threads"), String::from(threads)));
+        }
+        engine
+    }
+
+    #[test]
+    fn concurrency_resolution() {
+        let single = [engine_with_threads(None), engine_with_threads(None)];
+        let mixed = [
+            engine_with_threads(Some("4")),
+            engine_with_threads(Some("2")),
+        ];
+
+        assert_eq!(
+            resolve_concurrency(cli::Concurrency::Fixed(3), &mixed, 16),
+            3
+        );
+        assert_eq!(resolve_concurrency(cli::Concurrency::Auto, &single, 16), 15);
+        assert_eq!(resolve_concurrency(cli::Concurrency::Auto, &mixed, 16), 3);
+        assert_eq!(resolve_concurrency(cli::Concurrency::Auto, &mixed, 4), 1);
+        assert_eq!(resolve_concurrency(cli::Concurrency::Auto, &single, 1), 1);
+    }
+
+    #[test]
+    fn oversubscription_detection() {
+        let single = [engine_with_threads(None), engine_with_threads(None)];
+        let mixed = [
+            engine_with_threads(Some("4")),
+            engine_with_threads(Some("2")),
+        ];
+
+        assert_eq!(oversubscription(16, &single, 16), None);
+        assert_eq!(oversubscription(17, &single, 16), Some((17, 16)));
+        assert_eq!(oversubscription(4, &mixed, 16), None);
+        assert_eq!(oversubscription(5, &mixed, 16), Some((20, 4)));
+        assert_eq!(oversubscription(1, &mixed, 2), Some((4, 1)));
+    }
+
+    /// Hands out tickets forever, counting how many were taken.
+    struct Counter {
+        tickets: u64,
+    }
+
+    impl Tournament for Counter {
+        fn next(&mut self) -> Option<MatchTicket> {
+            self.tickets += 1;
+            Some(new_mr().ticket)
+        }
+        fn match_started(&mut self, _: MatchTicket) {}
+        fn match_complete(&mut self, _: MatchResult) -> TournamentState {
+            TournamentState::Continue
+        }
+        fn print_interval_report(&self) {}
+        fn tournament_complete(&self) {}
+        fn expected_maximum_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+            None
+        }
+        fn sprt_expected_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn take_resumed_games(&mut self) -> Vec<crate::pgn::ImportedGame> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn paused_runner_pulls_no_tickets() {
+        let mut tournament = Counter { tickets: 0 };
+        let mut ticket = None;
+
+        for _ in 0..3 {
+            pull_ticket(&mut tournament, &mut ticket, true);
+        }
+        assert_eq!(tournament.tickets, 0);
+        assert!(ticket.is_none());
+
+        pull_ticket(&mut tournament, &mut ticket, false);
+        assert_eq!(tournament.tickets, 1);
+        assert!(ticket.is_some());
+
+        // A ticket pulled before pausing is kept until it can be started.
+        pull_ticket(&mut tournament, &mut ticket, true);
+        pull_ticket(&mut tournament, &mut ticket, false);
+        assert_eq!(tournament.tickets, 1);
+        assert!(ticket.take().is_some());
+
+        pull_ticket(&mut tournament, &mut ticket, false);
+        assert_eq!(tournament.tickets, 2);
+    }
+
+    #[test]
+    fn report_trigger_by_games() {
+        let options = cli::ReportIntervalOptions {
+            games: Some(3),
+            time: None,
+        };
+        let now = Instant::now();
+        let mut trigger = ReportTrigger::new(options, now);
+        let fired: Vec<bool> = (0..6).map(|_| trigger.match_complete(now)).collect();
+        assert_eq!(fired, [false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn report_trigger_by_time() {
+        let options = cli::ReportIntervalOptions {
+            games: None,
+            time: Some(Duration::from_millis(20)),
+        };
+        let mut trigger = ReportTrigger::new(options, Instant::now());
+        assert!(!trigger.match_complete(Instant::now()));
+        thread::sleep(Duration::from_millis(30));
+        assert!(trigger.match_complete(Instant::now()));
+        assert!(!trigger.match_complete(Instant::now()));
+    }
+
+    #[test]
+    fn report_trigger_whichever_first() {
+        let options = cli::ReportIntervalOptions {
+            games: Some(4),
+            time: Some(Duration::from_secs(60)),
+        };
+        let start = Instant::now();
+        let mut trigger = ReportTrigger::new(options, start);
+        assert!(!trigger.match_complete(start + Duration::from_secs(10)));
+        assert!(trigger.match_complete(start + Duration::from_secs(61)));
+        // The game counter restarts after a time-triggered report.
+        assert!(!trigger.match_complete(start + Duration::from_secs(62)));
+        assert!(!trigger.match_complete(start + Duration::from_secs(63)));
+        assert!(!trigger.match_complete(start + Duration::from_secs(64)));
+        assert!(trigger.match_complete(start + Duration::from_secs(65)));
+    }
+
+    #[test]
+    fn bestmove_tokens() {
+        let outcome = |sfen: &str, bestmove: &str| {
+            let record = engine::MoveRecord {
+                mstr: String::from(bestmove),
+                m: shogi::Move::parse(bestmove).unwrap_or_default(),
+                ..engine::MoveRecord::default()
+            };
+            let mut game = shogi::Game::new(shogi::Position::parse(sfen).unwrap());
+            bestmove_outcome(&mut game, &record)
+        };
+        let startpos = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        assert_eq!(outcome(startpos, "7g7f"), GameOutcome::Undetermined);
+        assert_eq!(
+            outcome(startpos, "resign"),
+            GameOutcome::Resignation(Color::Sente)
+        );
+        assert_eq!(
+            outcome(startpos, "win"),
+            GameOutcome::LossByIllegal(Color::Sente)
+        );
+        assert_eq!(
+            outcome(startpos, "7g7e"),
+            GameOutcome::LossByIllegal(Color::Sente)
+        );
+        for bestmove in ["", "(none)"] {
+            assert_eq!(
+                outcome(startpos, bestmove),
+                GameOutcome::LossByNoMove(Color::Sente)
+            );
+        }
+        // A stalemate from `shogi::tests::test_outcome`.
+        assert_eq!(
+            outcome("8k/6G2/7G1/9/9/9/9/9/4K4 w - 1", ""),
+            GameOutcome::Stalemated(Color::Gote)
+        );
+    }
+
+    /// Serves a single connection with a USI engine that answers every `go` with the next of
+    /// `bestmoves`.
+    fn scripted_engine(name: &'static str, bestmoves: &'static [&'static str]) -> String {
+        slow_scripted_engine(name, bestmoves, Duration::ZERO)
+    }
+
+    /// A `scripted_engine` that thinks for `think` before each `bestmove`.
+    fn slow_scripted_engine(
+        name: &'static str,
+        bestmoves: &'static [&'static str],
+        think: Duration,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut bestmoves = bestmoves.iter();
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { return };
+                let reply = match line.split_whitespace().next() {
+                    Some("usi") => format!("id name {name}\nusiok\n"),
+                    Some("isready") => String::from("readyok\n"),
+                    Some("go") => match bestmoves.next() {
+                        Some(bestmove) => {
+                            thread::sleep(think);
+                            format!("info depth 1 score cp 0\nbestmove {bestmove}\n")
+                        }
+                        None => return,
+                    },
+                    _ => continue,
+                };
+                if writer.write_all(reply.as_bytes()).is_err() {
+                    return;
+                }
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn single_match() {
+        let builder = |host| engine::EngineBuilder {
+            host: Some(host),
+            ..engine::EngineBuilder::default()
+        };
+        let sente = builder(scripted_engine("sente", &["7g7f", "2g2f"]));
+        let gote = builder(scripted_engine("gote", &["3c3d", "resign"]));
+        let opening = book::Opening::parse("startpos", shogi::Variant::Standard).unwrap();
+        let tc = tc::TimeControl::parse("10+1").unwrap();
+
+        let result = run_match(
+            &sente,
+            &gote,
+            opening,
+            tc,
+            &cli::AdjudicationOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.outcome, GameOutcome::Resignation(Color::Gote));
+        let moves: Vec<&str> = result.moves.iter().map(|m| m.mstr.as_str()).collect();
+        assert_eq!(moves, ["7g7f", "3c3d", "2g2f", "resign"]);
+        assert!(result.moves.iter().all(|m| m.time_left.is_some()));
+        assert!(result.game_end >= result.game_start);
+
+        let invalid = book::Opening::parse("startpos moves 7g7e", shogi::Variant::Standard);
+        let err = run_match(
+            &sente,
+            &gote,
+            invalid.unwrap(),
+            tc,
+            &cli::AdjudicationOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn termination_reasons() {
+        use shogi::TerminationReason;
+        let play = |opening: &str,
+                    sente: &'static [&'static str],
+                    gote: &'static [&'static str],
+                    max_moves: Option<u64>| {
+            let builder = |host| engine::EngineBuilder {
+                host: Some(host),
+                ..engine::EngineBuilder::default()
+            };
+            let adjudication = cli::AdjudicationOptions {
+                max_moves,
+                ..cli::AdjudicationOptions::default()
+            };
+            run_match(
+                &builder(scripted_engine("sente", sente)),
+                &builder(scripted_engine("gote", gote)),
+                book::Opening::parse(opening, shogi::Variant::Standard).unwrap(),
+                tc::TimeControl::parse("10+1").unwrap(),
+                &adjudication,
+            )
+            .unwrap()
+            .reason()
+        };
+
+        assert_eq!(
+            play("startpos", &["7g7f"], &["resign"], None),
+            Some(TerminationReason::Resign)
+        );
+        assert_eq!(
+            play("4k4/9/4P4/9/9/9/9/9/4K4 b G 1", &["G*5b"], &[], None),
+            Some(TerminationReason::Checkmate)
+        );
+        assert_eq!(
+            play("startpos", &["7g7f"], &["7g7f"], None),
+            Some(TerminationReason::IllegalMove)
+        );
+        assert_eq!(
+            play("startpos", &["7g7f", "2g2f"], &["3c3d", "8c8d"], Some(3)),
+            Some(TerminationReason::MaxMoves)
+        );
+    }
+
+    #[test]
+    fn watchdog_forfeit() {
+        let builder = |host| engine::EngineBuilder {
+            host: Some(host),
+            ..engine::EngineBuilder::default()
+        };
+        let adjudication = cli::AdjudicationOptions {
+            watchdog: Some(cli::WatchdogOptions {
+                time_factor: 1.0,
+                forfeit: true,
+                ..cli::WatchdogOptions::default()
+            }),
+            ..cli::AdjudicationOptions::default()
+        };
+        // A budget of 200ms for each move, well within the 2s on the clock.
+        let result = run_match(
+            &builder(slow_scripted_engine(
+                "sente",
+                &["7g7f"],
+                Duration::from_millis(500),
+            )),
+            &builder(scripted_engine("gote", &["3c3d"])),
+            book::Opening::parse("startpos", shogi::Variant::Standard).unwrap(),
+            tc::TimeControl::parse("2+0").unwrap(),
+            &adjudication,
+        )
+        .unwrap();
+        assert_eq!(result.outcome, GameOutcome::LossByWatchdog(Color::Sente));
+        assert_eq!(result.moves.len(), 1);
+        assert!(result.moves[0].time_left.is_some());
+    }
+
+    #[test]
+    fn aborted_game() {
+        let builder = |host| engine::EngineBuilder {
+            host: Some(host),
+            ..engine::EngineBuilder::default()
+        };
+        let builders = [
+            builder(scripted_engine("sente", &["7g7f"])),
+            builder(scripted_engine("gote", &["3c3d"])),
+        ];
+        let engine_options = builders.clone().map(|builder| cli::EngineOptions {
+            builder,
+            ..cli::EngineOptions::default()
+        });
+        let mut engines: Vec<_> = builders.iter().map(|b| b.init().unwrap()).collect();
+        let ticket = MatchTicket {
+            id: 0,
+            engines: [0, 1],
+            bucket: None,
+            opening: book::Opening::parse("startpos", shogi::Variant::Standard).unwrap(),
+        };
+
+        let result = play_ticket(
+            &engine_options,
+            &cli::AdjudicationOptions::default(),
+            shogi::ImpasseRule::default(),
+            &mut engines,
+            &ticket,
+            &AtomicBool::new(true),
+        )
+        .unwrap();
+        assert_eq!(result.outcome, GameOutcome::Undetermined);
+        assert!(result.moves.is_empty());
+    }
+
+    #[test]
+    fn gote_to_move_opening() {
+        let builder = |host| engine::EngineBuilder {
+            host: Some(host),
+            ..engine::EngineBuilder::default()
+        };
+        // Engine 0 plays gote, which moves first, on a longer clock than engine 1.
+        let builders = [
+            builder(scripted_engine("gote", &["3c3d", "8c8d"])),
+            builder(scripted_engine("sente", &["2g2f", "resign"])),
+        ];
+        let engine_options = ["60+0", "10+0"]
+            .into_iter()
+            .zip(builders.clone())
+            .map(|(tc, builder)| cli::EngineOptions {
+                builder,
+                time_control: tc::TimeControl::parse(tc).unwrap(),
+                ..cli::EngineOptions::default()
+            })
+            .collect::<Vec<_>>();
+        let mut engines: Vec<_> = builders.iter().map(|b| b.init().unwrap()).collect();
+        let ticket = MatchTicket {
+            id: 0,
+            engines: [1, 0],
+            bucket: None,
+            opening: book::Opening::parse("startpos moves 7g7f", shogi::Variant::Standard).unwrap(),
+        };
+
+        let result = play_ticket(
+            &engine_options,
+            &cli::AdjudicationOptions::default(),
+            shogi::ImpasseRule::default(),
+            &mut engines,
+            &ticket,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let moves: Vec<_> = result
+            .moves
+            .iter()
+            .map(|m| (m.stm, m.mstr.as_str()))
+            .collect();
+        assert_eq!(
+            moves,
+            [
+                (Some(Color::Gote), "3c3d"),
+                (Some(Color::Sente), "2g2f"),
+                (Some(Color::Gote), "8c8d"),
+                (Some(Color::Sente), "resign"),
+            ]
+        );
+        // Each side is charged on its own engine's clock.
+        let time_left = |i: usize| result.moves[i].time_left.unwrap();
+        assert!(time_left(0) > Duration::from_secs(50));
+        assert!(time_left(1) <= Duration::from_secs(10));
+
+        // The winner is reported by colour, which the ticket maps back to the engine.
+        assert_eq!(result.outcome, GameOutcome::Resignation(Color::Sente));
+        let winner = result.outcome.winner().unwrap();
+        assert_eq!(result.ticket.engines[winner.to_index()], 0);
+    }
+
+    #[test]
+    fn warmup_is_untimed() {
+        let sente = engine::EngineBuilder {
+            host: Some(scripted_engine("sente", &["2g2f", "7g7f", "2g2f"])),
+            warmup: Some(engine::Warmup::Depth(10)),
+            ..engine::EngineBuilder::default()
+        };
+        let gote = engine::EngineBuilder {
+            host: Some(scripted_engine("gote", &["3c3d", "resign"])),
+            ..engine::EngineBuilder::default()
+        };
+        let opening = book::Opening::parse("startpos", shogi::Variant::Standard).unwrap();
+        let tc = tc::TimeControl::parse("10+1").unwrap();
+
+        let result = run_match(
+            &sente,
+            &gote,
+            opening,
+            tc,
+            &cli::AdjudicationOptions::default(),
+        )
+        .unwrap();
+        // The warmup's bestmove is discarded rather than played.
+        let moves: Vec<&str> = result.moves.iter().map(|m| m.mstr.as_str()).collect();
+        assert_eq!(moves, ["7g7f", "3c3d", "2g2f", "resign"]);
+        assert!(result.moves[0].time_left.unwrap() > Duration::from_secs(10));
+    }
+
+    #[test]
+    fn repetition_spans_book() {
+        let builder = |host| engine::EngineBuilder {
+            host: Some(host),
+            ..engine::EngineBuilder::default()
+        };
+        let sente = builder(scripted_engine("sente", &[]));
+        let gote = builder(scripted_engine("gote", &["5b5a"]));
+        // The starting position has occurred three times by the end of the book, so gote's first
+        // move completes a fourfold repetition.
+        let opening = book::Opening::parse(
+            "startpos moves 5i5h 5a5b 5h5i 5b5a 5i5h 5a5b 5h5i 5b5a 5i5h 5a5b 5h5i",
+            shogi::Variant::Standard,
+        )
+        .unwrap();
+        let tc = tc::TimeControl::parse("10+1").unwrap();
+
+        let result = run_match(
+            &sente,
+            &gote,
+            opening,
+            tc,
+            &cli::AdjudicationOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.outcome, GameOutcome::DrawBySennichite);
+        assert_eq!(result.reason(), Some(shogi::TerminationReason::Sennichite));
+        let moves: Vec<&str> = result.moves.iter().map(|m| m.mstr.as_str()).collect();
+        assert_eq!(moves, ["5b5a"]);
+    }
+
+    #[test]
+    fn watchdog_flags() {
+        let watchdog = cli::WatchdogOptions {
+            max_nps: Some(100_000_000),
+            min_nps: Some(1000),
+            ..cli::WatchdogOptions::default()
+        };
+        let record = |nps, measured_ms| engine::MoveRecord {
+            nps,
+            measured_time: Duration::from_millis(measured_ms),
+            ..engine::MoveRecord::default()
+        };
+        let allotted = Some(Duration::from_secs(1));
+        let check =
+            |record, allotted, outcome| watchdog_check(&watchdog, &record, allotted, outcome);
+
+        assert_eq!(
+            check(record(2_000_000, 500), allotted, StepResult::Ok),
+            None
+        );
+        assert_eq!(
+            check(record(u64::MAX / 2, 500), allotted, StepResult::Ok),
+            Some(format!(
+                "reported {} nps, above the limit of 100000000",
+                u64::MAX / 2
+            ))
+        );
+        assert!(check(record(10, 500), allotted, StepResult::Ok).is_some());
+        // No nps reported at all.
+        assert_eq!(check(record(0, 500), allotted, StepResult::Ok), None);
+
+        assert!(check(record(2_000_000, 3500), allotted, StepResult::Ok).is_some());
+        // Overruns that lost on time, or without a clock to overrun, are not the watchdog's business.
+        assert_eq!(
+            check(record(2_000_000, 3500), allotted, StepResult::TimeElapsed),
+            None
+        );
+        assert_eq!(check(record(2_000_000, 3500), None, StepResult::Ok), None);
+    }
+
+    #[test]
+    fn loses_on_time() {
+        let tc = tc::TimeControl::Fischer {
+            base: Duration::from_secs(1),
+            increment: Duration::ZERO,
+        };
+        let mut clock = tc::EngineTime::new(tc, Duration::from_millis(100));
+
+        let mut record = engine::MoveRecord::default();
+        let outcome = charge_clock(&mut clock, &mut record, Duration::from_millis(600));
+        assert_eq!(outcome, StepResult::Ok);
+        assert_eq!(record.measured_time, Duration::from_millis(600));
+        assert_eq!(record.time_left, Some(Duration::from_millis(400)));
+
+        // Within the time margin.
+        let mut record = engine::MoveRecord::default();
+        let outcome = charge_clock(&mut clock, &mut record, Duration::from_millis(450));
+        assert_eq!(outcome, StepResult::Ok);
+        assert_eq!(record.time_left, Some(Duration::ZERO));
+
+        let mut record = engine::MoveRecord::default();
+        let outcome = charge_clock(&mut clock, &mut record, Duration::from_millis(150));
+        assert_eq!(outcome, StepResult::TimeElapsed);
+        assert_eq!(record.time_left, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn move_units() {
+        // Returns the ply at which the game is adjudicated when every move scores 0.
+        let adjudicated_at = |adjudication: &cli::AdjudicationOptions| {
+            let mut mr = new_mr();
+            for ply in 1.. {
+                let stm = if ply % 2 == 1 {
+                    Color::Sente
+                } else {
+                    Color::Gote
+                };
+                append(&mut mr, stm, Score::Cp(0));
+                do_adjudication(stm, adjudication, [1.0, 1.0], &mut mr);
+                if mr.outcome.is_determined() {
+                    return (ply, mr.outcome);
+                }
+            }
+            unreachable!()
+        };
+        let draw = |move_unit| cli::AdjudicationOptions {
+            max_moves: None,
+            draw: Some(cli::DrawAdjudicationOptions {
+                move_number: 0,
+                move_count: 10,
+                score: 20,
+            }),
+            move_unit,
+            ..cli::AdjudicationOptions::default()
+        };
+        let max_moves = |move_unit| cli::AdjudicationOptions {
+            max_moves: Some(10),
+            move_unit,
+            ..cli::AdjudicationOptions::default()
+        };
+
+        assert_eq!(
+            adjudicated_at(&draw(cli::MoveUnit::Plies)),
+            (10, GameOutcome::DrawByAdjudication)
+        );
+        assert_eq!(
+            adjudicated_at(&draw(cli::MoveUnit::FullMoves)),
+            (20, GameOutcome::DrawByAdjudication)
+        );
+        assert_eq!(
+            adjudicated_at(&max_moves(cli::MoveUnit::Plies)),
+            (10, GameOutcome::DrawByMoveLimit)
+        );
+        assert_eq!(
+            adjudicated_at(&max_moves(cli::MoveUnit::FullMoves)),
+            (20, GameOutcome::DrawByMoveLimit)
+        );
+    }
+
+    #[test]
+    fn score_scale() {
+        let adjudication = cli::AdjudicationOptions {
+            max_moves: None,
+            draw: Some(cli::DrawAdjudicationOptions {
+                move_number: 0,
+                move_count: 4,
+                score: 20,
+            }),
+            resign: Some(cli::ResignAdjudicationOptions {
+                two_sided: false,
+                move_count: 2,
+                score: 200,
+            }),
+            ..cli::AdjudicationOptions::default()
+        };
+
+        // Gote's engine reports scores at twice the scale of sente's.
+        let adjudicate = |gote_cp: [i32; 2], score_scales| {
+            let mut mr = new_mr();
+            append(&mut mr, Color::Sente, Score::Cp(10));
+            append(&mut mr, Color::Gote, Score::Cp(gote_cp[0]));
+            append(&mut mr, Color::Sente, Score::Cp(-10));
+            append(&mut mr, Color::Gote, Score::Cp(gote_cp[1]));
+            do_adjudication(Color::Gote, &adjudication, score_scales, &mut mr);
+            mr.outcome
+        };
+
+        assert_eq!(adjudicate([30, -30], [1.0, 1.0]), GameOutcome::Undetermined);
+        assert_eq!(
+            adjudicate([30, -30], [1.0, 2.0]),
+            GameOutcome::DrawByAdjudication
+        );
+        assert_eq!(
+            adjudicate([15, -15], [1.0, 1.0]),
+            GameOutcome::DrawByAdjudication
+        );
+
+        assert_eq!(
+            adjudicate([-300, -500], [1.0, 1.0]),
+            GameOutcome::WinByAdjudication(Color::Sente)
+        );
+        assert_eq!(
+            adjudicate([-300, -500], [1.0, 2.0]),
+            GameOutcome::Undetermined
+        );
+        assert_eq!(
+            adjudicate([-150, -250], [1.0, 1.0]),
+            GameOutcome::Undetermined
+        );
+    }
+
+    #[test]
+    fn bounded_scores_are_ignored() {
+        let adjudication = cli::AdjudicationOptions {
+            max_moves: None,
+            resign: Some(cli::ResignAdjudicationOptions {
+                two_sided: false,
+                move_count: 1,
+                score: 200,
+            }),
+            ..cli::AdjudicationOptions::default()
+        };
+        let adjudicate = |score_bound| {
+            let mut mr = new_mr();
+            append(&mut mr, Color::Sente, Score::Cp(30));
+            mr.moves.push(engine::MoveRecord {
+                stm: Some(Color::Gote),
+                score: Score::Mate(-5),
+                score_bound,
+                exact_score: Score::Cp(-20),
+                ..engine::MoveRecord::default()
+            });
+            do_adjudication(Color::Gote, &adjudication, [1.0, 1.0], &mut mr);
+            mr.outcome
+        };
+
+        assert_eq!(
+            adjudicate(Some(engine::ScoreBound::Upper)),
+            GameOutcome::Undetermined
+        );
+        assert_eq!(
+            adjudicate(None),
+            GameOutcome::WinByAdjudication(Color::Sente)
+        );
+    }
+
     #[test]
     fn test_resign_1() {
         let mut mr = new_mr();
@@ -341,13 +1496,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 1,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::WinByAdjudication(Color::Sente));
@@ -357,13 +1513,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::WinByAdjudication(Color::Sente));
@@ -373,13 +1530,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 3,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::Undetermined);
@@ -389,13 +1547,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 2,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::WinByAdjudication(Color::Sente));
@@ -405,13 +1564,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 4,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::WinByAdjudication(Color::Sente));
@@ -421,13 +1581,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 6,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::Undetermined);
@@ -452,13 +1613,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::WinByAdjudication(Color::Sente));
@@ -468,13 +1630,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 2,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::Undetermined);
@@ -484,13 +1647,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: true,
                     move_count: 4,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::Undetermined);
@@ -515,13 +1679,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::Undetermined);
@@ -546,13 +1711,14 @@ mod tests {
             Color::Gote,
             &cli::AdjudicationOptions {
                 max_moves: None,
-                draw: None,
                 resign: Some(cli::ResignAdjudicationOptions {
                     two_sided: false,
                     move_count: 2,
                     score: 200,
                 }),
+                ..cli::AdjudicationOptions::default()
             },
+            [1.0, 1.0],
             &mut mr,
         );
         assert!(mr.outcome == GameOutcome::WinByAdjudication(Color::Sente));
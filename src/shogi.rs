@@ -50,6 +50,122 @@ impl ops::Not for Color {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// 5x5 minishogi. The board occupies files 1-5 and ranks a-e, and has no lances or knights.
+    Minishogi,
+}
+
+/// How games in which a king has entered the opponent's camp (入玉) are decided.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum ImpasseRule {
+    /// A king in the opponent's camp may declare a win (`bestmove win`) with 28 points for
+    /// sente or 27 points for gote. Otherwise play continues.
+    #[default]
+    Point27,
+    /// As `Point27`, but the game also ends as soon as both kings have entered the opponent's
+    /// camp, with the side to move not in check. A side with fewer than 24 points loses,
+    /// otherwise it is a draw (持将棋).
+    Point24,
+    /// A king that safely reaches the opponent king's starting square wins (入玉トライ).
+    /// Win declarations are not allowed.
+    Try,
+}
+
+impl ImpasseRule {
+    pub fn parse(s: &str) -> Option<ImpasseRule> {
+        match s {
+            "27point" => Some(ImpasseRule::Point27),
+            "24point" => Some(ImpasseRule::Point24),
+            "try" => Some(ImpasseRule::Try),
+            _ => None,
+        }
+    }
+}
+
+impl Variant {
+    pub fn parse(s: &str) -> Option<Variant> {
+        match s {
+            "standard" => Some(Variant::Standard),
+            "minishogi" => Some(Variant::Minishogi),
+            _ => None,
+        }
+    }
+
+    /// Number of files (and ranks) on the board.
+    pub fn size(self) -> i8 {
+        match self {
+            Variant::Standard => 9,
+            Variant::Minishogi => 5,
+        }
+    }
+
+    fn square_count(self) -> usize {
+        (self.size() * self.size()) as usize
+    }
+
+    fn promotion_ranks(self) -> i8 {
+        match self {
+            Variant::Standard => 3,
+            Variant::Minishogi => 1,
+        }
+    }
+
+    pub fn contains(self, sq: Square) -> bool {
+        sq.file() < self.size() && sq.rank() < self.size()
+    }
+
+    /// The `i`-th square in sfen order.
+    fn fen_square(self, i: usize) -> Square {
+        let size = self.size() as usize;
+        Square::new((size - 1 - i % size) as i8, (i / size) as i8).unwrap()
+    }
+
+    /// Rank of `sq` as seen by `color`, where rank 0 is the furthest rank from `color`.
+    fn relative_rank(self, sq: Square, color: Color) -> i8 {
+        match color {
+            Color::Sente => sq.rank(),
+            Color::Gote => self.size() - 1 - sq.rank(),
+        }
+    }
+
+    /// The square `color`'s king has to reach to win under the try rule, which is where the
+    /// opponent's king starts.
+    fn try_square(self, color: Color) -> Square {
+        match (self, color) {
+            (Variant::Standard, Color::Sente) => Square::new(4, 0).unwrap(),
+            (Variant::Standard, Color::Gote) => Square::new(4, 8).unwrap(),
+            (Variant::Minishogi, Color::Sente) => Square::new(0, 0).unwrap(),
+            (Variant::Minishogi, Color::Gote) => Square::new(4, 4).unwrap(),
+        }
+    }
+
+    fn has_piece_type(self, pt: PieceType) -> bool {
+        match self {
+            Variant::Standard => true,
+            Variant::Minishogi => !matches!(pt.demote(), PieceType::Lance | PieceType::Knight),
+        }
+    }
+
+    fn startpos(self) -> &'static str {
+        match self {
+            Variant::Standard => "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            Variant::Minishogi => "rbsgk/4p/5/P4/KGSBR b - 1",
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Standard => write!(f, "standard"),
+            Variant::Minishogi => write!(f, "minishogi"),
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 #[repr(transparent)]
 pub struct Square(u8);
@@ -73,12 +189,6 @@ impl Square {
         Self::new(file as i8, rank as i8)
     }
 
-    pub fn from_fen_ordering(i: usize) -> Option<Square> {
-        let file = (8 - i % 9) as i8;
-        let rank = (i / 9) as i8;
-        Square::new(file, rank)
-    }
-
     pub fn file(self) -> i8 {
         (self.0 % 9) as i8
     }
@@ -90,17 +200,6 @@ impl Square {
     pub fn to_index(self) -> usize {
         self.0 as usize
     }
-
-    pub fn normalize_to_sente(self, current_color: Color) -> Square {
-        match current_color {
-            Color::Sente => self,
-            Color::Gote => Square::new(self.file(), 8 - self.rank()).unwrap(),
-        }
-    }
-
-    pub fn is_promo_square(self, color: Color) -> bool {
-        self.normalize_to_sente(color).0 < 27
-    }
 }
 
 impl ops::Sub for Square {
@@ -209,10 +308,45 @@ pub enum PieceType {
 }
 
 impl PieceType {
+    /// Value of the piece when counting points for impasse.
+    fn impasse_points(self) -> usize {
+        match self.demote() {
+            PieceType::None => 0,
+            PieceType::King => 0,
+            PieceType::Rook | PieceType::Bishop => 5,
+            PieceType::Pawn
+            | PieceType::Lance
+            | PieceType::Knight
+            | PieceType::Silver
+            | PieceType::Gold => 1,
+            _ => panic!("should be unreachable"),
+        }
+    }
+
     pub fn promotable(self) -> bool {
         self as u8 >= Self::Pawn as u8 && self as u8 <= Self::Silver as u8
     }
 
+    /// Rough material value in pawns, for judging whether a position is balanced. Promoted
+    /// minor pieces count as a gold.
+    fn material_value(self) -> i32 {
+        match self {
+            PieceType::None | PieceType::King => 0,
+            PieceType::Pawn => 1,
+            PieceType::Lance => 3,
+            PieceType::Knight => 4,
+            PieceType::Silver => 5,
+            PieceType::Gold
+            | PieceType::Tokin
+            | PieceType::NariLance
+            | PieceType::NariKnight
+            | PieceType::NariSilver => 6,
+            PieceType::Bishop => 8,
+            PieceType::Rook | PieceType::Horse => 10,
+            PieceType::Dragon => 12,
+        }
+    }
+
     pub fn promoted(self) -> bool {
         self as u8 >= Self::Tokin as u8
     }
@@ -348,6 +482,41 @@ impl fmt::Display for Move {
     }
 }
 
+/// Zobrist keys used to hash positions, e.g. for binary opening book lookups. The keys are
+/// generated from a fixed seed so hashes are stable between runs and builds.
+struct Zobrist {
+    board: [[[u64; 81]; 16]; 2],
+    hand: [[[u64; 19]; 8]; 2],
+    gote_to_move: u64,
+}
+
+impl Zobrist {
+    fn new() -> Zobrist {
+        // SplitMix64
+        let mut state: u64 = 0x5348_4f47_4954_4553;
+        let mut next = || {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        let mut zobrist = Zobrist {
+            board: [[[0; 81]; 16]; 2],
+            hand: [[[0; 19]; 8]; 2],
+            gote_to_move: 0,
+        };
+        for key in zobrist.board.iter_mut().flatten().flatten() {
+            *key = next();
+        }
+        for key in zobrist.hand.iter_mut().flatten().flatten() {
+            *key = next();
+        }
+        zobrist.gote_to_move = next();
+        zobrist
+    }
+}
+
 lazy_static! {
     static ref ALL_MOVES: Vec<Move> = {
         let sq: Vec<Square> = (0..81).map(Square).collect();
@@ -372,6 +541,7 @@ lazy_static! {
         res.extend(iproduct!(&drop_ptypes, &sq).map(|(&pt, &sq)| Move::Drop(pt, sq)));
         res
     };
+    static ref ZOBRIST: Zobrist = Zobrist::new();
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -457,6 +627,7 @@ pub enum CheckState {
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Position {
+    variant: Variant,
     board: [Place; 81],
     hand: [Hand; 2],
     stm: Color,
@@ -465,11 +636,116 @@ pub struct Position {
 
 impl Default for Position {
     fn default() -> Self {
-        Position::parse("lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1").unwrap()
+        Position::startpos(Variant::Standard)
     }
 }
 
 impl Position {
+    pub fn startpos(variant: Variant) -> Position {
+        Position::parse(variant.startpos()).unwrap()
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn stm(&self) -> Color {
+        self.stm
+    }
+
+    /// The move number of the sfen, starting at 1.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Material of sente minus material of gote, in pawns, counting pieces on the board and in
+    /// hand. See `PieceType::material_value`.
+    pub fn material_balance(&self) -> i32 {
+        let sign = |color| if color == Color::Sente { 1 } else { -1 };
+        let board: i32 = self
+            .board
+            .iter()
+            .map(|p| sign(p.0) * p.1.material_value())
+            .sum();
+        let hand: i32 = [Color::Sente, Color::Gote]
+            .iter()
+            .flat_map(|&color| {
+                [
+                    PieceType::Rook,
+                    PieceType::Bishop,
+                    PieceType::Gold,
+                    PieceType::Silver,
+                    PieceType::Knight,
+                    PieceType::Lance,
+                    PieceType::Pawn,
+                ]
+                .map(|pt| {
+                    sign(color) * self.hand[color.to_index()].get(pt) as i32 * pt.material_value()
+                })
+            })
+            .sum();
+        board + hand
+    }
+
+    fn is_promo_square(&self, sq: Square, color: Color) -> bool {
+        self.variant.relative_rank(sq, color) < self.variant.promotion_ranks()
+    }
+
+    /// Impasse points held by `color`, counting pieces on the board and in hand: five for each
+    /// rook or bishop and one for every other piece apart from the king.
+    fn impasse_points(&self, color: Color) -> usize {
+        let board_points: usize = self
+            .board
+            .iter()
+            .filter(|p| p.0 == color)
+            .map(|p| p.1.impasse_points())
+            .sum();
+        let hand_points: usize = [
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Pawn,
+            PieceType::Lance,
+            PieceType::Knight,
+            PieceType::Silver,
+            PieceType::Gold,
+        ]
+        .iter()
+        .map(|pt| self.hand[color.to_index()].get(*pt) as usize * pt.impasse_points())
+        .sum();
+        board_points + hand_points
+    }
+
+    /// Zobrist hash of the board, both hands and the side to move. The move counter is not
+    /// part of the hash.
+    pub fn zobrist_hash(&self) -> u64 {
+        let mut hash = 0;
+        for (i, place) in self.board.iter().enumerate() {
+            if !place.is_empty() {
+                hash ^= ZOBRIST.board[place.0.to_index()][place.1 as usize][i];
+            }
+        }
+        for color in [Color::Sente, Color::Gote] {
+            for pt in [
+                PieceType::Pawn,
+                PieceType::Bishop,
+                PieceType::Rook,
+                PieceType::Lance,
+                PieceType::Knight,
+                PieceType::Silver,
+                PieceType::Gold,
+            ] {
+                let count = self.hand[color.to_index()].get(pt) as usize;
+                hash ^= ZOBRIST.hand[color.to_index()][pt as usize][count];
+            }
+        }
+        if self.stm == Color::Gote {
+            hash ^= ZOBRIST.gote_to_move;
+        }
+        hash
+    }
+
+    /// Whether the positions are the same for repetition: the same board, hands and side to move.
+    /// Unlike `==`, the move counter is ignored, as repetitions happen at different move numbers.
     pub fn is_clone_of(&self, other: &Position) -> bool {
         self.board == other.board && self.hand == other.hand && self.stm == other.stm
     }
@@ -483,6 +759,10 @@ impl Position {
         )
     }
 
+    pub fn piece_type_at(&self, sq: Square) -> PieceType {
+        self.board[sq.to_index()].1
+    }
+
     pub fn is_empty(&self, sq: Square) -> bool {
         self.board[sq.to_index()].1 == PieceType::None
     }
@@ -524,12 +804,12 @@ impl Position {
     }
 
     // 行き所のない駒
-    fn is_ikidokorononai(color: Color, pt: PieceType, sq: Square) -> bool {
-        let normalized_sq = sq.normalize_to_sente(color);
+    fn is_ikidokorononai(&self, color: Color, pt: PieceType, sq: Square) -> bool {
+        let rank = self.variant.relative_rank(sq, color);
         match pt {
             PieceType::None => true,
-            PieceType::Pawn | PieceType::Lance => normalized_sq.rank() <= 0,
-            PieceType::Knight => normalized_sq.rank() <= 1,
+            PieceType::Pawn | PieceType::Lance => rank <= 0,
+            PieceType::Knight => rank <= 1,
             _ => false,
         }
     }
@@ -545,13 +825,14 @@ impl Position {
             Move::Win => return None,
             Move::Resign => return None,
             Move::Drop(ptype, sq) => {
-                if !self.is_empty(sq)
+                if !self.variant.contains(sq)
+                    || !self.is_empty(sq)
                     // Invalid piece type to drop
                     || ptype.promoted() || ptype == PieceType::None || ptype == PieceType::King
                     // Illegal move rule: 二歩
                     || (ptype == PieceType::Pawn && self.is_nifu(sq.file()))
                     // Illegal move rule: 行き所のない駒
-                    || Position::is_ikidokorononai(self.stm, ptype, sq)
+                    || self.is_ikidokorononai(self.stm, ptype, sq)
                 {
                     return None;
                 }
@@ -569,13 +850,16 @@ impl Position {
                 let ptype = self.board[from.to_index()].1;
 
                 // Invalid moves
-                if from == to {
+                if from == to || !self.variant.contains(to) {
                     return None;
                 }
                 if !self.is_friendly(from) {
                     return None;
                 }
-                if promo && !from.is_promo_square(self.stm) && !to.is_promo_square(self.stm) {
+                if promo
+                    && !self.is_promo_square(from, self.stm)
+                    && !self.is_promo_square(to, self.stm)
+                {
                     return None;
                 }
                 if promo && !ptype.promotable() {
@@ -586,7 +870,7 @@ impl Position {
                 }
 
                 // Illegal move rule: 行き所のない駒
-                if !promo && Position::is_ikidokorononai(self.stm, ptype, to) {
+                if !promo && self.is_ikidokorononai(self.stm, ptype, to) {
                     return None;
                 }
 
@@ -663,6 +947,14 @@ impl Position {
             .collect()
     }
 
+    /// The sfen of this position without the move counter, so that transpositions reached at
+    /// different move numbers compare equal.
+    pub fn canonical_sfen(&self) -> String {
+        let sfen = self.to_string();
+        let (canonical, _ply) = sfen.rsplit_once(' ').unwrap();
+        String::from(canonical)
+    }
+
     pub fn parse(s: &str) -> Option<Position> {
         let mut it = s.split(' ');
         let board = it.next()?;
@@ -676,28 +968,48 @@ impl Position {
         }
     }
 
+    /// Parses an sfen. The variant is determined by the number of ranks on the board.
     pub fn parse_parts(board: &str, color: &str, hand: &str, ply: &str) -> Option<Position> {
-        Some(Position {
-            board: Position::parse_board(board)?,
+        let variant = match board.split('/').count() {
+            9 => Variant::Standard,
+            5 => Variant::Minishogi,
+            _ => return None,
+        };
+        let position = Position {
+            variant,
+            board: Position::parse_board(variant, board)?,
             hand: Position::parse_hand(hand)?,
             stm: Color::parse(color)?,
             ply: ply.parse().ok()?,
-        })
+        };
+
+        let board_ok = position.board.iter().all(|p| variant.has_piece_type(p.1));
+        let hand_ok = position.hand.iter().all(|h| {
+            [PieceType::Lance, PieceType::Knight]
+                .iter()
+                .all(|&pt| variant.has_piece_type(pt) || h.get(pt) == 0)
+        });
+        if board_ok && hand_ok {
+            Some(position)
+        } else {
+            None
+        }
     }
 
-    fn parse_board(s: &str) -> Option<[Place; 81]> {
+    fn parse_board(variant: Variant, s: &str) -> Option<[Place; 81]> {
         let mut board = [Place(Color::Sente, PieceType::None); 81];
 
+        let square_count = variant.square_count();
         let board_str = s.as_bytes();
         let mut place_index: usize = 0;
         let mut i: usize = 0;
 
-        while place_index < 81 && i < board_str.len() {
-            let sq = Square::from_fen_ordering(place_index).unwrap();
+        while place_index < square_count && i < board_str.len() {
+            let sq = variant.fen_square(place_index);
             let ch = board_str[i];
             match ch {
                 b'/' => {
-                    if sq.file() != 8 || place_index == 0 {
+                    if sq.file() != variant.size() - 1 || place_index == 0 {
                         return None;
                     }
                     i += 1;
@@ -751,7 +1063,7 @@ impl Position {
             place_index += 1;
         }
 
-        if place_index != 81 || i != board_str.len() {
+        if place_index != square_count || i != board_str.len() {
             return None;
         }
 
@@ -835,8 +1147,9 @@ impl fmt::Display for Position {
         // Board
         {
             let mut blanks = 0;
-            for i in 0..81 {
-                let sq = Square::from_fen_ordering(i).unwrap();
+            let square_count = self.variant.square_count();
+            for i in 0..square_count {
+                let sq = self.variant.fen_square(i);
                 let place = self.board[sq.to_index()];
                 if place.is_empty() {
                     blanks += 1;
@@ -852,7 +1165,7 @@ impl fmt::Display for Position {
                         write!(f, "{blanks}")?;
                         blanks = 0;
                     }
-                    if i != 80 {
+                    if i != square_count - 1 {
                         write!(f, "/")?;
                     }
                 }
@@ -905,20 +1218,127 @@ impl fmt::Display for Position {
     }
 }
 
+/// How a game came to an end, regardless of who won.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Termination {
+    Unterminated,
+    Normal,
+    IllegalMove,
+    TimeForfeit,
+    Disconnection,
+    Adjudication,
+}
+
+/// Why a game ended, without who won it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum TerminationReason {
+    Resign,
+    Checkmate,
+    /// The side to move has no legal moves but is not in check.
+    Stalemate,
+    Sennichite,
+    PerpetualCheck,
+    /// Impasse by points.
+    Impasse,
+    /// A win by reaching the try square.
+    Try,
+    /// A successful declaration of a win in impasse.
+    Declaration,
+    TimeForfeit,
+    IllegalMove,
+    /// A `bestmove` without a move, although there was a legal move to play.
+    NoMove,
+    Disconnect,
+    Adjudication,
+    MaxMoves,
+    /// A forfeit for failing a `-watchdog` check.
+    Watchdog,
+}
+
+impl TerminationReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TerminationReason::Resign => "resign",
+            TerminationReason::Checkmate => "checkmate",
+            TerminationReason::Stalemate => "stalemate",
+            TerminationReason::Sennichite => "sennichite",
+            TerminationReason::PerpetualCheck => "perpetual_check",
+            TerminationReason::Impasse => "impasse",
+            TerminationReason::Try => "try",
+            TerminationReason::Declaration => "declaration",
+            TerminationReason::TimeForfeit => "time_forfeit",
+            TerminationReason::IllegalMove => "illegal_move",
+            TerminationReason::NoMove => "no_move",
+            TerminationReason::Disconnect => "disconnect",
+            TerminationReason::Adjudication => "adjudication",
+            TerminationReason::MaxMoves => "max_moves",
+            TerminationReason::Watchdog => "watchdog",
+        }
+    }
+
+    pub fn termination(self) -> Termination {
+        match self {
+            TerminationReason::Resign
+            | TerminationReason::Checkmate
+            | TerminationReason::Stalemate
+            | TerminationReason::Sennichite
+            | TerminationReason::Impasse
+            | TerminationReason::Try
+            | TerminationReason::Declaration => Termination::Normal,
+            TerminationReason::PerpetualCheck
+            | TerminationReason::IllegalMove
+            | TerminationReason::NoMove => Termination::IllegalMove,
+            TerminationReason::TimeForfeit => Termination::TimeForfeit,
+            TerminationReason::Disconnect => Termination::Disconnection,
+            TerminationReason::Adjudication
+            | TerminationReason::MaxMoves
+            | TerminationReason::Watchdog => Termination::Adjudication,
+        }
+    }
+
+    /// Why the game ended, in more detail than `GameOutcome::to_pgn_termination_string`.
+    pub fn to_pgn_string(self) -> &'static str {
+        match self {
+            TerminationReason::Resign => "Resign",
+            TerminationReason::Checkmate => "Checkmate",
+            TerminationReason::Stalemate => "Stalemate",
+            TerminationReason::Sennichite => "Sennichite",
+            TerminationReason::PerpetualCheck => "Perpetual check",
+            TerminationReason::Impasse | TerminationReason::Declaration => "Impasse",
+            TerminationReason::Try => "Try",
+            TerminationReason::TimeForfeit => "Time forfeit",
+            TerminationReason::IllegalMove => "Illegal move",
+            TerminationReason::NoMove => "No move",
+            TerminationReason::Disconnect => "Disconnection",
+            TerminationReason::Adjudication => "Adjudication",
+            TerminationReason::MaxMoves => "Move limit",
+            TerminationReason::Watchdog => "Watchdog",
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum GameOutcome {
     Undetermined,
     Checkmated(Color),
+    Stalemated(Color),
     WinInImpasse(Color),
+    WinByImpassePoints(Color),
+    WinByTry(Color),
+    DrawByImpasse,
     DrawBySennichite,
     LossByPerpetual(Color),
     LossByIllegal(Color),
+    /// The engine replied `bestmove` without a move, although it had a legal move to play.
+    LossByNoMove(Color),
     Resignation(Color),
     LossByClock(Color),
     LossByDisconnection(Color),
     DrawByMoveLimit,
     DrawByAdjudication,
     WinByAdjudication(Color),
+    /// The engine failed a `-watchdog` check with `forfeit=true`.
+    LossByWatchdog(Color),
 }
 
 impl GameOutcome {
@@ -930,16 +1350,22 @@ impl GameOutcome {
         match self {
             GameOutcome::Undetermined => None,
             GameOutcome::Checkmated(color) => Some(!color),
+            GameOutcome::Stalemated(color) => Some(!color),
             GameOutcome::WinInImpasse(color) => Some(color),
+            GameOutcome::WinByImpassePoints(color) => Some(color),
+            GameOutcome::WinByTry(color) => Some(color),
+            GameOutcome::DrawByImpasse => None,
             GameOutcome::DrawBySennichite => None,
             GameOutcome::LossByPerpetual(color) => Some(!color),
             GameOutcome::LossByIllegal(color) => Some(!color),
+            GameOutcome::LossByNoMove(color) => Some(!color),
             GameOutcome::Resignation(color) => Some(!color),
             GameOutcome::LossByClock(color) => Some(!color),
             GameOutcome::LossByDisconnection(color) => Some(!color),
             GameOutcome::DrawByMoveLimit => None,
             GameOutcome::DrawByAdjudication => None,
             GameOutcome::WinByAdjudication(color) => Some(color),
+            GameOutcome::LossByWatchdog(color) => Some(!color),
         }
     }
 
@@ -947,18 +1373,57 @@ impl GameOutcome {
         self.is_determined() && self.winner().is_none()
     }
 
+    /// Why the game ended, or None if it hasn't.
+    pub fn reason(self) -> Option<TerminationReason> {
+        Some(match self {
+            GameOutcome::Undetermined => return None,
+            GameOutcome::Checkmated(_) => TerminationReason::Checkmate,
+            GameOutcome::Stalemated(_) => TerminationReason::Stalemate,
+            GameOutcome::WinInImpasse(_) => TerminationReason::Declaration,
+            GameOutcome::WinByImpassePoints(_) | GameOutcome::DrawByImpasse => {
+                TerminationReason::Impasse
+            }
+            GameOutcome::WinByTry(_) => TerminationReason::Try,
+            GameOutcome::DrawBySennichite => TerminationReason::Sennichite,
+            GameOutcome::LossByPerpetual(_) => TerminationReason::PerpetualCheck,
+            GameOutcome::LossByIllegal(_) => TerminationReason::IllegalMove,
+            GameOutcome::LossByNoMove(_) => TerminationReason::NoMove,
+            GameOutcome::Resignation(_) => TerminationReason::Resign,
+            GameOutcome::LossByClock(_) => TerminationReason::TimeForfeit,
+            GameOutcome::LossByDisconnection(_) => TerminationReason::Disconnect,
+            GameOutcome::DrawByMoveLimit => TerminationReason::MaxMoves,
+            GameOutcome::DrawByAdjudication | GameOutcome::WinByAdjudication(_) => {
+                TerminationReason::Adjudication
+            }
+            GameOutcome::LossByWatchdog(_) => TerminationReason::Watchdog,
+        })
+    }
+
     pub fn to_string(self) -> &'static str {
         match self {
             GameOutcome::Undetermined => "Undetermined (Game is still in play)",
             GameOutcome::DrawBySennichite => "Draw by 4-fold repetition",
             GameOutcome::Checkmated(Color::Sente) => "Gote mates",
             GameOutcome::Checkmated(Color::Gote) => "Sente mates",
+            GameOutcome::Stalemated(Color::Sente) => "Sente has no legal moves",
+            GameOutcome::Stalemated(Color::Gote) => "Gote has no legal moves",
             GameOutcome::WinInImpasse(Color::Sente) => "Sente wins by impasse",
             GameOutcome::WinInImpasse(Color::Gote) => "Gote wins by impasse",
+            GameOutcome::WinByImpassePoints(Color::Sente) => {
+                "Sente wins by impasse: Gote has fewer than 24 points"
+            }
+            GameOutcome::WinByImpassePoints(Color::Gote) => {
+                "Gote wins by impasse: Sente has fewer than 24 points"
+            }
+            GameOutcome::WinByTry(Color::Sente) => "Sente wins by try",
+            GameOutcome::WinByTry(Color::Gote) => "Gote wins by try",
+            GameOutcome::DrawByImpasse => "Draw by impasse",
             GameOutcome::LossByPerpetual(Color::Sente) => "Sente makes an illegal perpetual check",
             GameOutcome::LossByPerpetual(Color::Gote) => "Gote makes an illegal perpetual check",
             GameOutcome::LossByIllegal(Color::Sente) => "Sente makes an illegal move",
             GameOutcome::LossByIllegal(Color::Gote) => "Gote makes an illegal move",
+            GameOutcome::LossByNoMove(Color::Sente) => "Sente returns no move",
+            GameOutcome::LossByNoMove(Color::Gote) => "Gote returns no move",
             GameOutcome::Resignation(Color::Sente) => "Sente resigns",
             GameOutcome::Resignation(Color::Gote) => "Gote resigns",
             GameOutcome::LossByClock(Color::Sente) => "Sente loses on time",
@@ -968,23 +1433,29 @@ impl GameOutcome {
             GameOutcome::DrawByAdjudication => "Draw by adjudication",
             GameOutcome::WinByAdjudication(Color::Sente) => "Sente wins by adjudication",
             GameOutcome::WinByAdjudication(Color::Gote) => "Gote wins by adjudication",
+            GameOutcome::LossByWatchdog(Color::Sente) => "Sente forfeits by failing the watchdog",
+            GameOutcome::LossByWatchdog(Color::Gote) => "Gote forfeits by failing the watchdog",
         }
     }
 
+    pub fn termination(self) -> Termination {
+        self.reason()
+            .map_or(Termination::Unterminated, TerminationReason::termination)
+    }
+
+    /// Why the game ended, in more detail than `to_pgn_termination_string`.
+    pub fn to_pgn_reason_string(self) -> Option<&'static str> {
+        self.reason().map(TerminationReason::to_pgn_string)
+    }
+
     pub fn to_pgn_termination_string(self) -> &'static str {
-        match self {
-            GameOutcome::Undetermined => "unterminated",
-            GameOutcome::DrawBySennichite => "normal",
-            GameOutcome::Checkmated(_) => "normal",
-            GameOutcome::WinInImpasse(_) => "normal",
-            GameOutcome::LossByPerpetual(_) => "illegal move",
-            GameOutcome::LossByIllegal(_) => "illegal move",
-            GameOutcome::Resignation(_) => "normal",
-            GameOutcome::LossByClock(_) => "time forfeit",
-            GameOutcome::LossByDisconnection(_) => "abandoned",
-            GameOutcome::DrawByMoveLimit => "adjudication",
-            GameOutcome::DrawByAdjudication => "adjudication",
-            GameOutcome::WinByAdjudication(_) => "adjudication",
+        match self.termination() {
+            Termination::Unterminated => "unterminated",
+            Termination::Normal => "normal",
+            Termination::IllegalMove => "illegal move",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Disconnection => "abandoned",
+            Termination::Adjudication => "adjudication",
         }
     }
 }
@@ -995,6 +1466,7 @@ pub struct Game {
     moves: Vec<Move>,
     history: Vec<Position>,
     last_not_in_check_ply: [isize; 2],
+    impasse_rule: ImpasseRule,
 }
 
 impl Game {
@@ -1005,15 +1477,39 @@ impl Game {
             moves: vec![],
             history: vec![startpos],
             last_not_in_check_ply: [-1, -1],
+            impasse_rule: ImpasseRule::default(),
         }
     }
 
+    pub fn set_impasse_rule(&mut self, impasse_rule: ImpasseRule) {
+        self.impasse_rule = impasse_rule;
+    }
+
     pub fn stm(&self) -> Color {
         self.current_position.stm
     }
 
+    pub fn position(&self) -> &Position {
+        &self.current_position
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.current_position.get_check_state() == CheckState::Checkmate
+    }
+
+    /// The side to move has no legal moves but is not in check. Unlike chess, this is a loss.
+    pub fn is_stalemate(&self) -> bool {
+        !self.current_position.is_in_check() && !self.current_position.has_legal_move()
+    }
+
+    /// The game as the argument of a USI `position` command. Games from the standard start
+    /// position use `startpos`, as some engines do not accept its sfen.
     pub fn usi_string(&self) -> String {
-        let mut string = format!("sfen {}", self.history[0]);
+        let mut string = if self.history[0] == Position::default() {
+            String::from("startpos")
+        } else {
+            format!("sfen {}", self.history[0])
+        };
         if !self.moves.is_empty() {
             string += " moves";
             string.extend(self.moves.iter().map(|m| format!(" {m}")));
@@ -1031,7 +1527,8 @@ impl Game {
         }
 
         if m == Move::Win {
-            return if self.valid_impasse_win_declaration() {
+            return if self.impasse_rule != ImpasseRule::Try && self.valid_impasse_win_declaration()
+            {
                 GameOutcome::WinInImpasse(stm)
             } else {
                 GameOutcome::LossByIllegal(stm)
@@ -1046,16 +1543,31 @@ impl Game {
         }
 
         assert!(stm != self.current_position.stm);
+
+        // Moving into check is illegal, so the king is known to be safe on the try square.
+        if self.impasse_rule == ImpasseRule::Try
+            && self.current_position.king_sq(stm) == self.current_position.variant.try_square(stm)
+        {
+            return GameOutcome::WinByTry(stm);
+        }
+
         let stm = self.current_position.stm;
 
         match self.current_position.get_check_state() {
             CheckState::None => {
-                self.last_not_in_check_ply[stm.to_index()] = (self.history.len() - 1) as isize
+                self.last_not_in_check_ply[stm.to_index()] = (self.history.len() - 1) as isize;
+                if !self.current_position.has_legal_move() {
+                    return GameOutcome::Stalemated(stm);
+                }
             }
             CheckState::Check => {}
             CheckState::Checkmate => return GameOutcome::Checkmated(stm),
         }
 
+        if let Some(outcome) = self.impasse_outcome() {
+            return outcome;
+        }
+
         let (num_clones, first_clone) = {
             let mut num_clones = 0;
             let mut i = self.history.len() - 1;
@@ -1084,23 +1596,35 @@ impl Game {
         }
     }
 
+    /// Scores the game by impasse points under the 24-point rule, once both kings have entered.
+    fn impasse_outcome(&self) -> Option<GameOutcome> {
+        let pos = &self.current_position;
+        if self.impasse_rule != ImpasseRule::Point24
+            || pos.variant != Variant::Standard
+            || pos.is_in_check()
+            || !pos.is_promo_square(pos.king_sq(Color::Sente), Color::Sente)
+            || !pos.is_promo_square(pos.king_sq(Color::Gote), Color::Gote)
+        {
+            return None;
+        }
+
+        const DRAW_POINTS: usize = 24;
+        let sente_points = pos.impasse_points(Color::Sente);
+        let gote_points = pos.impasse_points(Color::Gote);
+        Some(if sente_points < DRAW_POINTS {
+            GameOutcome::WinByImpassePoints(Color::Gote)
+        } else if gote_points < DRAW_POINTS {
+            GameOutcome::WinByImpassePoints(Color::Sente)
+        } else {
+            GameOutcome::DrawByImpasse
+        })
+    }
+
     fn valid_impasse_win_declaration(&self) -> bool {
         let stm = self.current_position.stm;
         let pos = self.current_position;
 
-        let ptype_points = |pt: &PieceType| match pt.demote() {
-            PieceType::None => 0,
-            PieceType::King => 0,
-            PieceType::Rook | PieceType::Bishop => 5,
-            PieceType::Pawn
-            | PieceType::Lance
-            | PieceType::Knight
-            | PieceType::Silver
-            | PieceType::Gold => 1,
-            _ => panic!("should be unreachable"),
-        };
-
-        if !pos.king_sq(stm).is_promo_square(stm) {
+        if pos.variant != Variant::Standard || !pos.is_promo_square(pos.king_sq(stm), stm) {
             return false;
         }
         if pos.get_check_state() != CheckState::None {
@@ -1115,20 +1639,7 @@ impl Game {
             .collect();
 
         let piece_count = pieces.len();
-        let board_points: usize = pieces.iter().map(ptype_points).sum();
-        let hand_points: usize = [
-            PieceType::Rook,
-            PieceType::Bishop,
-            PieceType::Pawn,
-            PieceType::Lance,
-            PieceType::Knight,
-            PieceType::Silver,
-            PieceType::Gold,
-        ]
-        .iter()
-        .map(|pt| pos.hand[stm.to_index()].get(*pt) as usize * ptype_points(pt))
-        .sum();
-        let points = board_points + hand_points;
+        let points = pos.impasse_points(stm);
 
         match stm {
             Color::Sente => piece_count >= 10 && points >= 28,
@@ -1153,6 +1664,20 @@ mod tests {
         )
     }
 
+    #[test]
+    fn material_balance() {
+        assert_eq!(Position::default().material_balance(), 0);
+        assert_eq!(Position::startpos(Variant::Minishogi).material_balance(), 0);
+        // Gote is missing its rook, and sente has a captured pawn in hand.
+        let position =
+            Position::parse("lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b P 1")
+                .unwrap();
+        assert_eq!(position.material_balance(), 11);
+        // A dragon for gote against a silver in sente's hand.
+        let position = Position::parse("4k4/9/9/9/9/9/9/1+r7/4K4 b S 1").unwrap();
+        assert_eq!(position.material_balance(), -7);
+    }
+
     #[test]
     fn roundtrip_fens() {
         let cases = vec![
@@ -1169,6 +1694,77 @@ mod tests {
         }
     }
 
+    #[test]
+    fn zobrist_hash() {
+        let play = |moves: &[&str]| {
+            moves.iter().fold(Position::default(), |position, m| {
+                position.do_move(Move::parse(m).unwrap()).unwrap()
+            })
+        };
+        let a = play(&["7g7f", "3c3d", "2g2f"]);
+        let b = play(&["2g2f", "3c3d", "7g7f"]);
+        assert_eq!(a.zobrist_hash(), b.zobrist_hash());
+        assert_ne!(a.zobrist_hash(), play(&["7g7f", "3c3d"]).zobrist_hash());
+        assert_ne!(
+            Position::default().zobrist_hash(),
+            Position::parse("lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1")
+                .unwrap()
+                .zobrist_hash()
+        );
+
+        let captured = play(&["7g7f", "3c3d", "8h2b+"]);
+        let no_hand = Position::parse(&captured.to_string().replace(" B ", " - ")).unwrap();
+        assert_ne!(captured.zobrist_hash(), no_hand.zobrist_hash());
+    }
+
+    #[test]
+    fn usi_startpos() {
+        let mut game = Game::new(Position::default());
+        assert_eq!(game.usi_string(), "startpos");
+        game.do_move(Move::parse("7g7f").unwrap());
+        game.do_move(Move::parse("3c3d").unwrap());
+        assert_eq!(game.usi_string(), "startpos moves 7g7f 3c3d");
+
+        // Any other start, even the start position at another move number, needs its sfen.
+        for sfen in [
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 3",
+            "rbsgk/4p/5/P4/KGSBR b - 1",
+        ] {
+            let game = Game::new(Position::parse(sfen).unwrap());
+            assert_eq!(game.usi_string(), format!("sfen {sfen}"));
+        }
+    }
+
+    #[test]
+    fn repetition_ignores_move_counter() {
+        let startpos = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+        let a = Position::parse(startpos).unwrap();
+        let b = Position::parse(&startpos.replace(" 1", " 57")).unwrap();
+        assert_ne!(a, b);
+        assert!(a.is_clone_of(&b));
+        assert_eq!(a.zobrist_hash(), b.zobrist_hash());
+
+        // A game starting at move 57 still reaches sennichite, and engines see the counter.
+        let mut game = Game::new(b);
+        let moves = "2h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b 6h7h 8b9b 7h6h 9b8b";
+        for m in moves.split_whitespace() {
+            assert_eq!(
+                game.do_move(Move::parse(m).unwrap()),
+                GameOutcome::Undetermined
+            );
+        }
+        assert_eq!(
+            game.do_move(Move::parse("6h7h").unwrap()),
+            GameOutcome::DrawBySennichite
+        );
+        assert!(game.usi_string().starts_with(&format!(
+            "sfen {} moves 2h7h",
+            startpos.replace(" 1", " 57")
+        )));
+        assert_eq!(game.position().ply(), 70);
+    }
+
     #[test]
     fn uchifuzume() {
         let cases = vec![
@@ -1220,6 +1816,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_perft_minishogi() {
+        test_perft("rbsgk/4p/5/P4/KGSBR b - 1", vec![1, 14, 181, 2512, 35401]);
+    }
+
+    #[test]
+    fn minishogi_sfen() {
+        let position = Position::parse("rbsgk/4p/5/P4/KGSBR b - 1").unwrap();
+        assert_eq!(position.variant(), Variant::Minishogi);
+        assert_eq!(position, Position::startpos(Variant::Minishogi));
+        assert_eq!(position.to_string(), "rbsgk/4p/5/P4/KGSBR b - 1");
+
+        // No lances or knights, and no squares beyond the 5x5 board.
+        assert!(Position::parse("rbsgk/4p/5/P4/KGSBL b - 1").is_none());
+        assert!(Position::parse("rbsgk/4p/5/P4/KGSBR b N 1").is_none());
+        assert!(Position::parse("rbsgk/4p/5/P4/KGSBR/5 b - 1").is_none());
+        assert!(!position.is_legal(Move::parse("5e6e").unwrap()));
+    }
+
+    #[test]
+    fn minishogi_promotion_zone() {
+        let position = Position::parse("4k/5/S4/5/K4 b - 1").unwrap();
+        // Rank b is the promotion zone in standard shogi but not in minishogi.
+        assert!(!position.is_legal(Move::parse("5c5b+").unwrap()));
+        let position = position.do_move(Move::parse("5c5b").unwrap()).unwrap();
+        let position = position.do_move(Move::parse("1a2a").unwrap()).unwrap();
+        assert!(position.is_legal(Move::parse("5b4a+").unwrap()));
+
+        let position = Position::parse("4k/5/s4/5/K4 w - 1").unwrap();
+        assert!(!position.is_legal(Move::parse("5c5d+").unwrap()));
+        assert!(position.is_legal(Move::parse("5c4d").unwrap()));
+        let position = Position::parse("4k/5/5/s4/4K w - 1").unwrap();
+        assert!(position.is_legal(Move::parse("5d5e+").unwrap()));
+    }
+
     #[test]
     fn test_perft_matsuri() {
         test_perft(
@@ -1244,6 +1875,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn termination_reasons() {
+        let cases = [
+            (
+                GameOutcome::Checkmated(Color::Gote),
+                TerminationReason::Checkmate,
+            ),
+            (
+                GameOutcome::Stalemated(Color::Sente),
+                TerminationReason::Stalemate,
+            ),
+            (
+                GameOutcome::WinInImpasse(Color::Sente),
+                TerminationReason::Declaration,
+            ),
+            (
+                GameOutcome::WinByImpassePoints(Color::Gote),
+                TerminationReason::Impasse,
+            ),
+            (GameOutcome::WinByTry(Color::Sente), TerminationReason::Try),
+            (GameOutcome::DrawByImpasse, TerminationReason::Impasse),
+            (GameOutcome::DrawBySennichite, TerminationReason::Sennichite),
+            (
+                GameOutcome::LossByPerpetual(Color::Gote),
+                TerminationReason::PerpetualCheck,
+            ),
+            (
+                GameOutcome::LossByIllegal(Color::Sente),
+                TerminationReason::IllegalMove,
+            ),
+            (
+                GameOutcome::LossByNoMove(Color::Sente),
+                TerminationReason::NoMove,
+            ),
+            (
+                GameOutcome::Resignation(Color::Gote),
+                TerminationReason::Resign,
+            ),
+            (
+                GameOutcome::LossByClock(Color::Sente),
+                TerminationReason::TimeForfeit,
+            ),
+            (
+                GameOutcome::LossByDisconnection(Color::Gote),
+                TerminationReason::Disconnect,
+            ),
+            (GameOutcome::DrawByMoveLimit, TerminationReason::MaxMoves),
+            (
+                GameOutcome::DrawByAdjudication,
+                TerminationReason::Adjudication,
+            ),
+            (
+                GameOutcome::WinByAdjudication(Color::Sente),
+                TerminationReason::Adjudication,
+            ),
+            (
+                GameOutcome::LossByWatchdog(Color::Gote),
+                TerminationReason::Watchdog,
+            ),
+        ];
+        for (outcome, reason) in cases {
+            assert_eq!(outcome.reason(), Some(reason), "{outcome:?}");
+        }
+        assert_eq!(GameOutcome::Undetermined.reason(), None);
+    }
+
     #[test]
     fn test_outcome() {
         let cases = vec![
@@ -1265,12 +1962,30 @@ mod tests {
                 "6b5b",
                 GameOutcome::LossByPerpetual(Color::Sente),
             ),
+            (
+                "lnsgkgsnl/7b1/p1ppppppp/7P1/9/9/P1PPPPP1P/3SK1GR1/Lr3gSNL w Pbnp 1",
+                "4i5i 5h4h 5i4i 4h5h 4i5i 5h4h 5i4i 4h5h 4i5i 5h4h 5i4i",
+                "4h5h",
+                GameOutcome::LossByPerpetual(Color::Gote),
+            ),
+            (
+                "4k4/9/4P4/9/9/9/9/9/4K4 b G 1",
+                "",
+                "G*5b",
+                GameOutcome::Checkmated(Color::Gote),
+            ),
+            (
+                "8k/6G2/9/7G1/9/9/9/9/4K4 b - 1",
+                "",
+                "2d2c",
+                GameOutcome::Stalemated(Color::Gote),
+            ),
         ];
         for (sfen, moves, last_move, expected_outcome) in cases {
             println!("{sfen}");
             let position = Position::parse(sfen).unwrap();
             let mut game = Game::new(position);
-            for mstr in moves.split(' ') {
+            for mstr in moves.split_whitespace() {
                 let m = Move::parse(mstr).unwrap();
                 let intermediate_outcome = game.do_move(m);
                 println!("{m}: {:?}", intermediate_outcome);
@@ -1282,4 +1997,120 @@ mod tests {
             assert_eq!(final_outcome, expected_outcome);
         }
     }
+
+    #[test]
+    fn impasse_points() {
+        let cases = [
+            // Sente has exactly 24 points.
+            (
+                "9/9/9/4K4/9/9/9/4k4/9 b RB4G4S4N2Lrb2l18p 1",
+                GameOutcome::DrawByImpasse,
+            ),
+            // Sente has 23 points.
+            (
+                "9/9/9/4K4/9/9/9/4k4/9 b RB4G4S4NLrb3l18p 1",
+                GameOutcome::WinByImpassePoints(Color::Gote),
+            ),
+            // Promoted pieces on the board count as their unpromoted type.
+            (
+                "9/9/9/4K4/4+P4/9/9/4k4/9 b RB4G4S4NLrb3l17p 1",
+                GameOutcome::DrawByImpasse,
+            ),
+            // Gote has 23 points.
+            (
+                "9/9/9/4K4/9/9/9/4k4/9 b RB4G4S4N4L5Prb13p 1",
+                GameOutcome::WinByImpassePoints(Color::Sente),
+            ),
+        ];
+        for (sfen, expected_outcome) in cases {
+            let mut game = Game::new(Position::parse(sfen).unwrap());
+            game.set_impasse_rule(ImpasseRule::Point24);
+            assert_eq!(
+                game.do_move(Move::parse("5d5c").unwrap()),
+                expected_outcome,
+                "{sfen}"
+            );
+
+            // Only the 24-point rule ends the game when both kings have entered.
+            let mut game = Game::new(Position::parse(sfen).unwrap());
+            assert_eq!(
+                game.do_move(Move::parse("5d5c").unwrap()),
+                GameOutcome::Undetermined
+            );
+        }
+
+        // Play continues while only one king has entered.
+        let mut game =
+            Game::new(Position::parse("4k4/9/9/4K4/9/9/9/9/9 b RB4G4S4N2Lrb2l18p 1").unwrap());
+        game.set_impasse_rule(ImpasseRule::Point24);
+        assert_eq!(
+            game.do_move(Move::parse("5d5c").unwrap()),
+            GameOutcome::Undetermined
+        );
+    }
+
+    #[test]
+    fn try_rule() {
+        let try_game = |sfen: &str| {
+            let mut game = Game::new(Position::parse(sfen).unwrap());
+            game.set_impasse_rule(ImpasseRule::Try);
+            game
+        };
+
+        let mut game = try_game("9/4K4/9/9/9/9/9/9/4k4 b - 1");
+        assert_eq!(
+            game.do_move(Move::parse("5b5a").unwrap()),
+            GameOutcome::WinByTry(Color::Sente)
+        );
+
+        let mut game = try_game("4K4/9/9/9/9/9/9/4k4/9 w - 1");
+        assert_eq!(
+            game.do_move(Move::parse("5h5i").unwrap()),
+            GameOutcome::WinByTry(Color::Gote)
+        );
+
+        // Reaching any other square, or the try square without the try rule, is not a win.
+        let mut game = try_game("9/4K4/9/9/9/9/9/9/4k4 b - 1");
+        assert_eq!(
+            game.do_move(Move::parse("5b4a").unwrap()),
+            GameOutcome::Undetermined
+        );
+        let mut game = Game::new(Position::parse("9/4K4/9/9/9/9/9/9/4k4 b - 1").unwrap());
+        assert_eq!(
+            game.do_move(Move::parse("5b5a").unwrap()),
+            GameOutcome::Undetermined
+        );
+
+        // A try onto an attacked square is an illegal move.
+        let mut game = try_game("1r7/4K4/9/9/9/9/9/9/4k4 b - 1");
+        assert!(!game.position().is_legal(Move::parse("5b5a").unwrap()));
+        assert_eq!(
+            game.do_move(Move::parse("5b5a").unwrap()),
+            GameOutcome::LossByIllegal(Color::Sente)
+        );
+
+        // Win declarations are not allowed under the try rule.
+        let mut game = try_game("9/4K4/9/9/9/9/9/9/4k4 b - 1");
+        assert_eq!(
+            game.do_move(Move::Win),
+            GameOutcome::LossByIllegal(Color::Sente)
+        );
+
+        let mut game = try_game("5/4K/5/5/k4 b - 1");
+        assert_eq!(
+            game.do_move(Move::parse("1b1a").unwrap()),
+            GameOutcome::WinByTry(Color::Sente)
+        );
+    }
+
+    #[test]
+    fn terminal_positions() {
+        let mut game = Game::new(Position::parse("4k4/9/4P4/9/9/9/9/9/4K4 b G 1").unwrap());
+        assert!(!game.is_checkmate() && !game.is_stalemate());
+        game.do_move(Move::parse("G*5b").unwrap());
+        assert!(game.is_checkmate() && !game.is_stalemate());
+
+        let game = Game::new(Position::parse("8k/6G2/7G1/9/9/9/9/9/4K4 w - 1").unwrap());
+        assert!(!game.is_checkmate() && game.is_stalemate());
+    }
 }
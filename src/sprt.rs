@@ -3,16 +3,66 @@
 
 use std::num::FpCategory;
 
-use crate::stats::Penta;
+use crate::stats::{Penta, Wdl};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SprtModel {
+    /// Bounds are in normalized elo, see [1].
+    #[default]
+    Normalized,
+    /// Bounds are in logistic elo, as in the classic pentanomial GSPRT.
+    Logistic,
+    /// Bounds are in normalized elo, but the LLR is computed from the win/draw/loss results of
+    /// single games rather than from game pairs.
+    Trinomial,
+}
+
+impl SprtModel {
+    pub fn elo_label(self) -> &'static str {
+        match self {
+            SprtModel::Normalized | SprtModel::Trinomial => "nElo",
+            SprtModel::Logistic => "Elo",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtVerdict {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+impl SprtVerdict {
+    /// The process exit code used with `-exitcode`.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            SprtVerdict::AcceptH1 => 0,
+            SprtVerdict::AcceptH0 => 1,
+            SprtVerdict::Continue => 2,
+        }
+    }
+
+    pub fn to_result_string(self) -> &'static str {
+        match self {
+            SprtVerdict::AcceptH1 => "H1",
+            SprtVerdict::AcceptH0 => "H0",
+            SprtVerdict::Continue => "inconclusive",
+        }
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct SprtParameters {
     lower_bound: f64,
     upper_bound: f64,
-    nelo0: f64,
-    nelo1: f64,
-    t0: f64,
-    t1: f64,
+    model: SprtModel,
+    /// Whether the LLR is computed from game pairs rather than from single games.
+    paired: bool,
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
 }
 
 impl SprtParameters {
@@ -22,21 +72,70 @@ impl SprtParameters {
     /// alpha : False positive rate (Type I error)
     /// beta : False negative rate (Type II error)
     pub fn new(nelo0: f64, nelo1: f64, alpha: f64, beta: f64) -> SprtParameters {
-        let c_et = 800.0 / f64::ln(10.0);
-        let lower_bound = (beta / (1.0 - alpha)).ln();
-        let upper_bound = ((1.0 - beta) / alpha).ln();
-        let t0 = nelo0 / c_et;
-        let t1 = nelo1 / c_et;
+        Self::with_model(SprtModel::Normalized, nelo0, nelo1, alpha, beta)
+    }
+
+    /// Constructs parameters to use for a SPRT test with bounds in logistic elo.
+    /// elo0 : Represents the H0 hypothesis that the logistic elo difference is elo0
+    /// elo1 : Represents the H1 hypothesis that the logistic elo difference is elo1
+    /// alpha : False positive rate (Type I error)
+    /// beta : False negative rate (Type II error)
+    pub fn new_logistic(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> SprtParameters {
+        Self::with_model(SprtModel::Logistic, elo0, elo1, alpha, beta)
+    }
+
+    /// Constructs parameters to use for a SPRT test on trinomial results, with bounds in
+    /// normalized elo.
+    pub fn new_trinomial(nelo0: f64, nelo1: f64, alpha: f64, beta: f64) -> SprtParameters {
+        Self::with_model(SprtModel::Trinomial, nelo0, nelo1, alpha, beta)
+    }
+
+    fn with_model(model: SprtModel, elo0: f64, elo1: f64, alpha: f64, beta: f64) -> SprtParameters {
+        let (lower_bound, upper_bound) = Self::bounds(alpha, beta);
         SprtParameters {
             lower_bound,
             upper_bound,
-            nelo0,
-            nelo1,
-            t0,
-            t1,
+            model,
+            paired: model != SprtModel::Trinomial,
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        }
+    }
+
+    fn bounds(alpha: f64, beta: f64) -> (f64, f64) {
+        ((beta / (1.0 - alpha)).ln(), ((1.0 - beta) / alpha).ln())
+    }
+
+    /// Returns the same test with a different alpha and beta, recomputing the LLR bounds. The
+    /// LLR itself does not depend on alpha and beta, so results collected so far remain valid.
+    pub fn with_significance(&self, alpha: f64, beta: f64) -> SprtParameters {
+        SprtParameters {
+            paired: self.paired,
+            ..Self::with_model(self.model, self.elo0, self.elo1, alpha, beta)
+        }
+    }
+
+    /// Returns the same test computed from the results of single games, for a schedule whose
+    /// games cannot be paired. The bounds stay in the units of `model()`.
+    pub fn unpaired(&self) -> SprtParameters {
+        SprtParameters {
+            paired: false,
+            ..*self
         }
     }
 
+    /// Whether the LLR is computed from game pairs, which is fixed when the test is set up.
+    pub fn is_paired(&self) -> bool {
+        self.paired
+    }
+
+    /// Returns the false positive rate (alpha) and false negative rate (beta) of the test.
+    pub fn significance(&self) -> (f64, f64) {
+        (self.alpha, self.beta)
+    }
+
     /// Bounds on LLR for SPRT termination.
     /// If LLR falls below the lower bound, that demonstrates the hypothesis that elo = elo0 is more likely.
     /// If LLR falls above the upper bound, that demonstrates the hypothesis that elo = elo1 is more likely,
@@ -45,32 +144,129 @@ impl SprtParameters {
         (self.lower_bound, self.upper_bound)
     }
 
-    /// Returns the elo bounds provided to the constructor
-    pub fn nelo_bounds(&self) -> (f64, f64) {
-        (self.nelo0, self.nelo1)
+    /// Returns the elo bounds provided to the constructor, in the units of `model()`
+    pub fn elo_bounds(&self) -> (f64, f64) {
+        (self.elo0, self.elo1)
     }
 
-    /// Calculates the LLR for the given pentanomial results, given our SPRT parameters
-    pub fn llr(&self, penta: Penta) -> f64 {
-        let prob = regularize(penta.to_probs());
-        let count = penta.pair_count();
-        llr(
-            count as f64,
-            prob,
-            [0.0, 0.25, 0.5, 0.75, 1.0],
-            self.t0 * f64::sqrt(2.0),
-            self.t1 * f64::sqrt(2.0),
-        )
+    pub fn model(&self) -> SprtModel {
+        self.model
+    }
+
+    /// The number of samples the LLR is computed from: games, or game pairs if `is_paired()`.
+    pub fn sample_count(&self, wdl: Wdl, penta: Penta) -> u64 {
+        if self.paired {
+            penta.pair_count()
+        } else {
+            wdl.game_count()
+        }
+    }
+
+    /// Calculates the LLR for the given results, given our SPRT parameters. `wdl` and `penta`
+    /// are the trinomial and pentanomial views of the same games.
+    pub fn llr(&self, wdl: Wdl, penta: Penta) -> f64 {
+        if self.paired {
+            self.llr_pentanomial(penta)
+        } else {
+            self.llr_trinomial(wdl)
+        }
+    }
+
+    fn llr_trinomial(&self, wdl: Wdl) -> f64 {
+        if wdl.game_count() == 0 {
+            return 0.0;
+        }
+        let prob = regularize(wdl.to_probs());
+        let count = wdl.game_count() as f64;
+        let score = [0.0, 0.5, 1.0];
+        match self.model {
+            SprtModel::Normalized | SprtModel::Trinomial => {
+                let c_et = 800.0 / f64::ln(10.0);
+                llr(count, prob, score, self.elo0 / c_et, self.elo1 / c_et)
+            }
+            SprtModel::Logistic => llr_logistic(
+                count,
+                prob,
+                score,
+                logistic_score(self.elo0),
+                logistic_score(self.elo1),
+            ),
+        }
     }
 
-    pub fn should_terminate(&self, penta: Penta) -> bool {
+    fn llr_pentanomial(&self, penta: Penta) -> f64 {
         if penta.pair_count() == 0 {
-            return false;
+            return 0.0;
+        }
+        let prob = regularize(penta.to_probs());
+        let count = penta.pair_count() as f64;
+        let score = [0.0, 0.25, 0.5, 0.75, 1.0];
+        match self.model {
+            SprtModel::Normalized | SprtModel::Trinomial => {
+                let c_et = 800.0 / f64::ln(10.0);
+                let t0 = self.elo0 / c_et;
+                let t1 = self.elo1 / c_et;
+                llr(count, prob, score, t0 * f64::sqrt(2.0), t1 * f64::sqrt(2.0))
+            }
+            SprtModel::Logistic => llr_logistic(
+                count,
+                prob,
+                score,
+                logistic_score(self.elo0),
+                logistic_score(self.elo1),
+            ),
+        }
+    }
+
+    /// Determines which hypothesis, if any, the given results accept.
+    pub fn verdict(&self, wdl: Wdl, penta: Penta) -> SprtVerdict {
+        if self.sample_count(wdl, penta) == 0 {
+            return SprtVerdict::Continue;
         }
-        let llr = self.llr(penta);
+        let llr = self.llr(wdl, penta);
         let (lower_bound, upper_bound) = self.llr_bounds();
-        llr <= lower_bound || llr >= upper_bound
+        if llr <= lower_bound {
+            SprtVerdict::AcceptH0
+        } else if llr >= upper_bound {
+            SprtVerdict::AcceptH1
+        } else {
+            SprtVerdict::Continue
+        }
+    }
+
+    /// Roughly estimates the number of further games needed to reach the (upper, lower) LLR
+    /// bounds, by linearly extrapolating the average LLR gained per sample (game or game pair)
+    /// so far. A bound which the LLR is currently moving away from is never reached (infinity).
+    pub fn expected_games(&self, wdl: Wdl, penta: Penta) -> (f64, f64) {
+        let samples = self.sample_count(wdl, penta);
+        if samples == 0 {
+            return (f64::INFINITY, f64::INFINITY);
+        }
+        let games_per_sample = if self.paired { 2.0 } else { 1.0 };
+        let llr = self.llr(wdl, penta);
+        let slope = llr / samples as f64;
+        let (lower_bound, upper_bound) = self.llr_bounds();
+        let samples_to = |bound: f64| {
+            let samples = (bound - llr) / slope;
+            if samples >= 0.0 {
+                samples.ceil()
+            } else {
+                f64::INFINITY
+            }
+        };
+        (
+            games_per_sample * samples_to(upper_bound),
+            games_per_sample * samples_to(lower_bound),
+        )
     }
+
+    pub fn should_terminate(&self, wdl: Wdl, penta: Penta) -> bool {
+        self.verdict(wdl, penta) != SprtVerdict::Continue
+    }
+}
+
+fn logistic_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10.0f64.powf(-elo / 400.0))
 }
 
 /// Compute log-likelihood ratio for t = t0 versus t = t1.
@@ -142,6 +338,51 @@ fn mle<const N: usize>(prob: [f64; N], score: [f64; N], mu_ref: f64, t_star: f64
     p
 }
 
+/// Compute log-likelihood ratio for an expected score of s0 versus s1.
+fn llr_logistic<const N: usize>(
+    count: f64,
+    prob: [f64; N],
+    score: [f64; N],
+    s0: f64,
+    s1: f64,
+) -> f64 {
+    let p0 = mle_expected(prob, score, s0);
+    let p1 = mle_expected(prob, score, s1);
+    count * mean(std::array::from_fn(|i| p1[i].ln() - p0[i].ln()), prob)
+}
+
+/// Compute the maximum likelihood estimate for a discrete
+/// probability distribution that has an expected score of s,
+/// given `self` is an empirical distribution.
+fn mle_expected<const N: usize>(prob: [f64; N], score: [f64; N], s: f64) -> [f64; N] {
+    const THETA_EPSILON: f64 = 1e-7;
+
+    let phi: [f64; N] = std::array::from_fn(|i| score[i] - s);
+
+    let u = phi
+        .iter()
+        .min_by(|a, b| a.partial_cmp(b).expect("unexpected NaN"))
+        .unwrap();
+    let v = phi
+        .iter()
+        .max_by(|a, b| a.partial_cmp(b).expect("unexpected NaN"))
+        .unwrap();
+    let min_theta = -1.0 / v;
+    let max_theta = -1.0 / u;
+
+    let theta = itp(
+        |x: f64| (0..N).map(|i| prob[i] * phi[i] / (1.0 + x * phi[i])).sum(),
+        (min_theta, max_theta),
+        (f64::INFINITY, -f64::INFINITY),
+        0.1,
+        2.0,
+        0.99,
+        THETA_EPSILON,
+    );
+
+    std::array::from_fn(|i| prob[i] / (1.0 + theta * phi[i]))
+}
+
 fn regularize<const N: usize>(x: [f64; N]) -> [f64; N] {
     x.map(|x| x.max(1e-3))
 }
@@ -216,3 +457,208 @@ where
 
     (a + b) / 2.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn penta(ll: u64, dl: u64, dd: u64, wl: u64, wd: u64, ww: u64) -> Penta {
+        Penta {
+            ll,
+            dl,
+            dd,
+            wl,
+            wd,
+            ww,
+        }
+    }
+
+    /// The trinomial results of the games making up `penta`.
+    fn wdl(penta: Penta) -> Wdl {
+        Wdl {
+            w: 2 * penta.ww + penta.wd + penta.wl,
+            d: 2 * penta.dd + penta.wd + penta.dl,
+            l: 2 * penta.ll + penta.dl + penta.wl,
+        }
+    }
+
+    #[test]
+    fn logistic_llr() {
+        // Reference values computed with fishtest's LLR_logistic formulation.
+        let sprt = SprtParameters::new_logistic(0.0, 5.0, 0.05, 0.05);
+        let cases = [
+            (penta(10, 40, 60, 20, 50, 15), 0.5086027770405743),
+            (penta(15, 50, 60, 20, 40, 10), -0.6710447371101823),
+            (penta(100, 400, 600, 200, 500, 150), 5.086027770405743),
+        ];
+        for (penta, expected) in cases {
+            let llr = sprt.llr(wdl(penta), penta);
+            assert!((llr - expected).abs() < 1e-4, "{llr} != {expected}");
+        }
+    }
+
+    #[test]
+    fn significance() {
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.1);
+        assert_eq!(sprt.significance(), (0.05, 0.1));
+        let (lower, upper) = sprt.llr_bounds();
+        assert!((lower - (0.1f64 / 0.95).ln()).abs() < 1e-12);
+        assert!((upper - (0.9f64 / 0.05).ln()).abs() < 1e-12);
+
+        let stricter = sprt.with_significance(0.01, 0.01);
+        assert_eq!(stricter.significance(), (0.01, 0.01));
+        assert_eq!(stricter.elo_bounds(), sprt.elo_bounds());
+        assert_eq!(stricter.model(), sprt.model());
+        assert!((stricter.llr_bounds().1 - 99f64.ln()).abs() < 1e-12);
+        assert!((stricter.llr_bounds().0 + 99f64.ln()).abs() < 1e-12);
+        assert_eq!(stricter.with_significance(0.05, 0.1), sprt);
+    }
+
+    #[test]
+    fn verdict_exit_codes() {
+        assert_eq!(SprtVerdict::AcceptH1.exit_code(), 0);
+        assert_eq!(SprtVerdict::AcceptH0.exit_code(), 1);
+        assert_eq!(SprtVerdict::Continue.exit_code(), 2);
+        assert_eq!(SprtVerdict::Continue.to_result_string(), "inconclusive");
+    }
+
+    #[test]
+    fn verdicts() {
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        assert_eq!(
+            sprt.verdict(wdl(Penta::default()), Penta::default()),
+            SprtVerdict::Continue
+        );
+        assert_eq!(
+            sprt.verdict(
+                wdl(penta(10, 40, 60, 20, 50, 15)),
+                penta(10, 40, 60, 20, 50, 15)
+            ),
+            SprtVerdict::Continue
+        );
+        assert_eq!(
+            sprt.verdict(
+                wdl(penta(100, 400, 600, 200, 1000, 300)),
+                penta(100, 400, 600, 200, 1000, 300)
+            ),
+            SprtVerdict::AcceptH1
+        );
+        assert_eq!(
+            sprt.verdict(
+                wdl(penta(300, 1000, 600, 200, 400, 100)),
+                penta(300, 1000, 600, 200, 400, 100)
+            ),
+            SprtVerdict::AcceptH0
+        );
+    }
+
+    #[test]
+    fn expected_games() {
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        assert_eq!(
+            sprt.expected_games(wdl(Penta::default()), Penta::default()),
+            (f64::INFINITY, f64::INFINITY)
+        );
+
+        let (upper, lower) = sprt.expected_games(
+            wdl(penta(10, 40, 60, 20, 50, 15)),
+            penta(10, 40, 60, 20, 50, 15),
+        );
+        assert!(upper.is_finite() && upper > 0.0);
+        assert_eq!(lower, f64::INFINITY);
+
+        let (more_extreme_upper, _) = sprt.expected_games(
+            wdl(penta(10, 40, 60, 20, 40, 25)),
+            penta(10, 40, 60, 20, 40, 25),
+        );
+        assert!(more_extreme_upper < upper);
+
+        let (upper, lower) = sprt.expected_games(
+            wdl(penta(15, 50, 60, 20, 40, 10)),
+            penta(15, 50, 60, 20, 40, 10),
+        );
+        assert_eq!(upper, f64::INFINITY);
+        assert!(lower.is_finite() && lower > 0.0);
+
+        let (_, more_extreme_lower) = sprt.expected_games(
+            wdl(penta(25, 40, 60, 20, 40, 10)),
+            penta(25, 40, 60, 20, 40, 10),
+        );
+        assert!(more_extreme_lower < lower);
+    }
+
+    #[test]
+    fn normalized_is_default() {
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        assert_eq!(sprt.model(), SprtModel::Normalized);
+        assert_eq!(sprt.model().elo_label(), "nElo");
+        assert!(
+            sprt.llr(
+                wdl(penta(10, 40, 60, 20, 50, 15)),
+                penta(10, 40, 60, 20, 50, 15)
+            ) > 0.0
+        );
+        assert!(
+            sprt.llr(
+                wdl(penta(15, 50, 60, 20, 40, 10)),
+                penta(15, 50, 60, 20, 40, 10)
+            ) < 0.0
+        );
+    }
+
+    #[test]
+    fn trinomial_agrees_with_pentanomial() {
+        let pentanomial = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        let trinomial = SprtParameters::new_trinomial(0.0, 5.0, 0.05, 0.05);
+        assert_eq!(trinomial.model().elo_label(), "nElo");
+        for (penta, sign) in [
+            (penta(10, 40, 60, 20, 50, 15), 1.0),
+            (penta(15, 50, 60, 20, 40, 10), -1.0),
+            (penta(100, 400, 600, 200, 1000, 300), 1.0),
+        ] {
+            let wdl = wdl(penta);
+            assert_eq!(pentanomial.llr(wdl, penta).signum(), sign);
+            assert_eq!(trinomial.llr(wdl, penta).signum(), sign);
+        }
+        assert_eq!(
+            trinomial.verdict(wdl(penta(100, 400, 600, 200, 1000, 300)), Penta::default()),
+            SprtVerdict::AcceptH1
+        );
+    }
+
+    #[test]
+    fn model_is_fixed() {
+        // Single games that would accept H1 on their own do not move a paired test.
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        let wdl = Wdl {
+            w: 1000,
+            d: 100,
+            l: 10,
+        };
+        assert_eq!(sprt.llr(wdl, Penta::default()), 0.0);
+        assert_eq!(sprt.verdict(wdl, Penta::default()), SprtVerdict::Continue);
+        assert_eq!(
+            sprt.expected_games(wdl, Penta::default()),
+            (f64::INFINITY, f64::INFINITY)
+        );
+
+        // An unpaired test ignores pairs, whether or not any have completed.
+        let unpaired = sprt.unpaired();
+        assert!(!unpaired.is_paired());
+        let trinomial = SprtParameters::new_trinomial(0.0, 5.0, 0.05, 0.05);
+        let penta = penta(100, 400, 600, 200, 1000, 300);
+        for penta in [Penta::default(), penta] {
+            assert_eq!(unpaired.llr(wdl, penta), trinomial.llr(wdl, penta));
+            assert_eq!(unpaired.verdict(wdl, penta), SprtVerdict::AcceptH1);
+        }
+        assert!(!unpaired.with_significance(0.1, 0.1).is_paired());
+
+        // Logistic bounds stay logistic when computed from single games.
+        let logistic = SprtParameters::new_logistic(0.0, 5.0, 0.05, 0.05).unpaired();
+        assert_eq!(logistic.model(), SprtModel::Logistic);
+        assert_ne!(logistic.llr(wdl, penta), unpaired.llr(wdl, penta));
+        let (upper, lower) = unpaired.expected_games(Wdl { w: 3, d: 1, l: 0 }, penta);
+        assert!(upper.is_finite());
+        assert_eq!(lower, f64::INFINITY);
+    }
+}
@@ -1,4 +1,84 @@
-const NORM_PPF_0_975: f64 = 1.959963984540054;
+/// Inverse of the standard normal CDF, using Acklam's rational approximation.
+/// Relative error is below 1.15e-9 over the whole range.
+pub fn norm_ppf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    if p <= 0.0 {
+        f64::NEG_INFINITY
+    } else if p >= 1.0 {
+        f64::INFINITY
+    } else if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    }
+}
+
+/// Standard normal CDF, via the complementary error function approximation from Numerical
+/// Recipes. Relative error is below 1.2e-7.
+pub fn norm_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let erfc = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        1.0 - erfc / 2.0
+    } else {
+        erfc / 2.0
+    }
+}
+
+/// Normal quantile bounding a two-sided confidence interval at the given level, e.g. 0.95.
+fn two_sided_quantile(confidence: f64) -> f64 {
+    norm_ppf(1.0 - (1.0 - confidence) / 2.0)
+}
 
 fn score<const N: usize>(probs: [f64; N]) -> f64 {
     probs
@@ -28,7 +108,7 @@ fn normalized_elo(score: f64, variance: f64) -> f64 {
     (score - 0.5) / (2.0 * variance).sqrt() * (800.0 / f64::ln(10.0))
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Wdl {
     pub w: u64,
     pub d: u64,
@@ -66,6 +146,9 @@ impl Wdl {
     }
 
     pub fn to_probs(self) -> [f64; 3] {
+        if self.game_count() == 0 {
+            return [0.0; 3];
+        }
         let gc = self.game_count() as f64;
         [self.l as f64 / gc, self.d as f64 / gc, self.w as f64 / gc]
     }
@@ -73,9 +156,13 @@ impl Wdl {
     pub fn score(&self) -> f64 {
         score(self.to_probs())
     }
+
+    pub fn variance(&self) -> f64 {
+        variance(self.to_probs(), self.score())
+    }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Penta {
     pub ll: u64,
     pub dl: u64,
@@ -175,6 +262,9 @@ impl Penta {
     }
 
     pub fn to_probs(self) -> [f64; 5] {
+        if self.pair_count() == 0 {
+            return [0.0; 5];
+        }
         let pc = self.pair_count() as f64;
         [
             self.ll as f64 / pc,
@@ -193,39 +283,109 @@ impl Penta {
         variance(self.to_probs(), self.score())
     }
 
-    pub fn logistic_elo(&self) -> (f64, f64) {
+    /// Returns the Elo estimate and the half-width of its `confidence` interval, or None if
+    /// there are no completed pairs yet.
+    pub fn logistic_elo(&self, confidence: f64) -> Option<(f64, f64)> {
+        if self.pair_count() == 0 {
+            return None;
+        }
         let score = self.score();
         let variance = self.variance();
         let per_pair_variance = variance / self.pair_count() as f64;
-        let score_lower = score - NORM_PPF_0_975 * per_pair_variance.sqrt();
-        let score_upper = score + NORM_PPF_0_975 * per_pair_variance.sqrt();
+        let quantile = two_sided_quantile(confidence);
+        let score_lower = score - quantile * per_pair_variance.sqrt();
+        let score_upper = score + quantile * per_pair_variance.sqrt();
 
         let elo_lower = logistic_elo(score_lower);
         let elo = logistic_elo(score);
         let elo_upper = logistic_elo(score_upper);
 
-        (elo, (elo_upper - elo_lower) / 2.0)
+        Some((elo, (elo_upper - elo_lower) / 2.0))
     }
 
-    pub fn normalized_elo(&self) -> (f64, f64) {
+    /// Returns the normalized Elo estimate and the half-width of its `confidence` interval, or
+    /// None if there are no completed pairs yet, or if the variance is zero.
+    pub fn normalized_elo(&self, confidence: f64) -> Option<(f64, f64)> {
         let score = self.score();
         let variance = self.variance();
+        if self.pair_count() == 0 || variance == 0.0 {
+            return None;
+        }
         let per_pair_variance = variance / self.pair_count() as f64;
-        let score_lower = score - NORM_PPF_0_975 * per_pair_variance.sqrt();
-        let score_upper = score + NORM_PPF_0_975 * per_pair_variance.sqrt();
+        let quantile = two_sided_quantile(confidence);
+        let score_lower = score - quantile * per_pair_variance.sqrt();
+        let score_upper = score + quantile * per_pair_variance.sqrt();
 
         let elo_lower = normalized_elo(score_lower, variance);
         let elo = normalized_elo(score, variance);
         let elo_upper = normalized_elo(score_upper, variance);
 
-        (elo, (elo_upper - elo_lower) / 2.0)
+        Some((elo, (elo_upper - elo_lower) / 2.0))
+    }
+
+    /// Converts normalized Elo to logistic Elo, at the variance observed in these results. None if
+    /// there are no completed pairs yet, if the variance is zero, or if `nelo` is so large at
+    /// this variance that the score it stands for is not strictly between 0 and 1.
+    pub fn nelo_to_elo(&self, nelo: f64) -> Option<f64> {
+        let variance = self.variance();
+        if self.pair_count() == 0 || variance == 0.0 {
+            return None;
+        }
+        let score = 0.5 + nelo * (2.0 * variance).sqrt() * f64::ln(10.0) / 800.0;
+        if score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        // Adding zero turns the -0.0 of an even score into 0.0, which prints without a sign.
+        Some(logistic_elo(score) + 0.0)
+    }
+
+    /// Converts logistic Elo to normalized Elo, the inverse of `nelo_to_elo`.
+    pub fn elo_to_nelo(&self, elo: f64) -> Option<f64> {
+        let variance = self.variance();
+        if self.pair_count() == 0 || variance == 0.0 {
+            return None;
+        }
+        let score = 1.0 / (1.0 + 10.0f64.powf(-elo / 400.0));
+        Some(normalized_elo(score, variance))
     }
 
-    pub fn dd_wl_ratio(&self) -> f64 {
-        self.dd as f64 / self.wl as f64
+    pub fn dd_wl_ratio(&self) -> Option<f64> {
+        if self.wl == 0 {
+            return None;
+        }
+        Some(self.dd as f64 / self.wl as f64)
     }
 }
 
+/// Returns the score, its standard error and the likelihood of superiority (the probability that
+/// the true score is above 50%), or None if there are no results yet. The pentanomial variance is
+/// used once there are completed pairs, as it accounts for the correlation between the two games
+/// of a pair; until then the results of single games are used.
+pub fn score_uncertainty(wdl: Wdl, penta: Penta) -> Option<(f64, f64, f64)> {
+    let (score, standard_error) = if penta.pair_count() > 0 {
+        (
+            penta.score(),
+            (penta.variance() / penta.pair_count() as f64).sqrt(),
+        )
+    } else if wdl.game_count() > 0 {
+        (
+            wdl.score(),
+            (wdl.variance() / wdl.game_count() as f64).sqrt(),
+        )
+    } else {
+        return None;
+    };
+    let los = if standard_error > 0.0 {
+        norm_cdf((score - 0.5) / standard_error)
+    } else if score == 0.5 {
+        0.5
+    } else {
+        // No spread at all, e.g. nothing but wins.
+        (score > 0.5) as u8 as f64
+    };
+    Some((score, standard_error, los))
+}
+
 impl std::fmt::Display for Penta {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -239,3 +399,140 @@ impl std::fmt::Display for Penta {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_wdl() {
+        let wdl = Wdl::default();
+        assert_eq!(wdl.game_count(), 0);
+        assert_eq!(wdl.to_probs(), [0.0; 3]);
+        assert!(!wdl.score().is_nan());
+    }
+
+    #[test]
+    fn normal_cdf() {
+        assert!((norm_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((norm_cdf(1.959963984540054) - 0.975).abs() < 1e-7);
+        assert!((norm_cdf(-1.0) - 0.15865525393145707).abs() < 1e-7);
+        for p in [0.001, 0.2, 0.5, 0.9, 0.999] {
+            assert!((norm_cdf(norm_ppf(p)) - p).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn score_standard_error() {
+        assert_eq!(score_uncertainty(Wdl::default(), Penta::default()), None);
+
+        let penta = Penta {
+            ll: 0,
+            dl: 10,
+            dd: 20,
+            wl: 10,
+            wd: 20,
+            ww: 0,
+        };
+        let wdl = Wdl {
+            w: 30,
+            d: 70,
+            l: 20,
+        };
+        let (score, se, los) = score_uncertainty(wdl, penta).unwrap();
+        assert!((score - 0.5416666666666667).abs() < 1e-12);
+        assert!((se - 0.022178776975932377).abs() < 1e-12);
+        assert!((los - 0.9698554130046991).abs() < 1e-6);
+
+        // Without pairs, the trinomial variance of single games is used.
+        let wdl = Wdl {
+            w: 50,
+            d: 40,
+            l: 30,
+        };
+        let (score, se, los) = score_uncertainty(wdl, Penta::default()).unwrap();
+        assert!((score - 0.5833333333333334).abs() < 1e-12);
+        assert!((se - 0.036483126490454716).abs() < 1e-12);
+        assert!((los - 0.9888189635733637).abs() < 1e-6);
+
+        let (_, se, los) = score_uncertainty(Wdl { w: 3, d: 0, l: 0 }, Penta::default()).unwrap();
+        assert_eq!((se, los), (0.0, 1.0));
+    }
+
+    #[test]
+    fn elo_conversion() {
+        let penta = Penta {
+            ll: 3,
+            dl: 10,
+            dd: 20,
+            wl: 10,
+            wd: 25,
+            ww: 6,
+        };
+        let (elo, _) = penta.logistic_elo(0.95).unwrap();
+        let (nelo, _) = penta.normalized_elo(0.95).unwrap();
+        assert!((penta.nelo_to_elo(nelo).unwrap() - elo).abs() < 1e-9);
+        assert!((penta.elo_to_nelo(elo).unwrap() - nelo).abs() < 1e-9);
+        assert_eq!(penta.elo_to_nelo(0.0), Some(0.0));
+        assert_eq!(Penta::default().nelo_to_elo(5.0), None);
+        assert_eq!(Penta::ONE_DD.nelo_to_elo(5.0), None);
+        // At this variance, a score of 1 is about 520 nElo.
+        assert!(penta.nelo_to_elo(500.0).unwrap() > 600.0);
+        assert_eq!(penta.nelo_to_elo(550.0), None);
+        assert_eq!(penta.nelo_to_elo(-550.0), None);
+    }
+
+    #[test]
+    fn empty_penta() {
+        let penta = Penta::default();
+        assert_eq!(penta.pair_count(), 0);
+        assert_eq!(penta.to_probs(), [0.0; 5]);
+        assert!(!penta.score().is_nan());
+        assert!(!penta.variance().is_nan());
+        assert_eq!(penta.logistic_elo(0.95), None);
+        assert_eq!(penta.normalized_elo(0.95), None);
+        assert_eq!(penta.dd_wl_ratio(), None);
+    }
+
+    #[test]
+    fn nonempty_penta() {
+        let penta = Penta::ONE_WW + Penta::ONE_DD + Penta::ONE_WL;
+        assert_eq!(penta.pair_count(), 3);
+        assert_eq!(penta.dd_wl_ratio(), Some(1.0));
+        let (elo, _) = penta.logistic_elo(0.95).unwrap();
+        assert!(elo > 0.0);
+        let (nelo, _) = penta.normalized_elo(0.95).unwrap();
+        assert!(nelo > 0.0);
+    }
+
+    #[test]
+    fn normal_quantiles() {
+        assert!((norm_ppf(0.975) - 1.959963984540054).abs() < 1e-8);
+        assert!((norm_ppf(0.995) - 2.5758293035489004).abs() < 1e-8);
+        assert!((norm_ppf(0.01) + 2.3263478740408408).abs() < 1e-8);
+        assert_eq!(norm_ppf(0.5), 0.0);
+    }
+
+    #[test]
+    fn confidence_widens_error_bars() {
+        let penta = Penta {
+            ll: 10,
+            dl: 40,
+            dd: 50,
+            wl: 10,
+            wd: 50,
+            ww: 15,
+        };
+        let (elo_95, diff_95) = penta.logistic_elo(0.95).unwrap();
+        let (elo_99, diff_99) = penta.logistic_elo(0.99).unwrap();
+        assert_eq!(elo_95, elo_99);
+        assert!(diff_99 > diff_95);
+
+        let (nelo_95, ndiff_95) = penta.normalized_elo(0.95).unwrap();
+        let (nelo_99, ndiff_99) = penta.normalized_elo(0.99).unwrap();
+        assert_eq!(nelo_95, nelo_99);
+        assert!(ndiff_99 > ndiff_95);
+        // Normalized Elo is linear in score, so the ratio is exactly that of the quantiles.
+        assert!((ndiff_99 / ndiff_95 - norm_ppf(0.995) / norm_ppf(0.975)).abs() < 1e-9);
+    }
+}
@@ -14,22 +14,87 @@ pub enum TimeControl {
     None,
     Nodes(u64),
     MoveTime(Duration),
+    /// Once `base` is used up, each move must be made within `byoyomi`. Overrunning it uses up
+    /// one of the `periods` and starts the next, and the flag only falls when the last period
+    /// runs out.
     Byoyomi {
         base: Duration,
         byoyomi: Duration,
+        periods: u32,
     },
     Fischer {
         base: Duration,
         increment: Duration,
     },
+    /// The clock only starts running after `delay` has elapsed on each move.
+    SimpleDelay {
+        base: Duration,
+        delay: Duration,
+    },
+    /// After each move the time used is given back, up to at most `delay`.
+    Bronstein {
+        base: Duration,
+        delay: Duration,
+    },
+    /// Classical controls: `first.moves` moves must be made within `first.base`, after which
+    /// the clock is topped up with the base of the next segment. That is `then` if given, and
+    /// otherwise `first` again. A segment without a move count is played to the end of the game.
+    Segmented {
+        first: Segment,
+        then: Option<Segment>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub moves: Option<u32>,
+    pub base: Duration,
+    pub increment: Duration,
 }
 
 impl TimeControl {
-    pub fn parse(s: &str) -> Option<TimeControl> {
-        None.or_else(|| Self::try_parse_fischer(s))
+    pub fn parse(s: &str) -> Result<TimeControl, String> {
+        let tc = None
+            .or_else(|| Self::try_parse_infinite(s))
+            .or_else(|| Self::try_parse_segmented(s))
+            .or_else(|| Self::try_parse_delay(s))
+            .or_else(|| Self::try_parse_fischer(s))
             .or_else(|| Self::try_parse_byoyomi(s))
             .or_else(|| Self::try_parse_movetime(s))
             .or_else(|| Self::try_parse_nodes(s))
+            .ok_or_else(|| format!("Unrecognised time control `{s}`"))?;
+        if tc.is_zero() {
+            return Err(format!("Time control `{s}` leaves no time to think"));
+        }
+        Ok(tc)
+    }
+
+    /// Whether the time control gives no time (or nodes) at all for a move.
+    fn is_zero(&self) -> bool {
+        match *self {
+            TimeControl::None => false,
+            TimeControl::Nodes(nodes) => nodes == 0,
+            TimeControl::MoveTime(movetime) => movetime.is_zero(),
+            TimeControl::Byoyomi {
+                base,
+                byoyomi,
+                periods: _,
+            } => base.is_zero() && byoyomi.is_zero(),
+            TimeControl::Fischer { base, increment } => base.is_zero() && increment.is_zero(),
+            TimeControl::SimpleDelay { base, delay } | TimeControl::Bronstein { base, delay } => {
+                base.is_zero() && delay.is_zero()
+            }
+            TimeControl::Segmented { first, then: _ } => {
+                first.base.is_zero() && first.increment.is_zero()
+            }
+        }
+    }
+
+    fn try_parse_infinite(s: &str) -> Option<TimeControl> {
+        match s {
+            "inf" | "infinite" | "none" => Some(TimeControl::None),
+            _ => None,
+        }
     }
 
     fn try_parse_fischer(s: &str) -> Option<TimeControl> {
@@ -59,9 +124,79 @@ impl TimeControl {
         })
     }
 
+    /// Parses `MOVES/TC` and `MOVES/TC:TC`, where each `TC` is a Fischer time control and the
+    /// second one may carry a move count of its own. As `:` also separates minutes and seconds,
+    /// the first segment must spell out its increment for the split to be recognised.
+    fn try_parse_segmented(s: &str) -> Option<TimeControl> {
+        let (moves, rest) = s.split_once('/')?;
+        let moves = moves.parse::<u32>().ok().filter(|&moves| moves > 0)?;
+
+        let segment = |moves, s: &str| match Self::try_parse_fischer(s)? {
+            TimeControl::Fischer { base, increment } => Some(Segment {
+                moves,
+                base,
+                increment,
+            }),
+            _ => unreachable!(),
+        };
+
+        for (i, _) in rest.match_indices(':') {
+            let (a, b) = (&rest[..i], &rest[i + 1..]);
+            if !a.contains('+') {
+                continue;
+            }
+            let then = match b.split_once('/') {
+                Some((then_moves, b)) => {
+                    let then_moves = then_moves.parse::<u32>().ok().filter(|&m| m > 0);
+                    then_moves.and_then(|m| segment(Some(m), b))
+                }
+                None => segment(None, b),
+            };
+            if let (Some(first), Some(then)) = (segment(Some(moves), a), then) {
+                return Some(TimeControl::Segmented {
+                    first,
+                    then: Some(then),
+                });
+            }
+        }
+
+        Some(TimeControl::Segmented {
+            first: segment(Some(moves), rest)?,
+            then: None,
+        })
+    }
+
+    fn try_parse_delay(s: &str) -> Option<TimeControl> {
+        let re = Regex::new(
+            r"^(?:(?<min>[0-9.]+)[:分m])?(?:(?<sec>[0-9.]+)[秒s]?)?\+(?<delay>[0-9.]+)[秒s]?(?<kind>delay|bronstein)$",
+        )
+        .unwrap();
+
+        let captures = re.captures(s)?;
+        let min = captures.name("min");
+        let sec = captures.name("sec");
+        let delay = captures.name("delay");
+
+        let to_float = |x: Option<Match>| x.map_or("0", |m| m.as_str()).parse::<f64>();
+        let min = to_float(min).ok()?;
+        let sec = to_float(sec).ok()?;
+        let delay = to_float(delay).ok()?;
+
+        let base = min * 60.0 + sec;
+
+        let base = Duration::from_millis((base * 1000.0) as u64);
+        let delay = Duration::from_millis((delay * 1000.0) as u64);
+
+        match &captures["kind"] {
+            "delay" => Some(TimeControl::SimpleDelay { base, delay }),
+            "bronstein" => Some(TimeControl::Bronstein { base, delay }),
+            _ => unreachable!(),
+        }
+    }
+
     fn try_parse_byoyomi(s: &str) -> Option<TimeControl> {
         let re = Regex::new(
-            r"^(?:(?<min>[0-9.]+)[:分m])?(?:(?<sec>[0-9.]+)[秒s]?)?[,、;](?<byoyomi>[0-9.]+)(?:[秒s](未満)?)?$",
+            r"^(?:(?<min>[0-9.]+)[:分m])?(?:(?<sec>[0-9.]+)[秒s]?)?[,、;](?<byoyomi>[0-9.]+)(?:[秒s](未満)?)?(?:[x×](?<periods>[0-9]+))?$",
         )
         .unwrap();
 
@@ -79,15 +214,20 @@ impl TimeControl {
 
         let base_ms = (base * 1000.0) as u64;
         let byoyomi_ms = (byoyomi * 1000.0) as u64;
+        let periods = match captures.name("periods") {
+            Some(periods) => periods.as_str().parse::<u32>().ok().filter(|&p| p > 0)?,
+            None => 1,
+        };
 
         Some(TimeControl::Byoyomi {
             base: Duration::from_millis(base_ms),
             byoyomi: Duration::from_millis(byoyomi_ms),
+            periods,
         })
     }
 
     fn try_parse_movetime(s: &str) -> Option<TimeControl> {
-        let re = Regex::new(r"^([0-9.]+)秒未満|movetime=([0-9.]+)[s秒]?$").unwrap();
+        let re = Regex::new(r"^(?:([0-9.]+)秒未満|(?:movetime|st)=([0-9.]+)[s秒]?)$").unwrap();
 
         let captures = re.captures(s)?;
         let (_, [movetime]) = captures.extract();
@@ -100,7 +240,7 @@ impl TimeControl {
     }
 
     fn try_parse_nodes(s: &str) -> Option<TimeControl> {
-        let re = Regex::new(r"^N=([0-9]+)$").unwrap();
+        let re = Regex::new(r"^(?:N|nodes)=([0-9]+)$").unwrap();
 
         let captures = re.captures(s)?;
         let (_, [nodes]) = captures.extract();
@@ -117,44 +257,76 @@ impl fmt::Display for TimeControl {
             TimeControl::None => write!(f, "infinite")?,
             TimeControl::Nodes(nodes) => write!(f, "N={nodes}")?,
             TimeControl::MoveTime(duration) => write!(f, "movetime={}s", duration.as_secs_f64())?,
-            TimeControl::Byoyomi { base, byoyomi } => {
-                let seconds = base.as_secs_f64();
-
-                let minutes = (seconds / 60.0).floor() as i64;
-                let seconds = seconds - minutes as f64 * 60.0;
-
-                if minutes > 0 {
-                    write!(f, "{minutes}m")?;
-                }
-                write!(f, "{seconds}s")?;
+            TimeControl::Byoyomi {
+                base,
+                byoyomi,
+                periods,
+            } => {
+                write_base(f, base)?;
                 write!(f, ",{}s", byoyomi.as_secs_f64())?;
+                if *periods > 1 {
+                    write!(f, "x{periods}")?;
+                }
             }
             TimeControl::Fischer { base, increment } => {
                 if !base.is_zero() || increment.is_zero() {
-                    let seconds = base.as_secs_f64();
-
-                    let minutes = (seconds / 60.0).floor() as i64;
-                    let seconds = seconds - minutes as f64 * 60.0;
-
-                    if minutes > 0 {
-                        write!(f, "{minutes}m")?;
-                    }
-                    write!(f, "{seconds}s")?;
+                    write_base(f, base)?;
                 }
                 if !increment.is_zero() {
                     write!(f, "+{}s", increment.as_secs_f64())?;
                 }
             }
+            TimeControl::SimpleDelay { base, delay } => {
+                write_base(f, base)?;
+                write!(f, "+{}delay", delay.as_secs_f64())?;
+            }
+            TimeControl::Bronstein { base, delay } => {
+                write_base(f, base)?;
+                write!(f, "+{}bronstein", delay.as_secs_f64())?;
+            }
+            TimeControl::Segmented { first, then } => {
+                write!(f, "{first}")?;
+                if let Some(then) = then {
+                    write!(f, ":{then}")?;
+                }
+            }
         }
         Ok(())
     }
 }
 
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(moves) = self.moves {
+            write!(f, "{moves}/")?;
+        }
+        write_base(f, &self.base)?;
+        write!(f, "+{}s", self.increment.as_secs_f64())
+    }
+}
+
+fn write_base(f: &mut fmt::Formatter<'_>, base: &Duration) -> fmt::Result {
+    let seconds = base.as_secs_f64();
+
+    let minutes = (seconds / 60.0).floor() as i64;
+    let seconds = seconds - minutes as f64 * 60.0;
+
+    if minutes > 0 {
+        write!(f, "{minutes}m")?;
+    }
+    write!(f, "{seconds}s")
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct EngineTime {
     tc: TimeControl,
     remaining: Duration,
     time_margin: Duration,
+    /// Moves made within the current segment of a `TimeControl::Segmented`.
+    segment_moves: u32,
+    in_then_segment: bool,
+    /// Byoyomi periods left.
+    periods: u32,
 }
 
 impl EngineTime {
@@ -165,24 +337,61 @@ impl EngineTime {
                 TimeControl::None | TimeControl::MoveTime(_) | TimeControl::Nodes(_) => {
                     Duration::ZERO
                 }
-                TimeControl::Byoyomi { base, byoyomi: _ } => base,
+                TimeControl::Byoyomi {
+                    base,
+                    byoyomi: _,
+                    periods: _,
+                } => base,
                 TimeControl::Fischer { base, increment } => base + increment,
+                TimeControl::SimpleDelay { base, delay: _ }
+                | TimeControl::Bronstein { base, delay: _ } => base,
+                TimeControl::Segmented { first, then: _ } => first.base + first.increment,
             },
             time_margin,
+            segment_moves: 0,
+            in_then_segment: false,
+            periods: match tc {
+                TimeControl::Byoyomi {
+                    base: _,
+                    byoyomi: _,
+                    periods,
+                } => periods,
+                _ => 0,
+            },
+        }
+    }
+
+    fn segment(&self) -> Option<Segment> {
+        match self.tc {
+            TimeControl::Segmented { first, then } => match then {
+                Some(then) if self.in_then_segment => Some(then),
+                _ => Some(first),
+            },
+            _ => None,
         }
     }
 
+    /// The number of moves left until the next time control, if there is one.
+    pub fn moves_to_go(&self) -> Option<u32> {
+        let moves = self.segment()?.moves?;
+        Some(moves - self.segment_moves)
+    }
+
     pub fn remaining(&self) -> Option<Duration> {
         match self.tc {
             TimeControl::None | TimeControl::MoveTime(_) | TimeControl::Nodes(_) => None,
             TimeControl::Byoyomi {
                 base: _,
                 byoyomi: _,
+                periods: _,
             }
             | TimeControl::Fischer {
                 base: _,
                 increment: _,
-            } => Some(self.remaining),
+            }
+            | TimeControl::SimpleDelay { base: _, delay: _ }
+            | TimeControl::Bronstein { base: _, delay: _ }
+            | TimeControl::Segmented { first: _, then: _ } => Some(self.remaining),
         }
     }
 
@@ -196,7 +405,11 @@ impl EngineTime {
                     StepResult::Ok
                 }
             }
-            TimeControl::Byoyomi { base: _, byoyomi } => {
+            TimeControl::Byoyomi {
+                base: _,
+                byoyomi,
+                periods: _,
+            } => {
                 let duration = if self.remaining < duration {
                     let rem = self.remaining;
                     self.remaining = Duration::ZERO;
@@ -205,24 +418,66 @@ impl EngineTime {
                     self.remaining -= duration;
                     Duration::ZERO
                 };
-                if duration > byoyomi + self.time_margin {
+                // Each period overrun in full is used up, and the move continues in the next.
+                let overrun = duration.saturating_sub(self.time_margin);
+                let used = match overrun.as_nanos().checked_sub(1) {
+                    None => 0,
+                    Some(_) if byoyomi.is_zero() => self.periods,
+                    Some(overrun) => {
+                        (overrun / byoyomi.as_nanos()).min(self.periods as u128) as u32
+                    }
+                };
+                self.periods -= used;
+                if self.periods == 0 {
                     StepResult::TimeElapsed
                 } else {
                     StepResult::Ok
                 }
             }
             TimeControl::Fischer { base: _, increment } => {
-                if self.remaining < duration {
+                if self.remaining + self.time_margin < duration {
                     self.remaining = Duration::ZERO;
-                    if self.remaining + self.time_margin < duration {
-                        return StepResult::TimeElapsed;
-                    }
-                } else {
-                    self.remaining -= duration;
+                    return StepResult::TimeElapsed;
                 }
+                self.remaining = self.remaining.saturating_sub(duration);
                 self.remaining += increment;
                 StepResult::Ok
             }
+            TimeControl::SimpleDelay { base: _, delay } => {
+                let duration = duration.saturating_sub(delay);
+                if self.remaining + self.time_margin < duration {
+                    self.remaining = Duration::ZERO;
+                    return StepResult::TimeElapsed;
+                }
+                self.remaining = self.remaining.saturating_sub(duration);
+                StepResult::Ok
+            }
+            TimeControl::Bronstein { base: _, delay } => {
+                if self.remaining + self.time_margin < duration {
+                    self.remaining = Duration::ZERO;
+                    return StepResult::TimeElapsed;
+                }
+                self.remaining = self.remaining.saturating_sub(duration);
+                self.remaining += duration.min(delay);
+                StepResult::Ok
+            }
+            TimeControl::Segmented { first: _, then } => {
+                let segment = self.segment().unwrap();
+                if self.remaining + self.time_margin < duration {
+                    self.remaining = Duration::ZERO;
+                    return StepResult::TimeElapsed;
+                }
+                self.remaining = self.remaining.saturating_sub(duration);
+                self.remaining += segment.increment;
+
+                self.segment_moves += 1;
+                if segment.moves == Some(self.segment_moves) {
+                    self.segment_moves = 0;
+                    self.in_then_segment = then.is_some();
+                    self.remaining += self.segment().unwrap().base;
+                }
+                StepResult::Ok
+            }
         }
     }
 
@@ -231,16 +486,61 @@ impl EngineTime {
         match self.tc {
             TimeControl::None | TimeControl::Nodes(_) => None,
             TimeControl::MoveTime(duration) => Some(timeout_margin + duration),
-            TimeControl::Byoyomi { base: _, byoyomi } => {
-                Some(timeout_margin + self.remaining + byoyomi)
-            }
+            TimeControl::Byoyomi {
+                base: _,
+                byoyomi,
+                periods: _,
+            } => Some(timeout_margin + self.remaining + byoyomi * self.periods),
             TimeControl::Fischer { base: _, increment } => {
                 Some(timeout_margin + self.remaining + increment)
             }
+            TimeControl::SimpleDelay { base: _, delay } => {
+                Some(timeout_margin + self.remaining + delay)
+            }
+            TimeControl::Bronstein { base: _, delay: _ } => Some(timeout_margin + self.remaining),
+            TimeControl::Segmented { first: _, then: _ } => {
+                Some(timeout_margin + self.remaining + self.segment().unwrap().increment)
+            }
+        }
+    }
+
+    /// A fair share of the clock for the next move: the time left spread over the moves to go,
+    /// or over `BUDGET_MOVES` without a move count, plus what the clock gives for the move
+    /// itself. Unlike `bestmove_timeout`, a move can take longer than this without losing on
+    /// time. None without a clock.
+    pub fn move_budget(&self) -> Option<Duration> {
+        let share = |time: Duration, moves_to_go: Option<u32>| {
+            time / moves_to_go.unwrap_or(BUDGET_MOVES).max(1)
+        };
+        match self.tc {
+            TimeControl::None | TimeControl::Nodes(_) => None,
+            TimeControl::MoveTime(duration) => Some(duration),
+            TimeControl::Byoyomi {
+                base: _,
+                byoyomi,
+                periods: _,
+            } => Some(share(self.remaining, None) + byoyomi),
+            // The increment for the move is already on the clock.
+            TimeControl::Fischer { base: _, increment } => {
+                Some(share(self.remaining.saturating_sub(increment), None) + increment)
+            }
+            TimeControl::SimpleDelay { base: _, delay }
+            | TimeControl::Bronstein { base: _, delay } => {
+                Some(share(self.remaining, None) + delay)
+            }
+            TimeControl::Segmented { first: _, then: _ } => {
+                let increment = self.segment().unwrap().increment;
+                let time = self.remaining.saturating_sub(increment);
+                Some(share(time, self.moves_to_go()) + increment)
+            }
         }
     }
 }
 
+/// Without a move count, the number of moves the remaining time is spread over by
+/// `EngineTime::move_budget`. Deliberately few, as engines spend more on some moves than others.
+const BUDGET_MOVES: u32 = 10;
+
 pub fn to_usi_string(color: Color, sente_time: &EngineTime, gote_time: &EngineTime) -> String {
     let (stm, nstm) = match color {
         Color::Sente => ('b', 'w'),
@@ -255,7 +555,11 @@ pub fn to_usi_string(color: Color, sente_time: &EngineTime, gote_time: &EngineTi
         TimeControl::None => String::new(),
         TimeControl::MoveTime(duration) => format!("{stm}time 0 byoyomi {}", duration.as_millis()),
         TimeControl::Nodes(nodes) => format!("nodes {nodes}"),
-        TimeControl::Byoyomi { base: _, byoyomi } => format!(
+        TimeControl::Byoyomi {
+            base: _,
+            byoyomi,
+            periods: _,
+        } => format!(
             "{stm}time {} byoyomi {}",
             stm_time.remaining.as_millis(),
             byoyomi.as_millis()
@@ -265,6 +569,24 @@ pub fn to_usi_string(color: Color, sente_time: &EngineTime, gote_time: &EngineTi
             stm_time.remaining.as_millis(),
             increment.as_millis()
         ),
+        // USI has no delay parameter, so the engine only sees its main time; the delay is
+        // accounted for locally in `EngineTime::step`.
+        TimeControl::SimpleDelay { base: _, delay: _ }
+        | TimeControl::Bronstein { base: _, delay: _ } => {
+            format!("{stm}time {}", stm_time.remaining.as_millis())
+        }
+        TimeControl::Segmented { first: _, then: _ } => {
+            let segment = stm_time.segment().unwrap();
+            let mut part = format!(
+                "{stm}time {} {stm}inc {}",
+                stm_time.remaining.as_millis(),
+                segment.increment.as_millis()
+            );
+            if let Some(moves_to_go) = stm_time.moves_to_go() {
+                part += &format!(" movestogo {moves_to_go}");
+            }
+            part
+        }
     };
 
     let nstm_part = match nstm_time.tc {
@@ -272,15 +594,429 @@ pub fn to_usi_string(color: Color, sente_time: &EngineTime, gote_time: &EngineTi
         TimeControl::Byoyomi {
             base: _,
             byoyomi: _,
+            periods: _,
         } => {
-            format!(" {nstm}time {}", nstm_time.remaining.as_millis())
+            format!("{nstm}time {}", nstm_time.remaining.as_millis())
         }
         TimeControl::Fischer { base: _, increment } => format!(
-            " {nstm}time {} {nstm}inc {}",
+            "{nstm}time {} {nstm}inc {}",
             nstm_time.remaining.as_millis(),
             increment.as_millis()
         ),
+        TimeControl::SimpleDelay { base: _, delay: _ }
+        | TimeControl::Bronstein { base: _, delay: _ } => {
+            format!("{nstm}time {}", nstm_time.remaining.as_millis())
+        }
+        TimeControl::Segmented { first: _, then: _ } => format!(
+            "{nstm}time {} {nstm}inc {}",
+            nstm_time.remaining.as_millis(),
+            nstm_time.segment().unwrap().increment.as_millis()
+        ),
     };
 
-    stm_part + &nstm_part
+    // Either side may lack a clock (e.g. `tc=none` against a timed opponent), so join the
+    // parts rather than assuming both are present.
+    [stm_part, nstm_part]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn parse_forms() {
+        let accepted = [
+            (
+                "10+0.1",
+                TimeControl::Fischer {
+                    base: secs(10),
+                    increment: Duration::from_millis(100),
+                },
+            ),
+            (
+                "1:00+1",
+                TimeControl::Fischer {
+                    base: secs(60),
+                    increment: secs(1),
+                },
+            ),
+            (
+                "0+1",
+                TimeControl::Fischer {
+                    base: secs(0),
+                    increment: secs(1),
+                },
+            ),
+            (
+                "5分,10秒",
+                TimeControl::Byoyomi {
+                    base: secs(300),
+                    byoyomi: secs(10),
+                    periods: 1,
+                },
+            ),
+            (
+                "5m,30sx3",
+                TimeControl::Byoyomi {
+                    base: secs(300),
+                    byoyomi: secs(30),
+                    periods: 3,
+                },
+            ),
+            (
+                "1/60",
+                TimeControl::Segmented {
+                    first: Segment {
+                        moves: Some(1),
+                        base: secs(60),
+                        increment: secs(0),
+                    },
+                    then: None,
+                },
+            ),
+            ("inf", TimeControl::None),
+            ("infinite", TimeControl::None),
+            ("st=2", TimeControl::MoveTime(secs(2))),
+            (
+                "movetime=0.5",
+                TimeControl::MoveTime(Duration::from_millis(500)),
+            ),
+            ("10秒未満", TimeControl::MoveTime(secs(10))),
+            ("nodes=100000", TimeControl::Nodes(100000)),
+            ("N=5000", TimeControl::Nodes(5000)),
+        ];
+        for (s, tc) in accepted {
+            assert_eq!(TimeControl::parse(s), Ok(tc), "{s}");
+        }
+
+        let rejected = [
+            "",
+            "0",
+            "0+0",
+            "0:00,0",
+            "10+",
+            "+",
+            "1.2.3",
+            "abc",
+            "st=",
+            "st=0",
+            "st=two",
+            "nodes=0",
+            "nodes=1e5",
+            "N=",
+            "xmovetime=1",
+            "movetime=1x",
+            "inf+1",
+            "0/60",
+            "5m,30sx0",
+            "5m,30x",
+        ];
+        for s in rejected {
+            assert!(TimeControl::parse(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn parse_delay() {
+        assert_eq!(
+            TimeControl::parse("3+2delay"),
+            Ok(TimeControl::SimpleDelay {
+                base: secs(3),
+                delay: secs(2)
+            })
+        );
+        assert_eq!(
+            TimeControl::parse("1m+2sbronstein"),
+            Ok(TimeControl::Bronstein {
+                base: secs(60),
+                delay: secs(2)
+            })
+        );
+        for tc in ["3+2delay", "1m+2sbronstein"] {
+            let tc = TimeControl::parse(tc).unwrap();
+            assert_eq!(TimeControl::parse(&tc.to_string()), Ok(tc));
+        }
+    }
+
+    #[test]
+    fn delay_vs_increment() {
+        let mut fischer = EngineTime::new(
+            TimeControl::Fischer {
+                base: secs(10),
+                increment: secs(2),
+            },
+            Duration::ZERO,
+        );
+        let mut simple = EngineTime::new(
+            TimeControl::SimpleDelay {
+                base: secs(10),
+                delay: secs(2),
+            },
+            Duration::ZERO,
+        );
+        let mut bronstein = EngineTime::new(
+            TimeControl::Bronstein {
+                base: secs(10),
+                delay: secs(2),
+            },
+            Duration::ZERO,
+        );
+
+        // A fast move: increment banks the unused time, delays do not.
+        for clock in [&mut fischer, &mut simple, &mut bronstein] {
+            assert_eq!(clock.step(secs(1)), StepResult::Ok);
+        }
+        assert_eq!(fischer.remaining(), Some(secs(13)));
+        assert_eq!(simple.remaining(), Some(secs(10)));
+        assert_eq!(bronstein.remaining(), Some(secs(10)));
+
+        // A slow move: both delays refund at most the delay.
+        for clock in [&mut fischer, &mut simple, &mut bronstein] {
+            assert_eq!(clock.step(secs(5)), StepResult::Ok);
+        }
+        assert_eq!(fischer.remaining(), Some(secs(10)));
+        assert_eq!(simple.remaining(), Some(secs(7)));
+        assert_eq!(bronstein.remaining(), Some(secs(7)));
+    }
+
+    #[test]
+    fn fischer_time_margin() {
+        let tc = TimeControl::Fischer {
+            base: secs(3),
+            increment: secs(1),
+        };
+
+        let mut clock = EngineTime::new(tc, Duration::from_millis(500));
+        assert_eq!(clock.step(Duration::from_millis(4300)), StepResult::Ok);
+        assert_eq!(clock.remaining(), Some(secs(1)));
+
+        let mut clock = EngineTime::new(tc, Duration::from_millis(500));
+        assert_eq!(
+            clock.step(Duration::from_millis(4600)),
+            StepResult::TimeElapsed
+        );
+        assert_eq!(clock.remaining(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn delay_flag_fall() {
+        let tc = TimeControl::SimpleDelay {
+            base: secs(3),
+            delay: secs(2),
+        };
+        // Simple delay lets the engine think for its remaining time plus the delay.
+        assert_eq!(
+            EngineTime::new(tc, Duration::ZERO).step(secs(5)),
+            StepResult::Ok
+        );
+        assert_eq!(
+            EngineTime::new(tc, Duration::ZERO).step(secs(6)),
+            StepResult::TimeElapsed
+        );
+
+        // Bronstein delay is only credited after the move, so it cannot save a flag.
+        let tc = TimeControl::Bronstein {
+            base: secs(3),
+            delay: secs(2),
+        };
+        assert_eq!(
+            EngineTime::new(tc, Duration::ZERO).step(secs(3)),
+            StepResult::Ok
+        );
+        assert_eq!(
+            EngineTime::new(tc, Duration::ZERO).step(secs(4)),
+            StepResult::TimeElapsed
+        );
+    }
+
+    #[test]
+    fn byoyomi_periods() {
+        let tc = TimeControl::Byoyomi {
+            base: secs(10),
+            byoyomi: secs(5),
+            periods: 3,
+        };
+        let mut time = EngineTime::new(tc, Duration::from_millis(100));
+        let go = |time: &EngineTime| to_usi_string(Color::Sente, time, time);
+        assert_eq!(
+            time.bestmove_timeout(),
+            Some(secs(25) + Duration::from_millis(150))
+        );
+
+        // Spilling 2s past the base fits in the first period.
+        assert_eq!(time.step(secs(12)), StepResult::Ok);
+        assert_eq!(time.periods, 3);
+        assert_eq!(go(&time), "btime 0 byoyomi 5000 wtime 0");
+        // Within the margin of a period, nothing is used up.
+        assert_eq!(time.step(Duration::from_millis(5100)), StepResult::Ok);
+        assert_eq!(time.periods, 3);
+        // Overrunning one period moves on to the next, which starts afresh.
+        assert_eq!(time.step(secs(6)), StepResult::Ok);
+        assert_eq!(time.periods, 2);
+        assert_eq!(go(&time), "btime 0 byoyomi 5000 wtime 0");
+        assert_eq!(
+            time.bestmove_timeout(),
+            Some(secs(10) + Duration::from_millis(150))
+        );
+        assert_eq!(time.step(secs(8)), StepResult::Ok);
+        assert_eq!(time.periods, 1);
+        // The flag falls when the last period runs out.
+        assert_eq!(time.step(secs(5)), StepResult::Ok);
+        assert_eq!(
+            time.step(Duration::from_millis(5101)),
+            StepResult::TimeElapsed
+        );
+
+        // A long enough move can use up several periods at once.
+        let mut time = EngineTime::new(tc, Duration::ZERO);
+        assert_eq!(time.step(secs(21)), StepResult::Ok);
+        assert_eq!(time.periods, 1);
+        let mut time = EngineTime::new(tc, Duration::ZERO);
+        assert_eq!(time.step(secs(26)), StepResult::TimeElapsed);
+
+        assert_eq!(tc.to_string(), "10s,5sx3");
+        assert_eq!(TimeControl::parse(&tc.to_string()), Ok(tc));
+    }
+
+    #[test]
+    fn parse_segmented() {
+        let segment = |moves, base, increment| Segment {
+            moves,
+            base: secs(base),
+            increment: secs(increment),
+        };
+        assert_eq!(
+            TimeControl::parse("40/5m+0:3m+2"),
+            Ok(TimeControl::Segmented {
+                first: segment(Some(40), 300, 0),
+                then: Some(segment(None, 180, 2)),
+            })
+        );
+        assert_eq!(
+            TimeControl::parse("40/5:30"),
+            Ok(TimeControl::Segmented {
+                first: segment(Some(40), 330, 0),
+                then: None,
+            })
+        );
+        assert_eq!(
+            TimeControl::parse("40/5:30+1:20/1:00+0"),
+            Ok(TimeControl::Segmented {
+                first: segment(Some(40), 330, 1),
+                then: Some(segment(Some(20), 60, 0)),
+            })
+        );
+        assert!(TimeControl::parse("0/5m+0").is_err());
+        for tc in ["40/5m+0:3m+2", "40/5:30", "40/5:30+1:20/1:00+0"] {
+            let tc = TimeControl::parse(tc).unwrap();
+            assert_eq!(TimeControl::parse(&tc.to_string()), Ok(tc));
+        }
+    }
+
+    #[test]
+    fn segmented_control_boundary() {
+        let tc = TimeControl::parse("2/10+0:5+1").unwrap();
+        let mut sente = EngineTime::new(tc, Duration::ZERO);
+        let gote = EngineTime::new(tc, Duration::ZERO);
+        assert_eq!(
+            to_usi_string(Color::Sente, &sente, &gote),
+            "btime 10000 binc 0 movestogo 2 wtime 10000 winc 0"
+        );
+
+        assert_eq!(sente.step(secs(3)), StepResult::Ok);
+        assert_eq!(sente.moves_to_go(), Some(1));
+        assert_eq!(sente.remaining(), Some(secs(7)));
+
+        // Crossing the control adds the base of the next segment.
+        assert_eq!(sente.step(secs(3)), StepResult::Ok);
+        assert_eq!(sente.moves_to_go(), None);
+        assert_eq!(sente.remaining(), Some(secs(9)));
+        assert_eq!(
+            to_usi_string(Color::Sente, &sente, &gote),
+            "btime 9000 binc 1000 wtime 10000 winc 0"
+        );
+
+        assert_eq!(sente.step(secs(2)), StepResult::Ok);
+        assert_eq!(sente.remaining(), Some(secs(8)));
+        assert_eq!(sente.step(secs(9)), StepResult::TimeElapsed);
+    }
+
+    #[test]
+    fn repeating_segment() {
+        let tc = TimeControl::parse("2/10+0").unwrap();
+        let mut clock = EngineTime::new(tc, Duration::ZERO);
+        for _ in 0..3 {
+            assert_eq!(clock.moves_to_go(), Some(2));
+            assert_eq!(clock.step(secs(4)), StepResult::Ok);
+            assert_eq!(clock.moves_to_go(), Some(1));
+            assert_eq!(clock.step(secs(4)), StepResult::Ok);
+        }
+        assert_eq!(clock.remaining(), Some(secs(16)));
+    }
+
+    #[test]
+    fn move_budget() {
+        let budget = |tc: &str| {
+            EngineTime::new(TimeControl::parse(tc).unwrap(), Duration::ZERO).move_budget()
+        };
+        assert_eq!(budget("inf"), None);
+        assert_eq!(budget("10+1"), Some(secs(2)));
+        // The whole increment, however little is left of the base.
+        assert_eq!(budget("0+1"), Some(secs(1)));
+        assert_eq!(budget("5m,30sx3"), Some(secs(60)));
+        assert_eq!(budget("2/10+0"), Some(secs(5)));
+
+        let mut clock = EngineTime::new(TimeControl::parse("1m,10s").unwrap(), Duration::ZERO);
+        assert_eq!(clock.step(secs(60)), StepResult::Ok);
+        assert_eq!(clock.move_budget(), Some(secs(10)));
+    }
+
+    #[test]
+    fn asymmetric_clocks() {
+        let mut sente = EngineTime::new(TimeControl::parse("3+2").unwrap(), Duration::ZERO);
+        let mut gote = EngineTime::new(TimeControl::parse("5+0").unwrap(), Duration::ZERO);
+        assert_eq!(
+            to_usi_string(Color::Sente, &sente, &gote),
+            "btime 5000 binc 2000 wtime 5000 winc 0"
+        );
+        assert_eq!(sente.step(secs(1)), StepResult::Ok);
+        assert_eq!(
+            to_usi_string(Color::Gote, &sente, &gote),
+            "wtime 5000 winc 0 btime 6000 binc 2000"
+        );
+        assert_eq!(gote.step(secs(1)), StepResult::Ok);
+        assert_eq!(
+            to_usi_string(Color::Sente, &sente, &gote),
+            "btime 6000 binc 2000 wtime 4000 winc 0"
+        );
+
+        let byoyomi = EngineTime::new(TimeControl::parse("1:00,10").unwrap(), Duration::ZERO);
+        assert_eq!(
+            to_usi_string(Color::Sente, &byoyomi, &gote),
+            "btime 60000 byoyomi 10000 wtime 4000 winc 0"
+        );
+        assert_eq!(
+            to_usi_string(Color::Gote, &byoyomi, &gote),
+            "wtime 4000 winc 0 btime 60000"
+        );
+
+        let untimed = EngineTime::new(TimeControl::None, Duration::ZERO);
+        assert_eq!(
+            to_usi_string(Color::Sente, &untimed, &gote),
+            "wtime 4000 winc 0"
+        );
+        let nodes = EngineTime::new(TimeControl::Nodes(1000), Duration::ZERO);
+        assert_eq!(
+            to_usi_string(Color::Gote, &byoyomi, &nodes),
+            "nodes 1000 btime 60000"
+        );
+    }
 }
@@ -0,0 +1,238 @@
+use std::cell::Cell;
+
+use crate::{
+    events, pgn,
+    shogi::Color,
+    stats::Wdl,
+    tournament::{MatchResult, MatchTicket, Tournament, TournamentState},
+};
+
+pub struct EventsWrapper {
+    inner: Box<dyn Tournament>,
+    events: events::EventWriter,
+    match_complete_count: Cell<u64>,
+    /// Results of each engine as sente and as gote.
+    color_wdl: Vec<[Wdl; 2]>,
+}
+
+impl EventsWrapper {
+    pub fn new(inner: Box<dyn Tournament>, events: events::EventWriter) -> EventsWrapper {
+        let color_wdl = vec![[Wdl::default(); 2]; events.engine_count()];
+        EventsWrapper {
+            inner,
+            events,
+            match_complete_count: Cell::new(0),
+            color_wdl,
+        }
+    }
+}
+
+impl Tournament for EventsWrapper {
+    fn next(&mut self) -> Option<MatchTicket> {
+        self.inner.as_mut().next()
+    }
+    fn match_started(&mut self, ticket: MatchTicket) {
+        self.events.match_started(&ticket).unwrap();
+        self.inner.as_mut().match_started(ticket);
+    }
+    fn match_complete(&mut self, result: MatchResult) -> TournamentState {
+        for (i, record) in result.moves.iter().enumerate() {
+            self.events
+                .move_played(result.ticket.id, i + 1, record)
+                .unwrap();
+        }
+        self.events.match_complete(&result).unwrap();
+        if result.outcome.is_determined() {
+            for color in [Color::Sente, Color::Gote] {
+                let wdl = match result.outcome.winner() {
+                    Some(winner) if winner == color => Wdl::ONE_WIN,
+                    Some(_) => Wdl::ONE_LOSS,
+                    None => Wdl::ONE_DRAW,
+                };
+                let engine = result.ticket.engines[color.to_index()];
+                let total = &mut self.color_wdl[engine][color.to_index()];
+                *total = *total + wdl;
+            }
+        }
+        self.match_complete_count
+            .set(self.match_complete_count.get() + 1);
+        self.inner.as_mut().match_complete(result)
+    }
+    fn print_interval_report(&self) {
+        self.events
+            .interval_report(self.match_complete_count.get(), &self.color_wdl)
+            .unwrap();
+        self.inner.print_interval_report()
+    }
+    fn tournament_complete(&self) {
+        self.events
+            .tournament_complete(self.match_complete_count.get(), &self.color_wdl)
+            .unwrap();
+        self.inner.tournament_complete()
+    }
+    fn expected_maximum_match_count(&self) -> Option<u64> {
+        self.inner.as_ref().expected_maximum_match_count()
+    }
+    fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+        self.inner.sprt_verdict()
+    }
+    fn sprt_expected_match_count(&self) -> Option<u64> {
+        self.inner.sprt_expected_match_count()
+    }
+    fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame> {
+        self.inner.take_resumed_games()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{book, engine, engine::Score, shogi};
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Sink;
+
+    impl Tournament for Sink {
+        fn next(&mut self) -> Option<MatchTicket> {
+            None
+        }
+        fn match_started(&mut self, _: MatchTicket) {}
+        fn match_complete(&mut self, _: MatchResult) -> TournamentState {
+            TournamentState::Continue
+        }
+        fn print_interval_report(&self) {}
+        fn tournament_complete(&self) {}
+        fn expected_maximum_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+            None
+        }
+        fn sprt_expected_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn take_resumed_games(&mut self) -> Vec<crate::pgn::ImportedGame> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn two_move_game() {
+        let buffer = Rc::new(RefCell::new(vec![]));
+        let writer = events::EventWriter::from_writer(
+            Box::new(SharedBuffer(buffer.clone())),
+            vec![String::from("a"), String::from("b")],
+        );
+        let mut wrapper = EventsWrapper::new(Box::new(Sink), writer);
+
+        let ticket = MatchTicket {
+            id: 0,
+            engines: [0, 1],
+            bucket: None,
+            opening: book::Opening {
+                position: shogi::Position::default(),
+                moves: vec![],
+            },
+        };
+        let m1 = shogi::Move::parse("7g7f").unwrap();
+        let m2 = shogi::Move::parse("3c3d").unwrap();
+        let result = MatchResult {
+            ticket: ticket.clone(),
+            game_start: chrono::Utc::now(),
+            game_end: chrono::Utc::now(),
+            opening_plies: 0,
+            outcome: shogi::GameOutcome::Resignation(shogi::Color::Sente),
+            moves: vec![
+                engine::MoveRecord {
+                    stm: Some(shogi::Color::Sente),
+                    m: m1,
+                    mstr: String::from("7g7f"),
+                    score: Score::Cp(-20),
+                    depth: 10,
+                    ..engine::MoveRecord::default()
+                },
+                engine::MoveRecord {
+                    stm: Some(shogi::Color::Gote),
+                    m: m2,
+                    mstr: String::from("3c3d"),
+                    score: Score::Mate(3),
+                    depth: 12,
+                    ..engine::MoveRecord::default()
+                },
+            ],
+        };
+
+        wrapper.match_started(ticket);
+        wrapper.match_complete(result);
+        wrapper.print_interval_report();
+
+        let output = String::from_utf8(buffer.borrow().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                format!(
+                    r#"{{"event":"match_started","id":0,"sente":"a","gote":"b","position":"{}","moves":[]}}"#,
+                    shogi::Position::default()
+                )
+                .as_str(),
+                r#"{"event":"move","id":0,"ply":1,"stm":"b","m":"7g7f","mstr":"7g7f","score":{"cp":-20},"depth":10,"seldepth":0,"nodes":0,"nps":0,"engine_time":0,"hashfull":0,"tbhits":0,"measured_time_ms":0,"time_left_ms":null}"#,
+                r#"{"event":"move","id":0,"ply":2,"stm":"w","m":"3c3d","mstr":"3c3d","score":{"mate":3},"depth":12,"seldepth":0,"nodes":0,"nps":0,"engine_time":0,"hashfull":0,"tbhits":0,"measured_time_ms":0,"time_left_ms":null}"#,
+                r#"{"event":"match_complete","id":0,"result":"0-1","reason":"Sente resigns","termination":"normal","termination_reason":"resign","opening_plies":0,"plies":2}"#,
+                r#"{"event":"interval_report","match_complete_count":1,"engines":[{"name":"a","sente_wdl":{"w":0,"d":0,"l":1},"gote_wdl":{"w":0,"d":0,"l":0}},{"name":"b","sente_wdl":{"w":0,"d":0,"l":0},"gote_wdl":{"w":1,"d":0,"l":0}}]}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn colour_standings() {
+        let buffer = Rc::new(RefCell::new(vec![]));
+        let writer = events::EventWriter::from_writer(
+            Box::new(SharedBuffer(buffer.clone())),
+            vec![String::from("a"), String::from("b")],
+        );
+        let mut wrapper = EventsWrapper::new(Box::new(Sink), writer);
+
+        let result = |id, engines, outcome| MatchResult {
+            ticket: MatchTicket {
+                id,
+                engines,
+                bucket: None,
+                opening: book::Opening {
+                    position: shogi::Position::default(),
+                    moves: vec![],
+                },
+            },
+            game_start: chrono::Utc::now(),
+            game_end: chrono::Utc::now(),
+            opening_plies: 0,
+            outcome,
+            moves: vec![],
+        };
+        let gote_resigns = shogi::GameOutcome::Resignation(shogi::Color::Gote);
+        wrapper.match_complete(result(0, [0, 1], gote_resigns));
+        wrapper.match_complete(result(1, [1, 0], gote_resigns));
+        wrapper.match_complete(result(2, [0, 1], shogi::GameOutcome::DrawBySennichite));
+        // Aborted games have no result to count.
+        wrapper.match_complete(result(3, [1, 0], shogi::GameOutcome::Undetermined));
+        buffer.borrow_mut().clear();
+        wrapper.tournament_complete();
+
+        let output = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(
+            output.trim_end(),
+            r#"{"event":"tournament_complete","match_complete_count":4,"engines":[{"name":"a","sente_wdl":{"w":1,"d":1,"l":0},"gote_wdl":{"w":0,"d":0,"l":1}},{"name":"b","sente_wdl":{"w":1,"d":0,"l":0},"gote_wdl":{"w":0,"d":1,"l":1}}]}"#
+        );
+    }
+}
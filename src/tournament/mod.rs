@@ -1,11 +1,14 @@
-use crate::{engine, shogi};
+use crate::{book, engine, pgn, shogi, sprt};
 use chrono::{DateTime, Utc};
+use std::time::Duration;
 
+mod events_wrapper;
 mod pgn_out_wrapper;
 mod reporter_wrapper;
 mod round_robin;
 mod stats_wrapper;
 
+pub use events_wrapper::EventsWrapper;
 pub use pgn_out_wrapper::PgnOutWrapper;
 pub use reporter_wrapper::ReporterWrapper;
 pub use round_robin::RoundRobin;
@@ -14,18 +17,40 @@ pub use stats_wrapper::StatsWrapper;
 #[derive(Debug, Clone)]
 pub struct MatchTicket {
     pub id: u64,
-    pub opening: shogi::Position,
+    pub opening: book::Opening,
+    /// The sente and gote engines, in that order. With an opening that has gote to move, the
+    /// gote engine moves first; clocks and results still follow colours, not move order.
     pub engines: [usize; 2],
+    /// With `-openings buckets`, the index of the book file the opening came from, so that
+    /// results can be reported for each file.
+    pub bucket: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
 pub struct MatchResult {
     pub ticket: MatchTicket,
     pub game_start: DateTime<Utc>,
+    pub game_end: DateTime<Utc>,
+    /// Number of book moves played out before the engines took over.
+    pub opening_plies: usize,
     pub outcome: shogi::GameOutcome,
     pub moves: Vec<engine::MoveRecord>,
 }
 
+impl MatchResult {
+    /// Why the game ended, or None if it was not played out.
+    pub fn reason(&self) -> Option<shogi::TerminationReason> {
+        self.outcome.reason()
+    }
+
+    /// Wall-clock time from the start of the game to its end, never negative.
+    pub fn duration(&self) -> Duration {
+        (self.game_end - self.game_start)
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum TournamentState {
     Continue,
@@ -39,4 +64,71 @@ pub trait Tournament {
     fn print_interval_report(&self);
     fn tournament_complete(&self);
     fn expected_maximum_match_count(&self) -> Option<u64>;
+    /// The verdict of the SPRT, if one is being run.
+    fn sprt_verdict(&self) -> Option<sprt::SprtVerdict>;
+    /// Rough number of further games the SPRT needs to reach either bound, if one is being run
+    /// and it can be estimated yet.
+    fn sprt_expected_match_count(&self) -> Option<u64>;
+    /// The results of the games that `next` skipped since the last call, because a resumed
+    /// `-pgnout` file already records them.
+    fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame>;
+}
+
+/// A tournament without games to play, for reporting on imported results alone.
+pub struct NoGames;
+
+impl Tournament for NoGames {
+    fn next(&mut self) -> Option<MatchTicket> {
+        None
+    }
+    fn match_started(&mut self, _: MatchTicket) {}
+    fn match_complete(&mut self, _: MatchResult) -> TournamentState {
+        TournamentState::Stop
+    }
+    fn print_interval_report(&self) {}
+    fn tournament_complete(&self) {}
+    fn expected_maximum_match_count(&self) -> Option<u64> {
+        None
+    }
+    fn sprt_verdict(&self) -> Option<sprt::SprtVerdict> {
+        None
+    }
+    fn sprt_expected_match_count(&self) -> Option<u64> {
+        None
+    }
+    fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn match_duration() {
+        let game_start = Utc::now();
+        let mut result = MatchResult {
+            ticket: MatchTicket {
+                id: 0,
+                engines: [0, 1],
+                bucket: None,
+                opening: book::Opening {
+                    position: shogi::Position::default(),
+                    moves: vec![],
+                },
+            },
+            game_start,
+            game_end: game_start + chrono::TimeDelta::milliseconds(3_723_500),
+            opening_plies: 0,
+            outcome: shogi::GameOutcome::DrawBySennichite,
+            moves: vec![],
+        };
+        assert_eq!(result.duration(), Duration::from_millis(3_723_500));
+        assert_eq!(util::format_duration(result.duration()), "1:02:03");
+
+        result.game_end = game_start - chrono::TimeDelta::seconds(1);
+        assert_eq!(result.duration(), Duration::ZERO);
+    }
 }
@@ -14,18 +14,25 @@ impl PgnOutWrapper {
         options: &cli::PgnOutOptions,
         meta: &cli::MetaDataOptions,
         engine_options: Vec<cli::EngineOptions>,
-        engine_names: Vec<String>,
+        engines: Vec<cli::EngineIdentity>,
     ) -> Result<PgnOutWrapper, std::io::Error> {
         Ok(PgnOutWrapper {
             inner,
-            pgn: pgn::PgnWriter::new(options, meta, engine_options, engine_names)?,
+            pgn: pgn::PgnWriter::new(options, meta, engine_options, engines)?,
         })
     }
 }
 
 impl Tournament for PgnOutWrapper {
+    /// Skips the games already recorded in a resumed file, whose results are handed on by
+    /// `take_resumed_games`.
     fn next(&mut self) -> Option<MatchTicket> {
-        self.inner.as_mut().next()
+        loop {
+            let ticket = self.inner.as_mut().next()?;
+            if !self.pgn.take_recorded(&ticket) {
+                return Some(ticket);
+            }
+        }
     }
     fn match_started(&mut self, ticket: MatchTicket) {
         self.inner.as_mut().match_started(ticket);
@@ -43,4 +50,13 @@ impl Tournament for PgnOutWrapper {
     fn expected_maximum_match_count(&self) -> Option<u64> {
         self.inner.as_ref().expected_maximum_match_count()
     }
+    fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+        self.inner.sprt_verdict()
+    }
+    fn sprt_expected_match_count(&self) -> Option<u64> {
+        self.inner.sprt_expected_match_count()
+    }
+    fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame> {
+        self.pgn.take_resumed_games()
+    }
 }
@@ -1,11 +1,17 @@
 use crate::{
-    shogi,
+    pgn, progress, shogi,
     tournament::{MatchResult, MatchTicket, Tournament, TournamentState},
+    util,
 };
+use std::time::{Duration, Instant};
 
 pub struct ReporterWrapper {
     inner: Box<dyn Tournament>,
     engine_names: Vec<String>,
+    start: Instant,
+    completed_games: u64,
+    /// Total duration of the completed games.
+    game_time: Duration,
 }
 
 impl ReporterWrapper {
@@ -13,11 +19,69 @@ impl ReporterWrapper {
         ReporterWrapper {
             inner,
             engine_names,
+            start: Instant::now(),
+            completed_games: 0,
+            game_time: Duration::ZERO,
         }
     }
 }
 
+/// Formats the wall-clock time taken by the tournament and its throughput.
+fn format_throughput(completed_games: u64, elapsed: Duration) -> String {
+    let minutes = elapsed.as_secs_f64() / 60.0;
+    let rate = if minutes > 0.0 {
+        format!("{:.1}", completed_games as f64 / minutes)
+    } else {
+        String::from("n/a")
+    };
+    format!(
+        "Elapsed: {}, {completed_games} games, {rate} games/min",
+        util::format_duration(elapsed)
+    )
+}
+
+/// Formats the wall-clock time taken so far and an estimate of the time left for the
+/// `remaining_games`, if known. The estimate extrapolates the throughput so far, which already
+/// accounts for games played concurrently.
+fn format_eta(
+    completed_games: u64,
+    remaining_games: Option<u64>,
+    elapsed: Duration,
+    game_time: Duration,
+) -> String {
+    let mut line = format!("Elapsed: {}", util::format_duration(elapsed));
+    match remaining_games {
+        Some(remaining) if completed_games > 0 => {
+            let eta = elapsed.mul_f64(remaining as f64 / completed_games as f64);
+            line += &format!(
+                ", ETA: {} for {remaining} more games",
+                util::format_duration(eta)
+            );
+        }
+        _ => line += ", ETA: unknown",
+    }
+    if completed_games > 0 {
+        line += &format!(
+            ", average game {}",
+            util::format_duration(game_time / completed_games as u32)
+        );
+    }
+    line
+}
+
 impl ReporterWrapper {
+    /// Games left to play: the SPRT's estimate if it has one, capped by the length of the
+    /// tournament.
+    fn remaining_games(&self) -> Option<u64> {
+        let scheduled = self
+            .expected_maximum_match_count()
+            .map(|count| count.saturating_sub(self.completed_games));
+        match (self.sprt_expected_match_count(), scheduled) {
+            (Some(sprt), Some(scheduled)) => Some(sprt.min(scheduled)),
+            (sprt, scheduled) => sprt.or(scheduled),
+        }
+    }
+
     fn format_of_max_string(&self) -> String {
         match self.expected_maximum_match_count() {
             Some(count) => format!(" of {count}"),
@@ -31,40 +95,104 @@ impl Tournament for ReporterWrapper {
         self.inner.as_mut().next()
     }
     fn match_started(&mut self, ticket: MatchTicket) {
-        println!(
-            "Started game {}{} ({} vs {})",
-            ticket.id + 1,
-            self.format_of_max_string(),
-            &self.engine_names[ticket.engines[0]],
-            &self.engine_names[ticket.engines[1]]
-        );
+        progress::suspend(|| {
+            println!(
+                "Started game {}{} ({} vs {})",
+                ticket.id + 1,
+                self.format_of_max_string(),
+                &self.engine_names[ticket.engines[0]],
+                &self.engine_names[ticket.engines[1]]
+            )
+        });
         self.inner.as_mut().match_started(ticket)
     }
     fn match_complete(&mut self, result: MatchResult) -> TournamentState {
+        self.completed_games += 1;
+        self.game_time += result.duration();
         let ticket = &result.ticket;
-        println!(
-            "Finished game {} ({} vs {}): {} {{{}}}",
-            ticket.id + 1,
-            &self.engine_names[ticket.engines[0]],
-            &self.engine_names[ticket.engines[1]],
-            match result.outcome.winner() {
-                Some(shogi::Color::Sente) => "1-0",
-                Some(shogi::Color::Gote) => "0-1",
-                None if result.outcome.is_draw() => "1/2-1/2",
-                None => "undetermined",
-            },
-            result.outcome.to_string(),
-        );
+        progress::suspend(|| {
+            println!(
+                "Finished game {} ({} vs {}): {} {{{}}}",
+                ticket.id + 1,
+                &self.engine_names[ticket.engines[0]],
+                &self.engine_names[ticket.engines[1]],
+                match result.outcome.winner() {
+                    Some(shogi::Color::Sente) => "1-0",
+                    Some(shogi::Color::Gote) => "0-1",
+                    None if result.outcome.is_draw() => "1/2-1/2",
+                    None => "undetermined",
+                },
+                result.outcome.to_string(),
+            )
+        });
         self.inner.as_mut().match_complete(result)
     }
     fn print_interval_report(&self) {
+        println!(
+            "{}",
+            format_eta(
+                self.completed_games,
+                self.remaining_games(),
+                self.start.elapsed(),
+                self.game_time
+            )
+        );
         self.inner.print_interval_report()
     }
     fn tournament_complete(&self) {
+        progress::finish();
         println!("Tournament finished");
+        println!(
+            "{}",
+            format_throughput(self.completed_games, self.start.elapsed())
+        );
         self.inner.tournament_complete()
     }
     fn expected_maximum_match_count(&self) -> Option<u64> {
         self.inner.as_ref().expected_maximum_match_count()
     }
+    fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+        self.inner.sprt_verdict()
+    }
+    fn sprt_expected_match_count(&self) -> Option<u64> {
+        self.inner.sprt_expected_match_count()
+    }
+    fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame> {
+        self.inner.take_resumed_games()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        // Two games at a time, each taking ten minutes.
+        assert_eq!(
+            format_eta(30, Some(60), minutes(150), minutes(300)),
+            "Elapsed: 2:30:00, ETA: 5:00:00 for 60 more games, average game 0:10:00"
+        );
+        assert_eq!(
+            format_eta(30, None, minutes(150), minutes(300)),
+            "Elapsed: 2:30:00, ETA: unknown, average game 0:10:00"
+        );
+        assert_eq!(
+            format_eta(0, Some(60), Duration::ZERO, Duration::ZERO),
+            "Elapsed: 0:00:00, ETA: unknown"
+        );
+    }
+
+    #[test]
+    fn throughput() {
+        assert_eq!(
+            format_throughput(30, Duration::from_secs(600)),
+            "Elapsed: 0:10:00, 30 games, 3.0 games/min"
+        );
+        assert_eq!(
+            format_throughput(0, Duration::ZERO),
+            "Elapsed: 0:00:00, 0 games, n/a games/min"
+        );
+    }
 }
@@ -1,61 +1,139 @@
 use crate::{
-    book, cli,
+    book, cli, pgn,
+    shogi::Color,
     tournament::{MatchResult, MatchTicket, Tournament, TournamentState},
 };
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
 
-fn pairings_count(players: usize) -> u64 {
-    (players * (players - 1) / 2) as u64
+fn pairings_count(players: usize, gauntlet_seeds: Option<usize>) -> u64 {
+    match gauntlet_seeds {
+        Some(seeds) => (seeds * (players - seeds)) as u64,
+        None => (players * (players - 1) / 2) as u64,
+    }
 }
 
+/// Plays every pairing of engines in turn. In a gauntlet, the pairings are those of each seed
+/// engine against each of the other engines.
+///
+/// Each pairing plays `options.games` games (always even) on a single opening, alternating
+/// which engine is sente. Tickets `2k` and `2k + 1` therefore always form a pair on the same
+/// opening, which is what `StatsWrapper` relies on when it pairs sibling tickets via `id ^ 1`.
+/// The pair is colour-swapped unless the colour policy says otherwise, see `cli::ColorPolicy`.
+/// The book is only advanced once those games are complete, at which point we also move on to
+/// the next pairing.
+///
+/// Pairings are played in rounds that contain every pairing once, up to `options.rounds`
+/// rounds. With `-pairings random`, the order of each round and the engine that is sente first
+/// for each pairing are drawn from an RNG of their own, so the schedule only depends on the
+/// pairing seed.
 #[derive(Debug)]
 pub struct RoundRobin {
     match_index: u64,
     completed_matches: u64,
-    next_players: [usize; 2],
+    cycle: Vec<[usize; 2]>,
+    cycle_index: usize,
+    rng: ChaCha8Rng,
     total_matches: Option<u64>,
     players: usize,
+    color_policy: cli::ColorPolicy,
     options: cli::CliOptions,
     openings: book::OpeningBook,
 }
 
 impl RoundRobin {
-    pub fn new(options: &cli::CliOptions, openings: book::OpeningBook) -> RoundRobin {
+    pub fn new(options: &cli::CliOptions, openings: book::OpeningBook) -> Option<RoundRobin> {
         let players = options.engines.len();
-        RoundRobin {
+        assert!(options.games.is_multiple_of(2));
+
+        let total_matches = options
+            .rounds
+            .map(|g| pairings_count(players, options.gauntlet_seeds) * options.games * g);
+        let total_matches = match (total_matches, openings.remaining()) {
+            (Some(total_matches), Some(remaining)) => {
+                if remaining * options.games < total_matches {
+                    eprintln!(
+                        "Opening book has {remaining} unused openings, but {} are required with norepeat",
+                        total_matches / options.games
+                    );
+                    return None;
+                }
+                Some(total_matches)
+            }
+            (None, Some(remaining)) => Some(remaining * options.games),
+            (total_matches, None) => total_matches,
+        };
+
+        let rng = match options.pairing_seed() {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_os_rng(),
+        };
+
+        let mut round_robin = RoundRobin {
             match_index: 0,
             completed_matches: 0,
-            next_players: [0, 1],
+            cycle: vec![],
+            cycle_index: 0,
+            rng,
             players,
-            total_matches: options
-                .games
-                .map(|g| pairings_count(players) * options.rounds * g),
+            color_policy: options
+                .book
+                .as_ref()
+                .map_or(cli::ColorPolicy::default(), |book| book.color_policy),
+            total_matches,
             options: options.clone(),
             openings,
+        };
+        round_robin.start_cycle();
+        Some(round_robin)
+    }
+
+    fn start_cycle(&mut self) {
+        let players = self.players;
+        self.cycle = match self.options.gauntlet_seeds {
+            Some(seeds) => (0..seeds)
+                .flat_map(|a| (seeds..players).map(move |b| [a, b]))
+                .collect(),
+            None => (0..players)
+                .flat_map(|a| ((a + 1)..players).map(move |b| [a, b]))
+                .collect(),
+        };
+        if self.options.random_pairings {
+            self.cycle.shuffle(&mut self.rng);
+            for pairing in &mut self.cycle {
+                if self.rng.random_bool(0.5) {
+                    pairing.reverse();
+                }
+            }
         }
+        self.cycle_index = 0;
     }
 }
 
 impl Tournament for RoundRobin {
     fn next(&mut self) -> Option<MatchTicket> {
         let id = self.match_index;
-        let opening = self.openings.current();
+        let opening = self.openings.current()?;
+        let bucket = self.openings.current_bucket();
 
-        let mut players = self.next_players;
-        if id % self.options.rounds % 2 == 1 {
+        let mut players = self.cycle[self.cycle_index];
+        let second_of_pair = id % self.options.games % 2 == 1;
+        let swap = match self.color_policy {
+            cli::ColorPolicy::Alternate => second_of_pair,
+            cli::ColorPolicy::Book => second_of_pair != (opening.stm() == Color::Gote),
+            cli::ColorPolicy::FirstEngineSente => false,
+        };
+        if swap {
             players.reverse();
         }
 
         self.match_index += 1;
 
-        if self.match_index.is_multiple_of(self.options.rounds) {
+        if self.match_index.is_multiple_of(self.options.games) {
             self.openings.advance();
-            self.next_players[1] += 1;
-            if self.next_players[1] >= self.players {
-                self.next_players[0] += 1;
-                self.next_players[1] = self.next_players[0] + 1;
-                if self.next_players[1] >= self.players {
-                    self.next_players = [0, 1];
-                }
+            self.cycle_index += 1;
+            if self.cycle_index >= self.cycle.len() {
+                self.start_cycle();
             }
         }
 
@@ -68,6 +146,7 @@ impl Tournament for RoundRobin {
                 id,
                 opening,
                 engines: players,
+                bucket,
             })
         }
     }
@@ -88,4 +167,215 @@ impl Tournament for RoundRobin {
     fn expected_maximum_match_count(&self) -> Option<u64> {
         self.total_matches
     }
+    fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+        None
+    }
+    fn sprt_expected_match_count(&self) -> Option<u64> {
+        None
+    }
+    fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi;
+
+    fn test_openings() -> Vec<shogi::Position> {
+        let openings = [
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+            "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+        ];
+        openings
+            .iter()
+            .map(|sfen| shogi::Position::parse(sfen).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn pairs_share_opening_and_swap_colors() {
+        let openings = test_openings();
+        let options = cli::CliOptions {
+            engines: vec![cli::EngineOptions::default(); 3],
+            rounds: Some(2),
+            ..cli::CliOptions::default()
+        };
+        let mut rr =
+            RoundRobin::new(&options, book::OpeningBook::from_positions(openings, false)).unwrap();
+
+        let tickets: Vec<MatchTicket> = std::iter::from_fn(|| rr.next()).collect();
+        assert_eq!(tickets.len(), 12);
+
+        for pair in tickets.chunks(2) {
+            assert_eq!(pair[0].id ^ 1, pair[1].id);
+            assert_eq!(pair[0].opening, pair[1].opening);
+            assert_eq!(pair[0].engines[0], pair[1].engines[1]);
+            assert_eq!(pair[0].engines[1], pair[1].engines[0]);
+        }
+
+        for (a, b) in tickets.chunks(2).zip(tickets.chunks(2).skip(1)) {
+            assert_ne!(a[0].opening, b[0].opening);
+        }
+    }
+
+    #[test]
+    fn color_policies() {
+        let engines = |color_policy| {
+            let options = cli::CliOptions {
+                engines: vec![cli::EngineOptions::default(); 2],
+                book: Some(cli::BookOptions {
+                    color_policy,
+                    ..cli::BookOptions::default()
+                }),
+                rounds: Some(3),
+                ..cli::CliOptions::default()
+            };
+            // The second opening has gote to move.
+            let book = book::OpeningBook::from_positions(test_openings(), false);
+            let mut rr = RoundRobin::new(&options, book).unwrap();
+            std::iter::from_fn(|| rr.next())
+                .map(|ticket| ticket.engines)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            engines(cli::ColorPolicy::Alternate),
+            [[0, 1], [1, 0], [0, 1], [1, 0], [0, 1], [1, 0]]
+        );
+        assert_eq!(
+            engines(cli::ColorPolicy::Book),
+            [[0, 1], [1, 0], [1, 0], [0, 1], [0, 1], [1, 0]]
+        );
+        assert_eq!(
+            engines(cli::ColorPolicy::FirstEngineSente),
+            [[0, 1], [0, 1], [0, 1], [0, 1], [0, 1], [0, 1]]
+        );
+    }
+
+    #[test]
+    fn sequential_pairings() {
+        let options = cli::CliOptions {
+            engines: vec![cli::EngineOptions::default(); 3],
+            ..cli::CliOptions::default()
+        };
+        let book = book::OpeningBook::from_positions(test_openings(), false);
+        let mut rr = RoundRobin::new(&options, book).unwrap();
+        let engines: Vec<[usize; 2]> = (0..8).map(|_| rr.next().unwrap().engines).collect();
+        assert_eq!(
+            engines,
+            [
+                [0, 1],
+                [1, 0],
+                [0, 2],
+                [2, 0],
+                [1, 2],
+                [2, 1],
+                [0, 1],
+                [1, 0]
+            ]
+        );
+    }
+
+    #[test]
+    fn gauntlet_pairings() {
+        let options = cli::CliOptions {
+            engines: vec![cli::EngineOptions::default(); 4],
+            gauntlet_seeds: Some(1),
+            rounds: Some(1),
+            ..cli::CliOptions::default()
+        };
+        let book = book::OpeningBook::from_positions(test_openings(), false);
+        let mut rr = RoundRobin::new(&options, book).unwrap();
+        assert_eq!(rr.expected_maximum_match_count(), Some(6));
+        let engines: Vec<[usize; 2]> = std::iter::from_fn(|| rr.next())
+            .map(|ticket| ticket.engines)
+            .collect();
+        assert_eq!(engines, [[0, 1], [1, 0], [0, 2], [2, 0], [0, 3], [3, 0]]);
+    }
+
+    #[test]
+    fn random_pairings_are_reproducible() {
+        let schedule = |pairing_seed| {
+            let options = cli::CliOptions {
+                engines: vec![cli::EngineOptions::default(); 4],
+                random_pairings: true,
+                pairing_seed: Some(pairing_seed),
+                ..cli::CliOptions::default()
+            };
+            let book = book::OpeningBook::from_positions(test_openings(), false);
+            let mut rr = RoundRobin::new(&options, book).unwrap();
+            (0..48)
+                .map(|_| rr.next().unwrap().engines)
+                .collect::<Vec<_>>()
+        };
+
+        let schedule1 = schedule(7);
+        assert_eq!(schedule1, schedule(7));
+        assert_ne!(schedule1, schedule(8));
+
+        // Every cycle still plays each pairing exactly once, in both colours.
+        for cycle in schedule1.chunks(12) {
+            let mut pairings: Vec<[usize; 2]> = cycle
+                .chunks(2)
+                .map(|pair| {
+                    assert_eq!(pair[0], [pair[1][1], pair[1][0]]);
+                    let mut pairing = pair[0];
+                    pairing.sort();
+                    pairing
+                })
+                .collect();
+            pairings.sort();
+            assert_eq!(pairings, [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]]);
+        }
+    }
+
+    #[test]
+    fn rounds_repeat_every_pairing() {
+        let options = cli::CliOptions {
+            engines: vec![cli::EngineOptions::default(); 3],
+            rounds: Some(2),
+            ..cli::CliOptions::default()
+        };
+        let mut openings = test_openings();
+        openings.truncate(2);
+        let book = book::OpeningBook::from_positions(openings, false);
+        let mut rr = RoundRobin::new(&options, book).unwrap();
+        assert_eq!(rr.expected_maximum_match_count(), Some(12));
+
+        let tickets: Vec<MatchTicket> = std::iter::from_fn(|| rr.next()).collect();
+        assert_eq!(tickets.len(), 12);
+        for (id, ticket) in tickets.iter().enumerate() {
+            assert_eq!(ticket.id, id as u64);
+        }
+
+        let (first, second) = tickets.split_at(6);
+        for (a, b) in first.iter().zip(second) {
+            assert_eq!(a.engines, b.engines);
+            assert_ne!(a.opening, b.opening);
+        }
+    }
+
+    #[test]
+    fn no_repeat_bounds_match_count() {
+        let options = cli::CliOptions {
+            engines: vec![cli::EngineOptions::default(); 2],
+            ..cli::CliOptions::default()
+        };
+        let book = book::OpeningBook::from_positions(test_openings(), true);
+        let mut rr = RoundRobin::new(&options, book).unwrap();
+        assert_eq!(rr.expected_maximum_match_count(), Some(6));
+
+        let tickets: Vec<MatchTicket> = std::iter::from_fn(|| rr.next()).collect();
+        assert_eq!(tickets.len(), 6);
+
+        let options = cli::CliOptions {
+            rounds: Some(4),
+            ..options
+        };
+        let book = book::OpeningBook::from_positions(test_openings(), true);
+        assert!(RoundRobin::new(&options, book).is_none());
+    }
 }
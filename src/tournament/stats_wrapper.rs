@@ -1,25 +1,52 @@
-use std::{cmp::Ordering, collections::HashMap, path::Path};
+use itertools::iproduct;
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
 
 use crate::{
-    cli,
-    shogi::Color,
-    sprt::SprtParameters,
-    stats::{Penta, Wdl},
+    cli, engine, pgn, progress,
+    shogi::{Color, GameOutcome, Termination},
+    sprt::{SprtModel, SprtParameters, SprtVerdict},
+    stats::{self, Penta, Wdl},
     tournament::{MatchResult, MatchTicket, Tournament, TournamentState},
 };
 
 pub struct StatsWrapper {
     inner: Box<dyn Tournament>,
     engine_names: Vec<String>,
+    /// The `id name` of each engine, shown when it differs from the engine's name.
+    engine_versions: Vec<Option<String>>,
     engine_options: Vec<cli::EngineOptions>,
-    book_name: Option<String>,
+    book_files: Vec<String>,
     wdl_board: HashMap<(usize, usize), Wdl>,
     penta_board: HashMap<(usize, usize), Penta>,
+    /// Like `wdl_board` and `penta_board`, for the games of each bucket, keyed by bucket first.
+    bucket_wdl_board: HashMap<(usize, (usize, usize)), Wdl>,
+    bucket_penta_board: HashMap<(usize, (usize, usize)), Penta>,
     pending_pairing: HashMap<u64, ((usize, usize), Option<Color>)>,
+    terminations: Vec<TerminationCounts>,
+    /// Terminations of the first engine of `sprt_pair` in its games against the second.
+    pair_terminations: TerminationCounts,
+    performance: Option<Vec<Performance>>,
+    clock_desync: Vec<ClockDesync>,
     sprt: Option<SprtParameters>,
+    /// The engines whose results the SPRT is run on, as `[engine, opponent]`.
+    sprt_pair: [usize; 2],
+    sprt_drain: cli::SprtDrain,
+    gauntlet_seeds: Option<usize>,
+    confidence: f64,
+    elo_report: cli::EloReport,
+    fishtest: bool,
     match_ticket_count: u64,
     match_complete_count: u64,
     should_terminate: bool,
+    progress: bool,
+    /// Match id that the rounds of the next imported file are offset by, so that they pair up
+    /// only with each other and never with the games of this run.
+    import_id_base: u64,
 }
 
 impl StatsWrapper {
@@ -27,33 +54,130 @@ impl StatsWrapper {
         inner: Box<dyn Tournament>,
         engine_names: Vec<String>,
         engine_options: Vec<cli::EngineOptions>,
-        book_name: Option<String>,
-        sprt: Option<SprtParameters>,
+        book_files: Vec<String>,
+        sprt: Option<(SprtParameters, [usize; 2], cli::SprtDrain)>,
+        gauntlet_seeds: Option<usize>,
+        options: cli::StatsOptions,
     ) -> StatsWrapper {
         assert!(engine_names.len() == engine_options.len());
-        if sprt.is_some() {
-            assert!(engine_names.len() == 2);
+        // Checked by `cli::parse`, with a clearer error.
+        if let Some((_, pair, _)) = sprt {
+            assert!(pair.iter().all(|&i| i < engine_names.len()));
         }
+        let performance = options
+            .performance
+            .then(|| vec![Performance::default(); engine_names.len()]);
+        let terminations = vec![TerminationCounts::default(); engine_names.len()];
+        let clock_desync = vec![ClockDesync::default(); engine_names.len()];
         StatsWrapper {
             inner,
+            engine_versions: vec![None; engine_names.len()],
             engine_names,
             engine_options,
-            book_name,
+            book_files,
             wdl_board: HashMap::new(),
             penta_board: HashMap::new(),
+            bucket_wdl_board: HashMap::new(),
+            bucket_penta_board: HashMap::new(),
             pending_pairing: HashMap::new(),
-            sprt,
+            terminations,
+            pair_terminations: TerminationCounts::default(),
+            performance,
+            clock_desync,
+            sprt: sprt.map(|(sprt, _, _)| sprt),
+            sprt_pair: sprt.map_or([1, 0], |(_, pair, _)| pair),
+            sprt_drain: sprt.map_or(cli::SprtDrain::Complete, |(_, _, drain)| drain),
+            gauntlet_seeds,
+            confidence: options.confidence,
+            elo_report: options.elo,
+            fishtest: options.fishtest,
             match_ticket_count: 0,
             match_complete_count: 0,
             should_terminate: false,
+            progress: options.progress,
+            import_id_base: 1 << 62,
+        }
+    }
+    /// Adds the results of games from an earlier run, matching engines by name, and returns
+    /// how many were added. Games of engines not in this run are skipped.
+    pub fn import_games(&mut self, games: &[pgn::ImportedGame]) -> usize {
+        let engine = |name: &str| self.engine_names.iter().position(|n| n == name);
+        let mut unknown = vec![];
+        let mut imported = vec![];
+        for game in games {
+            match (engine(&game.sente), engine(&game.gote)) {
+                (Some(a), Some(b)) if a != b => imported.push((game.round, (a, b), game.winner)),
+                _ => unknown.extend([&game.sente, &game.gote]),
+            }
+        }
+        unknown.retain(|name| engine(name).is_none());
+        unknown.sort();
+        unknown.dedup();
+        if !unknown.is_empty() {
+            eprintln!(
+                "Warning; Skipping imported games of unknown engines: {}",
+                unknown.into_iter().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+
+        let mut count = 0;
+        let mut mismatched = 0;
+        let mut rounds = HashSet::new();
+        let mut last_round = None;
+        for (round, (a, b), winner) in imported {
+            // A round seen before starts another run, as in files joined together.
+            if !rounds.insert(round) {
+                self.next_import_run(last_round.take());
+                rounds = HashSet::from([round]);
+            }
+            last_round = last_round.max(Some(round));
+
+            let id = self.import_id_base + round;
+            if let Some(&(sibling, _)) = self.pending_pairing.get(&(id ^ 1))
+                && sibling != (a, b)
+                && sibling != (b, a)
+            {
+                mismatched += 1;
+                continue;
+            }
+            self.add_result(id, (a, b), None, winner);
+            count += 1;
+        }
+        self.next_import_run(last_round);
+        if mismatched > 0 {
+            eprintln!(
+                "Warning; Skipping {mismatched} imported games whose paired game was played by other engines"
+            );
         }
+        self.update_sprt();
+        count
+    }
+    /// Adds the results of the games that the inner tournament skipped because a resumed
+    /// `-pgnout` file already records them, as `import_games` does. Their rounds are the ids of
+    /// their tickets in this run, so a pair left half played when the run was interrupted is
+    /// completed by the game still to come.
+    fn resume_games(&mut self, games: &[pgn::ImportedGame]) -> usize {
+        let import_id_base = std::mem::replace(&mut self.import_id_base, 0);
+        let count = self.import_games(games);
+        self.import_id_base = import_id_base;
+        count
+    }
+    /// Moves the match ids of imported games past the rounds of the run just imported.
+    fn next_import_run(&mut self, last_round: Option<u64>) {
+        self.import_id_base += last_round.map_or(0, |round| (round + 2) & !1);
     }
-    fn add_result(&mut self, match_id: u64, (a, b): (usize, usize), result: Option<Color>) {
-        self.add_wdl((a, b), result);
-        self.add_wdl((b, a), result.map(|c| !c));
-        self.add_penta_half(match_id, (a, b), result);
+    fn add_result(
+        &mut self,
+        match_id: u64,
+        (a, b): (usize, usize),
+        bucket: Option<usize>,
+        result: Option<Color>,
+    ) {
+        self.add_wdl((a, b), bucket, result);
+        self.add_wdl((b, a), bucket, result.map(|c| !c));
+        self.add_penta_half(match_id, (a, b), bucket, result);
     }
-    fn add_wdl(&mut self, key: (usize, usize), result: Option<Color>) {
+    fn add_wdl(&mut self, key: (usize, usize), bucket: Option<usize>, result: Option<Color>) {
         let wdl = match result {
             Some(Color::Sente) => Wdl::ONE_WIN,
             None => Wdl::ONE_DRAW,
@@ -62,13 +186,32 @@ impl StatsWrapper {
 
         let old_value = self.wdl_board.get(&key).cloned().unwrap_or_default();
         self.wdl_board.insert(key, old_value + wdl);
+        if let Some(bucket) = bucket {
+            let key = (bucket, key);
+            let old_value = self.bucket_wdl_board.get(&key).cloned().unwrap_or_default();
+            self.bucket_wdl_board.insert(key, old_value + wdl);
+        }
     }
-    fn add_penta_half(&mut self, match_id: u64, (a, b): (usize, usize), result1: Option<Color>) {
+    /// The two games of a pair share an opening, so they are always in the same bucket.
+    fn add_penta_half(
+        &mut self,
+        match_id: u64,
+        (a, b): (usize, usize),
+        bucket: Option<usize>,
+        result1: Option<Color>,
+    ) {
         let sibling = match_id ^ 1;
-        if let Some(((b2, a2), result2)) = self.pending_pairing.remove(&sibling) {
-            assert!(a == a2 && b == b2);
+        if let Some((sibling_engines, result2)) = self.pending_pairing.remove(&sibling) {
+            // Express the sibling's result from the point of view of `a` as sente. Siblings are
+            // usually colour-swapped, but not with `colorpolicy=engineA-first`.
+            let result2 = if sibling_engines == (b, a) {
+                result2.map(|c| !c)
+            } else {
+                assert!(sibling_engines == (a, b));
+                result2
+            };
 
-            let penta = match (result1, result2.map(|c| !c)) {
+            let penta = match (result1, result2) {
                 (Some(Color::Sente), Some(Color::Sente)) => Penta::ONE_WW,
                 (Some(Color::Sente), None) => Penta::ONE_WD,
                 (None, Some(Color::Sente)) => Penta::ONE_WD,
@@ -83,6 +226,15 @@ impl StatsWrapper {
             let mut insert = |key: (usize, usize), penta: Penta| {
                 let old_value = self.penta_board.get(&key).cloned().unwrap_or_default();
                 self.penta_board.insert(key, old_value + penta);
+                if let Some(bucket) = bucket {
+                    let key = (bucket, key);
+                    let old_value = self
+                        .bucket_penta_board
+                        .get(&key)
+                        .cloned()
+                        .unwrap_or_default();
+                    self.bucket_penta_board.insert(key, old_value + penta);
+                }
             };
 
             insert((a, b), penta);
@@ -103,18 +255,81 @@ impl StatsWrapper {
             .map(|k| self.penta_board.get(&k).cloned().unwrap_or_default())
             .sum()
     }
+    fn add_termination(&mut self, engines: [usize; 2], outcome: GameOutcome) {
+        for engine in engines {
+            self.terminations[engine].add(engine, engines, outcome);
+        }
+        let [a, b] = self.sprt_pair;
+        if engines == [a, b] || engines == [b, a] {
+            self.pair_terminations.add(a, engines, outcome);
+        }
+    }
+    /// Number of game pairs with both games completed, over all pairings.
+    fn completed_pair_count(&self) -> u64 {
+        // Every pair is recorded from the point of view of both engines.
+        self.penta_board
+            .values()
+            .map(Penta::pair_count)
+            .sum::<u64>()
+            / 2
+    }
+    fn pair_wdl(&self, [a, b]: [usize; 2]) -> Wdl {
+        self.wdl_board.get(&(a, b)).cloned().unwrap_or_default()
+    }
+    fn pair_penta(&self, [a, b]: [usize; 2]) -> Penta {
+        self.penta_board.get(&(a, b)).cloned().unwrap_or_default()
+    }
+    fn add_moves(&mut self, engines: [usize; 2], moves: &[engine::MoveRecord]) {
+        for record in moves {
+            let Some(stm) = record.stm else {
+                continue;
+            };
+            let engine = engines[stm.to_index()];
+            if let Some(performance) = &mut self.performance {
+                performance[engine].add(record);
+            }
+            if let Some(mean) = self.clock_desync[engine].add(record) {
+                progress::suspend(|| {
+                    eprintln!(
+                        "Warning; {}'s reported search times differ from the measured move times by {:.0} ms on average; its clock may be out of sync",
+                        self.engine_names[engine],
+                        mean.as_secs_f64() * 1000.0
+                    )
+                });
+            }
+        }
+    }
+    pub fn set_engine_versions(&mut self, versions: Vec<Option<String>>) {
+        assert!(versions.len() == self.engine_names.len());
+        self.engine_versions = versions;
+    }
     pub fn print_stats(&self) {
+        if let Some(line) = format_versions(&self.engine_names, &self.engine_versions) {
+            println!("{line}");
+        }
         if self.engine_names.len() == 2 {
             self.print_head_to_head()
         } else {
-            self.print_table()
+            println!(
+                "{}",
+                format_pair_count(
+                    self.completed_pair_count(),
+                    self.expected_maximum_match_count()
+                )
+            );
+            self.print_table();
+            self.print_matrix();
+            self.print_terminations();
+            self.print_gauntlet();
+            self.print_sprt_llr();
+            self.print_buckets();
         }
+        self.print_fishtest();
+        self.print_performance();
     }
     pub fn print_head_to_head(&self) {
         let wdl = self.all_wdl_for(1);
         let penta = self.all_penta_for(1);
-        let (lelo, lelo_diff) = penta.logistic_elo();
-        let (nelo, nelo_diff) = penta.normalized_elo();
 
         let tc = compare(|i| self.engine_options[i].time_control.to_string());
         let threads = compare(|i| {
@@ -131,50 +346,138 @@ impl StatsWrapper {
                 .unwrap_or("null")
                 .to_string()
         });
-        let book = self
-            .book_name
-            .as_ref()
-            .and_then(|p| Path::new(p).file_name())
-            .map(|f| f.to_string_lossy().to_string())
-            .unwrap_or("null".to_string());
+        let book = if self.book_files.is_empty() {
+            String::from("null")
+        } else {
+            self.book_files
+                .iter()
+                .filter_map(|p| Path::new(p).file_name())
+                .map(|f| f.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("+")
+        };
 
         println!(
             "Results of {} vs {} ({tc}, {threads}, {hash}, {book}):",
             self.engine_names[0], self.engine_names[1]
         );
-        println!("Elo: {lelo:.2} +/- {lelo_diff:.2}, nElo: {nelo:.2} +/- {nelo_diff:.2}");
+        if let Some(line) = format_elo_line(penta, self.confidence, self.elo_report) {
+            println!("{line}");
+        }
         println!(
-            "Games: {}, Wins: {}, Draws: {}, Losses: {} (Score: {:.2}%)",
+            "Games: {}, Wins: {}, Draws: {}, Losses: {} ({})",
             wdl.game_count(),
             wdl.w,
             wdl.d,
             wdl.l,
-            wdl.score() * 100.0
+            format_score(wdl, penta)
         );
         println!(
-            "Pntml(0-2): {penta}, DD/WL Ratio: {:.2}",
-            penta.dd_wl_ratio()
+            "{}, Pntml(0-2): {penta}, DD/WL Ratio: {}",
+            format_pair_count(
+                self.completed_pair_count(),
+                self.expected_maximum_match_count()
+            ),
+            format_dd_wl_ratio(penta)
         );
+        self.print_sprt_llr();
+        self.print_buckets();
+        self.print_terminations();
+    }
+    fn print_terminations(&self) {
+        for line in format_terminations(&self.engine_names, &self.terminations) {
+            println!("{line}");
+        }
+    }
+    fn print_sprt_llr(&self) {
+        let wdl = self.pair_wdl(self.sprt_pair);
+        let penta = self.pair_penta(self.sprt_pair);
         if let Some(sprt) = self.sprt
-            && penta.pair_count() > 0
+            && sprt.sample_count(wdl, penta) > 0
         {
-            let llr = sprt.llr(penta);
-            let (llr_lower_bound, llr_upper_bound) = sprt.llr_bounds();
-            let (nelo_lower_bound, nelo_upper_bound) = sprt.nelo_bounds();
-            println!(
-                "LLR: {llr:.2} ({llr_lower_bound:.2}, {llr_upper_bound:.2}) [{nelo_lower_bound:.2}, {nelo_upper_bound:.2}]"
-            );
+            if self.engine_names.len() > 2 {
+                let [a, b] = self.sprt_pair;
+                println!(
+                    "SPRT of {} vs {}:",
+                    self.engine_names[a], self.engine_names[b]
+                );
+            }
+            println!("{}", format_sprt_llr(&sprt, wdl, penta));
+        }
+    }
+    /// Prints the results of the SPRT pair in each bucket, to check that they agree.
+    fn print_buckets(&self) {
+        let [a, b] = self.sprt_pair;
+        let mut buckets: Vec<usize> = self
+            .bucket_wdl_board
+            .keys()
+            .filter(|(_, key)| *key == (a, b))
+            .map(|&(bucket, _)| bucket)
+            .collect();
+        if buckets.is_empty() {
+            return;
+        }
+        buckets.sort();
+        let rows: Vec<(String, Wdl, Penta)> = buckets
+            .into_iter()
+            .map(|bucket| {
+                let name = self
+                    .book_files
+                    .get(bucket)
+                    .and_then(|file| Path::new(file).file_name())
+                    .map_or(format!("#{bucket}"), |file| {
+                        file.to_string_lossy().to_string()
+                    });
+                let key = (bucket, (a, b));
+                (
+                    name,
+                    self.bucket_wdl_board.get(&key).cloned().unwrap_or_default(),
+                    self.bucket_penta_board
+                        .get(&key)
+                        .cloned()
+                        .unwrap_or_default(),
+                )
+            })
+            .collect();
+        let title = format!(
+            "Results of {} vs {} by book file:",
+            self.engine_names[a], self.engine_names[b]
+        );
+        println!();
+        for line in
+            format_bucket_breakdown(title, &rows, self.sprt, self.confidence, self.elo_report)
+        {
+            println!("{line}");
+        }
+    }
+    fn print_gauntlet(&self) {
+        let Some(seeds) = self.gauntlet_seeds else {
+            return;
+        };
+        for seed in 0..seeds {
+            println!();
+            for line in format_pair_breakdown(
+                &self.engine_names,
+                seed,
+                seeds..self.engine_names.len(),
+                &self.wdl_board,
+                &self.penta_board,
+                self.confidence,
+                self.elo_report,
+            ) {
+                println!("{line}");
+            }
         }
     }
     pub fn print_table(&self) {
-        let mut table = Vec::<(&str, f64, Wdl, Penta)>::new();
+        let mut table = Vec::<TableRow>::new();
         let mut max_name_len = 20;
         let mut max_penta_len = 2;
 
         for (i, name) in self.engine_names.iter().enumerate() {
             let wdl = self.all_wdl_for(i);
             let penta = self.all_penta_for(i);
-            let (lelo, _) = penta.logistic_elo();
+            let lelo = penta.logistic_elo(self.confidence).map(|(lelo, _)| lelo);
 
             table.push((name, lelo, wdl, penta));
 
@@ -182,32 +485,136 @@ impl StatsWrapper {
             max_penta_len = max_penta_len.max(format!("{penta}").len());
         }
 
-        table.sort_by(|x, y| {
-            if x.1 == y.1 {
-                Ordering::Equal
-            } else if x.1 < y.1 || x.1.is_nan() {
-                Ordering::Greater
-            } else {
-                Ordering::Less
-            }
-        });
+        let anchors: Vec<Option<f64>> = self
+            .engine_options
+            .iter()
+            .map(|options| options.anchor_elo)
+            .collect();
+        let relative: Vec<Option<f64>> = table.iter().map(|row| row.1).collect();
+        let offset = anchor_offset(&relative, &anchors);
+
+        table.sort_by(rank_order);
 
         println!(
-            "{:>4} {:<max_name_len$} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}  {:>max_penta_len$}",
-            "Rank", "Name", "Elo", "+/-", "nElo", "+/-", "Games", "Score", "Penta"
+            "{:>4} {:<max_name_len$}{}{} {:>8} {:>8}  {:>max_penta_len$}",
+            "Rank",
+            "Name",
+            format_elo_headers(self.confidence, self.elo_report),
+            offset.map_or(String::new(), |_| format!(" {:>8}", "Rating")),
+            "Games",
+            "Score",
+            "Penta"
         );
         for (i, (name, lelo, wdl, penta)) in table.iter().enumerate() {
             let rank = i + 1;
-            let (_, lelo_diff) = penta.logistic_elo();
-            let (nelo, nelo_diff) = penta.normalized_elo();
+            let mut elo = format_elo_cells(*penta, self.confidence, self.elo_report);
+            if let Some(offset) = offset {
+                elo += &match lelo {
+                    Some(lelo) => format!(" {:>8}", format!("{:.2}", lelo + offset)),
+                    None => format!(" {:>8}", "-"),
+                };
+            }
             let game_count = wdl.game_count();
             let score = wdl.score() * 100.0;
             let penta = format!("{penta}");
             println!(
-                "{rank:>4} {name:<max_name_len$} {lelo:>8.2} {lelo_diff:>8.2} {nelo:>8.2} {nelo_diff:>8.2} {game_count:>8} {score:>7.2}%  {penta:>max_penta_len$}"
+                "{rank:>4} {name:<max_name_len$}{elo} {game_count:>8} {score:>7.2}%  {penta:>max_penta_len$}"
             );
         }
     }
+    pub fn print_matrix(&self) {
+        println!();
+        for line in format_matrix(&self.engine_names, &self.wdl_board) {
+            println!("{line}");
+        }
+    }
+    fn print_fishtest(&self) {
+        if !self.fishtest {
+            return;
+        }
+        let [a, b] = self.sprt_pair;
+        println!();
+        println!(
+            "Fishtest results of {} vs {}:",
+            self.engine_names[a], self.engine_names[b]
+        );
+        for line in format_fishtest(
+            self.pair_wdl(self.sprt_pair),
+            self.pair_penta(self.sprt_pair),
+            self.pair_terminations,
+        ) {
+            println!("{line}");
+        }
+    }
+    pub fn print_performance(&self) {
+        let Some(performance) = &self.performance else {
+            return;
+        };
+        println!();
+        for line in format_performance(&self.engine_names, performance) {
+            println!("{line}");
+        }
+    }
+    pub fn print_sprt_estimate(&self) {
+        let Some(sprt) = self.sprt else {
+            return;
+        };
+        let format_games = |games: f64| {
+            if games.is_finite() {
+                format!("~{games}")
+            } else {
+                String::from("never")
+            }
+        };
+        let (upper, lower) = sprt.expected_games(
+            self.pair_wdl(self.sprt_pair),
+            self.pair_penta(self.sprt_pair),
+        );
+        if upper.is_finite() || lower.is_finite() {
+            println!(
+                "SPRT estimate: {} more games to accept H1, {} more games to accept H0",
+                format_games(upper),
+                format_games(lower)
+            );
+        }
+    }
+    pub fn print_sprt_verdict(&self) {
+        let Some(sprt) = self.sprt else {
+            return;
+        };
+        let wdl = self.pair_wdl(self.sprt_pair);
+        let game_count = wdl.game_count();
+        match sprt.verdict(wdl, self.pair_penta(self.sprt_pair)) {
+            SprtVerdict::AcceptH1 => {
+                println!("SPRT: H1 accepted: elo gain likely (after {game_count} games)")
+            }
+            SprtVerdict::AcceptH0 => {
+                println!("SPRT: H0 accepted: no improvement (after {game_count} games)")
+            }
+            SprtVerdict::Continue => {
+                println!(
+                    "SPRT: inconclusive, stopped before reaching a bound (after {game_count} games)"
+                )
+            }
+        }
+    }
+    pub fn show_progress(&self) {
+        let head_to_head = (self.engine_names.len() == 2 || self.sprt.is_some()).then(|| {
+            (
+                self.pair_wdl(self.sprt_pair),
+                self.pair_penta(self.sprt_pair),
+            )
+        });
+        progress::show(format_progress(
+            self.match_complete_count,
+            self.completed_pair_count(),
+            self.expected_maximum_match_count(),
+            head_to_head,
+            self.sprt,
+            self.confidence,
+            self.elo_report,
+        ));
+    }
     fn next(&mut self) {
         self.match_ticket_count += 1;
     }
@@ -216,15 +623,23 @@ impl StatsWrapper {
     }
     fn match_complete(&mut self) {
         self.match_complete_count += 1;
+        self.update_sprt();
+    }
+    fn update_sprt(&mut self) {
         if let Some(sprt) = self.sprt
             && !self.should_terminate
         {
-            let penta = self.all_penta_for(1);
-            self.should_terminate = sprt.should_terminate(penta);
+            let wdl = self.pair_wdl(self.sprt_pair);
+            let penta = self.pair_penta(self.sprt_pair);
+            self.should_terminate = sprt.should_terminate(wdl, penta);
         }
     }
+    /// Once the SPRT has reached a verdict, the tournament stops when the games still being
+    /// played have finished, or straight away if they are to be cancelled.
     fn match_completete_should_terminate(&self) -> bool {
-        self.should_terminate && self.match_ticket_count == self.match_complete_count
+        self.should_terminate
+            && (self.sprt_drain == cli::SprtDrain::Cancel
+                || self.match_ticket_count == self.match_complete_count)
     }
 }
 
@@ -233,8 +648,16 @@ impl Tournament for StatsWrapper {
         if self.next_should_terminate() {
             None
         } else {
+            let ticket = self.inner.as_mut().next();
+            let resumed_games = self.inner.take_resumed_games();
+            if !resumed_games.is_empty() {
+                self.resume_games(&resumed_games);
+                if self.next_should_terminate() {
+                    return None;
+                }
+            }
             self.next();
-            self.inner.as_mut().next()
+            ticket
         }
     }
     fn match_started(&mut self, ticket: MatchTicket) {
@@ -242,8 +665,18 @@ impl Tournament for StatsWrapper {
     }
     fn match_complete(&mut self, result: MatchResult) -> TournamentState {
         let e = &result.ticket.engines;
-        self.add_result(result.ticket.id, (e[0], e[1]), result.outcome.winner());
+        self.add_result(
+            result.ticket.id,
+            (e[0], e[1]),
+            result.ticket.bucket,
+            result.outcome.winner(),
+        );
+        self.add_termination(result.ticket.engines, result.outcome);
+        self.add_moves(result.ticket.engines, &result.moves);
         self.match_complete();
+        if self.progress {
+            self.show_progress();
+        }
         let state = self.inner.as_mut().match_complete(result);
         if self.match_completete_should_terminate() {
             TournamentState::Stop
@@ -253,15 +686,569 @@ impl Tournament for StatsWrapper {
     }
     fn print_interval_report(&self) {
         self.print_stats();
+        self.print_sprt_estimate();
         self.inner.print_interval_report()
     }
     fn tournament_complete(&self) {
         self.print_stats();
+        self.print_sprt_verdict();
         self.inner.tournament_complete()
     }
     fn expected_maximum_match_count(&self) -> Option<u64> {
         self.inner.as_ref().expected_maximum_match_count()
     }
+    fn sprt_verdict(&self) -> Option<SprtVerdict> {
+        let sprt = self.sprt?;
+        Some(sprt.verdict(
+            self.pair_wdl(self.sprt_pair),
+            self.pair_penta(self.sprt_pair),
+        ))
+    }
+    fn sprt_expected_match_count(&self) -> Option<u64> {
+        let (upper, lower) = self.sprt?.expected_games(
+            self.pair_wdl(self.sprt_pair),
+            self.pair_penta(self.sprt_pair),
+        );
+        let games = upper.min(lower);
+        games.is_finite().then_some(games as u64)
+    }
+    fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame> {
+        // Already added to the stats by `next`.
+        vec![]
+    }
+}
+
+/// How many of an engine's games ended other than by normal play.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct TerminationCounts {
+    /// Losses on time, including engines that stopped responding.
+    time_forfeits: u64,
+    /// Losses by illegal move, including illegal perpetual checks.
+    illegal_moves: u64,
+    /// Losses by the engine disconnecting or crashing.
+    disconnections: u64,
+    /// Games won, lost or drawn by adjudication.
+    adjudications: u64,
+}
+
+impl TerminationCounts {
+    /// Counts how a game between `engines` ended for `engine`, one of them.
+    fn add(&mut self, engine: usize, engines: [usize; 2], outcome: GameOutcome) {
+        let lost = outcome
+            .winner()
+            .is_some_and(|winner| engines[(!winner).to_index()] == engine);
+        match outcome.termination() {
+            Termination::TimeForfeit if lost => self.time_forfeits += 1,
+            Termination::IllegalMove if lost => self.illegal_moves += 1,
+            Termination::Disconnection if lost => self.disconnections += 1,
+            Termination::Adjudication => self.adjudications += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Mean of the values added to it, ignoring zeros, which engines report for info fields they
+/// don't send.
+#[derive(Debug, Default, Clone, Copy)]
+struct NonZeroMean {
+    total: f64,
+    count: u64,
+}
+
+impl NonZeroMean {
+    fn add(&mut self, value: f64) {
+        if value != 0.0 {
+            self.total += value;
+            self.count += 1;
+        }
+    }
+
+    fn get(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total / self.count as f64)
+    }
+}
+
+/// Per-move search statistics of one engine, accumulated over all of its moves.
+#[derive(Debug, Default, Clone, Copy)]
+struct Performance {
+    move_count: u64,
+    depth: NonZeroMean,
+    seldepth: NonZeroMean,
+    nodes: NonZeroMean,
+    nps: NonZeroMean,
+    time: Duration,
+}
+
+impl Performance {
+    fn add(&mut self, record: &engine::MoveRecord) {
+        self.move_count += 1;
+        self.depth.add(record.depth as f64);
+        self.seldepth.add(record.seldepth as f64);
+        self.nodes.add(record.nodes as f64);
+        self.nps.add(record.nps as f64);
+        self.time += record.measured_time;
+    }
+
+    fn mean_time(&self) -> Option<Duration> {
+        (self.move_count > 0).then(|| self.time / self.move_count as u32)
+    }
+}
+
+/// Moves an engine must report its search time on before its clock is compared with ours.
+const CLOCK_DESYNC_MIN_MOVES: u64 = 10;
+
+/// Mean difference between the measured and the engine-reported move time above which the
+/// engine's clock is considered out of sync.
+const CLOCK_DESYNC_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Running comparison of an engine's reported search time (`info time`) against the time we
+/// measured for its moves.
+#[derive(Debug, Default, Clone, Copy)]
+struct ClockDesync {
+    move_count: u64,
+    total_difference: Duration,
+    warned: bool,
+}
+
+impl ClockDesync {
+    /// Adds a move, returning the mean difference when it first exceeds the threshold.
+    fn add(&mut self, record: &engine::MoveRecord) -> Option<Duration> {
+        // Moves without an `info time` can't be compared.
+        if record.engine_time == 0 {
+            return None;
+        }
+        let engine_time = Duration::from_millis(record.engine_time);
+        self.move_count += 1;
+        self.total_difference += record.measured_time.abs_diff(engine_time);
+
+        let mean = self.mean_difference()?;
+        if self.warned || self.move_count < CLOCK_DESYNC_MIN_MOVES || mean <= CLOCK_DESYNC_THRESHOLD
+        {
+            return None;
+        }
+        self.warned = true;
+        Some(mean)
+    }
+
+    fn mean_difference(&self) -> Option<Duration> {
+        (self.move_count > 0).then(|| self.total_difference / self.move_count as u32)
+    }
+}
+
+/// Pairs needed before the DD/WL ratio says anything about the openings.
+const DD_WL_MIN_PAIRS: u64 = 20;
+
+/// DD/WL ratio above which pairs mostly end drawn both ways, which wastes games.
+const DD_WL_DRAWISH_RATIO: f64 = 5.0;
+
+/// A row of the standings: name, logistic Elo, and results.
+type TableRow<'a> = (&'a str, Option<f64>, Wdl, Penta);
+
+/// Orders the standings by Elo, highest first, with engines that have no Elo yet last, then by
+/// games played and by name. This is a total order, so the ranking never depends on the order
+/// of the engines or the sort.
+fn rank_order(x: &TableRow, y: &TableRow) -> Ordering {
+    let elo = |row: &TableRow| row.1.filter(|elo| !elo.is_nan());
+    match (elo(x), elo(y)) {
+        (Some(x), Some(y)) => y.total_cmp(&x),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| y.2.game_count().cmp(&x.2.game_count()))
+    .then_with(|| x.0.cmp(y.0))
+}
+
+/// Formats the score with its standard error and likelihood of superiority, all taken from the
+/// completed pairs once there are any, so that a game still waiting for its pair is left out.
+fn format_score(wdl: Wdl, penta: Penta) -> String {
+    match stats::score_uncertainty(wdl, penta) {
+        Some((score, standard_error, los)) => format!(
+            "Score: {:.2}% +/- {:.2}%, LOS: {:.2}%",
+            score * 100.0,
+            standard_error * 100.0,
+            los * 100.0
+        ),
+        None => format!("Score: {:.2}%", wdl.score() * 100.0),
+    }
+}
+
+/// Formats the ratio of double draws to win-loss pairs, with a hint when it is high.
+fn format_dd_wl_ratio(penta: Penta) -> String {
+    match penta.dd_wl_ratio() {
+        Some(ratio) if penta.pair_count() >= DD_WL_MIN_PAIRS => {
+            if ratio > DD_WL_DRAWISH_RATIO {
+                format!(
+                    "{ratio:.2} (high; the openings may be too drawish or the time control too long)"
+                )
+            } else {
+                format!("{ratio:.2}")
+            }
+        }
+        _ => String::from("n/a"),
+    }
+}
+
+/// Formats a footnote listing the abnormal game endings of each engine that had any.
+fn format_terminations(engine_names: &[String], terminations: &[TerminationCounts]) -> Vec<String> {
+    engine_names
+        .iter()
+        .zip(terminations)
+        .filter(|(_, counts)| **counts != TerminationCounts::default())
+        .map(|(name, counts)| {
+            format!(
+                "{name}: time forfeits: {}, illegal moves: {}, disconnections: {}, adjudicated: {}",
+                counts.time_forfeits,
+                counts.illegal_moves,
+                counts.disconnections,
+                counts.adjudications
+            )
+        })
+        .collect()
+}
+
+/// Formats the engine performance table, with per-move averages for each engine.
+fn format_performance(engine_names: &[String], performance: &[Performance]) -> Vec<String> {
+    let name_width = engine_names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(20);
+    let format_mean = |mean: Option<f64>, precision: usize| match mean {
+        Some(mean) => format!("{mean:.precision$}"),
+        None => String::from("-"),
+    };
+
+    let mut lines = vec![format!(
+        "{:<name_width$} {:>8} {:>8} {:>8} {:>12} {:>12} {:>10}",
+        "Engine", "Moves", "Depth", "SelDepth", "Nodes", "NPS", "Time (ms)"
+    )];
+    for (name, performance) in engine_names.iter().zip(performance) {
+        lines.push(format!(
+            "{name:<name_width$} {:>8} {:>8} {:>8} {:>12} {:>12} {:>10}",
+            performance.move_count,
+            format_mean(performance.depth.get(), 2),
+            format_mean(performance.seldepth.get(), 2),
+            format_mean(performance.nodes.get(), 0),
+            format_mean(performance.nps.get(), 0),
+            match performance.mean_time() {
+                Some(time) => time.as_millis().to_string(),
+                None => String::from("-"),
+            },
+        ));
+    }
+    lines
+}
+
+/// Formats the Elo estimates selected by `elo_report` for the head-to-head results, or None
+/// before the first pair has completed.
+fn format_elo_line(penta: Penta, confidence: f64, elo_report: cli::EloReport) -> Option<String> {
+    if penta.pair_count() == 0 {
+        return None;
+    }
+    let mut parts = vec![];
+    if elo_report.logistic() {
+        let (elo, diff) = penta.logistic_elo(confidence)?;
+        parts.push(format!("Elo: {elo:.2} +/- {diff:.2}"));
+    }
+    if elo_report.normalized() {
+        parts.push(match penta.normalized_elo(confidence) {
+            Some((nelo, diff)) => format!("nElo: {nelo:.2} +/- {diff:.2}"),
+            None => String::from("nElo: n/a"),
+        });
+    }
+    Some(format!(
+        "{} ({} CI)",
+        parts.join(", "),
+        format_confidence(confidence)
+    ))
+}
+
+/// Formats the headers of the Elo table columns selected by `elo_report`, each preceded by a
+/// space.
+fn format_elo_headers(confidence: f64, elo_report: cli::EloReport) -> String {
+    let error_bar = format!("+/-{}", format_confidence(confidence));
+    let mut headers = String::new();
+    if elo_report.logistic() {
+        headers += &format!(" {:>8} {error_bar:>8}", "Elo");
+    }
+    if elo_report.normalized() {
+        headers += &format!(" {:>8} {error_bar:>8}", "nElo");
+    }
+    headers
+}
+
+/// Works out the shift from relative to absolute Elo given the declared Elo of the anchor engines,
+/// as the least-squares fit over the anchors that have a relative Elo: the mean of their
+/// differences. None if there is no such anchor.
+fn anchor_offset(relative: &[Option<f64>], anchors: &[Option<f64>]) -> Option<f64> {
+    let differences: Vec<f64> = relative
+        .iter()
+        .zip(anchors)
+        .filter_map(|(&relative, &anchor)| Some(anchor? - relative?))
+        .collect();
+    (!differences.is_empty()).then(|| differences.iter().sum::<f64>() / differences.len() as f64)
+}
+
+/// Formats the Elo table cells selected by `elo_report`, matching `format_elo_headers`.
+fn format_elo_cells(penta: Penta, confidence: f64, elo_report: cli::EloReport) -> String {
+    let format_elo = |elo: Option<(f64, f64)>| match elo {
+        Some((elo, diff)) => format!(" {:>8} {:>8}", format!("{elo:.2}"), format!("{diff:.2}")),
+        None => format!(" {:>8} {:>8}", "-", "-"),
+    };
+    let mut cells = String::new();
+    if elo_report.logistic() {
+        cells += &format_elo(penta.logistic_elo(confidence));
+    }
+    if elo_report.normalized() {
+        cells += &format_elo(penta.normalized_elo(confidence));
+    }
+    cells
+}
+
+/// Formats the results of engine `seed` against each of `opponents`, from the point of view of
+/// `seed`.
+fn format_pair_breakdown(
+    engine_names: &[String],
+    seed: usize,
+    opponents: impl Iterator<Item = usize> + Clone,
+    wdl_board: &HashMap<(usize, usize), Wdl>,
+    penta_board: &HashMap<(usize, usize), Penta>,
+    confidence: f64,
+    elo_report: cli::EloReport,
+) -> Vec<String> {
+    let name_width = opponents
+        .clone()
+        .map(|i| engine_names[i].chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(20);
+    let pentas: Vec<(usize, Penta)> = opponents
+        .map(|i| (i, penta_board.get(&(seed, i)).cloned().unwrap_or_default()))
+        .collect();
+    let penta_width = pentas
+        .iter()
+        .map(|(_, penta)| format!("{penta}").len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let mut lines = vec![
+        format!("Results of {} by opponent:", engine_names[seed]),
+        format!(
+            "{:<name_width$}{} {:>8} {:>8}  {:>penta_width$}",
+            "Opponent",
+            format_elo_headers(confidence, elo_report),
+            "Games",
+            "Score",
+            "Penta"
+        ),
+    ];
+    for (i, penta) in pentas {
+        let wdl = wdl_board.get(&(seed, i)).cloned().unwrap_or_default();
+        lines.push(format!(
+            "{:<name_width$}{} {:>8} {:>7.2}%  {:>penta_width$}",
+            engine_names[i],
+            format_elo_cells(penta, confidence, elo_report),
+            wdl.game_count(),
+            wdl.score() * 100.0,
+            format!("{penta}"),
+        ));
+    }
+    lines
+}
+
+/// Formats the results of an engine in each bucket, with the LLR of each if an SPRT is being
+/// run.
+fn format_bucket_breakdown(
+    title: String,
+    rows: &[(String, Wdl, Penta)],
+    sprt: Option<SprtParameters>,
+    confidence: f64,
+    elo_report: cli::EloReport,
+) -> Vec<String> {
+    let name_width = rows
+        .iter()
+        .map(|(name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(20);
+    let penta_width = rows
+        .iter()
+        .map(|(_, _, penta)| format!("{penta}").len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let mut lines = vec![
+        title,
+        format!(
+            "{:<name_width$}{} {:>8} {:>8}  {:>penta_width$}{}",
+            "Book",
+            format_elo_headers(confidence, elo_report),
+            "Games",
+            "Score",
+            "Penta",
+            sprt.map_or(String::new(), |_| format!(" {:>8}", "LLR"))
+        ),
+    ];
+    for (name, wdl, penta) in rows {
+        lines.push(format!(
+            "{:<name_width$}{} {:>8} {:>7.2}%  {:>penta_width$}{}",
+            name,
+            format_elo_cells(*penta, confidence, elo_report),
+            wdl.game_count(),
+            wdl.score() * 100.0,
+            format!("{penta}"),
+            sprt.map_or(String::new(), |sprt| format!(
+                " {:>8}",
+                format!("{:.2}", sprt.llr(*wdl, *penta))
+            ))
+        ));
+    }
+    lines
+}
+
+/// Formats results as fishtest reports them: the pentanomial in fishtest's order with its
+/// total, then the `stats` object fishtest keeps for a run. `terminations` are those of the
+/// tested engine, whose time losses and disconnections fishtest counts separately.
+fn format_fishtest(wdl: Wdl, penta: Penta, terminations: TerminationCounts) -> [String; 2] {
+    let pentanomial = [penta.ll, penta.dl, penta.dd + penta.wl, penta.wd, penta.ww];
+    let pairs: u64 = pentanomial.iter().sum();
+    let pentanomial = pentanomial.map(|count| count.to_string()).join(", ");
+    [
+        format!(
+            "Pentanomial [LL, LD, DD+WL, WD, WW]: [{pentanomial}], Total: {pairs} pairs, {} games",
+            pairs * 2
+        ),
+        format!(
+            "{{\"wins\": {}, \"losses\": {}, \"draws\": {}, \"crashes\": {}, \"time_losses\": {}, \"pentanomial\": [{pentanomial}]}}",
+            wdl.w, wdl.l, wdl.d, terminations.disconnections, terminations.time_forfeits
+        ),
+    ]
+}
+
+/// Formats the N×N results matrix. The cell in row `i` and column `j` is the W-D-L of engine `i`
+/// against engine `j`. Engine names are truncated to fit the columns.
+fn format_matrix(engine_names: &[String], wdl_board: &HashMap<(usize, usize), Wdl>) -> Vec<String> {
+    let count = engine_names.len();
+    let cell = |i: usize, j: usize| match wdl_board.get(&(i, j)) {
+        _ if i == j => String::from("-"),
+        Some(wdl) => format!("{}-{}-{}", wdl.w, wdl.d, wdl.l),
+        None => String::from("0-0-0"),
+    };
+
+    let max_cell_len = iproduct!(0..count, 0..count)
+        .map(|(i, j)| cell(i, j).chars().count())
+        .max()
+        .unwrap_or(0);
+    let column_width = max_cell_len.max(8);
+    let label_width = engine_names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(20);
+    let truncate = |name: &str, width: usize| name.chars().take(width).collect::<String>();
+
+    let mut lines = vec![];
+    let mut header = format!("{:<label_width$}", "");
+    for name in engine_names {
+        header += &format!(" {:>column_width$}", truncate(name, column_width));
+    }
+    lines.push(header);
+    for (i, name) in engine_names.iter().enumerate() {
+        let mut line = format!("{:<label_width$}", truncate(name, label_width));
+        for j in 0..count {
+            line += &format!(" {:>column_width$}", cell(i, j));
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Lists the engines whose `id name` tells more than their name, typically a version or commit.
+fn format_versions(engine_names: &[String], engine_versions: &[Option<String>]) -> Option<String> {
+    let versions: Vec<String> = engine_names
+        .iter()
+        .zip(engine_versions)
+        .filter_map(|(name, version)| match version {
+            Some(version) if version != name => Some(format!("{name} = {version}")),
+            _ => None,
+        })
+        .collect();
+    (!versions.is_empty()).then(|| format!("Versions: {}", versions.join(", ")))
+}
+
+/// Formats the LLR and its bounds, followed by the hypotheses with the units they are given in,
+/// which depend on the SPRT model, and once pairs have completed, the hypotheses converted to
+/// the other unit at the observed variance.
+fn format_sprt_llr(sprt: &SprtParameters, wdl: Wdl, penta: Penta) -> String {
+    let llr = sprt.llr(wdl, penta);
+    let (llr_lower_bound, llr_upper_bound) = sprt.llr_bounds();
+    let (elo_lower_bound, elo_upper_bound) = sprt.elo_bounds();
+    let (alpha, beta) = sprt.significance();
+    let label = sprt.model().elo_label();
+    let (convert, other_label): (fn(&Penta, f64) -> Option<f64>, _) = match sprt.model() {
+        SprtModel::Normalized | SprtModel::Trinomial => (Penta::nelo_to_elo, "Elo"),
+        SprtModel::Logistic => (Penta::elo_to_nelo, "nElo"),
+    };
+    let converted = convert(&penta, elo_lower_bound)
+        .zip(convert(&penta, elo_upper_bound))
+        .map(|(lower, upper)| format!(" ~ [{other_label} {lower:.2}, {upper:.2}]"))
+        .unwrap_or_default();
+    format!(
+        "LLR: {llr:.2} ({llr_lower_bound:.2}, {llr_upper_bound:.2}) [{label} {elo_lower_bound:.2}, {elo_upper_bound:.2}]{converted} (alpha {alpha}, beta {beta})"
+    )
+}
+
+/// Formats the live progress line from a snapshot of the stats.
+fn format_progress(
+    completed: u64,
+    completed_pairs: u64,
+    expected: Option<u64>,
+    head_to_head: Option<(Wdl, Penta)>,
+    sprt: Option<SprtParameters>,
+    confidence: f64,
+    elo_report: cli::EloReport,
+) -> String {
+    let mut line = match expected {
+        Some(expected) => format!("Games: {completed}/{expected}"),
+        None => format!("Games: {completed}"),
+    };
+    line += &format!(", {}", format_pair_count(completed_pairs, expected));
+    if let Some((wdl, penta)) = head_to_head {
+        line += &format!(", W: {} D: {} L: {}", wdl.w, wdl.d, wdl.l);
+        if !elo_report.logistic() {
+            if let Some((nelo, diff)) = penta.normalized_elo(confidence) {
+                line += &format!(", nElo: {nelo:.2} +/- {diff:.2}");
+            }
+        } else if let Some((elo, diff)) = penta.logistic_elo(confidence) {
+            line += &format!(", Elo: {elo:.2} +/- {diff:.2}");
+        }
+        if let Some(sprt) = sprt
+            && sprt.sample_count(wdl, penta) > 0
+        {
+            let (lower, upper) = sprt.llr_bounds();
+            line += &format!(
+                ", LLR: {:.2} ({lower:.2}, {upper:.2})",
+                sprt.llr(wdl, penta)
+            );
+        }
+    }
+    line
+}
+
+/// Formats the number of completed game pairs, out of the pairs in `expected_games` if known.
+fn format_pair_count(completed_pairs: u64, expected_games: Option<u64>) -> String {
+    match expected_games {
+        Some(expected_games) => format!("Pairs: {completed_pairs}/{}", expected_games / 2),
+        None => format!("Pairs: {completed_pairs}"),
+    }
+}
+
+/// Formats a confidence level such as 0.95 as a percentage, e.g. `95%`.
+fn format_confidence(confidence: f64) -> String {
+    format!("{}%", (confidence * 1000.0).round() / 10.0)
 }
 
 fn compare<F>(f: F) -> String
@@ -276,3 +1263,996 @@ where
         format!("{first} - {second}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confidence_labels() {
+        assert_eq!(format_confidence(0.95), "95%");
+        assert_eq!(format_confidence(0.999), "99.9%");
+    }
+
+    #[test]
+    fn performance_averages() {
+        let record = |depth, seldepth, nodes, nps, millis| engine::MoveRecord {
+            depth,
+            seldepth,
+            nodes,
+            nps,
+            measured_time: Duration::from_millis(millis),
+            ..engine::MoveRecord::default()
+        };
+
+        let mut performance = [Performance::default(), Performance::default()];
+        performance[0].add(&record(10, 20, 1000, 50000, 100));
+        performance[0].add(&record(12, 0, 3000, 70000, 300));
+        // Book exits and instant moves may come without any info at all.
+        performance[0].add(&record(0, 0, 0, 0, 20));
+
+        assert_eq!(performance[0].move_count, 3);
+        assert_eq!(performance[0].depth.get(), Some(11.0));
+        assert_eq!(performance[0].seldepth.get(), Some(20.0));
+        assert_eq!(performance[0].nodes.get(), Some(2000.0));
+        assert_eq!(performance[0].nps.get(), Some(60000.0));
+        assert_eq!(performance[0].mean_time(), Some(Duration::from_millis(140)));
+        assert_eq!(performance[1].depth.get(), None);
+        assert_eq!(performance[1].mean_time(), None);
+
+        let names = ["alpha", "beta"].map(String::from);
+        assert_eq!(
+            format_performance(&names, &performance),
+            [
+                "Engine                  Moves    Depth SelDepth        Nodes          NPS  Time (ms)",
+                "alpha                       3    11.00    20.00         2000        60000        140",
+                "beta                        0        -        -            -            -          -",
+            ]
+        );
+    }
+
+    #[test]
+    fn clock_desync_warning() {
+        let record = |engine_time, millis| engine::MoveRecord {
+            engine_time,
+            measured_time: Duration::from_millis(millis),
+            ..engine::MoveRecord::default()
+        };
+
+        let mut in_sync = ClockDesync::default();
+        for _ in 0..100 {
+            assert_eq!(in_sync.add(&record(1000, 1030)), None);
+        }
+        assert_eq!(in_sync.mean_difference(), Some(Duration::from_millis(30)));
+
+        let mut desync = ClockDesync::default();
+        // Moves without `info time` are not counted.
+        assert_eq!(desync.add(&record(0, 5000)), None);
+        for _ in 1..CLOCK_DESYNC_MIN_MOVES {
+            assert_eq!(desync.add(&record(1000, 2000)), None);
+        }
+        assert_eq!(
+            desync.add(&record(1000, 2000)),
+            Some(Duration::from_millis(1000))
+        );
+        // The warning only fires once.
+        assert_eq!(desync.add(&record(2000, 1000)), None);
+        assert_eq!(desync.move_count, CLOCK_DESYNC_MIN_MOVES + 1);
+    }
+
+    #[test]
+    fn elo_report_columns() {
+        use cli::EloReport;
+        let penta = Penta {
+            ll: 3,
+            dl: 10,
+            dd: 20,
+            wl: 10,
+            wd: 25,
+            ww: 6,
+        };
+        let both = format_elo_line(penta, 0.95, EloReport::Both).unwrap();
+        let lelo = format_elo_line(penta, 0.95, EloReport::Logistic).unwrap();
+        let nelo = format_elo_line(penta, 0.95, EloReport::Normalized).unwrap();
+        let (elo_part, _) = lelo.split_once(" (").unwrap();
+        let (nelo_part, _) = nelo.split_once(" (").unwrap();
+        assert_eq!(both, format!("{elo_part}, {nelo_part} (95% CI)"));
+        assert!(lelo.starts_with("Elo: ") && nelo.starts_with("nElo: "));
+        assert_eq!(
+            format_elo_line(Penta::default(), 0.95, EloReport::Both),
+            None
+        );
+
+        let cells = |report| format_elo_cells(penta, 0.95, report);
+        assert_eq!(
+            cells(EloReport::Both),
+            cells(EloReport::Logistic) + &cells(EloReport::Normalized)
+        );
+        assert_eq!(
+            format_elo_headers(0.95, EloReport::Both),
+            "      Elo   +/-95%     nElo   +/-95%"
+        );
+        assert_eq!(
+            format_elo_headers(0.95, EloReport::Normalized),
+            "     nElo   +/-95%"
+        );
+    }
+
+    #[test]
+    fn score_line() {
+        assert_eq!(
+            format_score(Wdl::default(), Penta::default()),
+            "Score: 0.00%"
+        );
+        let penta = Penta {
+            ll: 0,
+            dl: 10,
+            dd: 20,
+            wl: 10,
+            wd: 20,
+            ww: 0,
+        };
+        let wdl = Wdl {
+            w: 30,
+            d: 70,
+            l: 20,
+        };
+        assert_eq!(
+            format_score(wdl, penta),
+            "Score: 54.17% +/- 2.22%, LOS: 96.99%"
+        );
+        // A win whose paired game is still being played is left out of the score as well.
+        let wdl = Wdl { w: 31, ..wdl };
+        assert_eq!(
+            format_score(wdl, penta),
+            "Score: 54.17% +/- 2.22%, LOS: 96.99%"
+        );
+    }
+
+    #[test]
+    fn results_matrix() {
+        let names = ["alpha", "beta", "a-very-long-engine-name-indeed"].map(String::from);
+        let mut wdl_board = HashMap::new();
+        wdl_board.insert((0, 1), Wdl { w: 3, d: 1, l: 2 });
+        wdl_board.insert((1, 0), Wdl { w: 2, d: 1, l: 3 });
+        wdl_board.insert((0, 2), Wdl { w: 10, d: 0, l: 0 });
+        wdl_board.insert((2, 0), Wdl { w: 0, d: 0, l: 10 });
+
+        let lines = format_matrix(&names, &wdl_board);
+        assert_eq!(
+            lines,
+            [
+                "                        alpha     beta a-very-l",
+                "alpha                       -    3-1-2   10-0-0",
+                "beta                    2-1-3        -    0-0-0",
+                "a-very-long-engine-n   0-0-10    0-0-0        -",
+            ]
+        );
+    }
+
+    struct Sink;
+
+    impl Tournament for Sink {
+        fn next(&mut self) -> Option<MatchTicket> {
+            None
+        }
+        fn match_started(&mut self, _: MatchTicket) {}
+        fn match_complete(&mut self, _: MatchResult) -> TournamentState {
+            TournamentState::Continue
+        }
+        fn print_interval_report(&self) {}
+        fn tournament_complete(&self) {}
+        fn expected_maximum_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+            None
+        }
+        fn sprt_expected_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn take_resumed_games(&mut self) -> Vec<crate::pgn::ImportedGame> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn termination_counters() {
+        let names: Vec<String> = ["alpha", "beta"].map(String::from).to_vec();
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names.clone(),
+            vec![cli::EngineOptions::default(); 2],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+        assert!(format_terminations(&names, &stats.terminations).is_empty());
+
+        stats.add_termination([0, 1], GameOutcome::LossByClock(Color::Sente));
+        stats.add_termination([1, 0], GameOutcome::LossByClock(Color::Sente));
+        stats.add_termination([1, 0], GameOutcome::LossByIllegal(Color::Gote));
+        stats.add_termination([0, 1], GameOutcome::LossByPerpetual(Color::Gote));
+        stats.add_termination([0, 1], GameOutcome::LossByDisconnection(Color::Gote));
+        stats.add_termination([0, 1], GameOutcome::DrawByAdjudication);
+        stats.add_termination([1, 0], GameOutcome::WinByAdjudication(Color::Gote));
+        stats.add_termination([0, 1], GameOutcome::Resignation(Color::Sente));
+        stats.add_termination([0, 1], GameOutcome::DrawBySennichite);
+
+        assert_eq!(
+            stats.terminations,
+            [
+                TerminationCounts {
+                    time_forfeits: 1,
+                    illegal_moves: 1,
+                    disconnections: 0,
+                    adjudications: 2,
+                },
+                TerminationCounts {
+                    time_forfeits: 1,
+                    illegal_moves: 1,
+                    disconnections: 1,
+                    adjudications: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            format_terminations(&names, &stats.terminations),
+            [
+                "alpha: time forfeits: 1, illegal moves: 1, disconnections: 0, adjudicated: 2",
+                "beta: time forfeits: 1, illegal moves: 1, disconnections: 1, adjudicated: 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn fishtest_pair_terminations() {
+        let names: Vec<String> = ["alpha", "beta", "gamma"].map(String::from).to_vec();
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names,
+            vec![cli::EngineOptions::default(); 3],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+        // Only the losses of beta against alpha count for the default pair.
+        stats.add_termination([0, 1], GameOutcome::LossByClock(Color::Gote));
+        stats.add_termination([1, 2], GameOutcome::LossByClock(Color::Sente));
+        stats.add_termination([2, 1], GameOutcome::LossByDisconnection(Color::Gote));
+        stats.add_termination([1, 0], GameOutcome::LossByDisconnection(Color::Gote));
+        assert_eq!(stats.terminations[1].time_forfeits, 2);
+        assert_eq!(stats.terminations[1].disconnections, 1);
+        assert_eq!(
+            stats.pair_terminations,
+            TerminationCounts {
+                time_forfeits: 1,
+                ..TerminationCounts::default()
+            }
+        );
+    }
+
+    #[test]
+    fn sprt_drain() {
+        let names: Vec<String> = ["alpha", "beta"].map(String::from).to_vec();
+        // Bounds of zero on single games, so the first decisive result settles the test.
+        let sprt = SprtParameters::new(0.0, 5.0, 0.5, 0.5).unpaired();
+        let result = |id, engines| MatchResult {
+            ticket: MatchTicket {
+                id,
+                engines,
+                bucket: None,
+                opening: crate::book::Opening {
+                    position: crate::shogi::Position::default(),
+                    moves: vec![],
+                },
+            },
+            game_start: chrono::Utc::now(),
+            game_end: chrono::Utc::now(),
+            opening_plies: 0,
+            outcome: GameOutcome::Resignation(Color::Gote),
+            moves: vec![],
+        };
+        for (drain, expected) in [
+            (cli::SprtDrain::Complete, TournamentState::Continue),
+            (cli::SprtDrain::Cancel, TournamentState::Stop),
+        ] {
+            let mut stats = StatsWrapper::new(
+                Box::new(Sink),
+                names.clone(),
+                vec![cli::EngineOptions::default(); 2],
+                vec![],
+                Some((sprt, [1, 0], drain)),
+                None,
+                cli::StatsOptions::default(),
+            );
+            for _ in 0..4 {
+                Tournament::next(&mut stats);
+            }
+            // Three games are still being played when the verdict is reached.
+            assert_eq!(
+                Tournament::match_complete(&mut stats, result(0, [1, 0])),
+                expected
+            );
+            assert!(Tournament::next(&mut stats).is_none());
+            assert_eq!(stats.pair_penta([1, 0]), Penta::default());
+            assert_eq!(stats.pair_wdl([1, 0]), Wdl { w: 1, d: 0, l: 0 });
+            if drain == cli::SprtDrain::Complete {
+                Tournament::match_complete(&mut stats, result(1, [0, 1]));
+                Tournament::match_complete(&mut stats, result(2, [1, 0]));
+                assert_eq!(
+                    Tournament::match_complete(&mut stats, result(3, [0, 1])),
+                    TournamentState::Stop
+                );
+                assert_eq!(stats.pair_penta([1, 0]), Penta::ONE_WL + Penta::ONE_WL);
+            }
+        }
+    }
+
+    #[test]
+    fn pair_progress() {
+        let names: Vec<String> = ["alpha", "beta"].map(String::from).to_vec();
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names,
+            vec![cli::EngineOptions::default(); 2],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+        for id in 0..7 {
+            assert_eq!(stats.completed_pair_count(), id / 2);
+            let engines = if id % 2 == 0 { [0, 1] } else { [1, 0] };
+            Tournament::match_complete(
+                &mut stats,
+                MatchResult {
+                    ticket: MatchTicket {
+                        id,
+                        engines,
+                        bucket: None,
+                        opening: crate::book::Opening {
+                            position: crate::shogi::Position::default(),
+                            moves: vec![],
+                        },
+                    },
+                    game_start: chrono::Utc::now(),
+                    game_end: chrono::Utc::now(),
+                    opening_plies: 0,
+                    outcome: GameOutcome::Resignation(Color::Gote),
+                    moves: vec![],
+                },
+            );
+        }
+        assert_eq!(stats.completed_pair_count(), 3);
+        assert_eq!(format_pair_count(3, Some(7)), "Pairs: 3/3");
+    }
+
+    #[test]
+    fn anchored_ratings() {
+        let relative = [Some(50.0), Some(-10.0), None, Some(-40.0)];
+        assert_eq!(anchor_offset(&relative, &[None; 4]), None);
+        // An anchor without results of its own cannot place the table.
+        assert_eq!(
+            anchor_offset(&relative, &[None, None, Some(2000.0), None]),
+            None
+        );
+
+        let offset = anchor_offset(&relative, &[None, Some(3000.0), None, None]).unwrap();
+        assert_eq!(offset, 3010.0);
+        let ratings: Vec<_> = relative.iter().map(|r| r.map(|r| r + offset)).collect();
+        assert_eq!(ratings, [Some(3060.0), Some(3000.0), None, Some(2970.0)]);
+
+        // Anchors that disagree by 20 Elo with the measured gap are each missed by half of it.
+        let offset = anchor_offset(&relative, &[Some(3100.0), None, None, Some(3030.0)]).unwrap();
+        assert_eq!(offset, 3060.0);
+        assert_eq!(50.0 + offset, 3110.0);
+        assert_eq!(-40.0 + offset, 3020.0);
+    }
+
+    #[test]
+    fn gauntlet_pairs() {
+        let names: Vec<String> = ["seed", "base1", "base2"].map(String::from).to_vec();
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names.clone(),
+            vec![cli::EngineOptions::default(); 3],
+            vec![],
+            Some((sprt, [0, 2], cli::SprtDrain::Complete)),
+            Some(1),
+            cli::StatsOptions::default(),
+        );
+
+        // Games of the two opponents finish interleaved, as they would with concurrency.
+        let sente = Some(Color::Sente);
+        let gote = Some(Color::Gote);
+        stats.add_result(0, (0, 1), None, sente);
+        stats.add_result(2, (0, 2), None, None);
+        stats.add_result(4, (0, 1), None, gote);
+        stats.add_result(3, (2, 0), None, gote);
+        stats.add_result(1, (1, 0), None, gote);
+        stats.add_result(6, (0, 2), None, sente);
+        stats.add_result(5, (1, 0), None, None);
+        stats.add_result(7, (2, 0), None, sente);
+
+        let penta_vs_base1 = Penta::ONE_WW + Penta::ONE_DL;
+        let penta_vs_base2 = Penta::ONE_WD + Penta::ONE_WL;
+        assert_eq!(stats.pair_penta([0, 1]), penta_vs_base1);
+        assert_eq!(stats.pair_penta([0, 2]), penta_vs_base2);
+        assert_eq!(stats.pair_penta([2, 0]), penta_vs_base2.flip());
+        assert_eq!(stats.pair_wdl([0, 1]), Wdl { w: 2, d: 1, l: 1 });
+        assert!(stats.pending_pairing.is_empty());
+
+        let lines = format_pair_breakdown(
+            &names,
+            0,
+            1..3,
+            &stats.wdl_board,
+            &stats.penta_board,
+            0.95,
+            cli::EloReport::Both,
+        );
+        assert_eq!(lines[0], "Results of seed by opponent:");
+        for (line, name, penta) in [
+            (&lines[2], "base1", penta_vs_base1),
+            (&lines[3], "base2", penta_vs_base2),
+        ] {
+            let (elo, diff) = penta.logistic_elo(0.95).unwrap();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(fields[0], name);
+            assert_eq!(fields[1], format!("{elo:.2}"));
+            assert_eq!(fields[2], format!("{diff:.2}"));
+            assert_eq!(fields[5], "4");
+        }
+    }
+
+    #[test]
+    fn same_colour_pairs() {
+        let names: Vec<String> = ["alpha", "beta"].map(String::from).to_vec();
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names,
+            vec![cli::EngineOptions::default(); 2],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+
+        // With `colorpolicy=engineA-first`, both games of a pair have alpha as sente.
+        stats.add_result(0, (0, 1), None, Some(Color::Sente));
+        stats.add_result(1, (0, 1), None, None);
+        stats.add_result(2, (0, 1), None, Some(Color::Sente));
+        stats.add_result(3, (0, 1), None, Some(Color::Gote));
+        stats.add_result(4, (0, 1), None, Some(Color::Sente));
+        stats.add_result(5, (0, 1), None, Some(Color::Sente));
+
+        assert_eq!(
+            stats.pair_penta([0, 1]),
+            Penta::ONE_WD + Penta::ONE_WL + Penta::ONE_WW
+        );
+        assert_eq!(stats.pair_wdl([0, 1]), Wdl { w: 4, d: 1, l: 1 });
+        assert!(stats.pending_pairing.is_empty());
+    }
+
+    #[test]
+    fn mismatched_imported_pairs() {
+        let game = |round, sente: &str, gote: &str, winner| pgn::ImportedGame {
+            round,
+            sente: String::from(sente),
+            gote: String::from(gote),
+            winner,
+        };
+        let names: Vec<String> = ["alpha", "beta", "gamma"].map(String::from).to_vec();
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names,
+            vec![cli::EngineOptions::default(); 3],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+        let games = [
+            game(0, "alpha", "beta", Some(Color::Sente)),
+            // Not the sibling of round 0, so it is skipped rather than paired with it.
+            game(1, "alpha", "gamma", Some(Color::Sente)),
+            // A second run joined onto the first, whose rounds start again.
+            game(0, "beta", "gamma", None),
+            game(1, "gamma", "beta", Some(Color::Gote)),
+        ];
+        assert_eq!(stats.import_games(&games), 3);
+        assert_eq!(stats.pair_wdl([0, 1]), Wdl { w: 1, d: 0, l: 0 });
+        assert_eq!(stats.pair_wdl([0, 2]), Wdl::default());
+        assert_eq!(stats.pair_penta([0, 1]), Penta::default());
+        assert_eq!(stats.pair_penta([1, 2]), Penta::ONE_WD);
+    }
+
+    #[test]
+    fn imported_games() {
+        let path =
+            std::env::temp_dir().join(format!("shogitest-import-{}.pgn", std::process::id()));
+        let game = |round: u64, sente: &str, gote: &str, result: &str| {
+            format!(
+                "[Round \"{round}\"]\n[Sente \"{sente}\"]\n[Gote \"{gote}\"]\n[Result \"{result}\"]\n\n1. 2g2f\n{result}\n\n"
+            )
+        };
+        let contents = [
+            game(0, "alpha", "beta", "1-0"),
+            game(1, "beta", "alpha", "1/2-1/2"),
+            game(2, "alpha", "gamma", "0-1"),
+            game(4, "alpha", "beta", "undetermined"),
+        ]
+        .concat();
+        std::fs::write(&path, contents).unwrap();
+        let games = pgn::read_results(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(games.len(), 3);
+
+        let names: Vec<String> = ["alpha", "beta"].map(String::from).to_vec();
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names,
+            vec![cli::EngineOptions::default(); 2],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+        assert_eq!(stats.import_games(&games), 2);
+        assert_eq!(stats.pair_wdl([0, 1]), Wdl { w: 1, d: 1, l: 0 });
+        assert_eq!(stats.pair_penta([0, 1]), Penta::ONE_WD);
+        assert_eq!(stats.pair_penta([1, 0]), Penta::ONE_DL);
+
+        // A second file's rounds start again from zero, but pair only with each other.
+        assert_eq!(stats.import_games(&games[..1]), 1);
+        Tournament::match_complete(
+            &mut stats,
+            MatchResult {
+                ticket: MatchTicket {
+                    id: 1,
+                    engines: [1, 0],
+                    bucket: None,
+                    opening: crate::book::Opening {
+                        position: crate::shogi::Position::default(),
+                        moves: vec![],
+                    },
+                },
+                game_start: chrono::Utc::now(),
+                game_end: chrono::Utc::now(),
+                opening_plies: 0,
+                outcome: GameOutcome::DrawBySennichite,
+                moves: vec![],
+            },
+        );
+        assert_eq!(stats.pair_wdl([0, 1]), Wdl { w: 2, d: 2, l: 0 });
+        assert_eq!(stats.pair_penta([0, 1]), Penta::ONE_WD);
+        assert_eq!(stats.match_complete_count, 1);
+
+        // Games recorded by an interrupted run pair with the games of this run.
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            ["alpha", "beta"].map(String::from).to_vec(),
+            vec![cli::EngineOptions::default(); 2],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+        assert_eq!(stats.resume_games(&games[..1]), 1);
+        Tournament::match_complete(
+            &mut stats,
+            MatchResult {
+                ticket: MatchTicket {
+                    id: 1,
+                    engines: [1, 0],
+                    bucket: None,
+                    opening: crate::book::Opening {
+                        position: crate::shogi::Position::default(),
+                        moves: vec![],
+                    },
+                },
+                game_start: chrono::Utc::now(),
+                game_end: chrono::Utc::now(),
+                opening_plies: 0,
+                outcome: GameOutcome::DrawBySennichite,
+                moves: vec![],
+            },
+        );
+        assert_eq!(stats.pair_penta([0, 1]), Penta::ONE_WD);
+        assert_eq!(stats.import_id_base, 1 << 62);
+    }
+
+    /// Hands out the given tickets in order.
+    struct Tickets(std::vec::IntoIter<MatchTicket>);
+
+    impl Tournament for Tickets {
+        fn next(&mut self) -> Option<MatchTicket> {
+            self.0.next()
+        }
+        fn match_started(&mut self, _: MatchTicket) {}
+        fn match_complete(&mut self, _: MatchResult) -> TournamentState {
+            TournamentState::Continue
+        }
+        fn print_interval_report(&self) {}
+        fn tournament_complete(&self) {}
+        fn expected_maximum_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn sprt_verdict(&self) -> Option<crate::sprt::SprtVerdict> {
+            None
+        }
+        fn sprt_expected_match_count(&self) -> Option<u64> {
+            None
+        }
+        fn take_resumed_games(&mut self) -> Vec<pgn::ImportedGame> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn resumed_games() {
+        let path =
+            std::env::temp_dir().join(format!("shogitest-resumed-{}.pgn", std::process::id()));
+        let game = |round: u64, sente: &str, gote: &str, result: &str| {
+            format!(
+                "[Round \"{round}\"]\n[Sente \"{sente}\"]\n[Gote \"{gote}\"]\n[Result \"{result}\"]\n\n1. 2g2f\n{result}\n\n"
+            )
+        };
+        // Round 1 was played with the colours the other way round, and round 4 is not in the
+        // schedule of this run.
+        let contents = [
+            game(0, "alpha", "beta", "1-0"),
+            game(1, "alpha", "beta", "1-0"),
+            game(4, "alpha", "beta", "1-0"),
+        ]
+        .concat();
+        std::fs::write(&path, contents).unwrap();
+
+        let ticket = |id, engines| MatchTicket {
+            id,
+            engines,
+            bucket: None,
+            opening: crate::book::Opening {
+                position: crate::shogi::Position::default(),
+                moves: vec![],
+            },
+        };
+        let names: Vec<String> = ["alpha", "beta"].map(String::from).to_vec();
+        let pgn = crate::tournament::PgnOutWrapper::new(
+            Box::new(Tickets(
+                vec![ticket(0, [0, 1]), ticket(1, [1, 0])].into_iter(),
+            )),
+            &cli::PgnOutOptions {
+                file: path.to_str().unwrap().to_string(),
+                resume: true,
+                ..cli::PgnOutOptions::default()
+            },
+            &cli::MetaDataOptions {
+                event_name: String::from("?"),
+                site_name: String::from("?"),
+            },
+            vec![cli::EngineOptions::default(); 2],
+            names
+                .iter()
+                .map(|name| cli::EngineIdentity {
+                    name: name.clone(),
+                    usi_name: None,
+                    usi_author: None,
+                })
+                .collect(),
+        )
+        .unwrap();
+        let mut stats = StatsWrapper::new(
+            Box::new(pgn),
+            names,
+            vec![cli::EngineOptions::default(); 2],
+            vec![],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+
+        // Only round 0 is skipped and counted; round 1 is played again and counted once.
+        let replayed = Tournament::next(&mut stats).unwrap();
+        assert_eq!(replayed.id, 1);
+        assert_eq!(stats.pair_wdl([0, 1]), Wdl::ONE_WIN);
+        assert!(Tournament::next(&mut stats).is_none());
+        Tournament::match_complete(
+            &mut stats,
+            MatchResult {
+                ticket: replayed,
+                game_start: chrono::Utc::now(),
+                game_end: chrono::Utc::now(),
+                opening_plies: 0,
+                outcome: GameOutcome::DrawBySennichite,
+                moves: vec![],
+            },
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(stats.pair_wdl([0, 1]), Wdl { w: 1, d: 1, l: 0 });
+        assert_eq!(stats.pair_penta([0, 1]), Penta::ONE_WD);
+        assert_eq!(stats.match_complete_count, 1);
+    }
+
+    #[test]
+    fn bucket_pentas() {
+        let names: Vec<String> = ["alpha", "beta"].map(String::from).to_vec();
+        let mut stats = StatsWrapper::new(
+            Box::new(Sink),
+            names,
+            vec![cli::EngineOptions::default(); 2],
+            vec![String::from("books/a.epd"), String::from("books/b.epd")],
+            None,
+            None,
+            cli::StatsOptions::default(),
+        );
+        let (sente, gote) = (Some(Color::Sente), Some(Color::Gote));
+        // Pairs from the two books finish interleaved.
+        stats.add_result(0, (1, 0), Some(0), sente);
+        stats.add_result(2, (1, 0), Some(1), sente);
+        stats.add_result(1, (0, 1), Some(0), sente);
+        stats.add_result(3, (0, 1), Some(1), gote);
+        stats.add_result(4, (1, 0), Some(1), None);
+        stats.add_result(5, (0, 1), Some(1), None);
+
+        let bucket = |bucket| {
+            let key = (bucket, (1, 0));
+            (
+                stats
+                    .bucket_wdl_board
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_default(),
+                stats
+                    .bucket_penta_board
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_default(),
+            )
+        };
+        assert_eq!(bucket(0), (Wdl { w: 1, d: 0, l: 1 }, Penta::ONE_WL));
+        assert_eq!(
+            bucket(1),
+            (Wdl { w: 2, d: 2, l: 0 }, Penta::ONE_WW + Penta::ONE_DD)
+        );
+        assert_eq!(
+            stats.pair_penta([1, 0]),
+            Penta::ONE_WL + Penta::ONE_WW + Penta::ONE_DD
+        );
+
+        let rows = [
+            (String::from("a.epd"), bucket(0).0, bucket(0).1),
+            (String::from("b.epd"), bucket(1).0, bucket(1).1),
+        ];
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        let lines = format_bucket_breakdown(
+            String::from("Results of beta vs alpha by book file:"),
+            &rows,
+            Some(sprt),
+            0.95,
+            cli::EloReport::Logistic,
+        );
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("Book ") && lines[1].ends_with("LLR"));
+        assert!(lines[2].starts_with("a.epd ") && lines[2].contains(" 50.00%  [0, 0, 1, 0, 0]"));
+        assert!(lines[3].starts_with("b.epd ") && lines[3].contains(" 75.00%  [0, 0, 1, 0, 1]"));
+    }
+
+    #[test]
+    fn fishtest_layout() {
+        let penta = Penta {
+            ll: 1,
+            dl: 4,
+            dd: 10,
+            wl: 3,
+            wd: 6,
+            ww: 2,
+        };
+        let wdl = Wdl { w: 19, d: 30, l: 3 };
+        let terminations = TerminationCounts {
+            time_forfeits: 2,
+            illegal_moves: 1,
+            disconnections: 1,
+            adjudications: 5,
+        };
+        assert_eq!(
+            format_fishtest(wdl, penta, terminations),
+            [
+                "Pentanomial [LL, LD, DD+WL, WD, WW]: [1, 4, 13, 6, 2], Total: 26 pairs, 52 games",
+                "{\"wins\": 19, \"losses\": 3, \"draws\": 30, \"crashes\": 1, \"time_losses\": 2, \"pentanomial\": [1, 4, 13, 6, 2]}",
+            ]
+        );
+    }
+
+    #[test]
+    fn table_rank_order() {
+        let row = |name, elo, games| {
+            (
+                name,
+                elo,
+                Wdl {
+                    w: 0,
+                    d: games,
+                    l: 0,
+                },
+                Penta::default(),
+            )
+        };
+        let mut table = [
+            row("nan", Some(f64::NAN), 4),
+            row("unplayed", None, 0),
+            row("low", Some(-10.0), 4),
+            row("none-b", None, 2),
+            row("high", Some(25.0), 2),
+            row("none-a", None, 2),
+            row("nan-more", Some(f64::NAN), 6),
+            row("high-more", Some(25.0), 6),
+        ];
+        let expected = [
+            "high-more",
+            "high",
+            "low",
+            "nan-more",
+            "nan",
+            "none-a",
+            "none-b",
+            "unplayed",
+        ];
+        table.sort_by(rank_order);
+        assert_eq!(table.iter().map(|row| row.0).collect::<Vec<_>>(), expected);
+        table.reverse();
+        table.sort_by(rank_order);
+        assert_eq!(table.iter().map(|row| row.0).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn engine_versions() {
+        let names = [
+            String::from("dev"),
+            String::from("base"),
+            String::from("Other 2.0"),
+        ];
+        assert_eq!(
+            format_versions(
+                &names,
+                &[
+                    Some(String::from("Engine 1.3-dev (a1b2c3)")),
+                    None,
+                    Some(String::from("Other 2.0")),
+                ]
+            ),
+            Some(String::from("Versions: dev = Engine 1.3-dev (a1b2c3)"))
+        );
+        assert_eq!(format_versions(&names, &[None, None, None]), None);
+    }
+
+    #[test]
+    fn sprt_bounds_label() {
+        let penta = Penta {
+            ll: 5,
+            dl: 20,
+            dd: 30,
+            wl: 10,
+            wd: 25,
+            ww: 10,
+        };
+        let wdl = Wdl {
+            w: 55,
+            d: 105,
+            l: 40,
+        };
+        let line = |sprt: SprtParameters| {
+            let line = format_sprt_llr(&sprt, wdl, penta);
+            let (_, bounds) = line.split_once('[').unwrap();
+            bounds.split_once(']').unwrap().0.to_string()
+        };
+
+        assert_eq!(
+            line(SprtParameters::new(0.0, 5.0, 0.05, 0.05)),
+            "nElo 0.00, 5.00"
+        );
+        assert_eq!(
+            line(SprtParameters::new_trinomial(-2.0, 3.0, 0.05, 0.05)),
+            "nElo -2.00, 3.00"
+        );
+        assert_eq!(
+            line(SprtParameters::new_logistic(0.0, 10.0, 0.05, 0.05)),
+            "Elo 0.00, 10.00"
+        );
+        // The hypotheses are also shown in the other unit, once there are pairs to measure the
+        // variance with.
+        let converted = |sprt: SprtParameters, penta: Penta| {
+            let line = format_sprt_llr(&sprt, wdl, penta);
+            let (_, converted) = line.split_once(" ~ [")?;
+            Some(converted.split_once(']').unwrap().0.to_string())
+        };
+        let nelo = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        assert_eq!(
+            converted(nelo, penta),
+            Some(format!("Elo 0.00, {:.2}", penta.nelo_to_elo(5.0).unwrap()))
+        );
+        assert_eq!(
+            converted(SprtParameters::new_logistic(0.0, 10.0, 0.05, 0.05), penta),
+            Some(format!(
+                "nElo 0.00, {:.2}",
+                penta.elo_to_nelo(10.0).unwrap()
+            ))
+        );
+        assert_eq!(converted(nelo, Penta::default()), None);
+        assert!(
+            format_sprt_llr(&SprtParameters::new(0.0, 5.0, 0.05, 0.1), wdl, penta)
+                .ends_with("(alpha 0.05, beta 0.1)")
+        );
+    }
+
+    #[test]
+    fn progress_line() {
+        assert_eq!(
+            format_progress(0, 0, None, None, None, 0.95, cli::EloReport::Both),
+            "Games: 0, Pairs: 0"
+        );
+        assert_eq!(
+            format_progress(
+                0,
+                0,
+                Some(100),
+                Some((Wdl::default(), Penta::default())),
+                None,
+                0.95,
+                cli::EloReport::Both,
+            ),
+            "Games: 0/100, Pairs: 0/50, W: 0 D: 0 L: 0"
+        );
+
+        let wdl = Wdl { w: 5, d: 2, l: 3 };
+        let penta = Penta {
+            ll: 0,
+            dl: 1,
+            dd: 1,
+            wl: 1,
+            wd: 1,
+            ww: 1,
+        };
+        let sprt = SprtParameters::new(0.0, 5.0, 0.05, 0.05);
+        let line = format_progress(
+            10,
+            5,
+            Some(100),
+            Some((wdl, penta)),
+            Some(sprt),
+            0.95,
+            cli::EloReport::Both,
+        );
+        let (elo, diff) = penta.logistic_elo(0.95).unwrap();
+        let (lower, upper) = sprt.llr_bounds();
+        assert_eq!(
+            line,
+            format!(
+                "Games: 10/100, Pairs: 5/50, W: 5 D: 2 L: 3, Elo: {elo:.2} +/- {diff:.2}, LLR: {:.2} ({lower:.2}, {upper:.2})",
+                sprt.llr(wdl, penta)
+            )
+        );
+    }
+
+    #[test]
+    fn dd_wl_ratio_line() {
+        let pairs = |penta: Penta, count: u64| (0..count).map(|_| penta).sum::<Penta>();
+
+        assert_eq!(format_dd_wl_ratio(Penta::default()), "n/a");
+        assert_eq!(format_dd_wl_ratio(pairs(Penta::ONE_DD, 30)), "n/a");
+        assert_eq!(
+            format_dd_wl_ratio(pairs(Penta::ONE_DD, 6) + pairs(Penta::ONE_WL, 4)),
+            "n/a"
+        );
+
+        let balanced = pairs(Penta::ONE_DD, 12) + pairs(Penta::ONE_WL, 8);
+        assert_eq!(format_dd_wl_ratio(balanced), "1.50");
+
+        let drawish = pairs(Penta::ONE_DD, 24) + pairs(Penta::ONE_WL, 4);
+        assert!(format_dd_wl_ratio(drawish).starts_with("6.00 (high;"));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::engine::EngineResult;
+use log::info;
+use std::{
+    io::{Read, Result, Write},
+    net::{Shutdown, TcpStream},
+    process::{Child, ChildStdin, ChildStdout},
+    time::Duration,
+};
+use wait_timeout::ChildExt;
+
+/// The byte stream an engine speaks USI over.
+pub trait Transport: Write + std::fmt::Debug {
+    /// Reads whatever is available into `buf`, waiting at most `timeout` for data to arrive.
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> EngineResult<usize>;
+
+    /// Waits for the engine to go away after `quit` has been sent, forcing it if need be.
+    fn close(&mut self, name: &str);
+
+    /// Gets rid of an unresponsive engine straight away, without asking it to quit.
+    fn kill(&mut self, name: &str);
+}
+
+/// An engine running as a child process, spoken to over its stdin and stdout.
+#[derive(Debug)]
+pub struct ChildTransport {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+}
+
+impl ChildTransport {
+    pub fn new(mut child: Child) -> ChildTransport {
+        let stdout = child.stdout.take().unwrap();
+        let stdin = child.stdin.take().unwrap();
+        ChildTransport {
+            child,
+            stdin,
+            stdout,
+        }
+    }
+}
+
+impl Write for ChildTransport {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.stdin.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.stdin.flush()
+    }
+}
+
+impl Transport for ChildTransport {
+    #[cfg(unix)]
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> EngineResult<usize> {
+        use std::os::fd::AsRawFd;
+
+        let timeout_ms = match timeout {
+            Some(timeout) => timeout.as_millis().clamp(0, i32::MAX as u128) as i32,
+            None => -1,
+        };
+
+        loop {
+            let mut fds: [libc::pollfd; 1] = unsafe { std::mem::zeroed() };
+            fds[0].fd = self.stdout.as_raw_fd();
+            fds[0].events = libc::POLLIN;
+
+            let ready_count = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as u64, timeout_ms) };
+            if ready_count < 0 {
+                let err = std::io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EINTR) | Some(libc::EAGAIN) => continue,
+                    _ => return EngineResult::Err(err),
+                }
+            }
+
+            assert!(ready_count as usize <= fds.len());
+
+            if ready_count == 0 {
+                return EngineResult::Timeout;
+            }
+
+            return match self.stdout.read(buf) {
+                Err(err) => EngineResult::Err(err),
+                Ok(0) => EngineResult::Disconnected,
+                Ok(count) => EngineResult::Ok(count),
+            };
+        }
+    }
+
+    #[cfg(windows)]
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> EngineResult<usize> {
+        use std::os::windows::io::AsRawHandle;
+        use windows::{
+            Win32::Foundation::*, Win32::Storage::FileSystem::*, Win32::System::IO::*,
+            Win32::System::Threading::*,
+        };
+
+        let timeout_ms = match timeout {
+            Some(timeout) => timeout.as_millis().clamp(0, i32::MAX as u128) as u32,
+            None => INFINITE,
+        };
+
+        unsafe {
+            let handle = HANDLE(self.stdout.as_raw_handle());
+
+            let mut overlapped = OVERLAPPED::default();
+            overlapped.hEvent =
+                CreateEventW(None, true, false, None).expect("Could not create event");
+
+            if let Err(err) = ReadFile(handle, Some(buf), None, Some(&mut overlapped))
+                && err.code() != ERROR_IO_PENDING.into()
+            {
+                let _ = CloseHandle(overlapped.hEvent);
+                return EngineResult::Err(std::io::Error::other(format!(
+                    "ReadFile Failed: {:?}",
+                    err
+                )));
+            }
+
+            let mut bytes_read: u32 = 0;
+
+            match WaitForSingleObject(overlapped.hEvent, timeout_ms) {
+                WAIT_TIMEOUT => {
+                    // Wait for the cancellation to complete so `buf` is no longer in use.
+                    let _ = CancelIo(handle);
+                    let _ = GetOverlappedResult(handle, &overlapped, &mut bytes_read, true);
+                    let _ = CloseHandle(overlapped.hEvent);
+                    return EngineResult::Timeout;
+                }
+                WAIT_OBJECT_0 => {}
+                _ => {
+                    let _ = CloseHandle(overlapped.hEvent);
+                    return EngineResult::Err(std::io::Error::other("WaitForSingleObject Failed"));
+                }
+            }
+
+            if let Err(err) = GetOverlappedResult(handle, &overlapped, &mut bytes_read, false) {
+                let _ = CloseHandle(overlapped.hEvent);
+                return EngineResult::Err(std::io::Error::other(format!(
+                    "GetOverlappedResult Failed: {:?}",
+                    err
+                )));
+            }
+
+            let _ = CloseHandle(overlapped.hEvent);
+
+            if bytes_read == 0 {
+                EngineResult::Disconnected
+            } else {
+                EngineResult::Ok(bytes_read as usize)
+            }
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match self.child.wait_timeout(Duration::from_secs(10)) {
+            Ok(Some(_)) => info!("Quit engine {name} successfully"),
+            Ok(None) | Err(_) => {
+                info!("Timed out quitting engine {name}, attempting to kill...");
+                match self.child.kill() {
+                    Ok(_) => info!("Engine {name} killed"),
+                    Err(_) => info!("Failed to kill engine {name}, giving up"),
+                }
+            }
+        }
+    }
+
+    fn kill(&mut self, name: &str) {
+        match self.child.kill().and_then(|_| self.child.wait()) {
+            Ok(_) => info!("Engine {name} killed"),
+            Err(_) => info!("Failed to kill engine {name}, giving up"),
+        }
+    }
+}
+
+/// An engine listening on a TCP socket, e.g. on a remote machine.
+#[derive(Debug)]
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn connect(host: &str) -> Result<TcpTransport> {
+        let stream = TcpStream::connect(host)?;
+        stream.set_nodelay(true)?;
+        Ok(TcpTransport { stream })
+    }
+}
+
+impl Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Transport for TcpTransport {
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> EngineResult<usize> {
+        // A zero read timeout is rejected by the socket API.
+        let timeout = timeout.map(|timeout| timeout.max(Duration::MILLISECOND));
+        if let Err(err) = self.stream.set_read_timeout(timeout) {
+            return EngineResult::Err(err);
+        }
+
+        loop {
+            return match self.stream.read(buf) {
+                Ok(0) => EngineResult::Disconnected,
+                Ok(count) => EngineResult::Ok(count),
+                Err(err) => match err.kind() {
+                    std::io::ErrorKind::Interrupted => continue,
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                        EngineResult::Timeout
+                    }
+                    std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted => {
+                        EngineResult::Disconnected
+                    }
+                    _ => EngineResult::Err(err),
+                },
+            };
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match self.stream.shutdown(Shutdown::Both) {
+            Ok(_) => info!("Disconnected from engine {name}"),
+            Err(_) => info!("Failed to disconnect from engine {name}, giving up"),
+        }
+    }
+
+    fn kill(&mut self, name: &str) {
+        self.close(name)
+    }
+}